@@ -2,6 +2,7 @@
 extern crate test;
 
 #[cfg(test)]
+#[allow(clippy::needless_borrow)]
 mod tests {
     extern crate illumina_coordinates;
     use test::Bencher;
@@ -11,4 +12,16 @@ mod tests {
         let sequence_identifier = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
         b.iter(|| illumina_coordinates::parse_sequence_identifier(&sequence_identifier).unwrap());
     }
+
+    #[bench]
+    fn bench_parse_sequence_identifier_borrowed(b: &mut Bencher) {
+        let sequence_identifier = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+        b.iter(|| illumina_coordinates::parse_sequence_identifier_borrowed(sequence_identifier).unwrap());
+    }
+
+    #[bench]
+    fn bench_lazy_sequence_identifier_x(b: &mut Bencher) {
+        let sequence_identifier = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+        b.iter(|| illumina_coordinates::lazy::LazySequenceIdentifier::new(sequence_identifier).unwrap().x().unwrap());
+    }
 }
@@ -0,0 +1 @@
+//! Tests that read the output of `illumina_coordinates` with reference readers
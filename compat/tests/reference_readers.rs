@@ -0,0 +1,130 @@
+//! Reads the Parquet and Arrow output of `illumina_coordinates` with the reference Rust readers,
+//! and checks that every column has the expected type and values.
+
+use arrow::array::{Array, RecordBatch};
+use arrow::datatypes::DataType;
+use arrow::ipc::reader::StreamReader;
+use arrow::util::display::array_value_to_string;
+use bytes::Bytes;
+use illumina_coordinates::arrow::ArrowWriter;
+use illumina_coordinates::parquet::ParquetWriter;
+use illumina_coordinates::testing::{Arbitrary, Rng};
+use illumina_coordinates::{parse_sequence_identifier, SequenceIdentifier};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+const COLUMNS: [(&str, DataType, bool); 14] = [
+    ("sequencer_id", DataType::Utf8, false),
+    ("run_count", DataType::UInt16, false),
+    ("flow_cell_id", DataType::Utf8, false),
+    ("lane", DataType::UInt8, false),
+    ("side", DataType::UInt8, false),
+    ("swath", DataType::UInt8, false),
+    ("camera", DataType::UInt8, true),
+    ("tile", DataType::UInt8, false),
+    ("x", DataType::UInt16, false),
+    ("y", DataType::UInt16, false),
+    ("read", DataType::UInt8, false),
+    ("is_filtered", DataType::Boolean, false),
+    ("control_number", DataType::UInt16, false),
+    ("sample", DataType::Utf8, false),
+];
+
+/// Real identifiers, with and without a camera, followed by enough random ones to fill several
+/// row groups and batches unevenly
+fn seq_ids() -> Vec<SequenceIdentifier> {
+    let mut seq_ids: Vec<SequenceIdentifier> = [
+        "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+        "@NB501234:12:H2VGLBGXY:4:23612:11289:19567 1:Y:0:TAAGGCGA",
+        "@NS500358:204:HTN5KAFXY:4:21101:20886:1073 2:N:18:TAAGGCGA+GCGATCTA",
+    ].iter().map(|line| parse_sequence_identifier(line).unwrap()).collect();
+    let mut rng = Rng::new(7);
+    seq_ids.extend((0..997).map(|_| SequenceIdentifier::arbitrary(&mut rng)));
+    seq_ids
+}
+
+/// The value of each column of a row, as the reference readers display it
+fn row(seq_id: &SequenceIdentifier) -> Vec<Option<String>> {
+    vec![
+        Some(seq_id.sequencer_id.to_string()),
+        Some(seq_id.run_count.to_string()),
+        Some(seq_id.flow_cell_id.to_string()),
+        Some(seq_id.lane.to_string()),
+        Some(seq_id.side.to_string()),
+        Some(seq_id.swath.to_string()),
+        seq_id.camera.map(|camera| camera.to_string()),
+        Some(seq_id.tile.to_string()),
+        Some(seq_id.x.to_string()),
+        Some(seq_id.y.to_string()),
+        Some(seq_id.read.to_string()),
+        Some(seq_id.is_filtered.to_string()),
+        Some(seq_id.control_number.to_string()),
+        Some(seq_id.sample.to_string()),
+    ]
+}
+
+fn check_batches(batches: &[RecordBatch], seq_ids: &[SequenceIdentifier]) {
+    let mut rows = vec![];
+    for batch in batches {
+        let schema = batch.schema();
+        assert_eq!(schema.fields().len(), COLUMNS.len());
+        for (field, &(name, ref data_type, nullable)) in schema.fields().iter().zip(COLUMNS.iter()) {
+            assert_eq!((field.name().as_str(), field.data_type(), field.is_nullable()), (name, data_type, nullable));
+        }
+        for index in 0..batch.num_rows() {
+            rows.push(batch.columns().iter()
+                .map(|column| if column.is_null(index) { None } else { Some(array_value_to_string(column, index).unwrap()) })
+                .collect::<Vec<_>>());
+        }
+    }
+    let expected: Vec<_> = seq_ids.iter().map(row).collect();
+    assert_eq!(rows, expected);
+}
+
+#[test]
+fn test_parquet() {
+    let seq_ids = seq_ids();
+    for &row_group_size in &[1, 333, 1000, 1_000_000] {
+        let mut writer = ParquetWriter::with_row_group_size(vec![], row_group_size).unwrap();
+        for seq_id in &seq_ids {
+            writer.write(seq_id).unwrap();
+        }
+        let output = Bytes::from(writer.finish().unwrap());
+        let builder = ParquetRecordBatchReaderBuilder::try_new(output).unwrap();
+        assert_eq!(builder.metadata().num_row_groups(), seq_ids.len().div_ceil(row_group_size));
+        let batches = builder.build().unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        check_batches(&batches, &seq_ids);
+    }
+}
+
+#[test]
+fn test_parquet_empty() {
+    let output = Bytes::from(ParquetWriter::new(vec![]).unwrap().finish().unwrap());
+    let builder = ParquetRecordBatchReaderBuilder::try_new(output).unwrap();
+    assert_eq!(builder.metadata().file_metadata().num_rows(), 0);
+    assert_eq!(builder.schema().fields().len(), COLUMNS.len());
+    assert_eq!(builder.build().unwrap().count(), 0);
+}
+
+#[test]
+fn test_arrow() {
+    let seq_ids = seq_ids();
+    for &batch_size in &[1, 333, 1000, 65_536] {
+        let mut writer = ArrowWriter::with_batch_size(vec![], batch_size).unwrap();
+        for seq_id in &seq_ids {
+            writer.write(seq_id).unwrap();
+        }
+        let output = writer.finish().unwrap();
+        let reader = StreamReader::try_new(&output[..], None).unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches.len(), seq_ids.len().div_ceil(batch_size));
+        check_batches(&batches, &seq_ids);
+    }
+}
+
+#[test]
+fn test_arrow_empty() {
+    let output = ArrowWriter::new(vec![]).unwrap().finish().unwrap();
+    let reader = StreamReader::try_new(&output[..], None).unwrap();
+    assert_eq!(reader.schema().fields().len(), COLUMNS.len());
+    assert_eq!(reader.count(), 0);
+}
@@ -0,0 +1,38 @@
+#![no_main]
+use illumina_coordinates::header_format::{HeaderFormat, HeaderStyle, LegacyFormat};
+use illumina_coordinates::rewrite::Header;
+use illumina_coordinates::{parse_sequence_identifier, BorrowedSequenceIdentifier, SequenceIdentifier};
+use libfuzzer_sys::fuzz_target;
+
+// Malformed identifiers must produce an error, never a panic, and identifiers that parse must
+// parse the same way once they're written out again
+fuzz_target!(|data: &[u8]| {
+    if let Ok(seq_id) = BorrowedSequenceIdentifier::from_head(data) {
+        let seq_id = seq_id.into_owned();
+        assert_eq!(parse_sequence_identifier(&seq_id.to_string()).unwrap(), seq_id);
+    }
+    let (name, description) = match data.iter().position(|&byte| byte == b' ') {
+        Some(space) => (&data[..space], &data[space + 1..]),
+        None => (data, &[][..])
+    };
+    if let Ok(seq_id) = SequenceIdentifier::from_name_and_description(name, description) {
+        assert_eq!(parse_sequence_identifier(&seq_id.to_string()).unwrap(), seq_id);
+    }
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return
+    };
+    if let Ok(seq_id) = parse_sequence_identifier(text) {
+        assert_eq!(parse_sequence_identifier(&seq_id.to_string()).unwrap(), seq_id);
+    }
+    if let Ok(header) = Header::parse_preserving(text) {
+        let written = header.to_string();
+        let reparsed = Header::parse_preserving(&written).unwrap();
+        assert_eq!(reparsed.seq_id, header.seq_id);
+        assert_eq!(reparsed.to_string(), written);
+    }
+    let legacy = LegacyFormat::new(1, "FLOWCELL");
+    if let Ok(seq_id) = legacy.parse(text) {
+        assert_eq!(legacy.parse(&HeaderStyle::Legacy.header(&seq_id)).unwrap(), seq_id);
+    }
+});
@@ -0,0 +1,176 @@
+//! Replacement of the IDs in sequence identifiers with pseudonyms, so that data can be shared
+//! without revealing which facility, instrument or run produced it.
+//!
+//! Pseudonyms are derived from the real IDs and a secret key, so the same ID gets the same
+//! pseudonym in every file anonymized with the same key, and reads from one run still pair up.
+//! The parts of the IDs that only give the model of sequencer or the type of flow cell are kept,
+//! along with the coordinates, so the anonymized reads work with the rest of the crate. The
+//! derivation isn't cryptographic: keep the key secret, and don't rely on it against a determined
+//! attacker who knows some of the real IDs.
+//!
+//! The coordinates can also be moved, by a shift that's the same for every read on a tile but
+//! differs between tiles. Distances between the reads of a tile are unchanged, so anonymized data
+//! still works for benchmarking duplicate detection, but positions can't be matched against the
+//! originals or across tiles.
+
+use flow_cell::FlowCellType;
+use id_string::IdString;
+use subsample::{hash, mix};
+use super::SequenceIdentifier;
+
+/// Replaces the sequencer ID, run count and flow cell ID of reads with pseudonyms
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::anonymize::Anonymizer;
+///
+/// fn main() {
+///     let anonymizer = Anonymizer::new(b"a secret key");
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     let anonymized = anonymizer.anonymize(&seq_id);
+///     assert_ne!(anonymized.sequencer_id, seq_id.sequencer_id);
+///     assert_eq!(anonymized.instrument(), seq_id.instrument());
+///     assert_eq!(anonymized.flow_cell_type(), seq_id.flow_cell_type());
+///     assert_eq!((anonymized.x, anonymized.y), (4127, 8949));
+///     assert_eq!(anonymizer.anonymize(&seq_id), anonymized);
+/// }
+/// ```
+pub struct Anonymizer {
+    key: u64
+}
+
+impl Anonymizer {
+    /// Creates an anonymizer. Different keys give different pseudonyms.
+    pub fn new(key: &[u8]) -> Anonymizer {
+        Anonymizer { key: hash(0, key) }
+    }
+
+    /// Returns a copy of the identifier with pseudonyms in place of its IDs
+    pub fn anonymize(&self, seq_id: &SequenceIdentifier) -> SequenceIdentifier {
+        let sequencer_id = seq_id.sequencer_id.as_str();
+        // The letters before the serial number give the model
+        let serial_start = sequencer_id.find(|c: char| c.is_ascii_digit()).unwrap_or(0);
+        let flow_cell_id = seq_id.flow_cell_id.as_str();
+        let run = hash(hash(self.key, sequencer_id.as_bytes()), &seq_id.run_count.to_le_bytes());
+        let mut anonymized = seq_id.clone();
+        anonymized.sequencer_id = IdString::from(self.replace(sequencer_id, (serial_start, sequencer_id.len())).as_str());
+        anonymized.run_count = (run % 9999) as u16 + 1;
+        anonymized.flow_cell_id = IdString::from(self.replace(flow_cell_id, FlowCellType::serial_number_range(flow_cell_id)).as_str());
+        anonymized
+    }
+
+    /// Returns a copy of the identifier with pseudonyms in place of its IDs and its coordinates
+    /// moved by the tile's shift, from `tile_shift`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::anonymize::Anonymizer;
+    ///
+    /// fn main() {
+    ///     let anonymizer = Anonymizer::new(b"a secret key");
+    ///     let first = illumina_coordinates::parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+    ///     let second = illumina_coordinates::parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4180:8900 1:N:0:0").unwrap();
+    ///     let (first, second) = (anonymizer.anonymize_with_shift(&first, 1000), anonymizer.anonymize_with_shift(&second, 1000));
+    ///     assert_eq!((second.x - first.x, first.y - second.y), (53, 49));
+    /// }
+    /// ```
+    pub fn anonymize_with_shift(&self, seq_id: &SequenceIdentifier, max_shift: u16) -> SequenceIdentifier {
+        let mut anonymized = self.anonymize(seq_id);
+        let (x_shift, y_shift) = self.tile_shift(seq_id, max_shift);
+        // Real coordinates are far below the limit, so saturating never changes a distance in
+        // practice
+        anonymized.x = seq_id.x.saturating_add(x_shift);
+        anonymized.y = seq_id.y.saturating_add(y_shift);
+        anonymized
+    }
+
+    /// The amounts, each from 0 to `max_shift`, that `anonymize_with_shift` adds to the x- and
+    /// y-coordinates of reads on the identifier's tile. They're derived from the key and the real
+    /// run and tile, so the mates of a read get the same shift.
+    pub fn tile_shift(&self, seq_id: &SequenceIdentifier, max_shift: u16) -> (u16, u16) {
+        let mut state = hash(self.key, seq_id.sequencer_id.as_bytes());
+        state = hash(state, seq_id.flow_cell_id.as_bytes());
+        state = hash(state, &seq_id.run_count.to_le_bytes());
+        state = hash(state, &[seq_id.lane]);
+        state = hash(state, &seq_id.tile_field().to_le_bytes());
+        let range = u64::from(max_shift) + 1;
+        let x_shift = (state % range) as u16;
+        let y_shift = (mix(state) % range) as u16;
+        (x_shift, y_shift)
+    }
+
+    /// Replaces each digit and letter of the ID within the range with another of the same kind,
+    /// chosen by hashing the whole ID
+    fn replace(&self, id: &str, range: (usize, usize)) -> String {
+        let mut state = hash(self.key, id.as_bytes());
+        id.char_indices().map(|(index, c)| {
+            if index < range.0 || index >= range.1 {
+                return c;
+            }
+            state = mix(state);
+            let (base, count) = match c {
+                '0'..='9' => (b'0', 10),
+                'A'..='Z' => (b'A', 26),
+                'a'..='z' => (b'a', 26),
+                _ => return c
+            };
+            (base + (state % count) as u8) as char
+        }).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_anonymize() {
+        let anonymizer = Anonymizer::new(b"key");
+        for &name in &["A00123:8:HFWJ3DSXX:1:1101:1000:1000", "NS500358:204:HTN5KAFXY:1:11101:20886:1073",
+                       "FS10000456:3:BNT40323-1809:1:1101:1000:1000", "MN00123:5:000H2WHFJ:1:11102:1000:1000",
+                       "my-sequencer:1:my-flow-cell:1:1101:1000:1000"] {
+            let seq_id = parse_sequence_identifier(&format!("@{} 2:Y:0:ACGT", name)).unwrap();
+            let anonymized = anonymizer.anonymize(&seq_id);
+            assert_ne!(anonymized.sequencer_id, seq_id.sequencer_id, "{}", name);
+            assert_ne!(anonymized.flow_cell_id, seq_id.flow_cell_id, "{}", name);
+            assert_eq!(anonymized.instrument(), seq_id.instrument(), "{}", name);
+            assert_eq!(anonymized.flow_cell_type(), seq_id.flow_cell_type(), "{}", name);
+            assert_eq!(anonymized.tile_id(), seq_id.tile_id());
+            assert_eq!((anonymized.read, anonymized.is_filtered, &anonymized.sample), (2, true, &seq_id.sample));
+            assert_eq!(parse_sequence_identifier(&anonymized.to_string()).unwrap(), anonymized);
+        }
+        let first = parse_sequence_identifier("@A00123:8:HFWJ3DSXX:1:1101:1000:1000 1:N:0:0").unwrap();
+        let second = parse_sequence_identifier("@A00123:8:HFWJ3DSXX:2:2202:3000:3000 1:N:0:0").unwrap();
+        let (first, second) = (anonymizer.anonymize(&first), anonymizer.anonymize(&second));
+        assert_eq!((&first.sequencer_id, first.run_count, &first.flow_cell_id),
+                   (&second.sequencer_id, second.run_count, &second.flow_cell_id));
+        assert_ne!(Anonymizer::new(b"another key").anonymize(&first).flow_cell_id, first.flow_cell_id);
+    }
+
+    #[test]
+    fn test_anonymize_with_shift() {
+        let anonymizer = Anonymizer::new(b"key");
+        let read = |name: &str, comment: &str| parse_sequence_identifier(&format!("@A00123:8:HFWJ3DSXX:{} {}", name, comment)).unwrap();
+        let (first, second) = (read("1:1101:1000:5000", "1:N:0:0"), read("1:1101:1040:4990", "1:N:0:0"));
+        let (shifted_first, shifted_second) = (anonymizer.anonymize_with_shift(&first, 500), anonymizer.anonymize_with_shift(&second, 500));
+        assert_eq!((shifted_second.x - shifted_first.x, shifted_first.y - shifted_second.y), (40, 10));
+        assert_eq!(shifted_first.sequencer_id, anonymizer.anonymize(&first).sequencer_id);
+        assert_eq!(anonymizer.anonymize_with_shift(&read("1:1101:1000:5000", "2:N:0:0"), 500).x, shifted_first.x);
+
+        // Tiles get different shifts, each within the limit
+        let shifts: Vec<(u16, u16)> = (1..=20)
+            .map(|tile| anonymizer.tile_shift(&read(&format!("1:11{:02}:1000:1000", tile), "1:N:0:0"), 500))
+            .collect();
+        assert!(shifts.iter().all(|&(x, y)| x <= 500 && y <= 500));
+        assert!(shifts.iter().any(|&shift| shift != shifts[0]));
+        assert_eq!(anonymizer.tile_shift(&first, 0), (0, 0));
+        assert_eq!(anonymizer.anonymize_with_shift(&read("1:1101:65535:1", "1:N:0:0"), 500).x, 65535);
+    }
+}
@@ -0,0 +1,588 @@
+//! Writes parsed sequence identifiers as an Arrow IPC stream, so they can be piped into Arrow
+//! consumers such as DataFusion, DuckDB or another process without going through a file.
+//!
+//! The stream starts with the schema and is followed by one record batch per `batch_size` rows,
+//! each flushed as it's written, and an end-of-stream marker. Each field of `SequenceIdentifier`
+//! becomes a column of the same name: integer fields are unsigned integers of the same width, the
+//! camera is nullable, the filter flag is a boolean and the sample is a string. Buffers aren't
+//! compressed.
+//!
+//! The output can be read with e.g. `pyarrow.ipc.open_stream` in Python or `StreamReader` in the
+//! Rust `arrow` crate.
+//!
+//! The tests in `compat/` read the output back with that `StreamReader` and check the type and
+//! value of every column.
+
+use std::io::{self, Write};
+use super::SequenceIdentifier;
+
+/// The number of rows buffered in memory before they're written out as a record batch
+pub const DEFAULT_BATCH_SIZE: usize = 65_536;
+
+/// Precedes the length of each message
+const CONTINUATION: [u8; 4] = [0xff; 4];
+const METADATA_V5: i16 = 4;
+
+// Message header types
+const SCHEMA: u8 = 1;
+const RECORD_BATCH: u8 = 3;
+
+#[derive(Clone, Copy, PartialEq)]
+enum DataType {
+    UInt8,
+    UInt16,
+    Bool,
+    Utf8
+}
+
+impl DataType {
+    /// The value of the `Type` union's tag
+    fn type_type(self) -> u8 {
+        match self {
+            DataType::UInt8 | DataType::UInt16 => 2,
+            DataType::Utf8 => 5,
+            DataType::Bool => 6
+        }
+    }
+}
+
+struct Column {
+    name: &'static str,
+    data_type: DataType,
+    nullable: bool
+}
+
+const COLUMNS: [Column; 14] = [
+    Column { name: "sequencer_id", data_type: DataType::Utf8, nullable: false },
+    Column { name: "run_count", data_type: DataType::UInt16, nullable: false },
+    Column { name: "flow_cell_id", data_type: DataType::Utf8, nullable: false },
+    Column { name: "lane", data_type: DataType::UInt8, nullable: false },
+    Column { name: "side", data_type: DataType::UInt8, nullable: false },
+    Column { name: "swath", data_type: DataType::UInt8, nullable: false },
+    Column { name: "camera", data_type: DataType::UInt8, nullable: true },
+    Column { name: "tile", data_type: DataType::UInt8, nullable: false },
+    Column { name: "x", data_type: DataType::UInt16, nullable: false },
+    Column { name: "y", data_type: DataType::UInt16, nullable: false },
+    Column { name: "read", data_type: DataType::UInt8, nullable: false },
+    Column { name: "is_filtered", data_type: DataType::Bool, nullable: false },
+    Column { name: "control_number", data_type: DataType::UInt16, nullable: false },
+    Column { name: "sample", data_type: DataType::Utf8, nullable: false },
+];
+
+const CAMERA: usize = 6;
+const IS_FILTERED: usize = 11;
+
+/// Writes sequence identifiers as an Arrow IPC stream. Rows are buffered and written in record
+/// batches; `finish` must be called to write the last batch and the end of the stream.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::arrow::ArrowWriter;
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     let mut writer = ArrowWriter::new(vec![]).unwrap();
+///     writer.write(&seq_id).unwrap();
+///     let output = writer.finish().unwrap();
+///     assert!(output.starts_with(&[0xff; 4]) && output.ends_with(&[0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]));
+/// }
+/// ```
+pub struct ArrowWriter<W: Write> {
+    writer: W,
+    batch_size: usize,
+    values: Vec<Vec<u8>>,
+    offsets: Vec<Vec<u8>>,
+    is_filtered: Vec<bool>,
+    has_camera: Vec<bool>,
+    rows: usize
+}
+
+impl<W: Write> ArrowWriter<W> {
+    /// Creates a writer that uses the default batch size, and writes the schema
+    pub fn new(writer: W) -> io::Result<ArrowWriter<W>> {
+        ArrowWriter::with_batch_size(writer, DEFAULT_BATCH_SIZE)
+    }
+
+    /// Creates a writer that writes a record batch every `batch_size` rows, and writes the schema
+    pub fn with_batch_size(mut writer: W, batch_size: usize) -> io::Result<ArrowWriter<W>> {
+        write_message(&mut writer, &schema_message(), &[])?;
+        let mut writer = ArrowWriter {
+            writer,
+            batch_size: batch_size.max(1),
+            values: COLUMNS.iter().map(|_| vec![]).collect(),
+            offsets: COLUMNS.iter().map(|_| vec![]).collect(),
+            is_filtered: vec![],
+            has_camera: vec![],
+            rows: 0
+        };
+        writer.clear_offsets();
+        Ok(writer)
+    }
+
+    /// Writes a single sequence identifier as a row
+    pub fn write(&mut self, seq_id: &SequenceIdentifier) -> io::Result<()> {
+        write_string(&mut self.values[0], &mut self.offsets[0], &seq_id.sequencer_id);
+        self.values[1].extend_from_slice(&seq_id.run_count.to_le_bytes());
+        write_string(&mut self.values[2], &mut self.offsets[2], &seq_id.flow_cell_id);
+        self.values[3].push(seq_id.lane);
+        self.values[4].push(seq_id.side);
+        self.values[5].push(seq_id.swath);
+        // Null slots still take up space, with an undefined value
+        self.values[CAMERA].push(seq_id.camera.unwrap_or(0));
+        self.has_camera.push(seq_id.camera.is_some());
+        self.values[7].push(seq_id.tile);
+        self.values[8].extend_from_slice(&seq_id.x.to_le_bytes());
+        self.values[9].extend_from_slice(&seq_id.y.to_le_bytes());
+        self.values[10].push(seq_id.read);
+        self.is_filtered.push(seq_id.is_filtered);
+        self.values[12].extend_from_slice(&seq_id.control_number.to_le_bytes());
+        write_string(&mut self.values[13], &mut self.offsets[13], &seq_id.sample.to_string());
+        self.rows += 1;
+        if self.rows >= self.batch_size {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    /// Writes any buffered rows and the end-of-stream marker, and returns the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.rows > 0 {
+            self.write_batch()?;
+        }
+        self.writer.write_all(&CONTINUATION)?;
+        self.writer.write_all(&[0; 4])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// String columns start with the offset of their first value
+    fn clear_offsets(&mut self) {
+        for (column, offsets) in COLUMNS.iter().zip(&mut self.offsets) {
+            offsets.clear();
+            if column.data_type == DataType::Utf8 {
+                offsets.extend_from_slice(&0i32.to_le_bytes());
+            }
+        }
+    }
+
+    fn write_batch(&mut self) -> io::Result<()> {
+        self.values[IS_FILTERED] = pack_bits(&self.is_filtered);
+        self.is_filtered.clear();
+        let nulls = self.has_camera.iter().filter(|&&has_camera| !has_camera).count();
+
+        let mut nodes = vec![];
+        let mut buffers = vec![];
+        let mut body = vec![];
+        for (index, column) in COLUMNS.iter().enumerate() {
+            let null_count = if index == CAMERA { nulls } else { 0 };
+            nodes.push((self.rows as u64, null_count as u64));
+            // The validity bitmap can be left out when there are no nulls
+            let validity = if null_count > 0 { pack_bits(&self.has_camera) } else { vec![] };
+            push_buffer(&mut body, &mut buffers, &validity);
+            if column.data_type == DataType::Utf8 {
+                push_buffer(&mut body, &mut buffers, &self.offsets[index]);
+            }
+            push_buffer(&mut body, &mut buffers, &self.values[index]);
+        }
+
+        let mut builder = FlatBufferBuilder::new();
+        let nodes = builder.pairs(&nodes);
+        let buffers = builder.pairs(&buffers);
+        let batch = builder.table(&[(0, Field::I64(self.rows as i64)), (1, Field::Offset(nodes)), (2, Field::Offset(buffers))]);
+        let metadata = message(builder, RECORD_BATCH, batch, body.len());
+        write_message(&mut self.writer, &metadata, &body)?;
+        self.writer.flush()?;
+
+        for values in &mut self.values {
+            values.clear();
+        }
+        self.clear_offsets();
+        self.has_camera.clear();
+        self.rows = 0;
+        Ok(())
+    }
+}
+
+/// Writes all of the sequence identifiers as an Arrow IPC stream, and returns the underlying
+/// writer
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::arrow::write_arrow;
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_ids = vec![illumina_coordinates::parse_sequence_identifier(line).unwrap()];
+///     let bytes = write_arrow(vec![], &seq_ids).unwrap();
+///     assert!(bytes.starts_with(&[0xff; 4]));
+/// }
+/// ```
+pub fn write_arrow<'a, W, I>(writer: W, seq_ids: I) -> io::Result<W>
+    where W: Write, I: IntoIterator<Item=&'a SequenceIdentifier> {
+    let mut writer = ArrowWriter::new(writer)?;
+    for seq_id in seq_ids {
+        writer.write(seq_id)?;
+    }
+    writer.finish()
+}
+
+/// Packs booleans into bytes, eight at a time, least significant bit first
+fn pack_bits(values: &[bool]) -> Vec<u8> {
+    values.chunks(8)
+        .map(|chunk| chunk.iter().enumerate().fold(0u8, |byte, (i, &value)| byte | (value as u8) << i))
+        .collect()
+}
+
+fn write_string(values: &mut Vec<u8>, offsets: &mut Vec<u8>, value: &str) {
+    values.extend_from_slice(value.as_bytes());
+    offsets.extend_from_slice(&(values.len() as i32).to_le_bytes());
+}
+
+/// Appends a buffer to a record batch body, padded so that the next buffer is aligned to 8 bytes,
+/// and records its offset and length
+fn push_buffer(body: &mut Vec<u8>, buffers: &mut Vec<(u64, u64)>, buffer: &[u8]) {
+    buffers.push((body.len() as u64, buffer.len() as u64));
+    body.extend_from_slice(buffer);
+    body.resize(padded(body.len()), 0);
+}
+
+fn padded(size: usize) -> usize {
+    (size + 7) & !7
+}
+
+/// Writes an encapsulated message: a continuation marker, the length of the metadata, the
+/// metadata and the body. The metadata is padded so that the body is aligned to 8 bytes.
+fn write_message<W: Write>(writer: &mut W, metadata: &[u8], body: &[u8]) -> io::Result<()> {
+    writer.write_all(&CONTINUATION)?;
+    writer.write_all(&(padded(metadata.len()) as i32).to_le_bytes())?;
+    writer.write_all(metadata)?;
+    writer.write_all(&vec![0; padded(metadata.len()) - metadata.len()])?;
+    writer.write_all(body)
+}
+
+fn schema_message() -> Vec<u8> {
+    let mut builder = FlatBufferBuilder::new();
+    let fields: Vec<usize> = COLUMNS.iter().map(|column| {
+        let name = builder.string(column.name);
+        let data_type = match column.data_type {
+            DataType::UInt8 => builder.table(&[(0, Field::I32(8)), (1, Field::Bool(false))]),
+            DataType::UInt16 => builder.table(&[(0, Field::I32(16)), (1, Field::Bool(false))]),
+            DataType::Bool | DataType::Utf8 => builder.table(&[])
+        };
+        let children = builder.offsets(&[]);
+        builder.table(&[(0, Field::Offset(name)), (1, Field::Bool(column.nullable)),
+                        (2, Field::U8(column.data_type.type_type())), (3, Field::Offset(data_type)),
+                        (5, Field::Offset(children))])
+    }).collect();
+    let fields = builder.offsets(&fields);
+    let schema = builder.table(&[(0, Field::I16(0)), (1, Field::Offset(fields))]);
+    message(builder, SCHEMA, schema, 0)
+}
+
+fn message(mut builder: FlatBufferBuilder, header_type: u8, header: usize, body_length: usize) -> Vec<u8> {
+    let message = builder.table(&[(0, Field::I16(METADATA_V5)), (1, Field::U8(header_type)),
+                                  (2, Field::Offset(header)), (3, Field::I64(body_length as i64))]);
+    builder.finish(message)
+}
+
+/// A field of a FlatBuffers table
+enum Field {
+    Bool(bool),
+    U8(u8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    /// The position of an object that's already been built
+    Offset(usize)
+}
+
+impl Field {
+    fn size(&self) -> usize {
+        match *self {
+            Field::Bool(_) | Field::U8(_) => 1,
+            Field::I16(_) => 2,
+            Field::I32(_) | Field::Offset(_) => 4,
+            Field::I64(_) => 8
+        }
+    }
+}
+
+/// Builds the FlatBuffers that hold message metadata. Like the FlatBuffers library, it builds back
+/// to front, so that the offsets in each object point forward to objects already built. Positions
+/// are measured from the end of the buffer, which doesn't move as objects are prepended, and
+/// objects are aligned relative to the end; `finish` pads the whole buffer to a multiple of 8
+/// bytes so they're aligned from the start too.
+struct FlatBufferBuilder {
+    bytes: Vec<u8>
+}
+
+impl FlatBufferBuilder {
+    fn new() -> FlatBufferBuilder {
+        FlatBufferBuilder { bytes: vec![] }
+    }
+
+    fn position(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn prepend(&mut self, bytes: &[u8]) {
+        self.bytes.splice(0..0, bytes.iter().cloned());
+    }
+
+    /// Pads so that the start of the next `size` bytes prepended is aligned
+    fn align(&mut self, size: usize, alignment: usize) {
+        let padding = (alignment - (self.position() + size) % alignment) % alignment;
+        self.prepend(&vec![0; padding]);
+    }
+
+    /// The offset to write at a position to point to an object
+    fn offset_to(at: usize, object: usize) -> [u8; 4] {
+        ((at - object) as u32).to_le_bytes()
+    }
+
+    /// Builds a NUL-terminated string with a length prefix
+    fn string(&mut self, value: &str) -> usize {
+        self.align(value.len() + 5, 4);
+        self.prepend(&[0]);
+        self.prepend(value.as_bytes());
+        self.prepend(&(value.len() as u32).to_le_bytes());
+        self.position()
+    }
+
+    /// Builds a vector of offsets to objects
+    fn offsets(&mut self, objects: &[usize]) -> usize {
+        self.align(4 * objects.len() + 4, 4);
+        for &object in objects.iter().rev() {
+            let at = self.position() + 4;
+            self.prepend(&FlatBufferBuilder::offset_to(at, object));
+        }
+        self.prepend(&(objects.len() as u32).to_le_bytes());
+        self.position()
+    }
+
+    /// Builds a vector of structs of two 64-bit integers, such as `FieldNode` and `Buffer`
+    fn pairs(&mut self, pairs: &[(u64, u64)]) -> usize {
+        self.align(16 * pairs.len(), 8);
+        for &(first, second) in pairs.iter().rev() {
+            self.prepend(&second.to_le_bytes());
+            self.prepend(&first.to_le_bytes());
+        }
+        self.prepend(&(pairs.len() as u32).to_le_bytes());
+        self.position()
+    }
+
+    /// Builds a table from its fields, each given with its slot in the schema, preceded by its
+    /// vtable
+    fn table(&mut self, fields: &[(usize, Field)]) -> usize {
+        // Larger fields go first so that none needs padding except the first 8-byte field
+        let mut order: Vec<&(usize, Field)> = fields.iter().collect();
+        order.sort_by_key(|slot_and_field| !slot_and_field.1.size());
+        let mut size = 4;
+        let mut field_offsets = vec![0u16; fields.iter().map(|&(slot, _)| slot + 1).max().unwrap_or(0)];
+        for &&(slot, ref field) in &order {
+            size += (field.size() - size % field.size()) % field.size();
+            field_offsets[slot] = size as u16;
+            size += field.size();
+        }
+        self.align(size, 8);
+        let start = self.position() + size;
+        let vtable_size = 4 + 2 * field_offsets.len();
+
+        let mut table = vec![0; size];
+        // The vtable is immediately before the table
+        table[..4].copy_from_slice(&(vtable_size as i32).to_le_bytes());
+        for &(slot, ref field) in fields {
+            let offset = usize::from(field_offsets[slot]);
+            let bytes = match *field {
+                Field::Bool(value) => vec![value as u8],
+                Field::U8(value) => vec![value],
+                Field::I16(value) => value.to_le_bytes().to_vec(),
+                Field::I32(value) => value.to_le_bytes().to_vec(),
+                Field::I64(value) => value.to_le_bytes().to_vec(),
+                Field::Offset(object) => FlatBufferBuilder::offset_to(start - offset, object).to_vec()
+            };
+            table[offset..offset + bytes.len()].copy_from_slice(&bytes);
+        }
+        self.prepend(&table);
+
+        let mut vtable = vec![];
+        vtable.extend_from_slice(&(vtable_size as u16).to_le_bytes());
+        vtable.extend_from_slice(&(size as u16).to_le_bytes());
+        for offset in field_offsets {
+            vtable.extend_from_slice(&offset.to_le_bytes());
+        }
+        self.prepend(&vtable);
+        start
+    }
+
+    /// Adds the offset to the root table, and returns the buffer
+    fn finish(mut self, root: usize) -> Vec<u8> {
+        self.align(4, 8);
+        let at = self.position() + 4;
+        self.prepend(&FlatBufferBuilder::offset_to(at, root));
+        self.bytes
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    fn u32_at(bytes: &[u8], position: usize) -> u32 {
+        let mut value = [0u8; 4];
+        value.copy_from_slice(&bytes[position..position + 4]);
+        u32::from_le_bytes(value)
+    }
+
+    fn u16_at(bytes: &[u8], position: usize) -> u16 {
+        u16::from(bytes[position]) | u16::from(bytes[position + 1]) << 8
+    }
+
+    /// A table in a finished FlatBuffer
+    struct Table<'a> {
+        bytes: &'a [u8],
+        start: usize
+    }
+
+    impl<'a> Table<'a> {
+        fn root(bytes: &'a [u8]) -> Table<'a> {
+            Table { bytes, start: u32_at(bytes, 0) as usize }
+        }
+
+        /// The position of the field in the slot
+        fn field(&self, slot: usize) -> Option<usize> {
+            let vtable = self.start - u32_at(self.bytes, self.start) as usize;
+            if 4 + 2 * slot >= usize::from(u16_at(self.bytes, vtable)) {
+                return None;
+            }
+            match u16_at(self.bytes, vtable + 4 + 2 * slot) {
+                0 => None,
+                offset => Some(self.start + usize::from(offset))
+            }
+        }
+
+        /// The position of the object the field in the slot points to
+        fn object(&self, slot: usize) -> usize {
+            let at = self.field(slot).unwrap();
+            at + u32_at(self.bytes, at) as usize
+        }
+
+        fn table(&self, slot: usize) -> Table<'a> {
+            Table { bytes: self.bytes, start: self.object(slot) }
+        }
+    }
+
+    /// Splits a stream into the metadata and body of each message
+    fn messages(stream: &[u8]) -> Vec<(&[u8], &[u8])> {
+        let mut messages = vec![];
+        let mut position = 0;
+        loop {
+            assert_eq!(&stream[position..position + 4], &CONTINUATION);
+            let length = u32_at(stream, position + 4) as usize;
+            position += 8;
+            if length == 0 {
+                assert_eq!(position, stream.len());
+                return messages;
+            }
+            let metadata = &stream[position..position + length];
+            let message = Table::root(metadata);
+            let body_length = message.field(3).map_or(0, |at| u32_at(metadata, at) as usize);
+            messages.push((metadata, &stream[position + length..position + length + body_length]));
+            position += length + body_length;
+        }
+    }
+
+    #[test]
+    fn test_flat_buffer_builder() {
+        let mut builder = FlatBufferBuilder::new();
+        let name = builder.string("ab");
+        let root = builder.table(&[(0, Field::U8(7)), (1, Field::I64(-2)), (3, Field::Offset(name))]);
+        let bytes = builder.finish(root);
+        assert_eq!(bytes.len() % 8, 0);
+        let table = Table::root(&bytes);
+        assert_eq!(table.start % 4, 0);
+        assert_eq!(bytes[table.field(0).unwrap()], 7);
+        let long = table.field(1).unwrap();
+        assert_eq!(long % 8, 0);
+        assert_eq!(&bytes[long..long + 8], &(-2i64).to_le_bytes());
+        assert_eq!(table.field(2), None);
+        assert_eq!(table.field(4), None);
+        let name = table.object(3);
+        assert_eq!(&bytes[name..name + 7], &[2, 0, 0, 0, b'a', b'b', 0]);
+    }
+
+    #[test]
+    fn test_schema() {
+        let output = ArrowWriter::new(vec![]).unwrap().finish().unwrap();
+        assert_eq!(write_arrow(vec![], &[]).unwrap(), output);
+        let messages = messages(&output);
+        assert_eq!(messages.len(), 1);
+        let message = Table::root(messages[0].0);
+        assert_eq!(message.bytes[message.field(1).unwrap()], SCHEMA);
+        let fields = message.table(2).object(1);
+        assert_eq!(u32_at(message.bytes, fields) as usize, COLUMNS.len());
+        let camera_at = fields + 4 + 4 * CAMERA;
+        let camera = Table { bytes: message.bytes, start: camera_at + u32_at(message.bytes, camera_at) as usize };
+        let name = camera.object(0);
+        assert_eq!(&message.bytes[name..name + 10], b"\x06\0\0\0camera");
+        assert_eq!(message.bytes[camera.field(1).unwrap()], 1);
+        assert_eq!(message.bytes[camera.field(2).unwrap()], 2);
+        assert_eq!(u32_at(message.bytes, camera.table(3).field(0).unwrap()), 8);
+    }
+
+    #[test]
+    fn test_write_arrow() {
+        let mut writer = ArrowWriter::with_batch_size(vec![], 2).unwrap();
+        for line in &["@NB501234:12:H2VGLBGXY:4:23612:11289:19567 1:N:0:1", "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:Y:0:0",
+                      "@M03745:11:000000000-B54L5:1:2108:4128:8950 2:Y:0:ACGT"] {
+            writer.write(&parse_sequence_identifier(line).unwrap()).unwrap();
+        }
+        let output = writer.finish().unwrap();
+        let messages = messages(&output);
+        assert_eq!(messages.len(), 3);
+        for &(metadata, body) in &messages[1..] {
+            assert_eq!(metadata.len() % 8, 0);
+            assert_eq!(body.len() % 8, 0);
+            assert_eq!(Table::root(metadata).bytes[Table::root(metadata).field(1).unwrap()], RECORD_BATCH);
+        }
+
+        let (metadata, body) = messages[1];
+        let batch = Table::root(metadata).table(2);
+        assert_eq!(u32_at(metadata, batch.field(0).unwrap()), 2);
+        // Each node is a length and a null count; only the camera has a null
+        let nodes = batch.object(1);
+        assert_eq!(u32_at(metadata, nodes) as usize, COLUMNS.len());
+        assert_eq!(u32_at(metadata, nodes + 4 + 16 * CAMERA + 8), 1);
+        assert_eq!(u32_at(metadata, nodes + 4 + 8), 0);
+        // Strings have a validity, offsets and data buffer, the other columns a validity and data buffer
+        let buffers = batch.object(2);
+        assert_eq!(u32_at(metadata, buffers), 31);
+        let buffer = |index: usize| {
+            let at = buffers + 4 + 16 * index;
+            let offset = u32_at(metadata, at) as usize;
+            &body[offset..offset + u32_at(metadata, at + 8) as usize]
+        };
+        assert_eq!(buffer(0), &[]);
+        assert_eq!(buffer(1), &[0, 0, 0, 0, 8, 0, 0, 0, 14, 0, 0, 0]);
+        assert_eq!(buffer(2), b"NB501234M03745");
+        assert_eq!(buffer(4), &[12, 0, 11, 0]);
+        assert_eq!(buffer(9), &[4, 1]);
+        assert_eq!((buffer(14), buffer(15)), (&[0b01][..], &[6, 0][..]));
+        assert_eq!(buffer(19), &[0x19, 0x2c, 0x1f, 0x10]);
+        assert_eq!((buffer(24), buffer(25)), (&[][..], &[0b10][..]));
+        assert_eq!(buffer(29), &[0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(buffer(30), b"10");
+
+        let (metadata, body) = messages[2];
+        let batch = Table::root(metadata).table(2);
+        assert_eq!(u32_at(metadata, batch.field(0).unwrap()), 1);
+        assert!(body.windows(4).any(|window| window == b"ACGT"));
+    }
+}
@@ -0,0 +1,216 @@
+//! Detection of spatial artifacts, such as bubbles and smudges, from the positions of bad reads.
+//!
+//! Reads fail the filter or come out as duplicates at a fairly even rate across a healthy tile.
+//! An artifact on the flow cell makes the affected area stand out: a bubble or smudge produces a
+//! patch where most reads are bad. Each tile is divided into a grid with a `DensityHistogram`,
+//! bins where the share of bad reads is too high are flagged, and neighbouring flagged bins are
+//! reported together as one suspected artifact.
+
+use std::collections::{BTreeMap, HashSet};
+use density::DensityHistogram;
+use geometry::TileExtent;
+use super::{SequenceIdentifier, TileId};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Settings for finding artifacts
+pub struct ArtifactOptions {
+    /// The number of bins each tile is divided into along x and along y
+    pub bins: usize,
+    /// The smallest share of bad reads in a bin that flags it
+    pub min_bad_fraction: f64,
+    /// The fewest reads a bin must have to be flagged, so that sparse bins don't flag by chance
+    pub min_reads_per_bin: u32,
+    /// The fewest neighbouring flagged bins that are reported as an artifact
+    pub min_bins: usize
+}
+
+impl Default for ArtifactOptions {
+    fn default() -> ArtifactOptions {
+        ArtifactOptions { bins: 20, min_bad_fraction: 0.5, min_reads_per_bin: 10, min_bins: 2 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// An area of a tile where an unusual share of reads are bad
+pub struct SuspectedArtifact {
+    /// The tile the artifact is on
+    pub tile: TileId,
+    /// The smallest x-coordinate of the area
+    pub x_min: u16,
+    /// The largest x-coordinate of the area
+    pub x_max: u16,
+    /// The smallest y-coordinate of the area
+    pub y_min: u16,
+    /// The largest y-coordinate of the area
+    pub y_max: u16,
+    /// The number of reads in the flagged bins
+    pub reads: u64,
+    /// The number of bad reads in the flagged bins
+    pub bad_reads: u64
+}
+
+/// Collects the positions of reads, and of bad reads among them, to find suspected artifacts
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::artifacts::{ArtifactDetector, ArtifactOptions};
+/// use illumina_coordinates::geometry;
+///
+/// fn main() {
+///     let mut detector = ArtifactDetector::new(geometry::MISEQ, ArtifactOptions::default());
+///     for x in (1000..30000).step_by(50) {
+///         for y in (1000..30000).step_by(500) {
+///             // Reads within a bubble fail the filter
+///             let filter = if x > 5000 && x < 8000 && y > 20000 && y < 24000 { "Y" } else { "N" };
+///             let line = format!("@M03745:11:000000000-B54L5:1:2108:{}:{} 1:{}:0:0", x, y, filter);
+///             detector.add_read(&illumina_coordinates::parse_sequence_identifier(&line).unwrap());
+///         }
+///     }
+///     let artifacts = detector.artifacts();
+///     assert_eq!(artifacts.len(), 1);
+///     assert!(artifacts[0].x_min < 6500 && artifacts[0].x_max > 6500);
+/// }
+/// ```
+pub struct ArtifactDetector {
+    extent: TileExtent,
+    options: ArtifactOptions,
+    tiles: BTreeMap<TileId, (DensityHistogram, DensityHistogram)>
+}
+
+impl ArtifactDetector {
+    /// Creates a detector for tiles with the given extent
+    pub fn new(extent: TileExtent, options: ArtifactOptions) -> ArtifactDetector {
+        ArtifactDetector { extent, options, tiles: BTreeMap::new() }
+    }
+
+    /// Counts a read, which is bad if it failed the filter
+    pub fn add_read(&mut self, seq_id: &SequenceIdentifier) {
+        self.add(seq_id, seq_id.is_filtered);
+    }
+
+    /// Counts a read, which is bad if `is_bad` is set, for example because it's a duplicate
+    pub fn add(&mut self, seq_id: &SequenceIdentifier, is_bad: bool) {
+        let (extent, bins) = (self.extent, self.options.bins);
+        let (all, bad) = self.tiles.entry(seq_id.tile_id())
+            .or_insert_with(|| (DensityHistogram::new(extent, bins, bins), DensityHistogram::new(extent, bins, bins)));
+        all.add(seq_id.x, seq_id.y);
+        if is_bad {
+            bad.add(seq_id.x, seq_id.y);
+        }
+    }
+
+    /// The suspected artifacts, in order of tile and then of position
+    pub fn artifacts(&self) -> Vec<SuspectedArtifact> {
+        let mut artifacts = vec![];
+        for (&tile, (all, bad)) in &self.tiles {
+            let flagged = |column: usize, row: usize| {
+                let reads = all.count(column, row);
+                reads >= self.options.min_reads_per_bin
+                    && f64::from(bad.count(column, row)) >= self.options.min_bad_fraction * f64::from(reads)
+            };
+            let mut seen = HashSet::new();
+            for row in 0..all.rows() {
+                for column in 0..all.columns() {
+                    if seen.contains(&(column, row)) || !flagged(column, row) {
+                        continue;
+                    }
+                    // Gather the flagged bins connected to this one
+                    let mut area = vec![];
+                    let mut stack = vec![(column, row)];
+                    seen.insert((column, row));
+                    while let Some((column, row)) = stack.pop() {
+                        area.push((column, row));
+                        let mut neighbours = vec![(column + 1, row), (column, row + 1)];
+                        if column > 0 {
+                            neighbours.push((column - 1, row));
+                        }
+                        if row > 0 {
+                            neighbours.push((column, row - 1));
+                        }
+                        for (column, row) in neighbours {
+                            if column < all.columns() && row < all.rows() && !seen.contains(&(column, row))
+                                && flagged(column, row) {
+                                seen.insert((column, row));
+                                stack.push((column, row));
+                            }
+                        }
+                    }
+                    if area.len() >= self.options.min_bins {
+                        artifacts.push(describe(tile, all, bad, &area));
+                    }
+                }
+            }
+        }
+        artifacts
+    }
+}
+
+/// Summarizes an area of flagged bins
+fn describe(tile: TileId, all: &DensityHistogram, bad: &DensityHistogram, area: &[(usize, usize)]) -> SuspectedArtifact {
+    let mut artifact = SuspectedArtifact {
+        tile,
+        x_min: u16::MAX,
+        x_max: 0,
+        y_min: u16::MAX,
+        y_max: 0,
+        reads: 0,
+        bad_reads: 0
+    };
+    for &(column, row) in area {
+        let (x_min, x_max, y_min, y_max) = all.bin_bounds(column, row);
+        artifact.x_min = artifact.x_min.min(x_min);
+        artifact.x_max = artifact.x_max.max(x_max);
+        artifact.y_min = artifact.y_min.min(y_min);
+        artifact.y_max = artifact.y_max.max(y_max);
+        artifact.reads += u64::from(all.count(column, row));
+        artifact.bad_reads += u64::from(bad.count(column, row));
+    }
+    artifact
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    fn read(tile: u16, x: u16, y: u16) -> SequenceIdentifier {
+        parse_sequence_identifier(&format!("@M03745:11:000000000-B54L5:1:{}:{}:{} 1:N:0:0", tile, x, y)).unwrap()
+    }
+
+    #[test]
+    fn test_artifacts() {
+        let extent = TileExtent { x_min: 0, x_max: 99, y_min: 0, y_max: 99 };
+        let options = ArtifactOptions { bins: 10, min_bad_fraction: 0.5, min_reads_per_bin: 5, min_bins: 2 };
+        let mut detector = ArtifactDetector::new(extent, options);
+        for x in 0..100 {
+            for y in 0..100 {
+                // Two bad areas, one only a single bin, on tile 1101 and none on 1102
+                let bad = ((20..40).contains(&x) && (50..60).contains(&y)) || (x >= 90 && y >= 90);
+                detector.add(&read(1101, x, y), bad);
+                detector.add(&read(1102, x, y), false);
+            }
+        }
+        let artifacts = detector.artifacts();
+        assert_eq!(artifacts, vec![SuspectedArtifact {
+            tile: read(1101, 0, 0).tile_id(),
+            x_min: 20,
+            x_max: 39,
+            y_min: 50,
+            y_max: 59,
+            reads: 200,
+            bad_reads: 200
+        }]);
+    }
+
+    #[test]
+    fn test_sparse_bins_are_not_flagged() {
+        let options = ArtifactOptions { min_reads_per_bin: 2, ..ArtifactOptions::default() };
+        let mut detector = ArtifactDetector::new(TileExtent { x_min: 0, x_max: 99, y_min: 0, y_max: 99 }, options);
+        detector.add(&read(1101, 1, 1), true);
+        detector.add(&read(1101, 6, 1), true);
+        assert!(detector.artifacts().is_empty());
+    }
+}
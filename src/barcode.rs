@@ -0,0 +1,608 @@
+//! Utilities for matching index sequences (barcodes) against the indexes expected for a run,
+//! allowing for sequencing errors.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::str::{self, FromStr};
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sample_sheet::SampleSheet;
+use super::{IlluminaError, Sample};
+
+/// The longest barcode a `Barcode` holds, including the `+` between dual indexes. This is enough
+/// for two 23-base indexes, longer than any Illumina index.
+pub const MAX_LENGTH: usize = 48;
+
+/// The number of positions at which two sequences differ, or `None` if they have different
+/// lengths. An `N` never matches anything, including another `N`.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::barcode::hamming_distance;
+///
+/// fn main() {
+///     assert_eq!(hamming_distance("TAAGGCGA", "TAAGGCGT"), Some(1));
+///     assert_eq!(hamming_distance("TAAGGCGA", "TAAGGCG"), None);
+/// }
+/// ```
+pub fn hamming_distance(a: &str, b: &str) -> Option<usize> {
+    if a.len() != b.len() {
+        return None;
+    }
+    Some(a.bytes()
+        .zip(b.bytes())
+        .filter(|&(x, y)| x != y || x == b'N')
+        .count())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The outcome of correcting an observed barcode against a whitelist
+pub enum Correction<'a> {
+    /// The barcode is on the whitelist
+    Exact(&'a str),
+    /// The barcode is within the mismatch budget of one whitelisted barcode, and closer to it
+    /// than to any other
+    Corrected {
+        /// The whitelisted barcode
+        barcode: &'a str,
+        /// The number of mismatches between them
+        mismatches: usize
+    },
+    /// The barcode is equally close to several whitelisted barcodes, so it can't be corrected
+    Ambiguous(Vec<&'a str>),
+    /// No whitelisted barcode is within the mismatch budget
+    Unmatched
+}
+
+impl<'a> Correction<'a> {
+    /// The whitelisted barcode the observed one was corrected to, if it was
+    pub fn barcode(&self) -> Option<&'a str> {
+        match *self {
+            Correction::Exact(barcode) | Correction::Corrected { barcode, .. } => Some(barcode),
+            Correction::Ambiguous(_) | Correction::Unmatched => None
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+/// An index sequence, as in the sample field of a sequence identifier: the bases `A`, `C`, `G`,
+/// `T` and `N`, with a `+` between the two indexes of a dual-indexed barcode. It's stored inline,
+/// so it never allocates, and holds up to `MAX_LENGTH` characters.
+///
+/// It dereferences to `str` and compares equal to `str` and `String`, like `IdString`.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::barcode::Barcode;
+///
+/// fn main() {
+///     let barcode = Barcode::new("TAAGGCGA+CTCTCTAT").unwrap();
+///     assert_eq!(barcode.index_pair(), Some(("TAAGGCGA", "CTCTCTAT")));
+///     assert_eq!(barcode.reverse_complement(), "TCGCCTTA+ATAGAGAG");
+///     assert_eq!(barcode.hamming_distance(&Barcode::new("TAAGGCGT+CTCTCTAT").unwrap()), Some(1));
+///     assert!(Barcode::new("TAAGGCGX").is_err());
+/// }
+/// ```
+pub struct Barcode {
+    length: u8,
+    bytes: [u8; MAX_LENGTH]
+}
+
+impl Barcode {
+    /// Checks that the text is a barcode and stores it. It must have at least one base, only
+    /// `A`, `C`, `G`, `T` and `N`, at most one `+` with bases on both sides, and no more than
+    /// `MAX_LENGTH` characters.
+    pub fn new(text: &str) -> Result<Barcode, IlluminaError> {
+        let invalid = || IlluminaError::FormatError(format!("not a barcode: {:?}", text));
+        if text.is_empty() || text.len() > MAX_LENGTH {
+            return Err(invalid());
+        }
+        let mut pluses = 0;
+        for (index, byte) in text.bytes().enumerate() {
+            match byte {
+                b'A' | b'C' | b'G' | b'T' | b'N' => {},
+                b'+' if index > 0 && index + 1 < text.len() => pluses += 1,
+                _ => return Err(invalid())
+            }
+        }
+        if pluses > 1 {
+            return Err(invalid());
+        }
+        let mut bytes = [0; MAX_LENGTH];
+        bytes[..text.len()].copy_from_slice(text.as_bytes());
+        Ok(Barcode { length: text.len() as u8, bytes })
+    }
+
+    /// The barcode as a `&str`
+    pub fn as_str(&self) -> &str {
+        // Only ASCII that was checked by `new` is ever stored
+        match str::from_utf8(&self.bytes[..self.length as usize]) {
+            Ok(text) => text,
+            Err(_) => unreachable!("Barcode holds invalid UTF-8")
+        }
+    }
+
+    /// The i7 and i5 indexes, if the barcode is dual-indexed
+    pub fn index_pair(&self) -> Option<(&str, &str)> {
+        let text = self.as_str();
+        let plus = text.find('+')?;
+        Some((&text[..plus], &text[plus + 1..]))
+    }
+
+    /// The number of positions at which the barcodes differ, or `None` if they have different
+    /// lengths. An `N` never matches anything.
+    pub fn hamming_distance(&self, other: &Barcode) -> Option<usize> {
+        hamming_distance(self, other)
+    }
+
+    /// The reverse complement of the barcode. The indexes of a dual-indexed barcode are each
+    /// reverse-complemented in place, so the i7 index stays first.
+    pub fn reverse_complement(&self) -> Barcode {
+        let mut reversed = *self;
+        let length = self.length as usize;
+        let plus = self.as_str().find('+').unwrap_or(length);
+        for &(start, end) in &[(0, plus), ((plus + 1).min(length), length)] {
+            for (target, &base) in reversed.bytes[start..end].iter_mut().zip(self.bytes[start..end].iter().rev()) {
+                *target = match base {
+                    b'A' => b'T',
+                    b'C' => b'G',
+                    b'G' => b'C',
+                    b'T' => b'A',
+                    other => other
+                };
+            }
+        }
+        reversed
+    }
+}
+
+impl FromStr for Barcode {
+    type Err = IlluminaError;
+
+    fn from_str(text: &str) -> Result<Barcode, IlluminaError> {
+        Barcode::new(text)
+    }
+}
+
+impl Deref for Barcode {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Barcode {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for Barcode {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for Barcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Barcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl PartialEq for Barcode {
+    fn eq(&self, other: &Barcode) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Barcode {}
+
+impl PartialEq<str> for Barcode {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for Barcode {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Barcode {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialOrd for Barcode {
+    fn partial_cmp(&self, other: &Barcode) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Barcode {
+    fn cmp(&self, other: &Barcode) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for Barcode {
+    // Hashes like a `str`, as `Borrow<str>` requires
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Barcode {
+    // Serializes as a string
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Barcode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Barcode, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Barcode::new(&text).map_err(de::Error::custom)
+    }
+}
+
+/// Corrects observed barcodes to the closest barcode on a whitelist, as long as it's within a
+/// given number of mismatches, and reports the barcodes that are equally close to two or more
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::barcode::{BarcodeCorrector, Correction};
+///
+/// fn main() {
+///     let corrector = BarcodeCorrector::new(vec!["TAAGGCGA", "TAAGGCTT"], 1);
+///     assert_eq!(corrector.correct("TAAGGCGA"), Correction::Exact("TAAGGCGA"));
+///     assert_eq!(corrector.correct("TAAGGAGA"), Correction::Corrected { barcode: "TAAGGCGA", mismatches: 1 });
+///     assert_eq!(corrector.correct("TAAGGCGT"), Correction::Ambiguous(vec!["TAAGGCGA", "TAAGGCTT"]));
+///     assert_eq!(corrector.correct("CCCCCCCC"), Correction::Unmatched);
+///     assert_eq!(corrector.min_distance(), Some(2));
+/// }
+/// ```
+pub struct BarcodeCorrector {
+    whitelist: Vec<String>,
+    // The position of each whitelisted barcode, for exact matches without a scan
+    positions: HashMap<String, usize>,
+    max_mismatches: usize
+}
+
+impl BarcodeCorrector {
+    /// Creates a corrector for the whitelisted barcodes that tolerates up to `max_mismatches`
+    /// differences. Repeated barcodes are only kept once.
+    pub fn new<I, S>(whitelist: I, max_mismatches: usize) -> BarcodeCorrector
+        where I: IntoIterator<Item=S>, S: Into<String> {
+        let mut corrector = BarcodeCorrector { whitelist: vec![], positions: HashMap::new(), max_mismatches };
+        for barcode in whitelist {
+            let barcode = barcode.into();
+            if !corrector.positions.contains_key(&barcode) {
+                corrector.positions.insert(barcode.clone(), corrector.whitelist.len());
+                corrector.whitelist.push(barcode);
+            }
+        }
+        corrector
+    }
+
+    /// Creates a corrector for the barcodes of the samples in a sample sheet, from every lane.
+    /// Dual-indexed barcodes are written `index+index2`, as in sequence identifiers.
+    pub fn from_sample_sheet(sheet: &SampleSheet, max_mismatches: usize) -> BarcodeCorrector {
+        BarcodeCorrector::new(sheet.entries.iter().filter_map(|entry| entry.barcode()), max_mismatches)
+    }
+
+    /// The whitelisted barcodes, in the order they were given
+    pub fn whitelist(&self) -> &[String] {
+        &self.whitelist
+    }
+
+    /// The most mismatches a barcode can have and still be corrected
+    pub fn max_mismatches(&self) -> usize {
+        self.max_mismatches
+    }
+
+    /// The smallest number of mismatches between two whitelisted barcodes of the same length,
+    /// or `None` if there are no such pairs. Unless it's more than twice the mismatch budget, some
+    /// barcodes will be ambiguous.
+    pub fn min_distance(&self) -> Option<usize> {
+        let mut min = None;
+        for (index, a) in self.whitelist.iter().enumerate() {
+            for b in &self.whitelist[index + 1..] {
+                if let Some(distance) = hamming_distance(a, b) {
+                    min = Some(min.map_or(distance, |min: usize| min.min(distance)));
+                }
+            }
+        }
+        min
+    }
+
+    /// Corrects an observed barcode
+    pub fn correct(&self, barcode: &str) -> Correction<'_> {
+        if let Some(&position) = self.positions.get(barcode) {
+            if !barcode.contains('N') {
+                return Correction::Exact(&self.whitelist[position]);
+            }
+        }
+        let mut best: Vec<&str> = vec![];
+        let mut best_distance = usize::MAX;
+        for expected in &self.whitelist {
+            match hamming_distance(expected, barcode) {
+                Some(distance) if distance > self.max_mismatches || distance > best_distance => {},
+                Some(distance) if distance == best_distance => best.push(expected),
+                Some(distance) => {
+                    best = vec![expected];
+                    best_distance = distance;
+                },
+                None => {}
+            }
+        }
+        match best.len() {
+            0 => Correction::Unmatched,
+            1 if best_distance == 0 => Correction::Exact(best[0]),
+            1 => Correction::Corrected { barcode: best[0], mismatches: best_distance },
+            _ => Correction::Ambiguous(best)
+        }
+    }
+
+    /// Corrects the barcode of a read from Undetermined Reads. Sample numbers are never
+    /// matched.
+    pub fn correct_sample(&self, sample: &Sample) -> Correction<'_> {
+        match sample.as_sequence() {
+            Some(barcode) => self.correct(barcode),
+            None => Correction::Unmatched
+        }
+    }
+}
+
+/// Assigns observed barcodes to the closest of a set of expected barcodes, as long as it's within
+/// a given number of mismatches. Barcodes that are equally close to two or more expected barcodes
+/// aren't assigned. This is a `BarcodeCorrector` that only gives the barcodes it corrects to.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::barcode::{Barcode, BarcodeMatcher};
+/// use illumina_coordinates::Sample;
+///
+/// fn main() {
+///     let matcher = BarcodeMatcher::new(vec!["TAAGGCGA", "CGTACTAG"], 1);
+///     assert_eq!(matcher.assign("TAAGGCGT"), Some("TAAGGCGA"));
+///     assert_eq!(matcher.assign("TAAGGAAT"), None);
+///     assert_eq!(matcher.assign_sample(&Sample::Sequence(Barcode::new("CGTACTAC").unwrap())), Some("CGTACTAG"));
+/// }
+/// ```
+pub struct BarcodeMatcher {
+    corrector: BarcodeCorrector
+}
+
+impl BarcodeMatcher {
+    /// Creates a matcher for the expected barcodes that tolerates up to `max_mismatches`
+    /// differences
+    pub fn new<I, S>(expected: I, max_mismatches: usize) -> BarcodeMatcher
+        where I: IntoIterator<Item=S>, S: Into<String> {
+        BarcodeMatcher { corrector: BarcodeCorrector::new(expected, max_mismatches) }
+    }
+
+    /// The expected barcodes
+    pub fn expected(&self) -> &[String] {
+        self.corrector.whitelist()
+    }
+
+    /// Returns the expected barcode closest to the observed one, if it's within the allowed
+    /// number of mismatches and no other expected barcode is equally close
+    pub fn assign(&self, barcode: &str) -> Option<&str> {
+        self.corrector.correct(barcode).barcode()
+    }
+
+    /// Assigns the barcode of a read from Undetermined Reads. Sample numbers are never assigned.
+    pub fn assign_sample(&self, sample: &Sample) -> Option<&str> {
+        self.assign(sample.as_sequence()?)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Counts how often each barcode appears among reads from Undetermined Reads. The most frequent
+/// undetermined barcodes usually point to sample sheet typos or samples missing from it.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::barcode::{Barcode, BarcodeTally};
+/// use illumina_coordinates::Sample;
+///
+/// fn main() {
+///     let mut tally = BarcodeTally::new();
+///     tally.add(&Sample::Sequence(Barcode::new("TAAGGCGA").unwrap()));
+///     tally.add(&Sample::Sequence(Barcode::new("TAAGGCGA").unwrap()));
+///     tally.add(&Sample::Sequence(Barcode::new("CGTACTAG").unwrap()));
+///     assert_eq!(tally.top(1), vec![("TAAGGCGA", 2)]);
+/// }
+/// ```
+pub struct BarcodeTally {
+    counts: HashMap<String, u64>,
+    total: u64
+}
+
+impl BarcodeTally {
+    /// Creates an empty tally
+    pub fn new() -> BarcodeTally {
+        BarcodeTally::default()
+    }
+
+    /// Counts the barcode of a read. Sample numbers are ignored.
+    pub fn add(&mut self, sample: &Sample) {
+        if let Some(barcode) = sample.as_sequence() {
+            self.add_barcode(barcode);
+        }
+    }
+
+    /// Counts a barcode
+    pub fn add_barcode(&mut self, barcode: &str) {
+        if let Some(count) = self.counts.get_mut(barcode) {
+            *count += 1;
+        } else {
+            self.counts.insert(barcode.to_string(), 1);
+        }
+        self.total += 1;
+    }
+
+    /// The number of barcodes counted
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The number of distinct barcodes counted
+    pub fn distinct(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// The number of times the barcode was counted
+    pub fn count(&self, barcode: &str) -> u64 {
+        self.counts.get(barcode).cloned().unwrap_or(0)
+    }
+
+    /// The `n` most frequent barcodes and their counts, most frequent first. Barcodes with equal
+    /// counts are ordered alphabetically.
+    pub fn top(&self, n: usize) -> Vec<(&str, u64)> {
+        let mut counts: Vec<(&str, u64)> = self.counts.iter()
+            .map(|(barcode, &count)| (barcode.as_str(), count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts.truncate(n);
+        counts
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sample_sheet::parse_sample_sheet;
+
+    #[test]
+    fn test_hamming_distance() {
+        assert_eq!(hamming_distance("", ""), Some(0));
+        assert_eq!(hamming_distance("ACGT", "ACGT"), Some(0));
+        assert_eq!(hamming_distance("ACGT", "TGCA"), Some(4));
+        assert_eq!(hamming_distance("ACGN", "ACGN"), Some(1));
+        assert_eq!(hamming_distance("ACGT+AAAA", "ACGT+AAAT"), Some(1));
+    }
+
+    #[test]
+    fn test_assign() {
+        let matcher = BarcodeMatcher::new(vec!["AAAAAAAA", "CCCCCCCC", "AAAATTTT"], 2);
+        assert_eq!(matcher.assign("AAAAAAAA"), Some("AAAAAAAA"));
+        assert_eq!(matcher.assign("AAAAAAAC"), Some("AAAAAAAA"));
+        assert_eq!(matcher.assign("CCCCCCAA"), Some("CCCCCCCC"));
+        assert_eq!(matcher.assign("CCCCCAAA"), None);
+        assert_eq!(matcher.assign("AAAAAA"), None);
+    }
+
+    #[test]
+    fn test_assign_tie() {
+        let matcher = BarcodeMatcher::new(vec!["AAAAAAAA", "AAAAAATT"], 1);
+        assert_eq!(matcher.assign("AAAAAAAT"), None);
+        assert_eq!(matcher.assign("AAAAAATT"), Some("AAAAAATT"));
+    }
+
+    #[test]
+    fn test_assign_sample() {
+        let matcher = BarcodeMatcher::new(vec!["AAAAAAAA"], 1);
+        assert_eq!(matcher.assign_sample(&Sample::Number(1)), None);
+        assert_eq!(BarcodeMatcher::new(vec!["300"], 0).assign_sample(&Sample::Other("300".to_string())), None);
+        assert_eq!(BarcodeMatcher::new(vec!["aaaa"], 0).assign_sample(&Sample::Other("aaaa".to_string())), Some("aaaa"));
+        assert_eq!(matcher.assign_sample(&Sample::Sequence(Barcode::new("AAAAAAAN").unwrap())), Some("AAAAAAAA"));
+    }
+
+    #[test]
+    fn test_barcode() {
+        for text in &["A", "ACGTN", "TAAGGCGA+CTCTCTAT", &"A".repeat(MAX_LENGTH)] {
+            assert_eq!(Barcode::new(text).unwrap(), *text);
+        }
+        for text in &["", "acgt", "AC GT", "+ACGT", "ACGT+", "AC+GT+AC", "1", &"A".repeat(MAX_LENGTH + 1)] {
+            assert!(Barcode::new(text).is_err(), "{}", text);
+        }
+        let barcode: Barcode = "AACN+GGTA".parse().unwrap();
+        assert_eq!(barcode.reverse_complement(), "NGTT+TACC");
+        assert_eq!(barcode.reverse_complement().reverse_complement(), barcode);
+        assert_eq!(Barcode::new("ACCG").unwrap().reverse_complement(), "CGGT");
+        assert_eq!(barcode.hamming_distance(&Barcode::new("AACN").unwrap()), None);
+        assert_eq!(format!("{:?} {}", barcode, barcode), "\"AACN+GGTA\" AACN+GGTA");
+        let mut counts = HashMap::new();
+        counts.insert(barcode, 1);
+        assert_eq!(counts.get("AACN+GGTA"), Some(&1));
+    }
+
+    #[test]
+    fn test_correct() {
+        let corrector = BarcodeCorrector::new(vec!["AAAAAAAA", "AAAAAATT", "CCCCCCCC", "AAAAAAAA"], 2);
+        assert_eq!(corrector.whitelist().len(), 3);
+        assert_eq!(corrector.correct("AAAAAAAA"), Correction::Exact("AAAAAAAA"));
+        assert_eq!(corrector.correct("AAAAAAAN"), Correction::Corrected { barcode: "AAAAAAAA", mismatches: 1 });
+        assert_eq!(corrector.correct("AAAAAAAT"), Correction::Ambiguous(vec!["AAAAAAAA", "AAAAAATT"]));
+        assert_eq!(corrector.correct("AAAAAATC").barcode(), Some("AAAAAATT"));
+        assert_eq!(corrector.correct("CCCCCAAA"), Correction::Unmatched);
+        assert_eq!(corrector.correct("AAAA"), Correction::Unmatched);
+        assert_eq!(corrector.correct_sample(&Sample::Number(1)), Correction::Unmatched);
+        assert_eq!(BarcodeCorrector::new(vec!["300"], 0).correct_sample(&Sample::Other("300".to_string())), Correction::Unmatched);
+        assert_eq!(corrector.min_distance(), Some(2));
+        assert_eq!(BarcodeCorrector::new(vec!["AAAA", "CC"], 1).min_distance(), None);
+    }
+
+    #[test]
+    fn test_from_sample_sheet() {
+        let sheet = parse_sample_sheet("[Data]\nLane,Sample_ID,index,index2\n1,S1,TAAGGCGA,GCGTAAGA\n\
+                                        2,S1,TAAGGCGA,GCGTAAGA\n2,S2,CGTACTAG,\n2,S3,,\n").unwrap();
+        let corrector = BarcodeCorrector::from_sample_sheet(&sheet, 1);
+        assert_eq!(corrector.whitelist(), &["TAAGGCGA+GCGTAAGA".to_string(), "CGTACTAG".to_string()][..]);
+        assert_eq!(corrector.max_mismatches(), 1);
+    }
+
+    #[test]
+    fn test_tally() {
+        let mut tally = BarcodeTally::new();
+        for barcode in &["CCCC", "AAAA", "GGGG", "AAAA", "CCCC", "AAAA"] {
+            tally.add(&Sample::Sequence(Barcode::new(barcode).unwrap()));
+        }
+        tally.add(&Sample::Number(1));
+        tally.add(&Sample::Other("300".to_string()));
+        assert_eq!(tally.total(), 6);
+        assert_eq!(tally.count("300"), 0);
+        assert_eq!(tally.distinct(), 3);
+        assert_eq!(tally.count("CCCC"), 2);
+        assert_eq!(tally.count("TTTT"), 0);
+        assert_eq!(tally.top(2), vec![("AAAA", 3), ("CCCC", 2)]);
+        assert_eq!(tally.top(10).len(), 3);
+    }
+
+    #[test]
+    fn test_tally_ties() {
+        let mut tally = BarcodeTally::new();
+        tally.add_barcode("GGGG");
+        tally.add_barcode("AAAA");
+        assert_eq!(tally.top(2), vec![("AAAA", 1), ("GGGG", 1)]);
+    }
+}
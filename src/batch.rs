@@ -0,0 +1,289 @@
+//! A columnar representation of many sequence identifiers, for analyzing whole runs.
+//!
+//! Each field is stored in its own `Vec`, and the sequencer IDs, flow cell IDs and samples, which
+//! rarely vary within a file, are stored once and referred to by index. A batch of identifiers
+//! takes a fraction of the memory of a `Vec<SequenceIdentifier>`, and statistics over a single
+//! field only need to touch that field's column.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use super::{parse_sequence_identifier_borrowed, BorrowedSequenceIdentifier, IlluminaError, Sample, SequenceIdentifier};
+
+#[derive(Debug, Clone)]
+/// Distinct values of a column, each stored once
+struct Table<T> {
+    values: Vec<T>,
+    indexes: HashMap<String, u32>
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Table<T> {
+        Table { values: vec![], indexes: HashMap::new() }
+    }
+}
+
+impl<T> Table<T> {
+    /// The index of the value with the given key, adding the value if it's new
+    fn index<F: FnOnce() -> T>(&mut self, key: &str, value: F) -> u32 {
+        if let Some(&index) = self.indexes.get(key) {
+            return index;
+        }
+        let index = self.values.len() as u32;
+        self.values.push(value());
+        self.indexes.insert(key.to_string(), index);
+        index
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Sequence identifiers stored column by column
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::batch::SequenceIdentifierBatch;
+///
+/// fn main() {
+///     let mut batch = SequenceIdentifierBatch::new();
+///     batch.push_parse("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+///     batch.push_parse("@M03745:11:000000000-B54L5:1:2108:4130:9012 1:Y:0:0").unwrap();
+///     assert_eq!(batch.len(), 2);
+///     let mean_x = batch.x().iter().map(|&x| f64::from(x)).sum::<f64>() / batch.len() as f64;
+///     assert_eq!(mean_x, 4128.5);
+///     assert_eq!(batch.flow_cell_id(1), "000000000-B54L5");
+/// }
+/// ```
+pub struct SequenceIdentifierBatch {
+    ids: Table<String>,
+    samples: Table<Sample>,
+    sequencer_id: Vec<u32>,
+    run_count: Vec<u16>,
+    flow_cell_id: Vec<u32>,
+    lane: Vec<u8>,
+    side: Vec<u8>,
+    swath: Vec<u8>,
+    camera: Vec<Option<u8>>,
+    tile: Vec<u8>,
+    x: Vec<u16>,
+    y: Vec<u16>,
+    read: Vec<u8>,
+    is_filtered: Vec<bool>,
+    control_number: Vec<u16>,
+    sample: Vec<u32>
+}
+
+impl SequenceIdentifierBatch {
+    /// Creates an empty batch
+    pub fn new() -> SequenceIdentifierBatch {
+        SequenceIdentifierBatch::default()
+    }
+
+    /// Parses a sequence identifier and adds it to the batch
+    pub fn push_parse(&mut self, text: &str) -> Result<(), IlluminaError> {
+        let seq_id = parse_sequence_identifier_borrowed(text)?;
+        self.push_borrowed(&seq_id);
+        Ok(())
+    }
+
+    /// Adds a sequence identifier that has already been parsed
+    pub fn push(&mut self, seq_id: &SequenceIdentifier) {
+        self.push_borrowed(&BorrowedSequenceIdentifier {
+            sequencer_id: Cow::Borrowed(&seq_id.sequencer_id),
+            run_count: seq_id.run_count,
+            flow_cell_id: Cow::Borrowed(&seq_id.flow_cell_id),
+            lane: seq_id.lane,
+            side: seq_id.side,
+            swath: seq_id.swath,
+            camera: seq_id.camera,
+            tile: seq_id.tile,
+            x: seq_id.x,
+            y: seq_id.y,
+            read: seq_id.read,
+            is_filtered: seq_id.is_filtered,
+            control_number: seq_id.control_number,
+            sample: seq_id.sample.clone()
+        });
+    }
+
+    pub(crate) fn push_borrowed(&mut self, seq_id: &BorrowedSequenceIdentifier) {
+        let sequencer_id = self.ids.index(&seq_id.sequencer_id, || seq_id.sequencer_id.to_string());
+        let flow_cell_id = self.ids.index(&seq_id.flow_cell_id, || seq_id.flow_cell_id.to_string());
+        let sample = match seq_id.sample {
+            Sample::Number(n) => self.samples.index(&n.to_string(), || Sample::Number(n)),
+            // Sequences and other fields can't be mistaken for numbers, so all share the same keys
+            Sample::Sequence(ref sequence) => self.samples.index(sequence, || Sample::Sequence(*sequence)),
+            Sample::Other(ref text) => self.samples.index(text, || Sample::Other(text.clone()))
+        };
+        self.sequencer_id.push(sequencer_id);
+        self.run_count.push(seq_id.run_count);
+        self.flow_cell_id.push(flow_cell_id);
+        self.lane.push(seq_id.lane);
+        self.side.push(seq_id.side);
+        self.swath.push(seq_id.swath);
+        self.camera.push(seq_id.camera);
+        self.tile.push(seq_id.tile);
+        self.x.push(seq_id.x);
+        self.y.push(seq_id.y);
+        self.read.push(seq_id.read);
+        self.is_filtered.push(seq_id.is_filtered);
+        self.control_number.push(seq_id.control_number);
+        self.sample.push(sample);
+    }
+
+    /// The number of identifiers in the batch
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    /// Whether the batch is empty
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Reassembles the identifier at `index`
+    pub fn get(&self, index: usize) -> Option<SequenceIdentifier> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(SequenceIdentifier {
+            sequencer_id: self.sequencer_id(index).into(),
+            run_count: self.run_count[index],
+            flow_cell_id: self.flow_cell_id(index).into(),
+            lane: self.lane[index],
+            side: self.side[index],
+            swath: self.swath[index],
+            camera: self.camera[index],
+            tile: self.tile[index],
+            x: self.x[index],
+            y: self.y[index],
+            read: self.read[index],
+            is_filtered: self.is_filtered[index],
+            control_number: self.control_number[index],
+            sample: self.sample(index).clone()
+        })
+    }
+
+    /// Iterates over the identifiers in the batch, reassembling each one
+    pub fn iter(&self) -> impl Iterator<Item=SequenceIdentifier> + '_ {
+        (0..self.len()).filter_map(move |index| self.get(index))
+    }
+
+    /// The sequencer ID of the identifier at `index`. Panics if `index` is out of bounds.
+    pub fn sequencer_id(&self, index: usize) -> &str {
+        &self.ids.values[self.sequencer_id[index] as usize]
+    }
+
+    /// The flow cell ID of the identifier at `index`. Panics if `index` is out of bounds.
+    pub fn flow_cell_id(&self, index: usize) -> &str {
+        &self.ids.values[self.flow_cell_id[index] as usize]
+    }
+
+    /// The sample of the identifier at `index`. Panics if `index` is out of bounds.
+    pub fn sample(&self, index: usize) -> &Sample {
+        &self.samples.values[self.sample[index] as usize]
+    }
+
+    /// The distinct samples in the batch, in the order they were first seen
+    pub fn distinct_samples(&self) -> &[Sample] {
+        &self.samples.values
+    }
+
+    /// The run counts of each identifier
+    pub fn run_count(&self) -> &[u16] {
+        &self.run_count
+    }
+
+    /// The lanes of each identifier
+    pub fn lane(&self) -> &[u8] {
+        &self.lane
+    }
+
+    /// The sides of each identifier
+    pub fn side(&self) -> &[u8] {
+        &self.side
+    }
+
+    /// The swaths of each identifier
+    pub fn swath(&self) -> &[u8] {
+        &self.swath
+    }
+
+    /// The cameras of each identifier
+    pub fn camera(&self) -> &[Option<u8>] {
+        &self.camera
+    }
+
+    /// The tiles of each identifier
+    pub fn tile(&self) -> &[u8] {
+        &self.tile
+    }
+
+    /// The x-coordinates of each identifier
+    pub fn x(&self) -> &[u16] {
+        &self.x
+    }
+
+    /// The y-coordinates of each identifier
+    pub fn y(&self) -> &[u16] {
+        &self.y
+    }
+
+    /// The read numbers of each identifier
+    pub fn read(&self) -> &[u8] {
+        &self.read
+    }
+
+    /// Whether each identifier was filtered
+    pub fn is_filtered(&self) -> &[bool] {
+        &self.is_filtered
+    }
+
+    /// The control numbers of each identifier
+    pub fn control_number(&self) -> &[u16] {
+        &self.control_number
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use barcode::Barcode;
+    use parse_sequence_identifier;
+
+    const LINES: [&str; 3] = [
+        "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+        "@M03745:11:000000000-B54L5:1:1101:1000:2000 2:Y:0:TAAGGC",
+        "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 1:N:0:0",
+    ];
+
+    #[test]
+    fn test_batch() {
+        let mut batch = SequenceIdentifierBatch::new();
+        for line in LINES.iter() {
+            batch.push_parse(line).unwrap();
+        }
+        assert!(batch.push_parse("@M03745:11").is_err());
+        assert_eq!(batch.len(), 3);
+        assert_eq!(batch.x(), &[4127, 1000, 20886]);
+        assert_eq!(batch.tile(), &[8, 1, 1]);
+        assert_eq!(batch.camera(), &[None, None, Some(1)]);
+        assert_eq!(batch.is_filtered(), &[false, true, false]);
+        assert_eq!(batch.sequencer_id(2), "NS500358");
+        assert_eq!(batch.ids.values.len(), 4);
+        assert_eq!(batch.distinct_samples(), &[Sample::Number(0), Sample::Sequence(Barcode::new("TAAGGC").unwrap())]);
+        for (seq_id, line) in batch.iter().zip(LINES.iter()) {
+            assert_eq!(seq_id, parse_sequence_identifier(line).unwrap());
+        }
+        assert!(batch.get(3).is_none());
+    }
+
+    #[test]
+    fn test_push() {
+        let mut batch = SequenceIdentifierBatch::new();
+        let seq_id = parse_sequence_identifier(LINES[1]).unwrap();
+        batch.push(&seq_id);
+        assert_eq!(batch.get(0), Some(seq_id));
+    }
+}
@@ -0,0 +1,387 @@
+//! Reads and writes BGZF files, the blocked gzip format used for indexed FASTQ, BAM and VCF files.
+//!
+//! A BGZF file is a series of gzip members of at most 64 KiB each, so any position in the
+//! uncompressed data can be reached by seeking to the start of a block and decompressing it. Such
+//! positions are given as virtual offsets: the offset of the block in the compressed file,
+//! shifted left by 16 bits, plus the offset within the decompressed block.
+
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use deflate::{crc32, deflate, inflate};
+
+pub(crate) const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+pub(crate) const FEXTRA: u8 = 4;
+
+/// The most uncompressed data written to a single block, leaving room for incompressible data to
+/// be stored without going over the 64 KiB block limit
+pub(crate) const MAX_BLOCK_DATA: usize = 0xff00;
+const MAX_BLOCK_SIZE: usize = 0x10000;
+const HEADER_SIZE: usize = 18;
+const TRAILER_SIZE: usize = 8;
+
+/// Whether the data starts with a BGZF block header
+pub fn is_bgzf(data: &[u8]) -> bool {
+    data.len() >= 16
+        && data[0..2] == GZIP_MAGIC
+        && data[2] == 8
+        && data[3] & FEXTRA != 0
+        && data[12] == b'B'
+        && data[13] == b'C'
+}
+
+/// Whether the data starts with a gzip header, which includes BGZF
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.len() >= 2 && data[0..2] == GZIP_MAGIC
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn le_u16(bytes: &[u8]) -> u16 {
+    u16::from(bytes[0]) | u16::from(bytes[1]) << 8
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    u32::from(le_u16(&bytes[0..2])) | u32::from(le_u16(&bytes[2..4])) << 16
+}
+
+/// Decompresses a BGZF file block by block, keeping track of the virtual offset of the next
+/// byte to be read
+pub struct BgzfReader<R: Read> {
+    inner: R,
+    block_offset: u64,
+    next_block_offset: u64,
+    block: Vec<u8>,
+    position: usize,
+    compressed: Vec<u8>
+}
+
+impl<R: Read> BgzfReader<R> {
+    /// Creates a reader positioned at the start of the BGZF data
+    pub fn new(inner: R) -> BgzfReader<R> {
+        BgzfReader {
+            inner,
+            block_offset: 0,
+            next_block_offset: 0,
+            block: vec![],
+            position: 0,
+            compressed: vec![]
+        }
+    }
+
+    /// The virtual offset of the next byte that will be read
+    pub fn virtual_offset(&self) -> u64 {
+        self.block_offset << 16 | self.position as u64
+    }
+
+    /// Returns the underlying reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads and decompresses the next block. Returns false if there are no more blocks.
+    fn read_block(&mut self) -> io::Result<bool> {
+        let block_size = match read_raw_block(&mut self.inner, &mut self.compressed)? {
+            Some(block_size) => block_size,
+            None => return Ok(false)
+        };
+        self.block.clear();
+        decompress_block(&self.compressed, &mut self.block)?;
+        self.block_offset = self.next_block_offset;
+        self.next_block_offset += block_size as u64;
+        self.position = 0;
+        Ok(true)
+    }
+}
+
+/// Reads the next block into `compressed`, leaving out its header. Returns the size of the whole
+/// block, or `None` if there are no more blocks.
+pub(crate) fn read_raw_block<R: Read>(inner: &mut R, compressed: &mut Vec<u8>) -> io::Result<Option<usize>> {
+    let mut header = [0u8; 12];
+    let mut filled = 0;
+    while filled < header.len() {
+        match inner.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "BGZF block header is truncated")),
+            Ok(n) => filled += n,
+            Err(ref error) if error.kind() == io::ErrorKind::Interrupted => {},
+            Err(error) => return Err(error)
+        }
+    }
+    if header[0..2] != GZIP_MAGIC || header[2] != 8 || header[3] & FEXTRA == 0 {
+        return Err(invalid("not a BGZF block"));
+    }
+    let extra_length = le_u16(&header[10..12]) as usize;
+    let mut extra = vec![0u8; extra_length];
+    inner.read_exact(&mut extra)?;
+    let mut block_size = None;
+    let mut subfields = &extra[..];
+    while subfields.len() >= 4 {
+        let length = le_u16(&subfields[2..4]) as usize;
+        if subfields[0] == b'B' && subfields[1] == b'C' && length == 2 && subfields.len() >= 6 {
+            block_size = Some(le_u16(&subfields[4..6]) as usize + 1);
+        }
+        subfields = &subfields[(4 + length).min(subfields.len())..];
+    }
+    let block_size = block_size.ok_or_else(|| invalid("gzip member has no BGZF block size"))?;
+    if block_size < 12 + extra_length + 8 {
+        return Err(invalid("BGZF block size is too small"));
+    }
+    compressed.resize(block_size - 12 - extra_length, 0);
+    inner.read_exact(compressed)?;
+    Ok(Some(block_size))
+}
+
+/// Decompresses a block read by `read_raw_block`, appending its data to `block` and checking its
+/// size and CRC
+pub(crate) fn decompress_block(compressed: &[u8], block: &mut Vec<u8>) -> io::Result<()> {
+    let trailer = compressed.len() - 8;
+    let start = block.len();
+    inflate(&compressed[..trailer], block)?;
+    if le_u32(&compressed[trailer + 4..]) as usize != block.len() - start {
+        return Err(invalid("BGZF block has the wrong uncompressed size"));
+    }
+    if le_u32(&compressed[trailer..trailer + 4]) != crc32(&block[start..]) {
+        return Err(invalid("BGZF block failed its CRC check"));
+    }
+    Ok(())
+}
+
+impl<R: Read + Seek> BgzfReader<R> {
+    /// Moves to a virtual offset previously returned by `virtual_offset`
+    pub fn seek_virtual(&mut self, virtual_offset: u64) -> io::Result<()> {
+        let block_offset = virtual_offset >> 16;
+        let position = (virtual_offset & 0xffff) as usize;
+        self.inner.seek(SeekFrom::Start(block_offset))?;
+        self.next_block_offset = block_offset;
+        self.block.clear();
+        self.position = 0;
+        if !self.read_block()? && position > 0 {
+            return Err(invalid("virtual offset is past the end of the file"));
+        }
+        if position > self.block.len() {
+            return Err(invalid("virtual offset is past the end of its block"));
+        }
+        self.block_offset = block_offset;
+        self.position = position;
+        Ok(())
+    }
+}
+
+/// Compresses data into BGZF blocks. Since BGZF is valid gzip, the output can be read by any
+/// gzip decompressor as well as indexed.
+///
+/// `finish` must be called once all the data has been written, to write the last block and the
+/// end-of-file marker.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use std::io::{Read, Write};
+/// use illumina_coordinates::bgzf::{BgzfReader, BgzfWriter};
+///
+/// fn main() {
+///     let mut writer = BgzfWriter::new(vec![]);
+///     writer.write_all(b"@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\n").unwrap();
+///     let compressed = writer.finish().unwrap();
+///
+///     let mut text = String::new();
+///     BgzfReader::new(&compressed[..]).read_to_string(&mut text).unwrap();
+///     assert!(text.starts_with("@M03745"));
+/// }
+/// ```
+pub struct BgzfWriter<W: Write> {
+    inner: W,
+    data: Vec<u8>,
+    compressed: Vec<u8>
+}
+
+impl<W: Write> BgzfWriter<W> {
+    /// Creates a writer
+    pub fn new(inner: W) -> BgzfWriter<W> {
+        BgzfWriter { inner, data: Vec::with_capacity(MAX_BLOCK_DATA), compressed: vec![] }
+    }
+
+    /// Compresses the buffered data and writes it as a block, if there is any
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.data.is_empty() {
+            return Ok(());
+        }
+        self.write_block_of(self.data.len())?;
+        self.data.clear();
+        Ok(())
+    }
+
+    /// Writes `length` bytes of the buffered data as a block, even if that's none
+    fn write_block_of(&mut self, length: usize) -> io::Result<()> {
+        self.compressed.clear();
+        compress_block(&self.data[..length], &mut self.compressed);
+        self.inner.write_all(&self.compressed)
+    }
+
+    /// Writes any buffered data and the end-of-file marker, and returns the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_block()?;
+        // The end-of-file marker is an empty block
+        self.write_block_of(0)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// Compresses the data, which must be at most `MAX_BLOCK_DATA` bytes, and appends it to `out` as
+/// a whole block
+pub(crate) fn compress_block(data: &[u8], out: &mut Vec<u8>) {
+    let start = out.len();
+    out.extend_from_slice(&[0x1f, 0x8b, 8, FEXTRA, 0, 0, 0, 0, 0, 0xff, 6, 0, b'B', b'C', 2, 0, 0, 0]);
+    deflate(data, out);
+    if out.len() - start + TRAILER_SIZE > MAX_BLOCK_SIZE {
+        // A single stored block, for data that didn't compress
+        out.truncate(start + HEADER_SIZE);
+        out.push(1);
+        out.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(!(data.len() as u16)).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+    let block_size = out.len() - start + TRAILER_SIZE;
+    out[start + 16..start + HEADER_SIZE].copy_from_slice(&((block_size - 1) as u16).to_le_bytes());
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(MAX_BLOCK_DATA - self.data.len());
+        self.data.extend_from_slice(&buf[..n]);
+        if self.data.len() == MAX_BLOCK_DATA {
+            self.write_block()?;
+        }
+        Ok(n)
+    }
+
+    /// Writes the buffered data as a block, so a flush ends the current block early
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_block()?;
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> BufRead for BgzfReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.position == self.block.len() {
+            if !self.read_block()? {
+                break;
+            }
+        }
+        Ok(&self.block[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.block.len());
+    }
+}
+
+impl<R: Read> Read for BgzfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let available = self.fill_buf()?;
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Builds a BGZF block that stores the data uncompressed
+    pub(crate) fn stored_block(data: &[u8]) -> Vec<u8> {
+        let block_size = 18 + 5 + data.len() + 8;
+        let mut block = vec![0x1f, 0x8b, 8, 4, 0, 0, 0, 0, 0, 0xff, 6, 0, b'B', b'C', 2, 0];
+        block.extend_from_slice(&((block_size - 1) as u16).to_le_bytes());
+        block.push(1);
+        block.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        block.extend_from_slice(&(!(data.len() as u16)).to_le_bytes());
+        block.extend_from_slice(data);
+        block.extend_from_slice(&crc32(data).to_le_bytes());
+        block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        block
+    }
+
+    #[test]
+    fn test_read_blocks() {
+        let mut file = stored_block(b"hello ");
+        file.extend(stored_block(b"world"));
+        file.extend(stored_block(b""));
+        assert!(is_bgzf(&file));
+        let mut reader = BgzfReader::new(&file[..]);
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "hello world");
+    }
+
+    #[test]
+    fn test_virtual_offsets() {
+        let first = stored_block(b"abc\ndef\n");
+        let second_offset = first.len() as u64;
+        let mut file = first;
+        file.extend(stored_block(b"ghi\n"));
+        let mut reader = BgzfReader::new(Cursor::new(file));
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(reader.virtual_offset(), 4);
+        let offset = reader.virtual_offset();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "ghi\n");
+        assert_eq!(reader.virtual_offset(), second_offset << 16 | 4);
+
+        reader.seek_virtual(offset).unwrap();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "def\n");
+        reader.seek_virtual(second_offset << 16).unwrap();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "ghi\n");
+    }
+
+    #[test]
+    fn test_write_blocks() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8 ^ (i.wrapping_mul(2_654_435_761) >> 28) as u8).collect();
+        let mut writer = BgzfWriter::new(vec![]);
+        writer.write_all(&data).unwrap();
+        let compressed = writer.finish().unwrap();
+        assert!(is_bgzf(&compressed));
+        // The standard end-of-file marker
+        assert_eq!(compressed[compressed.len() - 28..], eof_marker()[..]);
+        let mut out = vec![];
+        BgzfReader::new(&compressed[..]).read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+    }
+
+    fn eof_marker() -> Vec<u8> {
+        let mut eof = vec![0x1f, 0x8b, 8, 4, 0, 0, 0, 0, 0, 0xff, 6, 0, b'B', b'C', 2, 0, 0x1b, 0, 3, 0];
+        eof.extend_from_slice(&[0; 8]);
+        eof
+    }
+
+    #[test]
+    fn test_corrupt_block() {
+        let mut file = stored_block(b"hello");
+        let length = file.len();
+        file[length - 9] ^= 0xff;
+        let mut text = String::new();
+        assert!(BgzfReader::new(&file[..]).read_to_string(&mut text).is_err());
+        assert!(!is_bgzf(b"@M03745:11"));
+    }
+}
@@ -0,0 +1,55 @@
+//! `illumina-coords anonymize`: replaces the instrument, run and flow cell IDs in FASTQ headers
+//! with pseudonyms.
+
+use std::io;
+use illumina_coordinates::anonymize::Anonymizer;
+use illumina_coordinates::fastq::Record;
+use args::Args;
+use files::{open_fastq, print_summary, Output};
+use json::Object;
+
+pub const USAGE: &str = "usage: illumina-coords anonymize --key TEXT [--max-shift N] [--json] [--threads N] <input> <output>
+
+Copies the reads in <input> to <output>, compressed if the input is, replacing the sequencer ID,
+run count and flow cell ID in each header with pseudonyms, and prints the number of reads copied.
+The same key always gives the same pseudonyms, so the read 1 and read 2 files of a run still pair
+up. The letters that give the model of sequencer and type of flow cell, the lane, tile and
+coordinates, and the comment are kept, unless --max-shift moves the coordinates. The pseudonyms
+aren't encrypted: keep the key secret.
+
+options:
+    --key TEXT       the secret that pseudonyms are derived from
+    --max-shift N    move the coordinates of each tile's reads by up to N, keeping the distances
+                     between reads on a tile (default 0)
+    --json           print the number of reads as JSON
+    --threads N      decompress and compress on N threads (default 1)";
+
+pub fn run(args: Vec<String>) -> Result<(), String> {
+    let args = Args::parse(args, &["key", "max-shift", "threads"], &["json"])?;
+    let paths = args.positional(&["input", "output"])?;
+    let threads = args.threads()?;
+    let max_shift: u16 = args.parsed("max-shift")?.unwrap_or(0);
+    let anonymizer = match args.value("key") {
+        Some(key) => Anonymizer::new(key.as_bytes()),
+        None => return Err("--key is required".to_string())
+    };
+    let (mut reader, compressed) = open_fastq(&paths[0], threads)?;
+    let mut output = Output::create(&paths[1], compressed, threads)?;
+    let mut record = Record::default();
+    let mut anonymized = 0u64;
+    let input_error = |error| format!("{}: {}", paths[0], error);
+    let output_error = |error: io::Error| format!("{}: {}", paths[1], error);
+    while reader.read_record(&mut record).map_err(input_error)? {
+        let seq_id = record.sequence_identifier().map_err(input_error)?;
+        record.header = anonymizer.anonymize_with_shift(&seq_id, max_shift).to_string();
+        record.write_to(&mut output).map_err(output_error)?;
+        anonymized += 1;
+    }
+    output.finish().map_err(output_error)?;
+    if args.switch("json") {
+        print_summary(&paths[1], Object::new().number("reads", anonymized));
+    } else {
+        print_summary(&paths[1], format!("anonymized {} reads", anonymized));
+    }
+    Ok(())
+}
@@ -0,0 +1,99 @@
+//! A minimal command-line argument parser, enough for the subcommands' options.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Parses an inclusive range written as `START-END`, or a single value that starts and ends it
+pub fn parse_range<T: FromStr + PartialOrd + Copy>(text: &str) -> Option<(T, T)> {
+    let mut bounds = text.splitn(2, '-');
+    let start = bounds.next()?.trim().parse().ok()?;
+    let end = match bounds.next() {
+        Some(end) => end.trim().parse().ok()?,
+        None => start
+    };
+    if start > end {
+        return None;
+    }
+    Some((start, end))
+}
+
+/// Parsed options and positional arguments
+pub struct Args {
+    values: HashMap<String, String>,
+    switches: Vec<String>,
+    positional: Vec<String>
+}
+
+impl Args {
+    /// Parses arguments. `options` names the options that take a value and `switches` those that
+    /// don't, without their leading dashes. Values can be given as `--name value` or
+    /// `--name=value`, and everything after `--` is positional.
+    pub fn parse(args: Vec<String>, options: &[&str], switches: &[&str]) -> Result<Args, String> {
+        let mut parsed = Args { values: HashMap::new(), switches: vec![], positional: vec![] };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                parsed.positional.extend(args);
+                break;
+            }
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name.to_string(),
+                None => {
+                    parsed.positional.push(arg);
+                    continue;
+                }
+            };
+            let (name, inline_value) = match name.find('=') {
+                Some(equals) => (name[..equals].to_string(), Some(name[equals + 1..].to_string())),
+                None => (name, None)
+            };
+            if options.contains(&name.as_str()) {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => args.next().ok_or_else(|| format!("--{} needs a value", name))?
+                };
+                parsed.values.insert(name, value);
+            } else if switches.contains(&name.as_str()) && inline_value.is_none() {
+                parsed.switches.push(name);
+            } else {
+                return Err(format!("unknown option: {}", arg));
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// The value of an option, if it was given
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|value| value.as_str())
+    }
+
+    /// Whether a switch was given
+    pub fn switch(&self, name: &str) -> bool {
+        self.switches.iter().any(|switch| switch == name)
+    }
+
+    /// The number of threads given with `--threads`, or 1
+    pub fn threads(&self) -> Result<usize, String> {
+        match self.parsed("threads")?.unwrap_or(1) {
+            0 => Err("--threads must be at least 1".to_string()),
+            threads => Ok(threads)
+        }
+    }
+
+    /// Parses the value of an option, if it was given
+    pub fn parsed<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        match self.value(name) {
+            Some(value) => value.parse().map(Some).map_err(|_| format!("invalid value for --{}: {}", name, value)),
+            None => Ok(None)
+        }
+    }
+
+    /// The positional arguments, checking that there are exactly as many as expected
+    pub fn positional(&self, names: &[&str]) -> Result<&[String], String> {
+        if self.positional.len() != names.len() {
+            let names: Vec<String> = names.iter().map(|name| format!("<{}>", name)).collect();
+            return Err(format!("expected arguments: {}", names.join(" ")));
+        }
+        Ok(&self.positional)
+    }
+}
@@ -0,0 +1,76 @@
+//! `illumina-coords convert`: rewrites FASTQ headers in another format.
+
+use std::io;
+use illumina_coordinates::fastq::Record;
+use illumina_coordinates::header_format::{HeaderFormat, HeaderStyle, IlluminaFormat, LegacyFormat, ReadNameFormat};
+use args::Args;
+use files::{open_fastq, print_summary, Output};
+use json::Object;
+
+pub const USAGE: &str = "usage: illumina-coords convert --to FORMAT [--run-count N --flow-cell-id ID] [--json] [--threads N] <input> <output>
+
+Copies the reads in <input> to <output>, compressed if the input is, rewriting each header in
+FORMAT. Headers can be in any of the formats, and are recognized one by one.
+
+formats:
+    casava       the format of CASAVA 1.8 and later, e.g. @M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0.
+                 Read names without a comment are given one for an unfiltered, non-control read
+                 of sample 0.
+    legacy       the format from before CASAVA 1.8, e.g. @HWUSI-EAS100R:6:73:941:1973#0/1, which
+                 leaves out the run count, flow cell ID, filter flag and control number
+    read-name    the read name and read number without the comment, e.g.
+                 @M03745:11:000000000-B54L5:1:2108:4127:8949/1
+
+options:
+    --to FORMAT          the format to write
+    --run-count N        the run count to give reads with legacy headers
+    --flow-cell-id ID    the flow cell ID to give reads with legacy headers
+    --json               print the number of headers as JSON
+    --threads N          decompress and compress on N threads (default 1)";
+
+pub fn run(args: Vec<String>) -> Result<(), String> {
+    let args = Args::parse(args, &["to", "run-count", "flow-cell-id", "threads"], &["json"])?;
+    let paths = args.positional(&["input", "output"])?;
+    let threads = args.threads()?;
+    let style = match args.value("to") {
+        Some("casava") => HeaderStyle::Casava,
+        Some("legacy") => HeaderStyle::Legacy,
+        Some("read-name") => HeaderStyle::ReadName,
+        Some(format) => return Err(format!("unknown format: {}", format)),
+        None => return Err("--to is required".to_string())
+    };
+    let run_count: Option<u16> = args.parsed("run-count")?;
+    let flow_cell_id = args.value("flow-cell-id");
+    // Legacy headers can always be recognized, but only lose nothing if the run is given
+    let has_run = run_count.is_some() && flow_cell_id.is_some();
+    let legacy = LegacyFormat::new(run_count.unwrap_or(0), flow_cell_id.unwrap_or("unknown"));
+    let (mut reader, compressed) = open_fastq(&paths[0], threads)?;
+    let mut output = Output::create(&paths[1], compressed, threads)?;
+    let mut record = Record::default();
+    let mut converted = 0u64;
+    let input_error = |error| format!("{}: {}", paths[0], error);
+    let output_error = |error: io::Error| format!("{}: {}", paths[1], error);
+    while reader.read_record(&mut record).map_err(input_error)? {
+        let seq_id = match IlluminaFormat::default().parse(&record.header).or_else(|_| ReadNameFormat.parse(&record.header)) {
+            Ok(seq_id) => seq_id,
+            Err(_) if legacy.detect(&record.header) => {
+                if style != HeaderStyle::Legacy && !has_run {
+                    return Err(format!("{}: record {} has a legacy header, which doesn't give the run; use --run-count and --flow-cell-id",
+                                       paths[0], converted + 1));
+                }
+                legacy.parse(&record.header).map_err(input_error)?
+            },
+            Err(error) => return Err(format!("{}: record {}: {}", paths[0], converted + 1, error))
+        };
+        record.header = style.header(&seq_id);
+        record.write_to(&mut output).map_err(output_error)?;
+        converted += 1;
+    }
+    output.finish().map_err(output_error)?;
+    if args.switch("json") {
+        print_summary(&paths[1], Object::new().number("headers", converted));
+    } else {
+        print_summary(&paths[1], format!("converted {} headers", converted));
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! `illumina-coords dedupe`: removes or marks reads at the same or nearby positions.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use illumina_coordinates::dedupe::{DedupeOptions, Deduplicator};
+use illumina_coordinates::fastq::Record;
+use args::Args;
+use files::{open_fastq, Output, STDIO};
+use json::Object;
+
+pub const USAGE: &str = "usage: illumina-coords dedupe [--distance N] [--prefix N] [--mark] [--report PATH] [--json] [--threads N] <input> <output>
+
+Copies the reads in <input> to <output>, compressed if the input is, leaving out every read at
+the same position as an earlier read on its tile, or within --distance of one. Prints the number
+of reads and duplicates on each tile.
+
+options:
+    --distance N     the largest difference in x and in y between duplicates (default 0, identical
+                     coordinates only)
+    --prefix N       only count reads as duplicates if their first N bases match too
+    --mark           keep duplicates, adding a tab and `duplicate` to the end of their headers,
+                     rather than leaving them out. Tags after a tab are ignored when headers are
+                     parsed.
+    --report PATH    write the counts to PATH rather than printing them. They're printed to
+                     standard error if <output> is standard output.
+    --json           write the counts as JSON
+    --threads N      decompress and compress on N threads (default 1)";
+
+// What --mark adds to the headers of duplicates
+const MARK: &str = "\tduplicate";
+
+pub fn run(args: Vec<String>) -> Result<(), String> {
+    let args = Args::parse(args, &["distance", "prefix", "report", "threads"], &["mark", "json"])?;
+    let paths = args.positional(&["input", "output"])?;
+    let threads = args.threads()?;
+    let options = DedupeOptions {
+        distance: args.parsed("distance")?.unwrap_or(0),
+        prefix_length: args.parsed("prefix")?
+    };
+    let mark = args.switch("mark");
+    let (mut reader, compressed) = open_fastq(&paths[0], threads)?;
+    let mut output = Output::create(&paths[1], compressed, threads)?;
+    let mut deduplicator = Deduplicator::new(options);
+    let mut record = Record::default();
+    let input_error = |error| format!("{}: {}", paths[0], error);
+    let output_error = |error: io::Error| format!("{}: {}", paths[1], error);
+    while reader.read_record(&mut record).map_err(input_error)? {
+        let seq_id = record.sequence_identifier().map_err(input_error)?;
+        if deduplicator.is_duplicate(&seq_id, record.sequence.as_bytes()) {
+            if !mark {
+                continue;
+            }
+            // Reads marked by an earlier run are only marked once
+            if !record.header.ends_with(MARK) {
+                record.header.push_str(MARK);
+            }
+        }
+        record.write_to(&mut output).map_err(output_error)?;
+    }
+    output.finish().map_err(output_error)?;
+
+    let mut report: Box<dyn Write> = match args.value("report") {
+        Some(path) => Box::new(BufWriter::new(File::create(path).map_err(|error| format!("{}: {}", path, error))?)),
+        None if paths[1] == STDIO => Box::new(io::stderr()),
+        None => Box::new(io::stdout())
+    };
+    let report_error = |error: io::Error| format!("writing report: {}", error);
+    let total = deduplicator.total();
+    if args.switch("json") {
+        let tiles: Vec<Object> = deduplicator.tiles().iter()
+            .map(|(tile, counts)| Object::new().number("lane", tile.lane).number("tile", tile.tile_field())
+                 .number("reads", counts.reads).number("duplicates", counts.duplicates))
+            .collect();
+        let summary = Object::new().objects("tiles", &tiles).number("reads", total.reads).number("duplicates", total.duplicates);
+        writeln!(report, "{}", summary).map_err(report_error)?;
+        return report.flush().map_err(report_error);
+    }
+    writeln!(report, "lane\ttile\treads\tduplicates").map_err(report_error)?;
+    for (tile, counts) in deduplicator.tiles() {
+        writeln!(report, "{}\t{}\t{}\t{}", tile.lane, tile.tile_field(), counts.reads, counts.duplicates)
+            .map_err(report_error)?;
+    }
+    writeln!(report, "total\t\t{}\t{}", total.reads, total.duplicates).map_err(report_error)?;
+    report.flush().map_err(report_error)
+}
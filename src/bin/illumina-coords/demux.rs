@@ -0,0 +1,45 @@
+//! `illumina-coords demux`: assigns Undetermined reads to samples by their barcodes.
+
+use std::fs;
+use illumina_coordinates::demux::{demultiplex_reader, Demultiplexer};
+use illumina_coordinates::sample_sheet::SampleSheet;
+use args::Args;
+use files::{open_input, STDIO};
+use json::files;
+
+pub const USAGE: &str = "usage: illumina-coords demux --sample-sheet <SampleSheet.csv> [--mismatches N] [--json] [--threads N] <input> <output-dir>
+
+Writes each read to a file in <output-dir> for the sample its barcode matches, to an `ambiguous`
+file if it's equally close to the indexes of several samples, or to an `unassigned` file,
+compressed if the input is, and prints the number of reads in each file.
+
+options:
+    --sample-sheet PATH    the sample sheet listing each sample's index sequences
+    --mismatches N         the most mismatches allowed between a barcode and an index (default 1)
+    --json                 print the files and their numbers of reads as JSON
+    --threads N            decompress on N threads (default 1)";
+
+pub fn run(args: Vec<String>) -> Result<(), String> {
+    let args = Args::parse(args, &["sample-sheet", "mismatches", "threads"], &["json"])?;
+    let paths = args.positional(&["input", "output-dir"])?;
+    let sheet_path = args.value("sample-sheet").ok_or("--sample-sheet is required")?;
+    let sheet = SampleSheet::from_path(sheet_path).map_err(|error| format!("{}: {}", sheet_path, error))?;
+    let demultiplexer = Demultiplexer::new(&sheet, args.parsed("mismatches")?.unwrap_or(1));
+    fs::create_dir_all(&paths[1]).map_err(|error| format!("{}: {}", paths[1], error))?;
+    let (reader, compressed) = open_input(&paths[0], args.threads()?)?;
+    // Parts are named after the input file, so give standard input a name
+    let name = match (paths[0].as_str(), compressed) {
+        (STDIO, true) => "stdin.fastq.gz",
+        (STDIO, false) => "stdin.fastq",
+        (path, _) => path
+    };
+    let counts = demultiplex_reader(reader, compressed, name, &paths[1], &demultiplexer).map_err(|error| format!("{}: {}", paths[0], error))?;
+    if args.switch("json") {
+        println!("{}", files(&counts));
+        return Ok(());
+    }
+    for (path, records) in &counts {
+        println!("{}\t{}", path.display(), records);
+    }
+    Ok(())
+}
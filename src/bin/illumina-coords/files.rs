@@ -0,0 +1,94 @@
+//! Opening the input and output files of the subcommands. A path of `-` is standard input or
+//! output.
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use illumina_coordinates::bgzf::BgzfWriter;
+use illumina_coordinates::fastq::FastqReader;
+use illumina_coordinates::parallel::{decompress_reader, ParallelBgzfWriter};
+
+/// The path that stands for standard input or output
+pub const STDIO: &str = "-";
+
+/// Where an output file's data goes once it's been compressed
+type Sink = BufWriter<Box<dyn Write>>;
+
+/// A FASTQ output file, compressed if the input was
+pub enum Output {
+    Plain(Sink),
+    Compressed(BgzfWriter<Sink>),
+    Parallel(ParallelBgzfWriter<Sink>)
+}
+
+impl Output {
+    /// Creates the output file, compressing it as BGZF, on `threads` threads if there's more than
+    /// one, if `compressed` is set
+    pub fn create(path: &str, compressed: bool, threads: usize) -> Result<Output, String> {
+        let file: Box<dyn Write> = if path == STDIO {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(path).map_err(|error| format!("{}: {}", path, error))?)
+        };
+        let file = BufWriter::new(file);
+        Ok(match (compressed, threads > 1) {
+            (true, true) => Output::Parallel(ParallelBgzfWriter::new(file, threads)),
+            (true, false) => Output::Compressed(BgzfWriter::new(file)),
+            (false, _) => Output::Plain(file)
+        })
+    }
+
+    /// Flushes the file, writing the end of the BGZF stream if it's compressed
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Output::Plain(mut writer) => writer.flush(),
+            Output::Compressed(writer) => writer.finish().map(|_| ()),
+            Output::Parallel(writer) => writer.finish().map(|_| ())
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Output::Plain(ref mut writer) => writer.write(buf),
+            Output::Compressed(ref mut writer) => writer.write(buf),
+            Output::Parallel(ref mut writer) => writer.write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Output::Plain(ref mut writer) => writer.flush(),
+            Output::Compressed(ref mut writer) => writer.flush(),
+            Output::Parallel(ref mut writer) => writer.flush()
+        }
+    }
+}
+
+/// Opens an input file, decompressing it on up to `threads` threads if it's gzipped. Also returns
+/// whether it was.
+pub fn open_input(path: &str, threads: usize) -> Result<(Box<dyn BufRead>, bool), String> {
+    let opened = if path == STDIO {
+        decompress_reader(io::stdin(), threads)
+    } else {
+        File::open(path).and_then(|file| decompress_reader(file, threads))
+    };
+    opened.map_err(|error| format!("{}: {}", path, error))
+}
+
+/// Opens a FASTQ file like `open_input`
+pub fn open_fastq(path: &str, threads: usize) -> Result<(FastqReader<Box<dyn BufRead>>, bool), String> {
+    let (reader, compressed) = open_input(path, threads)?;
+    Ok((FastqReader::new(reader), compressed))
+}
+
+/// Prints a subcommand's results to standard output, or to standard error if the reads are
+/// being written to standard output
+pub fn print_summary<T: Display>(output: &str, summary: T) {
+    if output == STDIO {
+        eprintln!("{}", summary);
+    } else {
+        println!("{}", summary);
+    }
+}
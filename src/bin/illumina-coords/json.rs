@@ -0,0 +1,59 @@
+//! Machine-readable results for the subcommands' `--json` switch.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use illumina_coordinates::jsonl::escape;
+
+/// A JSON object, written with its fields in the order they were added
+#[derive(Default)]
+pub struct Object {
+    fields: Vec<String>
+}
+
+impl Object {
+    /// Creates an object with no fields
+    pub fn new() -> Object {
+        Object::default()
+    }
+
+    /// Adds a number or boolean
+    pub fn number<T: fmt::Display>(mut self, name: &str, value: T) -> Object {
+        self.fields.push(format!("{}:{}", escape(name), value));
+        self
+    }
+
+    /// Adds a string
+    pub fn string(mut self, name: &str, value: &str) -> Object {
+        self.fields.push(format!("{}:{}", escape(name), escape(value)));
+        self
+    }
+
+    /// Adds an array of strings
+    pub fn strings(mut self, name: &str, values: &[String]) -> Object {
+        let values: Vec<String> = values.iter().map(|value| escape(value)).collect();
+        self.fields.push(format!("{}:[{}]", escape(name), values.join(",")));
+        self
+    }
+
+    /// Adds an array of objects
+    pub fn objects(mut self, name: &str, values: &[Object]) -> Object {
+        let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        self.fields.push(format!("{}:[{}]", escape(name), values.join(",")));
+        self
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{{}}}", self.fields.join(","))
+    }
+}
+
+/// The files a subcommand wrote and the number of reads in each, as `{"files":[{"path":...,"reads":...}]}`
+pub fn files(counts: &BTreeMap<PathBuf, u64>) -> Object {
+    let files: Vec<Object> = counts.iter()
+        .map(|(path, reads)| Object::new().string("path", &path.display().to_string()).number("reads", reads))
+        .collect();
+    Object::new().objects("files", &files)
+}
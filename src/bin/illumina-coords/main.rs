@@ -0,0 +1,72 @@
+//! Command-line tools for Illumina FASTQ files, built on the illumina_coordinates library.
+
+extern crate illumina_coordinates;
+
+mod anonymize;
+mod args;
+mod convert;
+mod dedupe;
+mod demux;
+mod files;
+mod json;
+mod region;
+mod sample;
+mod split;
+mod validate;
+
+use std::env;
+use std::process;
+
+/// Runs a subcommand with the arguments that follow its name
+type Command = fn(Vec<String>) -> Result<(), String>;
+
+const USAGE: &str = "usage: illumina-coords <command> [options]
+
+commands:
+    anonymize replace the instrument, run and flow cell IDs with stable pseudonyms
+    convert  rewrite FASTQ headers in another format
+    dedupe   remove or mark reads at the same or nearby positions
+    demux    assign Undetermined reads to samples by their barcodes
+    region   extract the reads within a rectangle on a tile or lane
+    sample   keep a reproducible subset of the reads in a FASTQ file
+    split    split a FASTQ file into one file per tile or tile range
+    validate check the headers of a FASTQ file and that its reads come from one run
+
+Run `illumina-coords <command> --help` for a command's options. Every command takes --json to
+print its results as a JSON object rather than text, and --threads N to decompress and compress
+on N threads. An <input> or <output> of - is standard input or output.";
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    let command = args.remove(0);
+    let (run, usage): (Command, &str) = match command.as_str() {
+        "anonymize" => (anonymize::run, anonymize::USAGE),
+        "convert" => (convert::run, convert::USAGE),
+        "dedupe" => (dedupe::run, dedupe::USAGE),
+        "demux" => (demux::run, demux::USAGE),
+        "region" => (region::run, region::USAGE),
+        "sample" => (sample::run, sample::USAGE),
+        "split" => (split::run, split::USAGE),
+        "validate" => (validate::run, validate::USAGE),
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            return;
+        },
+        _ => {
+            eprintln!("unknown command: {}\n\n{}", command, USAGE);
+            process::exit(2);
+        }
+    };
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", usage);
+        return;
+    }
+    if let Err(message) = run(args) {
+        eprintln!("illumina-coords {}: {}", command, message);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,80 @@
+//! `illumina-coords region`: extracts the reads within a rectangle on a tile or lane.
+
+use std::io;
+use illumina_coordinates::fastq::Record;
+use illumina_coordinates::filter::RegionFilter;
+use args::{parse_range, Args};
+use files::{open_fastq, print_summary, Output};
+use json::Object;
+
+pub const USAGE: &str = "usage: illumina-coords region --x MIN-MAX --y MIN-MAX (--tile TILE [--lane N] | --lane N --side N --lane-coordinates) [--json] [--threads N] <input> <output>
+
+Copies the reads in <input> whose clusters lie within the rectangle to <output>, compressed if the
+input is, and prints the number of reads copied. The bounds are inclusive.
+
+options:
+    --x MIN-MAX           the range of x-coordinates
+    --y MIN-MAX           the range of y-coordinates
+    --tile TILE           the tile, as written in sequence identifiers, e.g. 2108
+    --lane N              only take reads from this lane
+    --side N              the side of the lane, with --lane-coordinates
+    --lane-coordinates    the ranges are coordinates on the side of the lane rather than the tile,
+                          with the swaths side by side along x and the tiles of each swath one
+                          after another along y. The layout and tile size are inferred from the
+                          first read.
+    --json                print the number of reads as JSON
+    --threads N           decompress and compress on N threads (default 1)";
+
+pub fn run(args: Vec<String>) -> Result<(), String> {
+    let args = Args::parse(args, &["x", "y", "tile", "lane", "side", "threads"], &["lane-coordinates", "json"])?;
+    let paths = args.positional(&["input", "output"])?;
+    let threads = args.threads()?;
+    let range = |name: &str| -> Result<(u32, u32), String> {
+        let value = args.value(name).ok_or_else(|| format!("--{} is required", name))?;
+        parse_range(value).ok_or_else(|| format!("invalid range for --{}: {}", name, value))
+    };
+    let (x, y) = (range("x")?, range("y")?);
+    let lane: Option<u8> = args.parsed("lane")?;
+    let lane_coordinates = args.switch("lane-coordinates");
+    let tile = match (args.parsed::<u32>("tile")?, lane_coordinates) {
+        (Some(_), true) => return Err("--tile can't be used with --lane-coordinates".to_string()),
+        (None, false) => return Err("--tile is required".to_string()),
+        (tile, _) => tile
+    };
+    let side: Option<u8> = args.parsed("side")?;
+    let lane_and_side = match (lane_coordinates, lane, side) {
+        (true, Some(lane), Some(side)) => Some((lane, side)),
+        (true, _, _) => return Err("--lane-coordinates needs --lane and --side".to_string()),
+        (false, _, Some(_)) => return Err("--side only applies to --lane-coordinates".to_string()),
+        (false, _, None) => None
+    };
+    let (mut reader, compressed) = open_fastq(&paths[0], threads)?;
+    let mut output = Output::create(&paths[1], compressed, threads)?;
+    let mut filter = tile.map(|tile| RegionFilter::tile(lane, tile, x, y));
+    let mut record = Record::default();
+    let mut copied = 0u64;
+    let input_error = |error| format!("{}: {}", paths[0], error);
+    let output_error = |error: io::Error| format!("{}: {}", paths[1], error);
+    while reader.read_record(&mut record).map_err(input_error)? {
+        let seq_id = record.sequence_identifier().map_err(input_error)?;
+        if filter.is_none() {
+            let (lane, side) = lane_and_side.expect("lane coordinates are used without a tile");
+            let layout = seq_id.flow_cell_layout()
+                .ok_or_else(|| format!("{}: can't tell the flow cell layout from {}", paths[0], seq_id.flow_cell_id))?;
+            let extent = seq_id.instrument().and_then(|model| model.tile_extent())
+                .ok_or_else(|| format!("{}: can't tell the tile size from {}", paths[0], seq_id.sequencer_id))?;
+            filter = Some(RegionFilter::lane(layout, extent, lane, side, x, y));
+        }
+        if filter.iter().any(|filter| filter.contains(&seq_id)) {
+            copied += 1;
+            record.write_to(&mut output).map_err(output_error)?;
+        }
+    }
+    output.finish().map_err(output_error)?;
+    if args.switch("json") {
+        print_summary(&paths[1], Object::new().number("copied", copied));
+    } else {
+        print_summary(&paths[1], format!("copied {} reads", copied));
+    }
+    Ok(())
+}
@@ -0,0 +1,67 @@
+//! `illumina-coords sample`: keeps a reproducible subset of the reads in a FASTQ file.
+
+use std::io;
+use illumina_coordinates::fastq::Record;
+use illumina_coordinates::subsample::{SampleBy, Subsampler};
+use args::Args;
+use files::{open_fastq, print_summary, Output};
+use json::Object;
+
+pub const USAGE: &str = "usage: illumina-coords sample (--fraction F [--seed N] | --every N | --per-tile N) [--json] [--threads N] <input> <output>
+
+Copies a subset of the reads in <input> to <output>, compressed if the input is, and prints the
+number of reads kept. The same options always keep the same reads, and --fraction picks reads by
+their position, so it keeps the same clusters from the read 1 and read 2 files of a run.
+
+options:
+    --fraction F    keep each read with probability F
+    --seed N        pick a different subset with --fraction (default 0)
+    --every N       keep the first read and every Nth read after it
+    --per-tile N    keep the first N reads on each tile
+    --json          print the numbers of reads as JSON
+    --threads N     decompress and compress on N threads (default 1)";
+
+pub fn run(args: Vec<String>) -> Result<(), String> {
+    let args = Args::parse(args, &["fraction", "seed", "every", "per-tile", "threads"], &["json"])?;
+    let paths = args.positional(&["input", "output"])?;
+    let threads = args.threads()?;
+    let methods = [args.parsed::<f64>("fraction")?.map(|fraction| SampleBy::Fraction { fraction, seed: 0 }),
+                   args.parsed("every")?.map(SampleBy::EveryNth),
+                   args.parsed("per-tile")?.map(SampleBy::PerTile)];
+    let mut sample_by = match methods.iter().filter_map(|&method| method).collect::<Vec<_>>()[..] {
+        [method] => method,
+        _ => return Err("give exactly one of --fraction, --every and --per-tile".to_string())
+    };
+    match sample_by {
+        SampleBy::Fraction { ref fraction, ref mut seed } => {
+            if !(0.0..=1.0).contains(fraction) {
+                return Err(format!("--fraction must be between 0 and 1: {}", fraction));
+            }
+            *seed = args.parsed("seed")?.unwrap_or(0);
+        },
+        SampleBy::EveryNth(0) => return Err("--every must be at least 1".to_string()),
+        _ if args.value("seed").is_some() => return Err("--seed only applies to --fraction".to_string()),
+        _ => {}
+    }
+    let (mut reader, compressed) = open_fastq(&paths[0], threads)?;
+    let mut output = Output::create(&paths[1], compressed, threads)?;
+    let mut subsampler = Subsampler::new(sample_by);
+    let mut record = Record::default();
+    let (mut reads, mut kept) = (0u64, 0u64);
+    let input_error = |error| format!("{}: {}", paths[0], error);
+    let output_error = |error: io::Error| format!("{}: {}", paths[1], error);
+    while reader.read_record(&mut record).map_err(input_error)? {
+        reads += 1;
+        if subsampler.keep(&record.sequence_identifier().map_err(input_error)?) {
+            kept += 1;
+            record.write_to(&mut output).map_err(output_error)?;
+        }
+    }
+    output.finish().map_err(output_error)?;
+    if args.switch("json") {
+        print_summary(&paths[1], Object::new().number("reads", reads).number("kept", kept));
+    } else {
+        print_summary(&paths[1], format!("kept {} of {} reads", kept, reads));
+    }
+    Ok(())
+}
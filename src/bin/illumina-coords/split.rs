@@ -0,0 +1,47 @@
+//! `illumina-coords split`: splits a FASTQ file into one file per tile or tile range.
+
+use std::fs;
+use illumina_coordinates::split::{split_reader, SplitBy};
+use args::{parse_range, Args};
+use files::{open_input, STDIO};
+use json::files;
+
+pub const USAGE: &str = "usage: illumina-coords split [--ranges START-END,...] [--json] [--threads N] <input> <output-dir>
+
+Writes the records from each tile to a separate file in <output-dir>, compressed if the input is,
+and prints the number of reads in each file.
+
+options:
+    --ranges START-END,...    group tiles into inclusive ranges of tile numbers, e.g. 1101-1119
+    --json                    print the files and their numbers of reads as JSON
+    --threads N               decompress on N threads (default 1)";
+
+fn parse_ranges(text: &str) -> Result<Vec<(u32, u32)>, String> {
+    text.split(',').map(|range| parse_range(range).ok_or_else(|| format!("invalid tile range: {}", range))).collect()
+}
+
+pub fn run(args: Vec<String>) -> Result<(), String> {
+    let args = Args::parse(args, &["ranges", "threads"], &["json"])?;
+    let paths = args.positional(&["input", "output-dir"])?;
+    let split_by = match args.value("ranges") {
+        Some(ranges) => SplitBy::Ranges(parse_ranges(ranges)?),
+        None => SplitBy::Tile
+    };
+    fs::create_dir_all(&paths[1]).map_err(|error| format!("{}: {}", paths[1], error))?;
+    let (reader, compressed) = open_input(&paths[0], args.threads()?)?;
+    // Parts are named after the input file, so give standard input a name
+    let name = match (paths[0].as_str(), compressed) {
+        (STDIO, true) => "stdin.fastq.gz",
+        (STDIO, false) => "stdin.fastq",
+        (path, _) => path
+    };
+    let parts = split_reader(reader, compressed, name, &paths[1], &split_by).map_err(|error| format!("{}: {}", paths[0], error))?;
+    if args.switch("json") {
+        println!("{}", files(&parts));
+        return Ok(());
+    }
+    for (path, records) in &parts {
+        println!("{}\t{}", path.display(), records);
+    }
+    Ok(())
+}
@@ -0,0 +1,81 @@
+//! `illumina-coords validate`: checks every header in a FASTQ file.
+
+use illumina_coordinates::fastq::Record;
+use illumina_coordinates::validate::validate;
+use args::Args;
+use files::open_fastq;
+use json::Object;
+
+pub const USAGE: &str = "usage: illumina-coords validate [--max-errors N] [--no-geometry] [--json] [--threads N] <input>
+
+Checks that every header in <input> is a sequence identifier whose fields are in range for the
+sequencer and flow cell, and that all reads come from the same run on the same flow cell. Prints
+the line number and reason for each malformed header and exits with an error if there are any.
+
+options:
+    --max-errors N    print at most N malformed headers (default 10)
+    --no-geometry     only check that headers parse and come from one run, not that their lanes,
+                      tiles and coordinates are in range
+    --json            print the malformed headers and the numbers of headers as JSON
+    --threads N       decompress on N threads (default 1)";
+
+pub fn run(args: Vec<String>) -> Result<(), String> {
+    let args = Args::parse(args, &["max-errors", "threads"], &["no-geometry", "json"])?;
+    let paths = args.positional(&["input"])?;
+    let max_errors: u64 = args.parsed("max-errors")?.unwrap_or(10);
+    let check_geometry = !args.switch("no-geometry");
+    let json = args.switch("json");
+    let mut shown = vec![];
+    let (mut reader, _) = open_fastq(&paths[0], args.threads()?)?;
+    let mut record = Record::default();
+    let mut run = None;
+    let (mut records, mut errors) = (0u64, 0u64);
+    while reader.read_record(&mut record).map_err(|error| format!("{}: record {}: {}", paths[0], records + 1, error))? {
+        // Records are four lines long, so the header of the first record is line 1
+        let line = records * 4 + 1;
+        records += 1;
+        let reasons = match record.sequence_identifier() {
+            Ok(seq_id) => {
+                let mut reasons: Vec<String> = if check_geometry {
+                    validate(&seq_id).iter().map(|issue| issue.to_string()).collect()
+                } else {
+                    vec![]
+                };
+                let this_run = (seq_id.sequencer_id.to_string(), seq_id.run_count, seq_id.flow_cell_id.to_string());
+                match run {
+                    None => run = Some(this_run),
+                    Some(ref first) if *first != this_run => {
+                        reasons.push(format!("read is from run {} of {} on flow cell {}, but the first read is from run {} of {} on flow cell {}",
+                                             this_run.1, this_run.0, this_run.2, first.1, first.0, first.2));
+                    },
+                    Some(_) => {}
+                }
+                reasons
+            },
+            Err(error) => vec![error.to_string()]
+        };
+        if reasons.is_empty() {
+            continue;
+        }
+        errors += 1;
+        if errors <= max_errors {
+            if json {
+                shown.push(Object::new().number("line", line).strings("reasons", &reasons));
+            } else {
+                println!("{}:{}: {}", paths[0], line, reasons.join("; "));
+            }
+        }
+    }
+    if json {
+        println!("{}", Object::new().number("headers", records).number("malformed", errors).objects("errors", &shown));
+    } else if errors > max_errors {
+        println!("{}: {} more malformed headers not shown", paths[0], errors - max_errors);
+    }
+    if errors > 0 {
+        return Err(format!("{} of {} headers are malformed", errors, records));
+    }
+    if !json {
+        println!("{}: {} headers are valid", paths[0], records);
+    }
+    Ok(())
+}
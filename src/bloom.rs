@@ -0,0 +1,186 @@
+//! An approximate set of cluster positions, for screening huge runs for reads at positions that
+//! were already seen.
+//!
+//! Remembering every position of a NovaSeq run exactly takes tens of gigabytes even when packed
+//! into `u64`s. A Bloom filter answers "was this position seen before?" in a fixed amount of
+//! memory, at the cost of sometimes answering yes for a position that wasn't. It never answers
+//! no for one that was. At a false-positive rate of 1%, it needs about 1.2 bytes per position,
+//! so three billion reads fit in under 4 GB.
+
+use position::position_hash;
+use subsample::mix;
+use super::SequenceIdentifier;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A Bloom filter of positions, packed by `position::encode_position` where they fit
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::bloom::PositionSet;
+/// use illumina_coordinates::parse_sequence_identifier;
+///
+/// fn main() {
+///     let mut seen = PositionSet::new(1_000_000, 0.001);
+///     let read = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+///     let mate = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 2:N:0:0").unwrap();
+///     assert!(seen.insert(&read));
+///     assert!(!seen.insert(&mate));
+///     assert!(seen.contains(&read));
+/// }
+/// ```
+pub struct PositionSet {
+    bits: Vec<u64>,
+    bit_count: u64,
+    hash_count: u32,
+    len: u64
+}
+
+impl PositionSet {
+    /// Creates a filter sized to hold `expected_positions` positions with the given rate of false
+    /// positives. Rates are clamped to between one in a billion and one half.
+    pub fn new(expected_positions: u64, false_positive_rate: f64) -> PositionSet {
+        let rate = if false_positive_rate > 0.5 { 0.5 } else { false_positive_rate.max(1e-9) };
+        let ln2 = std::f64::consts::LN_2;
+        let bit_count = (-(expected_positions.max(1) as f64) * rate.ln() / (ln2 * ln2)).ceil() as u64;
+        let hash_count = (bit_count as f64 / expected_positions.max(1) as f64 * ln2).round() as u32;
+        PositionSet::with_size(bit_count, hash_count)
+    }
+
+    /// Creates a filter with the given number of bits, rounded up to a multiple of 64, that sets
+    /// `hash_count` bits for each position
+    pub fn with_size(bit_count: u64, hash_count: u32) -> PositionSet {
+        let words = ((bit_count.max(1) - 1) / 64 + 1) as usize;
+        PositionSet { bits: vec![0; words], bit_count: words as u64 * 64, hash_count: hash_count.max(1), len: 0 }
+    }
+
+    /// Adds a read's position, returning `true` if it wasn't in the set already. A `false` may be
+    /// a false positive.
+    pub fn insert(&mut self, seq_id: &SequenceIdentifier) -> bool {
+        self.insert_hash(position_hash(seq_id))
+    }
+
+    /// Adds a packed position, like `insert`
+    pub fn insert_position(&mut self, position: u64) -> bool {
+        self.insert_hash(mix(position))
+    }
+
+    fn insert_hash(&mut self, hash: u64) -> bool {
+        let mut added = false;
+        for bit in self.bit_indexes(hash) {
+            let (word, mask) = ((bit / 64) as usize, 1 << (bit % 64));
+            if self.bits[word] & mask == 0 {
+                self.bits[word] |= mask;
+                added = true;
+            }
+        }
+        if added {
+            self.len += 1;
+        }
+        added
+    }
+
+    /// Whether a read's position may be in the set. A `false` is always right.
+    pub fn contains(&self, seq_id: &SequenceIdentifier) -> bool {
+        self.contains_hash(position_hash(seq_id))
+    }
+
+    /// Whether a packed position may be in the set, like `contains`
+    pub fn contains_position(&self, position: u64) -> bool {
+        self.contains_hash(mix(position))
+    }
+
+    fn contains_hash(&self, hash: u64) -> bool {
+        self.bit_indexes(hash).all(|bit| self.bits[(bit / 64) as usize] & 1 << (bit % 64) != 0)
+    }
+
+    /// The number of positions added that weren't already in the set. False positives make this
+    /// a slight undercount.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether no positions have been added
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of bits in the filter
+    pub fn bit_count(&self) -> u64 {
+        self.bit_count
+    }
+
+    /// The number of bits set for each position
+    pub fn hash_count(&self) -> u32 {
+        self.hash_count
+    }
+
+    /// The expected rate of false positives, given how many positions have been added
+    pub fn false_positive_rate(&self) -> f64 {
+        let filled = 1.0 - (-(f64::from(self.hash_count)) * self.len as f64 / self.bit_count as f64).exp();
+        filled.powi(self.hash_count as i32)
+    }
+
+    /// The bits of a position's hash, from two hashes combined as in Kirsch and Mitzenmacher's
+    /// "Less Hashing, Same Performance"
+    fn bit_indexes(&self, first: u64) -> impl Iterator<Item=u64> {
+        // An odd step visits different bits for each hash
+        let step = mix(first) | 1;
+        let bit_count = self.bit_count;
+        (0..u64::from(self.hash_count)).map(move |i| first.wrapping_add(i.wrapping_mul(step)) % bit_count)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+    use position::encode_position;
+
+    #[test]
+    fn test_sizing() {
+        let set = PositionSet::new(1_000_000, 0.01);
+        assert_eq!(set.hash_count(), 7);
+        assert_eq!(set.bit_count(), 9_585_088);
+        assert_eq!(set.false_positive_rate(), 0.0);
+        assert_eq!(PositionSet::new(0, 2.0).hash_count(), 1);
+        assert_eq!(PositionSet::with_size(65, 0).bit_count(), 128);
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut set = PositionSet::new(1000, 0.01);
+        assert!(set.is_empty());
+        let seq_id = parse_sequence_identifier("@NS500358:204:HTN5KAFXY:4:21101:20886:1073 1:N:0:TAAGGC").unwrap();
+        assert!(!set.contains(&seq_id));
+        assert!(set.insert(&seq_id));
+        assert!(!set.insert(&seq_id));
+        assert!(set.contains_position(encode_position(&seq_id).unwrap()));
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn test_unpackable_position() {
+        let mut set = PositionSet::new(1000, 0.01);
+        let mut seq_id = parse_sequence_identifier("@NS500358:204:HTN5KAFXY:4:21101:20886:1073 1:N:0:TAAGGC").unwrap();
+        seq_id.camera = Some(255);
+        assert!(set.insert(&seq_id));
+        assert!(set.contains(&seq_id));
+        seq_id.camera = None;
+        assert!(!set.contains(&seq_id));
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let mut set = PositionSet::new(10_000, 0.01);
+        for position in 0..10_000u64 {
+            set.insert_position(position << 16);
+        }
+        assert!((0..10_000u64).all(|position| set.contains_position(position << 16)));
+        let false_positives = (0..10_000u64).filter(|&position| set.contains_position(position << 16 | 1)).count();
+        assert!(false_positives < 200, "{}", false_positives);
+        assert!((set.false_positive_rate() - 0.01).abs() < 0.002);
+    }
+}
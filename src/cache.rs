@@ -0,0 +1,698 @@
+//! A compact binary container for parsed sequence identifiers, so that the identifiers of a run
+//! can be cached on disk and reloaded without decompressing and parsing the FASTQ files again.
+//!
+//! The container starts with a magic number and a format version, and readers reject versions
+//! they don't know, so caches from another version of this crate are rebuilt rather than
+//! misread. Since version 2, a block of metadata about the run and the instrument comes next, so
+//! an archived run can be re-analyzed without its run folder. The block starts with its length,
+//! and each field in it is tagged and has its own length, so readers skip fields added by later
+//! versions. Records follow until the end of the file. Each record has a byte of flags, and the
+//! sequencer ID, run count, flow cell ID and sample are only written when they differ from the
+//! previous record's, since they rarely change within a file. Integers are written as
+//! variable-length integers.
+//!
+//! With the `bincode` and `postcard` features, a whole table of identifiers can instead be
+//! serialized with serde in either format. These containers start with a magic number of their
+//! own and a version, which changes whenever the serialized layout of `SequenceIdentifier` does,
+//! and readers reject versions they don't know in the same way.
+
+use std::io::{self, BufRead, Read, Write};
+#[cfg(feature = "bincode")]
+use bincode;
+#[cfg(feature = "postcard")]
+use postcard;
+use barcode::Barcode;
+use id_string::IdString;
+use instrument::InstrumentModel;
+use geometry::FlowCellLayout;
+use run_info::{ReadInfo, RunInfo};
+use super::{IlluminaError, Sample, SequenceIdentifier};
+
+const MAGIC: &[u8] = b"ICID";
+
+/// The version of the format that `CacheWriter` writes. `CacheReader` reads this version and
+/// version 1, which has no metadata block.
+pub const VERSION: u8 = 2;
+
+// Magic numbers of the serde containers
+#[cfg(feature = "bincode")]
+const BINCODE_MAGIC: &[u8] = b"ICBC";
+#[cfg(feature = "postcard")]
+const POSTCARD_MAGIC: &[u8] = b"ICPC";
+
+/// The version of the containers that `write_bincode` and `write_postcard` write, and the only
+/// one that `read_bincode` and `read_postcard` read
+#[cfg(any(feature = "bincode", feature = "postcard"))]
+pub const SERDE_VERSION: u8 = 1;
+
+// Metadata field tags
+const RUN_ID: u8 = 1;
+const SEQUENCER_ID: u8 = 2;
+const FLOW_CELL_ID: u8 = 3;
+const RUN_COUNT: u8 = 4;
+const DATE: u8 = 5;
+const READ: u8 = 6;
+const LAYOUT: u8 = 7;
+
+// Record flags
+const NEW_RUN: u8 = 1;
+const HAS_CAMERA: u8 = 1 << 1;
+const IS_FILTERED: u8 = 1 << 2;
+const NEW_SAMPLE: u8 = 1 << 3;
+
+// Sample tags
+const SAMPLE_NUMBER: u8 = 0;
+const SAMPLE_SEQUENCE: u8 = 1;
+const SAMPLE_OTHER: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// What's known about the run whose sequence identifiers are in a cache. Every field is optional,
+/// since a cache may be written from FASTQ files alone.
+pub struct CacheMetadata {
+    /// The name of the run folder
+    pub run_id: Option<String>,
+    /// ID of the sequencing machine
+    pub sequencer_id: Option<String>,
+    /// ID of the flow cell
+    pub flow_cell_id: Option<String>,
+    /// The number of sequencing runs the machine had performed
+    pub run_count: Option<u16>,
+    /// The date of the run, in whatever format the sequencer wrote it
+    pub date: Option<String>,
+    /// The reads and index reads, in the order they were sequenced
+    pub reads: Vec<ReadInfo>,
+    /// The layout of the flow cell
+    pub layout: Option<FlowCellLayout>
+}
+
+impl CacheMetadata {
+    /// Metadata about a run as described by its `RunInfo.xml`
+    pub fn from_run_info(run_info: &RunInfo) -> CacheMetadata {
+        CacheMetadata {
+            run_id: Some(run_info.run_id.clone()),
+            sequencer_id: Some(run_info.sequencer_id.clone()),
+            flow_cell_id: Some(run_info.flow_cell_id.clone()),
+            run_count: Some(run_info.run_count),
+            date: run_info.date.clone(),
+            reads: run_info.reads.clone(),
+            layout: Some(run_info.layout)
+        }
+    }
+
+    /// Metadata about the run of a sequence identifier, which only has the instrument and flow cell
+    pub fn from_sequence_identifier(seq_id: &SequenceIdentifier) -> CacheMetadata {
+        CacheMetadata {
+            sequencer_id: Some(seq_id.sequencer_id.to_string()),
+            flow_cell_id: Some(seq_id.flow_cell_id.to_string()),
+            run_count: Some(seq_id.run_count),
+            ..CacheMetadata::default()
+        }
+    }
+
+    /// The model of the sequencer, if its ID is known and recognized
+    pub fn instrument(&self) -> Option<InstrumentModel> {
+        self.sequencer_id.as_ref().and_then(|id| InstrumentModel::from_sequencer_id(id))
+    }
+
+    /// Encodes the fields that are set, each as a tag, a length and a value
+    fn encode(&self) -> Vec<u8> {
+        let mut block = vec![];
+        let mut field = |tag: u8, value: &[u8]| {
+            block.push(tag);
+            write_varint(&mut block, value.len() as u64);
+            block.extend_from_slice(value);
+        };
+        for &(tag, ref value) in &[(RUN_ID, &self.run_id), (SEQUENCER_ID, &self.sequencer_id),
+                                   (FLOW_CELL_ID, &self.flow_cell_id), (DATE, &self.date)] {
+            if let Some(value) = value {
+                field(tag, value.as_bytes());
+            }
+        }
+        if let Some(run_count) = self.run_count {
+            field(RUN_COUNT, &run_count.to_le_bytes());
+        }
+        for read in &self.reads {
+            let cycles = read.cycles.to_le_bytes();
+            field(READ, &[read.number, cycles[0], cycles[1], read.is_index as u8]);
+        }
+        if let Some(layout) = self.layout {
+            field(LAYOUT, &[layout.lanes, layout.sides, layout.swaths, layout.cameras, layout.tiles_per_swath]);
+        }
+        block
+    }
+
+    /// Decodes a block written by `encode`, skipping fields with unknown tags
+    fn decode(mut block: &[u8]) -> Result<CacheMetadata, IlluminaError> {
+        let invalid = |what: &str| IlluminaError::FormatError(format!("invalid {} in cache metadata", what));
+        let mut metadata = CacheMetadata::default();
+        while let Some((&tag, rest)) = block.split_first() {
+            block = rest;
+            let mut length = 0;
+            for shift in (0..64).step_by(7) {
+                let (&byte, rest) = block.split_first().ok_or_else(|| invalid("field length"))?;
+                block = rest;
+                length |= u64::from(byte & 0x7f) << shift;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            if length > block.len() as u64 {
+                return Err(invalid("field length"));
+            }
+            let (value, rest) = block.split_at(length as usize);
+            block = rest;
+            let string = || String::from_utf8(value.to_vec()).map_err(|_| invalid("string"));
+            match tag {
+                RUN_ID => metadata.run_id = Some(string()?),
+                SEQUENCER_ID => metadata.sequencer_id = Some(string()?),
+                FLOW_CELL_ID => metadata.flow_cell_id = Some(string()?),
+                DATE => metadata.date = Some(string()?),
+                RUN_COUNT => match *value {
+                    [low, high] => metadata.run_count = Some(u16::from_le_bytes([low, high])),
+                    _ => return Err(invalid("run count"))
+                },
+                READ => match *value {
+                    [number, low, high, is_index] => metadata.reads.push(ReadInfo {
+                        number, cycles: u16::from_le_bytes([low, high]), is_index: is_index != 0
+                    }),
+                    _ => return Err(invalid("read"))
+                },
+                LAYOUT => match *value {
+                    [lanes, sides, swaths, cameras, tiles_per_swath] => {
+                        metadata.layout = Some(FlowCellLayout { lanes, sides, swaths, cameras, tiles_per_swath });
+                    },
+                    _ => return Err(invalid("layout"))
+                },
+                _ => {}
+            }
+        }
+        Ok(metadata)
+    }
+}
+
+/// Writes sequence identifiers to a cache
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::cache::{CacheReader, CacheWriter};
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     let mut writer = CacheWriter::new(vec![]).unwrap();
+///     writer.write(&seq_id).unwrap();
+///     let bytes = writer.finish().unwrap();
+///     let reader = CacheReader::new(&bytes[..]).unwrap();
+///     let seq_ids: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+///     assert_eq!(seq_ids, vec![seq_id]);
+/// }
+/// ```
+pub struct CacheWriter<W: Write> {
+    writer: W,
+    previous: Option<SequenceIdentifier>,
+    buffer: Vec<u8>
+}
+
+impl<W: Write> CacheWriter<W> {
+    /// Creates a writer, and writes the magic number, version and an empty metadata block
+    pub fn new(writer: W) -> io::Result<CacheWriter<W>> {
+        CacheWriter::with_metadata(writer, &CacheMetadata::default())
+    }
+
+    /// Creates a writer, and writes the magic number, version and metadata
+    pub fn with_metadata(mut writer: W, metadata: &CacheMetadata) -> io::Result<CacheWriter<W>> {
+        let block = metadata.encode();
+        let mut header = MAGIC.to_vec();
+        header.push(VERSION);
+        write_varint(&mut header, block.len() as u64);
+        header.extend_from_slice(&block);
+        writer.write_all(&header)?;
+        Ok(CacheWriter { writer, previous: None, buffer: vec![] })
+    }
+
+    /// Writes a single sequence identifier
+    pub fn write(&mut self, seq_id: &SequenceIdentifier) -> io::Result<()> {
+        let (new_run, new_sample) = match self.previous {
+            Some(ref previous) => (previous.sequencer_id != seq_id.sequencer_id || previous.run_count != seq_id.run_count
+                                       || previous.flow_cell_id != seq_id.flow_cell_id,
+                                   previous.sample != seq_id.sample),
+            None => (true, true)
+        };
+        let mut flags = 0;
+        for &(flag, is_set) in &[(NEW_RUN, new_run), (HAS_CAMERA, seq_id.camera.is_some()),
+                                 (IS_FILTERED, seq_id.is_filtered), (NEW_SAMPLE, new_sample)] {
+            if is_set {
+                flags |= flag;
+            }
+        }
+
+        let buffer = &mut self.buffer;
+        buffer.clear();
+        buffer.push(flags);
+        if new_run {
+            write_string(buffer, &seq_id.sequencer_id);
+            write_varint(buffer, seq_id.run_count.into());
+            write_string(buffer, &seq_id.flow_cell_id);
+        }
+        buffer.extend_from_slice(&[seq_id.lane, seq_id.side, seq_id.swath]);
+        if let Some(camera) = seq_id.camera {
+            buffer.push(camera);
+        }
+        buffer.extend_from_slice(&[seq_id.tile, seq_id.read]);
+        write_varint(buffer, seq_id.x.into());
+        write_varint(buffer, seq_id.y.into());
+        write_varint(buffer, seq_id.control_number.into());
+        if new_sample {
+            match seq_id.sample {
+                Sample::Number(number) => buffer.extend_from_slice(&[SAMPLE_NUMBER, number]),
+                Sample::Sequence(ref sequence) => {
+                    buffer.push(SAMPLE_SEQUENCE);
+                    write_string(buffer, sequence);
+                },
+                Sample::Other(ref text) => {
+                    buffer.push(SAMPLE_OTHER);
+                    write_string(buffer, text);
+                }
+            }
+        }
+        self.writer.write_all(buffer)?;
+
+        if new_run || new_sample {
+            self.previous = Some(seq_id.clone());
+        }
+        Ok(())
+    }
+
+    /// Flushes and returns the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Writes all of the sequence identifiers to a cache, and returns the underlying writer
+pub fn write_cache<'a, W, I>(writer: W, seq_ids: I) -> io::Result<W>
+    where W: Write, I: IntoIterator<Item=&'a SequenceIdentifier> {
+    let mut writer = CacheWriter::new(writer)?;
+    for seq_id in seq_ids {
+        writer.write(seq_id)?;
+    }
+    writer.finish()
+}
+
+/// Reads the sequence identifiers in a cache, in the order they were written
+pub struct CacheReader<R: BufRead> {
+    reader: R,
+    version: u8,
+    metadata: CacheMetadata,
+    previous: Option<SequenceIdentifier>
+}
+
+impl<R: BufRead> CacheReader<R> {
+    /// Creates a reader, checks the magic number and version and reads the metadata
+    pub fn new(mut reader: R) -> Result<CacheReader<R>, IlluminaError> {
+        let mut header = [0u8; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(IlluminaError::FormatError("not a sequence identifier cache".to_string()));
+        }
+        let version = header[4];
+        if version != 1 && version != VERSION {
+            return Err(IlluminaError::FormatError(format!("unsupported sequence identifier cache version {}", version)));
+        }
+        let mut cache = CacheReader { reader, version, metadata: CacheMetadata::default(), previous: None };
+        if version >= 2 {
+            let length = cache.varint()?;
+            let block = cache.bytes(length)?;
+            cache.metadata = CacheMetadata::decode(&block)?;
+        }
+        Ok(cache)
+    }
+
+    /// The version of the format the cache was written in
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// The metadata about the run, which is empty for caches of version 1
+    pub fn metadata(&self) -> &CacheMetadata {
+        &self.metadata
+    }
+
+    /// Reads the next sequence identifier, or returns `None` at the end of the cache
+    pub fn read(&mut self) -> Result<Option<SequenceIdentifier>, IlluminaError> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let flags = self.byte()?;
+        let previous = self.previous.take();
+        let (sequencer_id, run_count, flow_cell_id) = match previous {
+            _ if flags & NEW_RUN != 0 => {
+                (IdString::from(self.string()?.as_str()), self.varint_u16()?, IdString::from(self.string()?.as_str()))
+            },
+            Some(ref previous) => (previous.sequencer_id.clone(), previous.run_count, previous.flow_cell_id.clone()),
+            None => return Err(IlluminaError::FormatError("first cached record has no run".to_string()))
+        };
+        let (lane, side, swath) = (self.byte()?, self.byte()?, self.byte()?);
+        let camera = if flags & HAS_CAMERA != 0 { Some(self.byte()?) } else { None };
+        let (tile, read) = (self.byte()?, self.byte()?);
+        let (x, y, control_number) = (self.varint_u16()?, self.varint_u16()?, self.varint_u16()?);
+        let sample = match previous {
+            _ if flags & NEW_SAMPLE != 0 => match self.byte()? {
+                SAMPLE_NUMBER => Sample::Number(self.byte()?),
+                SAMPLE_SEQUENCE => Sample::Sequence(Barcode::new(&self.string()?)?),
+                SAMPLE_OTHER => Sample::Other(self.string()?),
+                tag => return Err(IlluminaError::FormatError(format!("unknown sample tag {} in cache", tag)))
+            },
+            Some(previous) => previous.sample,
+            None => return Err(IlluminaError::FormatError("first cached record has no sample".to_string()))
+        };
+        let seq_id = SequenceIdentifier {
+            sequencer_id, run_count, flow_cell_id, lane, side, swath, camera, tile, x, y, read,
+            is_filtered: flags & IS_FILTERED != 0, control_number, sample
+        };
+        self.previous = Some(seq_id.clone());
+        Ok(Some(seq_id))
+    }
+
+    fn byte(&mut self) -> Result<u8, IlluminaError> {
+        let mut byte = [0u8];
+        self.reader.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, IlluminaError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(IlluminaError::FormatError("variable-length integer in cache is too long".to_string()))
+    }
+
+    fn varint_u16(&mut self) -> Result<u16, IlluminaError> {
+        let value = self.varint()?;
+        if value > u64::from(u16::MAX) {
+            return Err(IlluminaError::FormatError(format!("cached value {} is out of range", value)));
+        }
+        Ok(value as u16)
+    }
+
+    /// Reads `length` bytes. The length comes from the file, so nothing is allocated for bytes
+    /// that aren't there.
+    fn bytes(&mut self, length: u64) -> Result<Vec<u8>, IlluminaError> {
+        let mut bytes = vec![];
+        (&mut self.reader).take(length).read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < length {
+            return Err(IlluminaError::FormatError("cache ends partway through a field".to_string()));
+        }
+        Ok(bytes)
+    }
+
+    fn string(&mut self) -> Result<String, IlluminaError> {
+        let length = self.varint()?;
+        let bytes = self.bytes(length)?;
+        String::from_utf8(bytes).map_err(|_| IlluminaError::FormatError("cached string isn't valid UTF-8".to_string()))
+    }
+}
+
+impl<R: BufRead> Iterator for CacheReader<R> {
+    type Item = Result<SequenceIdentifier, IlluminaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+/// Reads all of the sequence identifiers in a cache
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::cache::{read_cache, write_cache};
+///
+/// fn main() {
+///     let lines = ["@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0", "@M03745:11:000000000-B54L5:1:2108:4128:8950 1:Y:0:0"];
+///     let seq_ids: Vec<_> = lines.iter()
+///         .map(|line| illumina_coordinates::parse_sequence_identifier(line).unwrap())
+///         .collect();
+///     let bytes = write_cache(vec![], &seq_ids).unwrap();
+///     assert_eq!(read_cache(&bytes[..]).unwrap(), seq_ids);
+/// }
+/// ```
+pub fn read_cache<R: BufRead>(reader: R) -> Result<Vec<SequenceIdentifier>, IlluminaError> {
+    CacheReader::new(reader)?.collect()
+}
+
+/// Serializes the sequence identifiers with bincode into a versioned container, and returns the
+/// underlying writer. Needs the `bincode` feature.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::cache::{read_bincode, write_bincode};
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_ids = vec![illumina_coordinates::parse_sequence_identifier(line).unwrap()];
+///     let bytes = write_bincode(vec![], &seq_ids).unwrap();
+///     assert_eq!(read_bincode(&bytes[..]).unwrap(), seq_ids);
+/// }
+/// ```
+#[cfg(feature = "bincode")]
+pub fn write_bincode<W: Write>(writer: W, seq_ids: &[SequenceIdentifier]) -> io::Result<W> {
+    let payload = bincode::serde::encode_to_vec(seq_ids, bincode::config::standard())
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+    write_container(writer, BINCODE_MAGIC, &payload)
+}
+
+/// Reads the sequence identifiers in a container written by `write_bincode`. Needs the
+/// `bincode` feature.
+#[cfg(feature = "bincode")]
+pub fn read_bincode<R: Read>(reader: R) -> Result<Vec<SequenceIdentifier>, IlluminaError> {
+    let payload = read_container(reader, BINCODE_MAGIC)?;
+    match bincode::serde::decode_from_slice(&payload, bincode::config::standard()) {
+        Ok((seq_ids, length)) if length == payload.len() => Ok(seq_ids),
+        Ok(_) => Err(IlluminaError::FormatError("bincode cache has trailing bytes".to_string())),
+        Err(error) => Err(IlluminaError::FormatError(format!("invalid bincode cache: {}", error)))
+    }
+}
+
+/// Serializes the sequence identifiers with postcard into a versioned container, and returns the
+/// underlying writer. Needs the `postcard` feature.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::cache::{read_postcard, write_postcard};
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_ids = vec![illumina_coordinates::parse_sequence_identifier(line).unwrap()];
+///     let bytes = write_postcard(vec![], &seq_ids).unwrap();
+///     assert_eq!(read_postcard(&bytes[..]).unwrap(), seq_ids);
+/// }
+/// ```
+#[cfg(feature = "postcard")]
+pub fn write_postcard<W: Write>(writer: W, seq_ids: &[SequenceIdentifier]) -> io::Result<W> {
+    let payload = postcard::to_stdvec(seq_ids)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+    write_container(writer, POSTCARD_MAGIC, &payload)
+}
+
+/// Reads the sequence identifiers in a container written by `write_postcard`. Needs the
+/// `postcard` feature.
+#[cfg(feature = "postcard")]
+pub fn read_postcard<R: Read>(reader: R) -> Result<Vec<SequenceIdentifier>, IlluminaError> {
+    let payload = read_container(reader, POSTCARD_MAGIC)?;
+    match postcard::take_from_bytes(&payload) {
+        Ok((seq_ids, &[])) => Ok(seq_ids),
+        Ok(_) => Err(IlluminaError::FormatError("postcard cache has trailing bytes".to_string())),
+        Err(error) => Err(IlluminaError::FormatError(format!("invalid postcard cache: {}", error)))
+    }
+}
+
+#[cfg(any(feature = "bincode", feature = "postcard"))]
+fn write_container<W: Write>(mut writer: W, magic: &[u8], payload: &[u8]) -> io::Result<W> {
+    writer.write_all(magic)?;
+    writer.write_all(&[SERDE_VERSION])?;
+    writer.write_all(payload)?;
+    writer.flush()?;
+    Ok(writer)
+}
+
+/// Checks the magic number and version of a serde container, and returns what follows them
+#[cfg(any(feature = "bincode", feature = "postcard"))]
+fn read_container<R: Read>(mut reader: R, magic: &[u8]) -> Result<Vec<u8>, IlluminaError> {
+    let mut header = [0u8; 5];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != magic {
+        return Err(IlluminaError::FormatError("not a sequence identifier cache".to_string()));
+    }
+    if header[4] != SERDE_VERSION {
+        return Err(IlluminaError::FormatError(format!("unsupported sequence identifier cache version {}", header[4])));
+    }
+    let mut payload = vec![];
+    reader.read_to_end(&mut payload)?;
+    Ok(payload)
+}
+
+/// Writes an unsigned LEB128 integer: seven bits per byte, least significant first
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    write_varint(buffer, value.len() as u64);
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_round_trip() {
+        let seq_ids: Vec<SequenceIdentifier> = [
+            "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+            "@M03745:11:000000000-B54L5:1:2108:4128:8950 2:Y:12:0",
+            "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 1:N:0:TAAGGC",
+            "@NS500358:204:HTN5KAFXY:4:23612:65535:19567 1:N:0:TAAGGC",
+            "@NS500358:204:HTN5KAFXY:4:23612:11289:19567 1:N:0:3",
+            "@NS500358:205:HTN5KAFXY:4:23612:11289:19567 1:N:0:3",
+            "@NS500358:205:HTN5KAFXY:4:23612:11290:19567 1:N:0:gatcag-ttaggc",
+        ].iter().map(|line| parse_sequence_identifier(line).unwrap()).collect();
+        let bytes = write_cache(vec![], &seq_ids).unwrap();
+        assert_eq!(&bytes[..6], b"ICID\x02\x00");
+        assert_eq!(read_cache(&bytes[..]).unwrap(), seq_ids);
+        // The second record repeats the run and sample of the first
+        let second = write_cache(vec![], &seq_ids[..2]).unwrap().len() - write_cache(vec![], &seq_ids[..1]).unwrap().len();
+        assert_eq!(second, 11);
+    }
+
+    #[test]
+    fn test_metadata() {
+        let run_info = RunInfo {
+            run_id: "160204_M03745_0011_000000000-B54L5".to_string(),
+            run_count: 11,
+            flow_cell_id: "000000000-B54L5".to_string(),
+            sequencer_id: "M03745".to_string(),
+            date: Some("160204".to_string()),
+            reads: vec![ReadInfo { number: 1, cycles: 301, is_index: false }, ReadInfo { number: 2, cycles: 8, is_index: true }],
+            layout: FlowCellLayout { lanes: 1, sides: 2, swaths: 1, cameras: 1, tiles_per_swath: 19 }
+        };
+        let metadata = CacheMetadata::from_run_info(&run_info);
+        assert_eq!(metadata.instrument(), Some(InstrumentModel::MiSeq));
+        let seq_id = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+        let mut writer = CacheWriter::with_metadata(vec![], &metadata).unwrap();
+        writer.write(&seq_id).unwrap();
+        let bytes = writer.finish().unwrap();
+        let reader = CacheReader::new(&bytes[..]).unwrap();
+        assert_eq!((reader.version(), reader.metadata()), (2, &metadata));
+        assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), vec![seq_id.clone()]);
+
+        let from_seq_id = CacheMetadata::from_sequence_identifier(&seq_id);
+        assert_eq!((from_seq_id.run_count, from_seq_id.run_id), (Some(11), None));
+
+        // Fields with unknown tags are skipped
+        let reader = CacheReader::new(&b"ICID\x02\x0c\x63\x02\x01\x02\x02\x06M03745"[..]).unwrap();
+        assert_eq!(reader.metadata().sequencer_id, Some("M03745".to_string()));
+
+        // Caches written before the metadata block are still read
+        let mut version_1 = b"ICID\x01".to_vec();
+        version_1.extend_from_slice(&write_cache(vec![], std::slice::from_ref(&seq_id)).unwrap()[6..]);
+        let reader = CacheReader::new(&version_1[..]).unwrap();
+        assert_eq!((reader.version(), reader.metadata()), (1, &CacheMetadata::default()));
+        assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), vec![seq_id]);
+    }
+
+    #[cfg(any(feature = "bincode", feature = "postcard"))]
+    fn serde_seq_ids() -> Vec<SequenceIdentifier> {
+        [
+            "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+            "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:18:TAAGGC+CTCTCT",
+            "@NS500358:205:HTN5KAFXY:4:23612:11290:19567 1:N:0:gatcag-ttaggc",
+        ].iter().map(|line| parse_sequence_identifier(line).unwrap()).collect()
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn test_bincode() {
+        let seq_ids = serde_seq_ids();
+        let bytes = write_bincode(vec![], &seq_ids).unwrap();
+        assert_eq!(&bytes[..5], b"ICBC\x01");
+        assert_eq!(read_bincode(&bytes[..]).unwrap(), seq_ids);
+        assert_eq!(read_bincode(&write_bincode(vec![], &[]).unwrap()[..]).unwrap(), vec![]);
+        assert!(read_bincode(&bytes[..bytes.len() - 1]).is_err());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(read_bincode(&trailing[..]).is_err());
+        let mut version_2 = bytes.clone();
+        version_2[4] = 2;
+        match read_bincode(&version_2[..]) {
+            Err(IlluminaError::FormatError(message)) => assert!(message.contains("version 2")),
+            _ => panic!("expected a version error")
+        }
+        assert!(read_bincode(&write_cache(vec![], &seq_ids).unwrap()[..]).is_err());
+        // The barcode of a sample is checked like a parsed one
+        let mut invalid = bytes.clone();
+        let barcode = invalid.windows(6).position(|window| window == b"TAAGGC").unwrap();
+        invalid[barcode] = b'X';
+        assert!(read_bincode(&invalid[..]).is_err());
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn test_postcard() {
+        let seq_ids = serde_seq_ids();
+        let bytes = write_postcard(vec![], &seq_ids).unwrap();
+        assert_eq!(&bytes[..5], b"ICPC\x01");
+        assert_eq!(read_postcard(&bytes[..]).unwrap(), seq_ids);
+        assert_eq!(read_postcard(&write_postcard(vec![], &[]).unwrap()[..]).unwrap(), vec![]);
+        assert!(read_postcard(&bytes[..bytes.len() - 1]).is_err());
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(read_postcard(&trailing[..]).is_err());
+        assert!(read_postcard(&b"ICPC\x02"[..]).is_err());
+        // Lengths beyond the end of the file are errors rather than huge allocations
+        assert!(read_postcard(&b"ICPC\x01\xff\xff\xff\xff\x0f"[..]).is_err());
+        #[cfg(feature = "bincode")]
+        assert!(read_postcard(&write_bincode(vec![], &seq_ids).unwrap()[..]).is_err());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(read_cache(&b"ICID"[..]).is_err());
+        assert!(read_cache(&b"TIX2\x01"[..]).is_err());
+        match read_cache(&b"ICID\x03"[..]) {
+            Err(IlluminaError::FormatError(message)) => assert!(message.contains("version 3")),
+            _ => panic!("expected a version error")
+        }
+        assert_eq!(read_cache(&b"ICID\x01"[..]).unwrap(), vec![]);
+        assert_eq!(read_cache(&b"ICID\x02\x00"[..]).unwrap(), vec![]);
+        assert!(read_cache(&b"ICID\x02"[..]).is_err());
+        assert!(read_cache(&b"ICID\x02\x03\x04\x01\x00"[..]).is_err());
+        // Lengths beyond the end of the file are errors rather than huge allocations
+        assert!(read_cache(&b"ICID\x02\xff\xff\xff\xff\xff\xff\xff\xff\x7f"[..]).is_err());
+        assert!(read_cache(&b"ICID\x01\x01\xff\xff\xff\xff\xff\xff\xff\xff\x7fM"[..]).is_err());
+        assert!(read_cache(&b"ICID\x01\x00\x01\x01\x01\x01\x01\x01\x01\x00"[..]).is_err());
+
+        let seq_id = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+        let bytes = write_cache(vec![], &[seq_id]).unwrap();
+        assert!(read_cache(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,425 @@
+//! Checks that a set of FASTQ files come from the same run.
+//!
+//! The files of one sequencing run, such as the read 1 and read 2 files of each lane, are often
+//! handled separately and can be mixed up with those of another run before they meet again.
+//! Every header names the instrument, the run and the flow cell, so a sample of headers from
+//! each file is enough to catch a file that doesn't belong.
+//!
+//! Files can also be mixed up inside: concatenating the FASTQ files of two runs gives one file
+//! whose headers switch run part of the way through. `RunSwitchDetector` finds the switches in a
+//! stream of reads.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::BufRead;
+use std::path::Path;
+use fastq::{FastqReader, Record};
+use gzip;
+use super::{IlluminaError, SequenceIdentifier};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The fields of a sequence identifier that name the run
+pub struct RunIdentity {
+    /// ID of the sequencing machine
+    pub sequencer_id: String,
+    /// The run count for the machine
+    pub run_count: u16,
+    /// ID of the flow cell
+    pub flow_cell_id: String
+}
+
+impl RunIdentity {
+    /// The run of a read
+    pub fn of(seq_id: &SequenceIdentifier) -> RunIdentity {
+        RunIdentity {
+            sequencer_id: seq_id.sequencer_id.to_string(),
+            run_count: seq_id.run_count,
+            flow_cell_id: seq_id.flow_cell_id.to_string()
+        }
+    }
+
+    /// Whether a read is from this run
+    pub fn matches(&self, seq_id: &SequenceIdentifier) -> bool {
+        seq_id.run_count == self.run_count && seq_id.sequencer_id == self.sequencer_id
+            && seq_id.flow_cell_id == self.flow_cell_id
+    }
+}
+
+impl fmt::Display for RunIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "run {} of {} on flow cell {}", self.run_count, self.sequencer_id, self.flow_cell_id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// The runs and lanes seen in a sample of a file's headers
+pub struct FileSample {
+    /// The number of headers sampled
+    pub headers: u64,
+    /// The runs of the sampled reads, in the order they were first seen
+    pub runs: Vec<RunIdentity>,
+    /// The lanes of the sampled reads
+    pub lanes: BTreeSet<u8>
+}
+
+impl FileSample {
+    /// Adds a read to the sample
+    pub fn add(&mut self, seq_id: &SequenceIdentifier) {
+        self.headers += 1;
+        let run = RunIdentity::of(seq_id);
+        if !self.runs.contains(&run) {
+            self.runs.push(run);
+        }
+        self.lanes.insert(seq_id.lane);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A way in which the files differ from each other or from the expected lanes. Files are
+/// numbered by their position in the list that was checked.
+pub enum Inconsistency {
+    /// A read was from another instrument than the first read of the first file
+    Instrument {
+        /// The file
+        file: usize,
+        /// The instrument of the first read of the first file
+        expected: String,
+        /// The instrument of the read
+        found: String
+    },
+    /// A read was from another run of the instrument than the first read of the first file
+    RunCount {
+        /// The file
+        file: usize,
+        /// The run count of the first read of the first file
+        expected: u16,
+        /// The run count of the read
+        found: u16
+    },
+    /// A read was from another flow cell than the first read of the first file
+    FlowCell {
+        /// The file
+        file: usize,
+        /// The flow cell of the first read of the first file
+        expected: String,
+        /// The flow cell of the read
+        found: String
+    },
+    /// A file had no headers to sample
+    Empty {
+        /// The file
+        file: usize
+    },
+    /// A file had reads from a lane that wasn't expected
+    UnexpectedLane {
+        /// The file
+        file: usize,
+        /// The lane
+        lane: u8
+    },
+    /// No file had reads from an expected lane
+    MissingLane {
+        /// The lane
+        lane: u8
+    }
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Inconsistency::Instrument { file, ref expected, ref found } => {
+                write!(f, "file {} has reads from instrument {}, expected {}", file + 1, found, expected)
+            },
+            Inconsistency::RunCount { file, expected, found } => {
+                write!(f, "file {} has reads from run {}, expected {}", file + 1, found, expected)
+            },
+            Inconsistency::FlowCell { file, ref expected, ref found } => {
+                write!(f, "file {} has reads from flow cell {}, expected {}", file + 1, found, expected)
+            },
+            Inconsistency::Empty { file } => write!(f, "file {} has no reads", file + 1),
+            Inconsistency::UnexpectedLane { file, lane } => write!(f, "file {} has reads from unexpected lane {}", file + 1, lane),
+            Inconsistency::MissingLane { lane } => write!(f, "no file has reads from lane {}", lane)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The result of checking a set of files
+pub struct ConsistencyReport {
+    /// The sample of each file, in the order the files were given
+    pub files: Vec<FileSample>,
+    /// How the files differ, in order of file, with missing lanes last
+    pub inconsistencies: Vec<Inconsistency>
+}
+
+impl ConsistencyReport {
+    /// Whether all files are from one run and cover the expected lanes
+    pub fn is_consistent(&self) -> bool {
+        self.inconsistencies.is_empty()
+    }
+
+    /// The run of the first read of the first file, if any file had reads
+    pub fn run(&self) -> Option<&RunIdentity> {
+        self.files.iter().filter_map(|file| file.runs.first()).next()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A point in a file where the reads change from one run to another
+pub struct RunSwitch {
+    /// The number of the first record from the new run, counting from 1
+    pub record: u64,
+    /// The line of the first record's header, counting from 1
+    pub line: u64,
+    /// The run of the records before the switch
+    pub from: RunIdentity,
+    /// The run of the records from the switch on
+    pub to: RunIdentity
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// Watches the reads of a file, in order, for changes of instrument, run or flow cell
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::consistency::RunSwitchDetector;
+///
+/// fn main() {
+///     let mut detector = RunSwitchDetector::new();
+///     for line in &["@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+///                   "@M03745:11:000000000-B54L5:1:2108:4128:8950 1:N:0:0",
+///                   "@M03745:12:000000000-C23K9:1:1101:1000:1000 1:N:0:0"] {
+///         detector.add(&illumina_coordinates::parse_sequence_identifier(line).unwrap());
+///     }
+///     let switch = detector.first_switch().unwrap();
+///     assert_eq!((switch.record, switch.line), (3, 9));
+///     assert_eq!(switch.to.flow_cell_id, "000000000-C23K9");
+/// }
+/// ```
+pub struct RunSwitchDetector {
+    records: u64,
+    run: Option<RunIdentity>,
+    switches: Vec<RunSwitch>
+}
+
+impl RunSwitchDetector {
+    /// Creates a detector that hasn't seen any reads
+    pub fn new() -> RunSwitchDetector {
+        RunSwitchDetector::default()
+    }
+
+    /// Checks the next read of the file, returning the switch if its run differs from that of
+    /// the read before it
+    pub fn add(&mut self, seq_id: &SequenceIdentifier) -> Option<&RunSwitch> {
+        self.records += 1;
+        if let Some(ref run) = self.run {
+            if run.matches(seq_id) {
+                return None;
+            }
+        }
+        let to = RunIdentity::of(seq_id);
+        let from = self.run.replace(to.clone())?;
+        // Records are four lines long, so the header of the first record is line 1
+        self.switches.push(RunSwitch { record: self.records, line: self.records * 4 - 3, from, to });
+        self.switches.last()
+    }
+
+    /// The number of reads checked
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// The first switch, if the run has changed
+    pub fn first_switch(&self) -> Option<&RunSwitch> {
+        self.switches.first()
+    }
+
+    /// Every switch so far, in order. A file of two concatenated runs has one switch, and one
+    /// whose reads alternate between runs has many.
+    pub fn switches(&self) -> &[RunSwitch] {
+        &self.switches
+    }
+}
+
+/// Reads a whole FASTQ file and returns the first point where its reads switch run, if they do
+pub fn find_run_switch<R: BufRead>(reader: R) -> Result<Option<RunSwitch>, IlluminaError> {
+    let mut reader = FastqReader::new(reader);
+    let mut record = Record::default();
+    let mut detector = RunSwitchDetector::new();
+    while reader.read_record(&mut record)? {
+        if let Some(switch) = detector.add(&record.sequence_identifier()?) {
+            return Ok(Some(switch.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// Samples the headers of up to `max_headers` records from the start of a FASTQ file
+pub fn sample<R: BufRead>(reader: R, max_headers: u64) -> Result<FileSample, IlluminaError> {
+    let mut reader = FastqReader::new(reader);
+    let mut record = Record::default();
+    let mut sample = FileSample::default();
+    while sample.headers < max_headers && reader.read_record(&mut record)? {
+        sample.add(&record.sequence_identifier()?);
+    }
+    Ok(sample)
+}
+
+/// Compares samples of the files' headers. Every read must be from the same instrument, run and
+/// flow cell as the first read of the first file. If `expected_lanes` is given, every read must
+/// be from one of them and each of them must appear in some file.
+pub fn compare(files: Vec<FileSample>, expected_lanes: Option<&BTreeSet<u8>>) -> ConsistencyReport {
+    let mut inconsistencies = vec![];
+    let reference = files.iter().filter_map(|file| file.runs.first()).next().cloned();
+    for (index, file) in files.iter().enumerate() {
+        if file.headers == 0 {
+            inconsistencies.push(Inconsistency::Empty { file: index });
+        }
+        if let Some(ref expected) = reference {
+            for run in &file.runs {
+                if run.sequencer_id != expected.sequencer_id {
+                    inconsistencies.push(Inconsistency::Instrument {
+                        file: index, expected: expected.sequencer_id.clone(), found: run.sequencer_id.clone()
+                    });
+                }
+                if run.run_count != expected.run_count {
+                    inconsistencies.push(Inconsistency::RunCount {
+                        file: index, expected: expected.run_count, found: run.run_count
+                    });
+                }
+                if run.flow_cell_id != expected.flow_cell_id {
+                    inconsistencies.push(Inconsistency::FlowCell {
+                        file: index, expected: expected.flow_cell_id.clone(), found: run.flow_cell_id.clone()
+                    });
+                }
+            }
+        }
+        if let Some(expected_lanes) = expected_lanes {
+            for &lane in file.lanes.difference(expected_lanes) {
+                inconsistencies.push(Inconsistency::UnexpectedLane { file: index, lane });
+            }
+        }
+    }
+    if let Some(expected_lanes) = expected_lanes {
+        let seen: BTreeSet<u8> = files.iter().flat_map(|file| file.lanes.iter().cloned()).collect();
+        for &lane in expected_lanes.difference(&seen) {
+            inconsistencies.push(Inconsistency::MissingLane { lane });
+        }
+    }
+    ConsistencyReport { files, inconsistencies }
+}
+
+/// Samples up to `max_headers` headers from the start of each FASTQ file, gzipped or not, and
+/// checks that the files come from one run, as `compare` does. Only the sampled headers are
+/// checked, so a file that switches runs further in isn't caught; the `validate` command of
+/// `illumina-coords` checks every header of a file.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use std::fs;
+/// use illumina_coordinates::consistency::{check_files, Inconsistency};
+///
+/// fn main() {
+///     let dir = std::env::temp_dir();
+///     let read1 = dir.join("illumina_coordinates_consistency_example_R1.fastq");
+///     let read2 = dir.join("illumina_coordinates_consistency_example_R2.fastq");
+///     fs::write(&read1, "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\nACGT\n+\nAAAA\n").unwrap();
+///     fs::write(&read2, "@M03745:12:000000000-B54L5:1:2108:4127:8949 2:N:0:0\nACGT\n+\nAAAA\n").unwrap();
+///     let report = check_files(&[&read1, &read2], 1000, None).unwrap();
+///     assert_eq!(report.inconsistencies, vec![Inconsistency::RunCount { file: 1, expected: 11, found: 12 }]);
+///     fs::remove_file(read1).unwrap();
+///     fs::remove_file(read2).unwrap();
+/// }
+/// ```
+pub fn check_files<P: AsRef<Path>>(paths: &[P], max_headers: u64, expected_lanes: Option<&BTreeSet<u8>>)
+        -> Result<ConsistencyReport, IlluminaError> {
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        files.push(sample(gzip::open(path)?, max_headers)?);
+    }
+    Ok(compare(files, expected_lanes))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    fn fastq(headers: &[&str]) -> String {
+        headers.iter().map(|header| format!("{}\nACGT\n+\nAAAA\n", header)).collect()
+    }
+
+    #[test]
+    fn test_sample() {
+        let text = fastq(&["@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:0",
+                           "@A00123:8:H3KJ7DSXX:2:1101:1000:1000 1:N:0:0",
+                           "@A00123:9:H3KJ7DSXX:3:1101:1000:1000 1:N:0:0"]);
+        let sampled = sample(text.as_bytes(), 2).unwrap();
+        assert_eq!(sampled.headers, 2);
+        assert_eq!(sampled.runs.len(), 1);
+        assert_eq!(sampled.lanes.iter().cloned().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(sample(text.as_bytes(), 10).unwrap().runs.len(), 2);
+        assert!(sample(&b"@read1\nACGT\n+\nAAAA\n"[..], 10).is_err());
+    }
+
+    #[test]
+    fn test_run_switch_detector() {
+        let mut detector = RunSwitchDetector::new();
+        let lines = ["@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:0",
+                     "@A00123:8:H3KJ7DSXX:2:1101:1000:1000 1:N:0:0",
+                     "@A00456:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:0",
+                     "@A00456:8:H3KJ7DSXX:1:1101:1000:1001 1:N:0:0",
+                     "@A00123:8:H3KJ7DSXX:1:1101:1000:1002 1:N:0:0"];
+        let switched: Vec<bool> = lines.iter()
+            .map(|line| detector.add(&parse_sequence_identifier(line).unwrap()).is_some())
+            .collect();
+        assert_eq!(switched, vec![false, false, true, false, true]);
+        assert_eq!(detector.records(), 5);
+        let switches = detector.switches();
+        assert_eq!(switches.iter().map(|switch| switch.line).collect::<Vec<_>>(), vec![9, 17]);
+        assert_eq!((switches[0].from.sequencer_id.as_str(), switches[0].to.sequencer_id.as_str()), ("A00123", "A00456"));
+        assert_eq!(switches[1].to, RunIdentity::of(&parse_sequence_identifier(lines[0]).unwrap()));
+    }
+
+    #[test]
+    fn test_find_run_switch() {
+        let text = fastq(&["@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:0", "@A00123:8:H3KJ7DSXX:1:1101:1000:1001 1:N:0:0"]);
+        assert_eq!(find_run_switch(text.as_bytes()).unwrap(), None);
+        let text = text + &fastq(&["@A00123:9:H3KJ7DSXX:1:1101:1000:1000 1:N:0:0"]);
+        let switch = find_run_switch(text.as_bytes()).unwrap().unwrap();
+        assert_eq!((switch.record, switch.line, switch.from.run_count, switch.to.run_count), (3, 9, 8, 9));
+    }
+
+    #[test]
+    fn test_compare() {
+        let files = [
+            fastq(&["@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:0"]),
+            fastq(&["@A00123:8:H3KJ7DSXX:2:1101:1000:1000 1:N:0:0", "@A00456:8:H5LM2DSXX:2:1101:1000:1000 1:N:0:0"]),
+            String::new(),
+            fastq(&["@A00123:8:H3KJ7DSXX:5:1101:1000:1000 1:N:0:0"])
+        ];
+        let samples = files.iter().map(|text| sample(text.as_bytes(), 100).unwrap()).collect();
+        let lanes = [1, 2, 3].iter().cloned().collect();
+        let report = compare(samples, Some(&lanes));
+        assert!(!report.is_consistent());
+        assert_eq!(report.run().unwrap().flow_cell_id, "H3KJ7DSXX");
+        assert_eq!(report.inconsistencies, vec![
+            Inconsistency::Instrument { file: 1, expected: "A00123".to_string(), found: "A00456".to_string() },
+            Inconsistency::FlowCell { file: 1, expected: "H3KJ7DSXX".to_string(), found: "H5LM2DSXX".to_string() },
+            Inconsistency::Empty { file: 2 },
+            Inconsistency::UnexpectedLane { file: 3, lane: 5 },
+            Inconsistency::MissingLane { lane: 3 }
+        ]);
+        assert_eq!(report.inconsistencies[1].to_string(), "file 2 has reads from flow cell H5LM2DSXX, expected H3KJ7DSXX");
+
+        let same = [fastq(&["@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:0"]),
+                        fastq(&["@A00123:8:H3KJ7DSXX:1:1101:1000:1000 2:N:0:0"])];
+        let report = compare(same.iter().map(|text| sample(text.as_bytes(), 100).unwrap()).collect(), None);
+        assert!(report.is_consistent());
+    }
+}
@@ -0,0 +1,78 @@
+//! Decoding of the control number in sequence identifiers.
+//!
+//! Illumina defines the control number as a bitfield: it's 0 when none of the control bits are
+//! set, and otherwise records which kinds of control the read matched. Pipelines differ in which
+//! bits they use, so the bits are exposed by number rather than by name.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+/// The control bits of a read
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:6:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     let flags = seq_id.control_flags();
+///     assert!(flags.is_control());
+///     assert!(flags.is_set(1) && flags.is_set(2) && !flags.is_set(0));
+///     assert_eq!(flags.set_bits().collect::<Vec<_>>(), vec![1, 2]);
+/// }
+/// ```
+pub struct ControlFlags(pub u16);
+
+impl ControlFlags {
+    /// The raw control number
+    pub fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// Whether any control bit is set
+    pub fn is_control(self) -> bool {
+        self.0 != 0
+    }
+
+    /// Whether the bit is set, counting from 0 for the least significant bit. Bits beyond the
+    /// width of the control number are never set.
+    pub fn is_set(self, bit: u8) -> bool {
+        bit < 16 && self.0 & (1 << bit) != 0
+    }
+
+    /// The numbers of the bits that are set, in increasing order
+    pub fn set_bits(self) -> impl Iterator<Item=u8> {
+        (0..16).filter(move |&bit| self.is_set(bit))
+    }
+}
+
+impl From<u16> for ControlFlags {
+    fn from(bits: u16) -> ControlFlags {
+        ControlFlags(bits)
+    }
+}
+
+impl fmt::Display for ControlFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags() {
+        let flags = ControlFlags(0b1000_0000_0000_0010);
+        assert!(flags.is_control());
+        assert!(flags.is_set(15));
+        assert!(!flags.is_set(16));
+        assert_eq!(flags.set_bits().collect::<Vec<_>>(), vec![1, 15]);
+        assert!(!ControlFlags::default().is_control());
+        assert_eq!(ControlFlags::from(6).to_string(), "6");
+    }
+}
@@ -0,0 +1,259 @@
+//! Approximate counts of index sequences, for Undetermined Reads too diverse to count exactly.
+//!
+//! A pool of many samples with long dual indexes can leave hundreds of millions of distinct
+//! barcodes in Undetermined Reads, nearly all of them sequencing errors seen once. Counting them
+//! in a `HashMap` takes more memory than the reads are worth. A count-min sketch keeps a fixed
+//! grid of counters instead, and never undercounts: each estimate is at most the true count plus
+//! a small fraction of all reads. The barcodes that matter are the common ones, such as a sample
+//! missing from the sample sheet, and those are tracked alongside the sketch as heavy hitters.
+
+use std::collections::HashMap;
+use barcode::Barcode;
+use subsample::{hash, mix};
+use super::SequenceIdentifier;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A count-min sketch of byte strings, as described by Cormode and Muthukrishnan
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::count_min::CountMinSketch;
+///
+/// fn main() {
+///     let mut sketch = CountMinSketch::with_error(0.001, 0.01);
+///     for _ in 0..5 {
+///         sketch.add(b"TAAGGCGA");
+///     }
+///     sketch.add(b"CTCTCTAT");
+///     assert_eq!(sketch.estimate(b"TAAGGCGA"), 5);
+///     assert_eq!(sketch.total(), 6);
+/// }
+/// ```
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    counts: Vec<u64>,
+    total: u64
+}
+
+impl CountMinSketch {
+    /// Creates a sketch with `depth` rows of `width` counters
+    pub fn new(width: usize, depth: usize) -> CountMinSketch {
+        let (width, depth) = (width.max(1), depth.max(1));
+        let size = width.checked_mul(depth).expect("too many counters for a count-min sketch");
+        CountMinSketch { width, depth, counts: vec![0; size], total: 0 }
+    }
+
+    /// Creates a sketch whose estimates exceed the true counts by more than `epsilon` times the
+    /// total with probability at most `delta`. `epsilon` is clamped to between one in a million
+    /// and 1, and `delta` to between one in a billion and one half.
+    pub fn with_error(epsilon: f64, delta: f64) -> CountMinSketch {
+        let epsilon = if epsilon > 1.0 { 1.0 } else { epsilon.max(1e-6) };
+        let delta = if delta > 0.5 { 0.5 } else { delta.max(1e-9) };
+        let width = (std::f64::consts::E / epsilon).ceil() as usize;
+        let depth = (1.0 / delta).ln().ceil() as usize;
+        CountMinSketch::new(width, depth)
+    }
+
+    /// The number of counters in each row
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The number of rows
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Counts a value once
+    pub fn add(&mut self, value: &[u8]) {
+        self.add_count(value, 1);
+    }
+
+    /// Counts a value `count` times
+    pub fn add_count(&mut self, value: &[u8], count: u64) {
+        for index in self.indexes(value) {
+            self.counts[index] += count;
+        }
+        self.total += count;
+    }
+
+    /// The estimated number of times a value was counted, which is never less than the true
+    /// number
+    pub fn estimate(&self, value: &[u8]) -> u64 {
+        self.indexes(value).map(|index| self.counts[index]).min().unwrap_or(0)
+    }
+
+    /// The number of values counted
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The counter of each row for a value, from two hashes combined as in Kirsch and
+    /// Mitzenmacher's "Less Hashing, Same Performance"
+    fn indexes(&self, value: &[u8]) -> impl Iterator<Item=usize> {
+        let first = hash(0, value);
+        let step = mix(first) | 1;
+        let width = self.width;
+        (0..self.depth).map(move |row| {
+            row * width + (first.wrapping_add((row as u64).wrapping_mul(step)) % width as u64) as usize
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Counts the index sequences of reads in a count-min sketch, keeping track of the most common
+/// ones
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::count_min::{BarcodeCounter, CountMinSketch};
+/// use illumina_coordinates::parse_sequence_identifier;
+///
+/// fn main() {
+///     let mut counter = BarcodeCounter::new(CountMinSketch::with_error(0.0001, 0.01), 100);
+///     for line in &["@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:TAAGGCGA+CTCTCTAT",
+///                   "@M03745:11:000000000-B54L5:1:2108:4128:8950 1:N:0:TAAGGCGA+CTCTCTAT",
+///                   "@M03745:11:000000000-B54L5:1:2108:4129:8951 1:N:0:GGGGGGGG+CTCTCTAT"] {
+///         counter.add(&parse_sequence_identifier(line).unwrap());
+///     }
+///     let hitters = counter.heavy_hitters(0.5);
+///     assert_eq!(hitters.len(), 1);
+///     assert_eq!((hitters[0].0.as_str(), hitters[0].1), ("TAAGGCGA+CTCTCTAT", 2));
+/// }
+/// ```
+pub struct BarcodeCounter {
+    sketch: CountMinSketch,
+    capacity: usize,
+    // The estimated counts of the barcodes that may be among the most common
+    candidates: HashMap<Barcode, u64>,
+    // No candidate's count is below this, once the candidates are full
+    floor: u64
+}
+
+impl BarcodeCounter {
+    /// Creates a counter that tracks up to `capacity` of the most common barcodes
+    pub fn new(sketch: CountMinSketch, capacity: usize) -> BarcodeCounter {
+        BarcodeCounter { sketch, capacity: capacity.max(1), candidates: HashMap::new(), floor: 0 }
+    }
+
+    /// Counts a read's index sequence. Reads whose sample is a number are skipped.
+    pub fn add(&mut self, seq_id: &SequenceIdentifier) {
+        if let Some(barcode) = seq_id.sample.as_barcode() {
+            self.add_barcode(barcode);
+        }
+    }
+
+    /// Counts a barcode
+    pub fn add_barcode(&mut self, barcode: &Barcode) {
+        self.sketch.add(barcode.as_bytes());
+        let count = self.sketch.estimate(barcode.as_bytes());
+        if let Some(candidate) = self.candidates.get_mut(barcode) {
+            *candidate = count;
+            return;
+        }
+        if self.candidates.len() < self.capacity {
+            self.candidates.insert(*barcode, count);
+        } else if count > self.floor {
+            // The floor may be out of date, since candidates' counts only grow
+            let (&smallest, &smallest_count) = self.candidates.iter().min_by_key(|&(_, &count)| count)
+                .expect("the candidates are full");
+            if count > smallest_count {
+                self.candidates.remove(&smallest);
+                self.candidates.insert(*barcode, count);
+            }
+            self.floor = self.candidates.values().cloned().min().unwrap_or(0);
+        }
+    }
+
+    /// The estimated number of reads with a barcode
+    pub fn estimate(&self, barcode: &str) -> u64 {
+        self.sketch.estimate(barcode.as_bytes())
+    }
+
+    /// The number of barcodes counted
+    pub fn total(&self) -> u64 {
+        self.sketch.total()
+    }
+
+    /// The sketch the barcodes are counted in
+    pub fn sketch(&self) -> &CountMinSketch {
+        &self.sketch
+    }
+
+    /// The tracked barcodes with at least `min_fraction` of all barcodes counted, most common
+    /// first, with their estimated counts. A barcode's estimate is checked against the tracked
+    /// barcodes every time it's counted, so common barcodes are tracked as long as there are
+    /// fewer than `capacity` of them.
+    pub fn heavy_hitters(&self, min_fraction: f64) -> Vec<(Barcode, u64)> {
+        let min_count = min_fraction * self.total() as f64;
+        let mut hitters: Vec<(Barcode, u64)> = self.candidates.iter()
+            .filter(|&(_, &count)| count as f64 >= min_count)
+            .map(|(&barcode, &count)| (barcode, count))
+            .collect();
+        hitters.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hitters
+    }
+
+    /// The `n` most common tracked barcodes, most common first, with their estimated counts
+    pub fn top(&self, n: usize) -> Vec<(Barcode, u64)> {
+        let mut top = self.heavy_hitters(0.0);
+        top.truncate(n);
+        top
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sketch() {
+        let mut sketch = CountMinSketch::with_error(0.01, 0.01);
+        assert_eq!((sketch.width(), sketch.depth()), (272, 5));
+        let clamped = [CountMinSketch::with_error(0.0, 0.5), CountMinSketch::with_error(-1.0, 2.0),
+                       CountMinSketch::with_error(2.0, 0.0)];
+        let sizes: Vec<(usize, usize)> = clamped.iter().map(|sketch| (sketch.width(), sketch.depth())).collect();
+        assert_eq!(sizes, vec![(2_718_282, 1), (2_718_282, 1), (3, 21)]);
+        assert_eq!(sketch.estimate(b"ACGT"), 0);
+        for value in 0..10_000u32 {
+            sketch.add(&value.to_le_bytes());
+        }
+        sketch.add_count(b"ACGT", 500);
+        assert_eq!(sketch.total(), 10_500);
+        let estimate = sketch.estimate(b"ACGT");
+        assert!((500..=500 + 105 * 3).contains(&estimate), "{}", estimate);
+        assert!((0..10_000u32).all(|value| sketch.estimate(&value.to_le_bytes()) >= 1));
+    }
+
+    #[test]
+    fn test_heavy_hitters() {
+        let mut counter = BarcodeCounter::new(CountMinSketch::new(2000, 4), 10);
+        let common = [Barcode::new("TAAGGCGA").unwrap(), Barcode::new("CGTACTAG").unwrap()];
+        let bases = ['A', 'C', 'G', 'T'];
+        for read in 0..20_000u32 {
+            match read % 10 {
+                0..=2 => counter.add_barcode(&common[0]),
+                3 => counter.add_barcode(&common[1]),
+                _ => {
+                    // Mostly rare barcodes, each seen a few times
+                    let noise: String = (0..8).map(|i| bases[(read.wrapping_mul(2_654_435_761) >> (2 * i + 8)) as usize % 4]).collect();
+                    counter.add_barcode(&Barcode::new(&noise).unwrap());
+                }
+            }
+        }
+        let hitters = counter.heavy_hitters(0.05);
+        assert_eq!(hitters.len(), 2);
+        assert_eq!(hitters[0].0, common[0]);
+        assert!((6000..6200).contains(&hitters[0].1), "{}", hitters[0].1);
+        assert_eq!(hitters[1].0, common[1]);
+        assert_eq!(counter.top(1).len(), 1);
+        assert_eq!(counter.top(100).len(), 10);
+        assert!(counter.estimate("CGTACTAG") >= 2000);
+    }
+}
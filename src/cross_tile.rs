@@ -0,0 +1,146 @@
+//! Detection of clusters imaged on two neighbouring tiles.
+//!
+//! The images of neighbouring tiles overlap slightly, so a cluster near the edge of one tile can
+//! be imaged again at the opposite edge of the next, and reported as two reads on different
+//! tiles. Duplicate-marking tools only compare reads on the same tile, so these pairs are
+//! missed. Converting positions to coordinates on the lane with `geometry::lane_coordinates`
+//! brings the two copies close together again.
+
+use std::collections::HashMap;
+use geometry::{adjacent_tiles, lane_coordinates, FlowCellLayout, TileExtent};
+use super::{SequenceIdentifier, TileId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Where a read was found
+pub struct ReadPosition {
+    /// The tile the read is on
+    pub tile: TileId,
+    /// The x-coordinate of the read
+    pub x: u16,
+    /// The y-coordinate of the read
+    pub y: u16
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Two reads with the same sequence on neighbouring tiles, close enough to be the same cluster
+pub struct CrossTileDuplicate {
+    /// The read seen first
+    pub first: ReadPosition,
+    /// The read seen second
+    pub second: ReadPosition
+}
+
+/// Finds reads with the same sequence near the shared edge of two neighbouring tiles. Only
+/// reads within `tolerance` of an edge of their tile are kept, and two reads are duplicates if
+/// their lane coordinates are within `tolerance` of each other in each of x and y, so the
+/// tolerance should be at least the overlap between the tile images.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::cross_tile::CrossTileDetector;
+/// use illumina_coordinates::flow_cell::FlowCellType;
+/// use illumina_coordinates::geometry;
+///
+/// fn main() {
+///     let layout = FlowCellType::MiSeqV2.layout();
+///     let mut detector = CrossTileDetector::new(layout, geometry::MISEQ, 300);
+///     for line in &["@M03745:11:000000000-B54L5:1:2108:4127:29900 1:N:0:0",
+///                   "@M03745:11:000000000-B54L5:1:2109:4130:1100 1:N:0:0"] {
+///         let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///         detector.add(&seq_id, b"ACGT");
+///     }
+///     assert_eq!(detector.duplicates().len(), 1);
+///     assert_eq!(detector.duplicates()[0].second.tile.tile, 9);
+/// }
+/// ```
+pub struct CrossTileDetector {
+    layout: FlowCellLayout,
+    extent: TileExtent,
+    tolerance: u16,
+    // The reads near an edge with each sequence, on each side of each lane
+    edge_reads: HashMap<(u8, u8, Vec<u8>), Vec<ReadPosition>>,
+    duplicates: Vec<CrossTileDuplicate>
+}
+
+impl CrossTileDetector {
+    /// Creates a detector for a flow cell with the given layout and tile extent
+    pub fn new(layout: FlowCellLayout, extent: TileExtent, tolerance: u16) -> CrossTileDetector {
+        CrossTileDetector { layout, extent, tolerance, edge_reads: HashMap::new(), duplicates: vec![] }
+    }
+
+    /// Checks a read with the given sequence against the earlier reads near the edges of the
+    /// neighbouring tiles
+    pub fn add(&mut self, seq_id: &SequenceIdentifier, sequence: &[u8]) {
+        if !self.extent.is_near_edge(seq_id.x, seq_id.y, self.tolerance) {
+            return;
+        }
+        let tile = seq_id.tile_id();
+        let here = match lane_coordinates(&self.layout, self.extent, tile, seq_id.x, seq_id.y) {
+            Some(coordinates) => coordinates,
+            None => return
+        };
+        let read = ReadPosition { tile, x: seq_id.x, y: seq_id.y };
+        let neighbours = adjacent_tiles(&self.layout, tile);
+        let earlier = self.edge_reads.entry((tile.lane, tile.side, sequence.to_vec())).or_default();
+        let tolerance = u32::from(self.tolerance);
+        for other in earlier.iter().filter(|other| neighbours.contains(&other.tile)) {
+            let there = lane_coordinates(&self.layout, self.extent, other.tile, other.x, other.y)
+                .expect("neighbouring tiles are on the flow cell");
+            if here.0.max(there.0) - here.0.min(there.0) <= tolerance
+                && here.1.max(there.1) - here.1.min(there.1) <= tolerance {
+                self.duplicates.push(CrossTileDuplicate { first: *other, second: read });
+            }
+        }
+        earlier.push(read);
+    }
+
+    /// The duplicates found so far, in the order their second reads were added
+    pub fn duplicates(&self) -> &[CrossTileDuplicate] {
+        &self.duplicates
+    }
+
+    /// The number of duplicates found so far on each tile, counted under the tile of the second
+    /// read
+    pub fn counts_by_tile(&self) -> HashMap<TileId, u64> {
+        let mut counts = HashMap::new();
+        for duplicate in &self.duplicates {
+            *counts.entry(duplicate.second.tile).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_cross_tile_duplicates() {
+        let layout = FlowCellLayout { lanes: 2, sides: 2, swaths: 2, cameras: 1, tiles_per_swath: 10 };
+        let extent = TileExtent { x_min: 1000, x_max: 10999, y_min: 1000, y_max: 10999 };
+        let mut detector = CrossTileDetector::new(layout, extent, 100);
+        let reads = [
+            // Along the swath, and across to the next swath
+            ("1:1101:5000:10990", "AAAA"), ("1:1102:5010:1005", "AAAA"),
+            ("1:1101:10995:3000", "CCCC"), ("1:1201:1002:3020", "CCCC"),
+            // Too far apart along the edge, on the far side of the tile, on another side or lane,
+            // not near an edge, or with another sequence
+            ("1:1101:5000:10990", "GGGG"), ("1:1102:5500:1005", "GGGG"), ("1:1101:5000:1005", "GGGG"),
+            ("1:2102:5000:1005", "AAAA"), ("2:1102:5000:1005", "AAAA"), ("1:1102:5000:5000", "AAAA"),
+            ("1:1102:5000:1005", "TTTT")
+        ];
+        for &(name, sequence) in &reads {
+            let seq_id = parse_sequence_identifier(&format!("@A00123:8:H3KJ7DSXX:{} 1:N:0:0", name)).unwrap();
+            detector.add(&seq_id, sequence.as_bytes());
+        }
+        let pairs: Vec<(u32, u32)> = detector.duplicates().iter()
+            .map(|duplicate| (duplicate.first.tile.tile_field(), duplicate.second.tile.tile_field()))
+            .collect();
+        assert_eq!(pairs, vec![(1101, 1102), (1101, 1201)]);
+        assert_eq!(detector.counts_by_tile().len(), 2);
+    }
+}
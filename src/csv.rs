@@ -0,0 +1,183 @@
+//! Writes parsed sequence identifiers as CSV, so they can be loaded straight into R or pandas.
+//!
+//! The columns are always written in the same order, which matches the order of the fields in
+//! `SequenceIdentifier`. Sample numbers and sequences share the `sample` column, and the camera
+//! is empty for tiles that don't have one. `OutputOptions` can rename the columns, or split the
+//! sample into a number and a sequence column.
+
+use std::io::{self, Write};
+use options::OutputOptions;
+use super::{Sample, SequenceIdentifier};
+
+/// The header row of the CSV output with the default output options
+pub const HEADER: &str = "sequencer_id,run_count,flow_cell_id,lane,side,swath,camera,tile,x,y,read,is_filtered,control_number,sample";
+
+/// Writes sequence identifiers as CSV rows, preceded by a header row
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::csv::CsvWriter;
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     let mut writer = CsvWriter::new(vec![]);
+///     writer.write(&seq_id).unwrap();
+///     let output = String::from_utf8(writer.into_inner()).unwrap();
+///     assert_eq!(output.lines().nth(1), Some("M03745,11,000000000-B54L5,1,2,1,,8,4127,8949,1,false,0,0"));
+/// }
+/// ```
+pub struct CsvWriter<W: Write> {
+    writer: W,
+    options: OutputOptions,
+    wrote_header: bool
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Creates a writer that uses the default output options. The header row is written along
+    /// with the first record.
+    pub fn new(writer: W) -> CsvWriter<W> {
+        CsvWriter::with_options(writer, OutputOptions::default())
+    }
+
+    /// Creates a writer that names and shapes columns according to the options
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::csv::CsvWriter;
+    /// use illumina_coordinates::options::{FieldNaming, OutputOptions};
+    ///
+    /// fn main() {
+    ///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+    ///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+    ///     let options = OutputOptions { naming: FieldNaming::CamelCase, split_sample: true };
+    ///     let mut writer = CsvWriter::with_options(vec![], options);
+    ///     writer.write(&seq_id).unwrap();
+    ///     let output = String::from_utf8(writer.into_inner()).unwrap();
+    ///     assert!(output.starts_with("sequencerId,runCount,flowCellId,"));
+    ///     assert!(output.lines().next().unwrap().ends_with(",sampleNumber,sampleSequence"));
+    ///     assert!(output.ends_with(",false,0,0,\n"));
+    /// }
+    /// ```
+    pub fn with_options(writer: W, options: OutputOptions) -> CsvWriter<W> {
+        CsvWriter { writer, options, wrote_header: false }
+    }
+
+    /// Writes the header row if it hasn't been written yet. This is only needed to produce a file
+    /// with no records.
+    pub fn write_header(&mut self) -> io::Result<()> {
+        if !self.wrote_header {
+            writeln!(self.writer, "{}", self.options.field_names().join(","))?;
+            self.wrote_header = true;
+        }
+        Ok(())
+    }
+
+    /// Writes a single sequence identifier as a row
+    pub fn write(&mut self, seq_id: &SequenceIdentifier) -> io::Result<()> {
+        self.write_header()?;
+        let sample = match seq_id.sample {
+            Sample::Number(n) if self.options.split_sample => format!("{},", n),
+            Sample::Sequence(ref sequence) if self.options.split_sample => format!(",{}", escape(sequence)),
+            Sample::Other(ref text) if self.options.split_sample => format!(",{}", escape(text)),
+            ref sample => escape(&sample.to_string())
+        };
+        writeln!(self.writer, "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                 escape(&seq_id.sequencer_id),
+                 seq_id.run_count,
+                 escape(&seq_id.flow_cell_id),
+                 seq_id.lane,
+                 seq_id.side,
+                 seq_id.swath,
+                 seq_id.camera.map(|camera| camera.to_string()).unwrap_or_default(),
+                 seq_id.tile,
+                 seq_id.x,
+                 seq_id.y,
+                 seq_id.read,
+                 seq_id.is_filtered,
+                 seq_id.control_number,
+                 sample)
+    }
+
+    /// Flushes the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Writes all of the sequence identifiers as CSV, including the header row
+pub fn write_csv<'a, W, I>(writer: W, seq_ids: I) -> io::Result<()>
+    where W: Write, I: IntoIterator<Item=&'a SequenceIdentifier> {
+    let mut writer = CsvWriter::new(writer);
+    writer.write_header()?;
+    for seq_id in seq_ids {
+        writer.write(seq_id)?;
+    }
+    writer.flush()
+}
+
+fn escape(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_write_csv() {
+        let seq_ids = vec![
+            parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap(),
+            parse_sequence_identifier("@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC").unwrap(),
+        ];
+        let mut output = vec![];
+        write_csv(&mut output, &seq_ids).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), format!("{}\n{}\n{}\n",
+            HEADER,
+            "M03745,11,000000000-B54L5,1,2,1,,8,4127,8949,1,false,0,0",
+            "NS500358,204,HTN5KAFXY,1,1,1,1,1,20886,1073,2,true,0,TAAGGC"));
+    }
+
+    #[test]
+    fn test_write_csv_empty() {
+        let mut output = vec![];
+        write_csv(&mut output, &[]).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), format!("{}\n", HEADER));
+    }
+
+    #[test]
+    fn test_split_sample() {
+        let options = OutputOptions { split_sample: true, ..OutputOptions::new() };
+        let mut writer = CsvWriter::with_options(vec![], options);
+        for line in &["@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:2", "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC"] {
+            writer.write(&parse_sequence_identifier(line).unwrap()).unwrap();
+        }
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], HEADER.replace(",sample", ",sample_number,sample_sequence"));
+        assert!(lines[1].ends_with(",false,0,2,"));
+        assert!(lines[2].ends_with(",true,0,,TAAGGC"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("M03745"), "M03745");
+        assert_eq!(escape("a,b"), "\"a,b\"");
+        assert_eq!(escape("a\"b"), "\"a\"\"b\"");
+    }
+}
@@ -0,0 +1,108 @@
+//! Builds a Polars `DataFrame` from parsed sequence identifiers, for exploratory analysis of
+//! coordinates without going through a file.
+//!
+//! Each field of `SequenceIdentifier` becomes a column of the same name, as in the Parquet and
+//! Arrow output: integer fields are unsigned integers of the same width, the camera is nullable,
+//! the filter flag is a boolean and the sample is a string.
+
+use polars::prelude::{Column, DataFrame};
+use super::SequenceIdentifier;
+
+/// Builds a data frame with one row per sequence identifier
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::dataframe::to_dataframe;
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_ids = vec![illumina_coordinates::parse_sequence_identifier(line).unwrap()];
+///     let frame = to_dataframe(&seq_ids);
+///     assert_eq!(frame.shape(), (1, 14));
+///     assert_eq!(frame.column("x").unwrap().u16().unwrap().get(0), Some(4127));
+/// }
+/// ```
+pub fn to_dataframe<'a, I: IntoIterator<Item=&'a SequenceIdentifier>>(seq_ids: I) -> DataFrame {
+    let mut sequencer_id = vec![];
+    let mut run_count = vec![];
+    let mut flow_cell_id = vec![];
+    let mut lane = vec![];
+    let mut side = vec![];
+    let mut swath = vec![];
+    let mut camera = vec![];
+    let mut tile = vec![];
+    let mut x = vec![];
+    let mut y = vec![];
+    let mut read = vec![];
+    let mut is_filtered = vec![];
+    let mut control_number = vec![];
+    let mut sample = vec![];
+    for seq_id in seq_ids {
+        sequencer_id.push(seq_id.sequencer_id.to_string());
+        run_count.push(seq_id.run_count);
+        flow_cell_id.push(seq_id.flow_cell_id.to_string());
+        lane.push(seq_id.lane);
+        side.push(seq_id.side);
+        swath.push(seq_id.swath);
+        camera.push(seq_id.camera);
+        tile.push(seq_id.tile);
+        x.push(seq_id.x);
+        y.push(seq_id.y);
+        read.push(seq_id.read);
+        is_filtered.push(seq_id.is_filtered);
+        control_number.push(seq_id.control_number);
+        sample.push(seq_id.sample.to_string());
+    }
+    let height = x.len();
+    let columns = vec![
+        Column::new("sequencer_id".into(), sequencer_id),
+        Column::new("run_count".into(), run_count),
+        Column::new("flow_cell_id".into(), flow_cell_id),
+        Column::new("lane".into(), lane),
+        Column::new("side".into(), side),
+        Column::new("swath".into(), swath),
+        Column::new("camera".into(), camera),
+        Column::new("tile".into(), tile),
+        Column::new("x".into(), x),
+        Column::new("y".into(), y),
+        Column::new("read".into(), read),
+        Column::new("is_filtered".into(), is_filtered),
+        Column::new("control_number".into(), control_number),
+        Column::new("sample".into(), sample)
+    ];
+    DataFrame::new(height, columns).expect("every column has a value for each identifier")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::DataType;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_to_dataframe() {
+        let seq_ids: Vec<SequenceIdentifier> = [
+            "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+            "@NS500358:204:HTN5KAFXY:4:21101:20886:1073 2:Y:18:TAAGGCGA+GCGATCTA"
+        ].iter().map(|line| parse_sequence_identifier(line).unwrap()).collect();
+        let frame = to_dataframe(&seq_ids);
+        assert_eq!(frame.shape(), (2, 14));
+        let names: Vec<&str> = frame.get_column_names().iter().map(|name| name.as_str()).collect();
+        assert_eq!(names, ["sequencer_id", "run_count", "flow_cell_id", "lane", "side", "swath", "camera", "tile", "x",
+                           "y", "read", "is_filtered", "control_number", "sample"]);
+        let types: Vec<DataType> = frame.dtypes();
+        assert_eq!(types, [DataType::String, DataType::UInt16, DataType::String, DataType::UInt8, DataType::UInt8,
+                           DataType::UInt8, DataType::UInt8, DataType::UInt8, DataType::UInt16, DataType::UInt16,
+                           DataType::UInt8, DataType::Boolean, DataType::UInt16, DataType::String]);
+        let camera = frame.column("camera").unwrap().u8().unwrap();
+        assert_eq!((camera.get(0), camera.get(1)), (None, Some(1)));
+        assert_eq!(frame.column("is_filtered").unwrap().bool().unwrap().get(1), Some(true));
+        assert_eq!(frame.column("control_number").unwrap().u16().unwrap().get(1), Some(18));
+        let sample = frame.column("sample").unwrap().str().unwrap();
+        assert_eq!((sample.get(0), sample.get(1)), (Some("0"), Some("TAAGGCGA+GCGATCTA")));
+        assert_eq!(to_dataframe(&[]).shape(), (0, 14));
+    }
+}
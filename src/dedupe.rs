@@ -0,0 +1,287 @@
+//! Removal of duplicate reads by their position on the flow cell.
+//!
+//! Reads at the same coordinates come from the same cluster, and reads a short distance apart on
+//! the same tile are usually optical duplicates: one cluster that was called twice, or that
+//! seeded a neighbour. Neither needs the reads to be aligned, so duplicates can be removed from
+//! FASTQ files directly. Checking that the reads also start with the same bases guards against
+//! discarding unrelated clusters that happen to be close together.
+//!
+//! `Deduplicator` works on a stream of reads and keeps the first of each group of duplicates.
+//! When the records are in memory, `select_representatives` can keep another member of each
+//! group instead, such as the one with the best quality scores, chosen by a `KeepPolicy`.
+
+use std::collections::{BTreeMap, HashMap};
+use fastq::Record;
+use super::{IlluminaError, SequenceIdentifier, TileId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Settings for finding duplicates
+pub struct DedupeOptions {
+    /// The largest difference in each of x and y between duplicates. With 0, only reads at
+    /// identical coordinates are duplicates.
+    pub distance: u16,
+    /// If set, duplicates must also share this many bases at the start of their sequences
+    pub prefix_length: Option<usize>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// The number of reads on a tile and how many of them were duplicates
+pub struct DuplicateCounts {
+    /// The number of reads
+    pub reads: u64,
+    /// The number of reads that duplicated an earlier read
+    pub duplicates: u64
+}
+
+impl DuplicateCounts {
+    /// The fraction of reads that were duplicates, or `None` if there were no reads
+    pub fn duplicate_rate(&self) -> Option<f64> {
+        if self.reads == 0 {
+            None
+        } else {
+            Some(self.duplicates as f64 / self.reads as f64)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A square of the tile that's `distance` wide, so that duplicates are always in the same or a
+/// neighbouring cell
+struct Cell {
+    tile: TileId,
+    prefix: Vec<u8>,
+    column: u16,
+    row: u16
+}
+
+/// Finds reads that duplicate an earlier read on the same tile. The first read of each group of
+/// duplicates is kept, and every read within `distance` of a kept read is a duplicate.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::dedupe::{DedupeOptions, Deduplicator};
+/// use illumina_coordinates::parse_sequence_identifier;
+///
+/// fn main() {
+///     let mut deduplicator = Deduplicator::new(DedupeOptions { distance: 100, prefix_length: None });
+///     let first = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+///     let second = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4180:8900 1:N:0:0").unwrap();
+///     assert!(!deduplicator.is_duplicate(&first, b"ACGT"));
+///     assert!(deduplicator.is_duplicate(&second, b"TTTT"));
+///     assert_eq!(deduplicator.total().duplicates, 1);
+/// }
+/// ```
+pub struct Deduplicator {
+    options: DedupeOptions,
+    // The coordinates of the first read of each group, and the number of the group
+    kept: HashMap<Cell, Vec<(u16, u16, usize)>>,
+    groups: usize,
+    tiles: BTreeMap<TileId, DuplicateCounts>
+}
+
+impl Deduplicator {
+    /// Creates a deduplicator that hasn't seen any reads
+    pub fn new(options: DedupeOptions) -> Deduplicator {
+        Deduplicator { options, kept: HashMap::new(), groups: 0, tiles: BTreeMap::new() }
+    }
+
+    /// Checks whether a read with the given sequence duplicates an earlier read, remembering it
+    /// if it doesn't
+    pub fn is_duplicate(&mut self, seq_id: &SequenceIdentifier, sequence: &[u8]) -> bool {
+        self.group(seq_id, sequence).1
+    }
+
+    /// Finds the group of duplicates a read belongs to, starting a new group if it doesn't
+    /// duplicate an earlier read. Groups are numbered in the order they're started. Returns the
+    /// group and whether the read is a duplicate.
+    fn group(&mut self, seq_id: &SequenceIdentifier, sequence: &[u8]) -> (usize, bool) {
+        let tile = seq_id.tile_id();
+        let prefix = match self.options.prefix_length {
+            Some(length) => sequence[..length.min(sequence.len())].to_vec(),
+            None => vec![]
+        };
+        let size = self.options.distance.max(1);
+        let (column, row) = (seq_id.x / size, seq_id.y / size);
+        let distance = self.options.distance;
+        let is_near = |&&(x, y, _): &&(u16, u16, usize)| {
+            x.max(seq_id.x) - x.min(seq_id.x) <= distance && y.max(seq_id.y) - y.min(seq_id.y) <= distance
+        };
+        let mut group = None;
+        'cells: for column in column.saturating_sub(1)..=column.saturating_add(1) {
+            for row in row.saturating_sub(1)..=row.saturating_add(1) {
+                let cell = Cell { tile, prefix: prefix.clone(), column, row };
+                if let Some(&(_, _, kept)) = self.kept.get(&cell).into_iter().flatten().find(&is_near) {
+                    group = Some(kept);
+                    break 'cells;
+                }
+            }
+        }
+        let counts = self.tiles.entry(tile).or_default();
+        counts.reads += 1;
+        match group {
+            Some(group) => {
+                counts.duplicates += 1;
+                (group, true)
+            },
+            None => {
+                let group = self.groups;
+                self.groups += 1;
+                self.kept.entry(Cell { tile, prefix, column, row }).or_default().push((seq_id.x, seq_id.y, group));
+                (group, false)
+            }
+        }
+    }
+
+    /// The counts for each tile that had at least one read
+    pub fn tiles(&self) -> &BTreeMap<TileId, DuplicateCounts> {
+        &self.tiles
+    }
+
+    /// The counts for all tiles
+    pub fn total(&self) -> DuplicateCounts {
+        self.tiles.values().fold(DuplicateCounts::default(), |total, counts| DuplicateCounts {
+            reads: total.reads + counts.reads,
+            duplicates: total.duplicates + counts.duplicates
+        })
+    }
+}
+
+/// Chooses which read of a group of duplicates is kept
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::dedupe::{select_representatives, DedupeOptions, KeepPolicy};
+/// use illumina_coordinates::fastq::Record;
+///
+/// /// Keeps the longest read
+/// struct Longest;
+///
+/// impl KeepPolicy for Longest {
+///     fn prefers(&self, candidate: &Record, kept: &Record) -> bool {
+///         candidate.sequence.len() > kept.sequence.len()
+///     }
+/// }
+///
+/// fn main() {
+///     let header = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0".to_string();
+///     let records = vec![
+///         Record { header: header.clone(), sequence: "AC".to_string(), quality: "FF".to_string() },
+///         Record { header, sequence: "ACGT".to_string(), quality: "FFFF".to_string() },
+///     ];
+///     let selection = select_representatives(&records, DedupeOptions::default(), &Longest).unwrap();
+///     assert_eq!((selection.kept, selection.discarded), (vec![1], vec![0]));
+/// }
+/// ```
+pub trait KeepPolicy {
+    /// Whether `candidate` should be kept in place of `kept`, the read of the group kept so far.
+    /// Reads are offered in the order they're given.
+    fn prefers(&self, candidate: &Record, kept: &Record) -> bool;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Keeps the first read of each group, like `Deduplicator`
+pub struct FirstSeen;
+
+impl KeepPolicy for FirstSeen {
+    fn prefers(&self, _: &Record, _: &Record) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Keeps the read of each group with the highest mean quality score, or the first of them if
+/// several have the highest
+pub struct HighestMeanQuality;
+
+impl KeepPolicy for HighestMeanQuality {
+    fn prefers(&self, candidate: &Record, kept: &Record) -> bool {
+        candidate.mean_quality() > kept.mean_quality()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// The reads kept and discarded by `select_representatives`, as indexes of the records in
+/// ascending order
+pub struct Selection {
+    /// One read from each group of duplicates, and every read that has no duplicates
+    pub kept: Vec<usize>,
+    /// The other reads
+    pub discarded: Vec<usize>
+}
+
+/// Finds the groups of duplicates among the records, in the same way as `Deduplicator`, and
+/// keeps the read from each that the policy picks
+pub fn select_representatives<P: KeepPolicy + ?Sized>(records: &[Record], options: DedupeOptions, policy: &P)
+        -> Result<Selection, IlluminaError> {
+    let mut deduplicator = Deduplicator::new(options);
+    let mut representatives: Vec<usize> = vec![];
+    for (index, record) in records.iter().enumerate() {
+        let (group, is_duplicate) = deduplicator.group(&record.sequence_identifier()?, record.sequence.as_bytes());
+        if !is_duplicate {
+            representatives.push(index);
+        } else if policy.prefers(record, &records[representatives[group]]) {
+            representatives[group] = index;
+        }
+    }
+    let mut is_kept = vec![false; records.len()];
+    for &index in &representatives {
+        is_kept[index] = true;
+    }
+    let (kept, discarded): (Vec<usize>, Vec<usize>) = (0..records.len()).partition(|&index| is_kept[index]);
+    Ok(Selection { kept, discarded })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    fn duplicates(options: DedupeOptions, reads: &[(&str, &str)]) -> Vec<bool> {
+        let mut deduplicator = Deduplicator::new(options);
+        reads.iter().map(|&(name, sequence)| {
+            let seq_id = parse_sequence_identifier(&format!("@A00123:8:H3KJ7DSXX:1:{} 1:N:0:0", name)).unwrap();
+            deduplicator.is_duplicate(&seq_id, sequence.as_bytes())
+        }).collect()
+    }
+
+    #[test]
+    fn test_identical_coordinates() {
+        let reads = [("1101:1000:1000", "ACGT"), ("1101:1000:1000", "TTTT"), ("1101:1000:1001", "ACGT"),
+                     ("1102:1000:1000", "ACGT")];
+        assert_eq!(duplicates(DedupeOptions::default(), &reads), vec![false, true, false, false]);
+    }
+
+    #[test]
+    fn test_nearby_coordinates() {
+        let options = DedupeOptions { distance: 100, prefix_length: Some(2) };
+        // Duplicates across cell boundaries, but not with another prefix or further away
+        let reads = [("1101:1099:1099", "ACGT"), ("1101:1150:1199", "ACTT"), ("1101:1150:1150", "TCGT"),
+                     ("1101:1200:1000", "ACGT"), ("1101:1000:1000", "A"), ("1101:1000:1000", "A")];
+        assert_eq!(duplicates(options, &reads), vec![false, true, false, false, false, true]);
+    }
+
+    #[test]
+    fn test_select_representatives() {
+        let record = |position: &str, quality: &str| Record {
+            header: format!("@A00123:8:H3KJ7DSXX:1:{} 1:N:0:0", position),
+            sequence: "ACGT".to_string(),
+            quality: quality.to_string()
+        };
+        let records = vec![record("1101:1000:1000", "####"), record("1101:1050:1000", "FFFF"), record("1101:5000:5000", "FF##"),
+                           record("1101:1000:1090", ":::F"), record("1101:5000:5010", "FFFF"), record("1101:1000:1000", "FFFF")];
+        let options = DedupeOptions { distance: 100, prefix_length: None };
+        let first = select_representatives(&records, options, &FirstSeen).unwrap();
+        assert_eq!(first, Selection { kept: vec![0, 2], discarded: vec![1, 3, 4, 5] });
+        let best = select_representatives(&records, options, &HighestMeanQuality).unwrap();
+        assert_eq!(best, Selection { kept: vec![1, 4], discarded: vec![0, 2, 3, 5] });
+
+        let mut unparseable = records.clone();
+        unparseable[3].header = "@read".to_string();
+        assert!(select_representatives(&unparseable, options, &FirstSeen).is_err());
+    }
+}
@@ -0,0 +1,533 @@
+//! A DEFLATE (RFC 1951) decoder and encoder and the CRC-32 checksum, which are all that's needed
+//! to read and write gzip and BGZF files without pulling in a compression library.
+
+use std::io::{self, BufRead};
+
+const MAX_BITS: usize = 15;
+const FAST_BITS: usize = 10;
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59,
+                                67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769,
+                                  1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10,
+                                  11, 11, 12, 12, 13, 13];
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// The largest distance a back-reference can reach
+pub(crate) const WINDOW_SIZE: usize = 32768;
+const HASH_BITS: usize = 15;
+const MAX_CHAIN: usize = 64;
+const NO_POSITION: usize = usize::MAX;
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+fn corrupt(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("corrupt deflate stream: {}", message))
+}
+
+/// Reads bits least-significant first, as DEFLATE packs them
+pub(crate) struct BitReader<R: BufRead> {
+    inner: R,
+    buffer: u64,
+    count: usize
+}
+
+impl<R: BufRead> BitReader<R> {
+    pub(crate) fn new(inner: R) -> BitReader<R> {
+        BitReader { inner, buffer: 0, count: 0 }
+    }
+
+    /// Loads whole bytes into the bit buffer until it holds at least 32 bits or the input ends
+    fn refill(&mut self) -> io::Result<()> {
+        while self.count < 32 {
+            let (byte, available) = {
+                let available = self.inner.fill_buf()?;
+                match available.first() {
+                    Some(&byte) => (byte, true),
+                    None => (0, false)
+                }
+            };
+            if !available {
+                break;
+            }
+            self.inner.consume(1);
+            self.buffer |= u64::from(byte) << self.count;
+            self.count += 8;
+        }
+        Ok(())
+    }
+
+    fn bits(&mut self, n: usize) -> io::Result<u32> {
+        if n == 0 {
+            return Ok(0);
+        }
+        if self.count < n {
+            self.refill()?;
+            if self.count < n {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "deflate stream ended early"));
+            }
+        }
+        let value = (self.buffer & ((1 << n) - 1)) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Discards bits up to the next byte boundary
+    fn align(&mut self) {
+        let extra = self.count % 8;
+        self.buffer >>= extra;
+        self.count -= extra;
+    }
+
+    /// Whether the input is exhausted once the stream is aligned to the next byte
+    pub(crate) fn at_end(&mut self) -> io::Result<bool> {
+        self.align();
+        if self.count > 0 {
+            return Ok(false);
+        }
+        Ok(self.inner.fill_buf()?.is_empty())
+    }
+
+    /// Reads a byte after the stream has been aligned, taking it from the bit buffer first
+    pub(crate) fn aligned_byte(&mut self) -> io::Result<u8> {
+        self.align();
+        Ok(self.bits(8)? as u8)
+    }
+}
+
+/// A canonical Huffman code, decoded with a lookup table for short codes and bit by bit for the
+/// rest
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+    fast: Vec<u16>
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Huffman> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err(corrupt("over-subscribed Huffman code"));
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        // Entries in the fast table hold the symbol in the low 9 bits and the code length above
+        let mut fast = vec![0u16; 1 << FAST_BITS];
+        let mut code: u32 = 0;
+        let mut index = 0;
+        for (length, &count) in counts.iter().enumerate().take(FAST_BITS + 1).skip(1) {
+            for _ in 0..count {
+                let reversed = reverse_bits(code, length);
+                let entry = symbols[index] | (length as u16) << 9;
+                let mut fill = reversed as usize;
+                while fill < fast.len() {
+                    fast[fill] = entry;
+                    fill += 1 << length;
+                }
+                code += 1;
+                index += 1;
+            }
+            code <<= 1;
+        }
+        Ok(Huffman { counts, symbols, fast })
+    }
+
+    fn decode<R: BufRead>(&self, reader: &mut BitReader<R>) -> io::Result<u16> {
+        if reader.count < FAST_BITS {
+            reader.refill()?;
+        }
+        if reader.count >= FAST_BITS {
+            let entry = self.fast[(reader.buffer & ((1 << FAST_BITS) - 1)) as usize];
+            if entry != 0 {
+                let length = (entry >> 9) as usize;
+                reader.buffer >>= length;
+                reader.count -= length;
+                return Ok(entry & 0x1ff);
+            }
+        }
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = i32::from(self.counts[length]);
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(corrupt("invalid Huffman code"))
+    }
+}
+
+fn reverse_bits(mut code: u32, length: usize) -> u32 {
+    let mut reversed = 0;
+    for _ in 0..length {
+        reversed = reversed << 1 | (code & 1);
+        code >>= 1;
+    }
+    reversed
+}
+
+fn fixed_codes() -> io::Result<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8
+        };
+    }
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5u8; 30])?))
+}
+
+fn dynamic_codes<R: BufRead>(reader: &mut BitReader<R>) -> io::Result<(Huffman, Huffman)> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(corrupt("too many codes"));
+    }
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut index = 0;
+    while index < lengths.len() {
+        let symbol = code_length_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => match index {
+                0 => return Err(corrupt("repeated code length with no previous length")),
+                _ => (lengths[index - 1], 3 + reader.bits(2)? as usize)
+            },
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize)
+        };
+        if index + repeat > lengths.len() {
+            return Err(corrupt("too many code lengths"));
+        }
+        for length in &mut lengths[index..index + repeat] {
+            *length = value;
+        }
+        index += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(corrupt("no end-of-block code"));
+    }
+    Ok((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?))
+}
+
+/// Decodes a single DEFLATE block, appending its output to `out`. Back-references may reach into
+/// data already in `out`, so callers that trim it must keep at least `WINDOW_SIZE` bytes. Returns
+/// whether this was the final block of the stream.
+pub(crate) fn inflate_block<R: BufRead>(reader: &mut BitReader<R>, out: &mut Vec<u8>) -> io::Result<bool> {
+    let is_final = reader.bits(1)? == 1;
+    let (literals, distances) = match reader.bits(2)? {
+        0 => {
+            let mut header = [0u8; 4];
+            for byte in &mut header {
+                *byte = reader.aligned_byte()?;
+            }
+            let length = u16::from(header[0]) | u16::from(header[1]) << 8;
+            let complement = u16::from(header[2]) | u16::from(header[3]) << 8;
+            if length != !complement {
+                return Err(corrupt("stored block length doesn't match its complement"));
+            }
+            for _ in 0..length {
+                out.push(reader.aligned_byte()?);
+            }
+            return Ok(is_final);
+        },
+        1 => fixed_codes()?,
+        2 => dynamic_codes(reader)?,
+        _ => return Err(corrupt("invalid block type"))
+    };
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        if symbol < 256 {
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(is_final);
+        }
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            return Err(corrupt("invalid length code"));
+        }
+        let length = LENGTH_BASE[symbol] as usize + reader.bits(LENGTH_EXTRA[symbol] as usize)? as usize;
+        let symbol = distances.decode(reader)? as usize;
+        if symbol >= DISTANCE_BASE.len() {
+            return Err(corrupt("invalid distance code"));
+        }
+        let distance = DISTANCE_BASE[symbol] as usize + reader.bits(DISTANCE_EXTRA[symbol] as usize)? as usize;
+        if distance > out.len() {
+            return Err(corrupt("distance reaches before start of output"));
+        }
+        let start = out.len() - distance;
+        for i in 0..length {
+            let byte = out[start + i];
+            out.push(byte);
+        }
+    }
+}
+
+/// Decodes a complete DEFLATE stream held in memory
+pub(crate) fn inflate(data: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+    let mut reader = BitReader::new(data);
+    while !inflate_block(&mut reader, out)? {}
+    Ok(())
+}
+
+/// Writes bits least-significant first
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    buffer: u64,
+    count: usize
+}
+
+impl<'a> BitWriter<'a> {
+    fn bits(&mut self, value: u32, n: usize) {
+        self.buffer |= u64::from(value) << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which DEFLATE packs most-significant bit first
+    fn code(&mut self, code: u32, length: usize) {
+        self.bits(reverse_bits(code, length), length);
+    }
+
+    fn literal(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xc0 + symbol - 280, 8)
+        }
+    }
+
+    fn back_reference(&mut self, length: usize, distance: usize) {
+        let index = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap_or(0);
+        self.literal(257 + index as u32);
+        self.bits((length - LENGTH_BASE[index] as usize) as u32, LENGTH_EXTRA[index] as usize);
+        let index = DISTANCE_BASE.iter().rposition(|&base| base as usize <= distance).unwrap_or(0);
+        self.code(index as u32, 5);
+        self.bits((distance - DISTANCE_BASE[index] as usize) as u32, DISTANCE_EXTRA[index] as usize);
+    }
+
+    fn finish(self) {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+    }
+}
+
+/// Finds previous occurrences of three-byte sequences, keeping one hash chain per position in the
+/// window
+struct Matcher {
+    head: Vec<usize>,
+    previous: Vec<usize>
+}
+
+impl Matcher {
+    fn new() -> Matcher {
+        Matcher { head: vec![NO_POSITION; 1 << HASH_BITS], previous: vec![NO_POSITION; WINDOW_SIZE] }
+    }
+
+    fn hash(data: &[u8], position: usize) -> usize {
+        let key = u32::from(data[position]) << 16 | u32::from(data[position + 1]) << 8 | u32::from(data[position + 2]);
+        (key.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, data: &[u8], position: usize) {
+        if position + MIN_MATCH <= data.len() {
+            let hash = Matcher::hash(data, position);
+            self.previous[position % WINDOW_SIZE] = self.head[hash];
+            self.head[hash] = position;
+        }
+    }
+
+    /// The length and distance of the longest earlier match for the data at `position`
+    fn longest_match(&self, data: &[u8], position: usize) -> (usize, usize) {
+        let limit = MAX_MATCH.min(data.len() - position);
+        if limit < MIN_MATCH {
+            return (0, 0);
+        }
+        let mut best = (0, 0);
+        let mut candidate = self.head[Matcher::hash(data, position)];
+        let mut chain = 0;
+        while candidate != NO_POSITION && candidate < position && position - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+            let length = data[candidate..candidate + limit].iter()
+                .zip(&data[position..position + limit])
+                .take_while(|&(a, b)| a == b)
+                .count();
+            if length > best.0 {
+                best = (length, position - candidate);
+                if length == limit {
+                    break;
+                }
+            }
+            let next = self.previous[candidate % WINDOW_SIZE];
+            if next >= candidate {
+                break;
+            }
+            candidate = next;
+            chain += 1;
+        }
+        best
+    }
+}
+
+/// Compresses data into a complete DEFLATE stream of a single block. Matches are found greedily
+/// and encoded with the fixed Huffman codes, which is simple and still does well on FASTQ data.
+pub(crate) fn deflate(data: &[u8], out: &mut Vec<u8>) {
+    let mut writer = BitWriter { out, buffer: 0, count: 0 };
+    // A final block that uses the fixed codes
+    writer.bits(0b011, 3);
+    let mut matcher = Matcher::new();
+    let mut position = 0;
+    while position < data.len() {
+        let (length, distance) = matcher.longest_match(data, position);
+        if length >= MIN_MATCH {
+            writer.back_reference(length, distance);
+            for offset in position..position + length {
+                matcher.insert(data, offset);
+            }
+            position += length;
+        } else {
+            writer.literal(u32::from(data[position]));
+            matcher.insert(data, position);
+            position += 1;
+        }
+    }
+    writer.literal(256);
+    writer.finish();
+}
+
+fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 == 1 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+    table
+}
+
+/// Computes a running CRC-32, as used by gzip
+pub(crate) struct Crc32 {
+    table: [u32; 256],
+    value: u32
+}
+
+impl Crc32 {
+    pub(crate) fn new() -> Crc32 {
+        Crc32 { table: crc_table(), value: 0xffff_ffff }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.value = self.table[((self.value ^ u32::from(byte)) & 0xff) as usize] ^ (self.value >> 8);
+        }
+    }
+
+    pub(crate) fn sum(&self) -> u32 {
+        !self.value
+    }
+}
+
+/// Computes the CRC-32 of a buffer
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.sum()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_inflate_stored() {
+        let data = [0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o'];
+        let mut out = vec![];
+        inflate(&data, &mut out).unwrap();
+        assert_eq!(out, b"hello");
+    }
+
+    #[test]
+    fn test_inflate_fixed() {
+        // zlib.compressobj(wbits=-15) output for b"hello hello hello hello"
+        let data = [0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x27, 0x01];
+        let mut out = vec![];
+        inflate(&data, &mut out).unwrap();
+        assert_eq!(out, b"hello hello hello hello");
+    }
+
+    #[test]
+    fn test_deflate() {
+        let mut empty = vec![];
+        deflate(b"", &mut empty);
+        assert_eq!(empty, vec![0x03, 0x00]);
+
+        let mut data = vec![];
+        for i in 0..500 {
+            data.extend(format!("@M03745:11:000000000-B54L5:1:2108:{}:{} 1:N:0:0\nACGTTGCA\n+\nFFFF#FFF\n", i * 7, i * 13).bytes());
+        }
+        data.extend((0..3000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8));
+        let mut compressed = vec![];
+        deflate(&data, &mut compressed);
+        assert!(compressed.len() < data.len() / 2);
+        let mut out = vec![];
+        inflate(&compressed, &mut out).unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn test_inflate_corrupt() {
+        let mut out = vec![];
+        assert!(inflate(&[0x07], &mut out).is_err());
+        assert!(inflate(&[0x01, 0x05, 0x00, 0x00, 0x00], &mut out).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! Demultiplexes reads from Undetermined Reads, routing each to the sample whose index sequence
+//! its barcode matches. This recovers reads that bcl2fastq or BCL Convert left undetermined
+//! because they were run with a stricter mismatch tolerance, or with a sample sheet that has
+//! since been corrected.
+//!
+//! Reads whose barcodes are equally close to the indexes of two or more samples can't be
+//! assigned safely. They're kept apart from the reads that match no sample, since many of them
+//! point to indexes that are too similar for the mismatch tolerance.
+
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map::Entry;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use barcode::{BarcodeCorrector, Correction};
+use fastq::FastqReader;
+use sample_sheet::SampleSheet;
+use split::{write_parts, write_reader_parts, UNASSIGNED};
+use super::{IlluminaError, SequenceIdentifier};
+
+/// The name of the part that holds reads whose barcodes are equally close to several samples
+pub const AMBIGUOUS: &str = "ambiguous";
+
+/// The expected barcodes for a lane and the names of the samples they belong to
+struct LaneSamples {
+    corrector: BarcodeCorrector,
+    names: HashMap<String, String>
+}
+
+impl LaneSamples {
+    fn new<'a, I: Iterator<Item=(String, &'a str)>>(barcodes: I, max_mismatches: usize) -> LaneSamples {
+        let mut names = HashMap::new();
+        for (barcode, name) in barcodes {
+            names.entry(barcode).or_insert_with(|| name.to_string());
+        }
+        let corrector = BarcodeCorrector::new(names.keys().cloned(), max_mismatches);
+        LaneSamples { corrector, names }
+    }
+}
+
+/// Assigns reads to samples by matching the barcode in their sequence identifier against the
+/// index sequences in a sample sheet. Dual-indexed barcodes are compared as `index+index2`, with
+/// mismatches counted across both indexes. If the sample sheet assigns samples to lanes, each
+/// read is only matched against the samples in its lane.
+///
+/// Only reads whose sample field is a sequence, as in Undetermined Reads, can be assigned.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::demux::Demultiplexer;
+/// use illumina_coordinates::sample_sheet::parse_sample_sheet;
+///
+/// fn main() {
+///     let sheet = parse_sample_sheet("[Data]\nSample_ID,Sample_Name,index\nS1,liver,TAAGGCGA\nS2,heart,CGTACTAG\n").unwrap();
+///     let demultiplexer = Demultiplexer::new(&sheet, 1);
+///     let line = "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 1:N:0:TAAGGCGT";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     assert_eq!(demultiplexer.assign(&seq_id), Some("liver"));
+/// }
+/// ```
+pub struct Demultiplexer {
+    lanes: HashMap<u8, LaneSamples>,
+    all_lanes: LaneSamples
+}
+
+impl Demultiplexer {
+    /// Creates a demultiplexer for the samples in a sample sheet, allowing up to `max_mismatches`
+    /// differences between a read's barcode and a sample's index
+    pub fn new(sheet: &SampleSheet, max_mismatches: usize) -> Demultiplexer {
+        let barcodes = |lane: Option<u8>| sheet.entries.iter()
+            .filter(move |entry| entry.lane.is_none() || entry.lane == lane)
+            .filter_map(|entry| entry.barcode().map(|barcode| (barcode, entry.name())));
+        let mut lanes = HashMap::new();
+        for lane in sheet.entries.iter().filter_map(|entry| entry.lane) {
+            lanes.entry(lane).or_insert_with(|| LaneSamples::new(barcodes(Some(lane)), max_mismatches));
+        }
+        Demultiplexer { lanes, all_lanes: LaneSamples::new(barcodes(None), max_mismatches) }
+    }
+
+    /// The name of the sample the read belongs to, if its barcode can be assigned to one
+    pub fn assign(&self, seq_id: &SequenceIdentifier) -> Option<&str> {
+        match self.classify(seq_id) {
+            Assignment::Sample(name) => Some(name),
+            Assignment::Ambiguous(_) | Assignment::Unassigned => None
+        }
+    }
+
+    /// Assigns the read like `assign`, telling apart reads that match several samples equally
+    /// well from reads that match none
+    pub fn classify(&self, seq_id: &SequenceIdentifier) -> Assignment<'_> {
+        let samples = self.lanes.get(&seq_id.lane).unwrap_or(&self.all_lanes);
+        let name = |barcode: &str| samples.names[barcode].as_str();
+        match samples.corrector.correct_sample(&seq_id.sample) {
+            Correction::Exact(barcode) | Correction::Corrected { barcode, .. } => Assignment::Sample(name(barcode)),
+            Correction::Ambiguous(barcodes) => Assignment::Ambiguous(barcodes.into_iter().map(name).collect()),
+            Correction::Unmatched => Assignment::Unassigned
+        }
+    }
+
+    /// Checks that every sample is written to a part of its own. Sample names that only differ in
+    /// the characters `file_name_safe` replaces, or that are the names of the reserved parts,
+    /// would mix different samples' reads in one file.
+    fn check_parts(&self) -> Result<(), IlluminaError> {
+        let mut samples: HashMap<String, &str> = HashMap::new();
+        for lane_samples in self.lanes.values().chain(Some(&self.all_lanes)) {
+            for name in lane_samples.names.values() {
+                let part = file_name_safe(name);
+                if part == AMBIGUOUS || part == UNASSIGNED {
+                    return Err(IlluminaError::FormatError(
+                        format!("sample {} would be written to the reserved {} file", name, part)));
+                }
+                match samples.entry(part) {
+                    Entry::Occupied(entry) => if *entry.get() != name.as_str() {
+                        return Err(IlluminaError::FormatError(
+                            format!("samples {} and {} would both be written to the {} file", entry.get(), name, entry.key())));
+                    },
+                    Entry::Vacant(entry) => {
+                        entry.insert(name);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The part a read is written to by `demultiplex`
+    fn part(&self, seq_id: &SequenceIdentifier) -> String {
+        match self.classify(seq_id) {
+            Assignment::Sample(name) => file_name_safe(name),
+            Assignment::Ambiguous(_) => AMBIGUOUS.to_string(),
+            Assignment::Unassigned => UNASSIGNED.to_string()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The sample a read was assigned to
+pub enum Assignment<'a> {
+    /// The read belongs to the named sample
+    Sample(&'a str),
+    /// The read's barcode is equally close to the indexes of these samples
+    Ambiguous(Vec<&'a str>),
+    /// The read's barcode matches no sample, or the read has a sample number
+    Unassigned
+}
+
+/// Replaces characters that could be a problem in a file name
+fn file_name_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect()
+}
+
+/// Writes each record of a FASTQ file to a file for the sample it's assigned to, to the
+/// `ambiguous` file if it matches several samples equally well, or to the `unassigned` file. Output files are named after the input with the sample name inserted before
+/// the extension, e.g. `Undetermined_S0_L001_R1_001_liver.fastq.gz`, and are compressed if the input
+/// is. Returns the path of each file written along with the number of records in it.
+///
+/// Returns an error before writing anything if two samples would share a file, because their
+/// names only differ in characters that aren't safe in file names, such as `a/b` and `a_b`, or
+/// because a sample is named `ambiguous` or `unassigned`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::demux::{demultiplex, Demultiplexer};
+/// use illumina_coordinates::sample_sheet::SampleSheet;
+///
+/// fn main() {
+///     let sheet = SampleSheet::from_path("SampleSheet.csv").unwrap();
+///     let demultiplexer = Demultiplexer::new(&sheet, 1);
+///     let counts = demultiplex("Undetermined_S0_L001_R1_001.fastq.gz", "recovered", &demultiplexer).unwrap();
+///     for (path, records) in &counts {
+///         println!("{}: {}", path.display(), records);
+///     }
+/// }
+/// ```
+pub fn demultiplex<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output_dir: Q, demultiplexer: &Demultiplexer)
+    -> Result<BTreeMap<PathBuf, u64>, IlluminaError> {
+    demultiplexer.check_parts()?;
+    write_parts(input, output_dir, |seq_id| demultiplexer.part(seq_id))
+}
+
+/// Assigns FASTQ records from a reader, such as standard input, to samples like `demultiplex`.
+/// Output files are named after `name` as if it were the input's path, and are compressed if
+/// `compressed` is set. Samples that would share a file are an error, as in `demultiplex`.
+pub fn demultiplex_reader<R: BufRead, P: AsRef<Path>, Q: AsRef<Path>>(reader: R, compressed: bool, name: P,
+                                                                      output_dir: Q, demultiplexer: &Demultiplexer)
+    -> Result<BTreeMap<PathBuf, u64>, IlluminaError> {
+    demultiplexer.check_parts()?;
+    write_reader_parts(FastqReader::new(reader), compressed, name, output_dir, |seq_id| demultiplexer.part(seq_id))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use parse_sequence_identifier;
+    use sample_sheet::parse_sample_sheet;
+    use split::part_path;
+
+    const SHEET: &str = "[Data]
+Lane,Sample_ID,Sample_Name,index,index2
+1,S1,liver,TAAGGCGA,GCGTAAGA
+2,S2,heart,TAAGGCGA,GCGTAAGA
+2,S3,lung/left,CGTACTAG,GCGTAAGA
+";
+
+    fn assign(demultiplexer: &Demultiplexer, line: &str) -> Option<String> {
+        demultiplexer.assign(&parse_sequence_identifier(line).unwrap()).map(|name| name.to_string())
+    }
+
+    #[test]
+    fn test_assign() {
+        let sheet = parse_sample_sheet(SHEET).unwrap();
+        let demultiplexer = Demultiplexer::new(&sheet, 1);
+        assert_eq!(assign(&demultiplexer, "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:TAAGGCGA+GCGTAAGA"), Some("liver".to_string()));
+        assert_eq!(assign(&demultiplexer, "@M03745:11:000000000-B54L5:2:2108:4127:8949 1:N:0:TAAGGCGA+GCGTAAGT"), Some("heart".to_string()));
+        assert_eq!(assign(&demultiplexer, "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:CGTACTAG+GCGTAAGA"), None);
+        assert_eq!(assign(&demultiplexer, "@M03745:11:000000000-B54L5:2:2108:4127:8949 1:N:0:TAAGGCGA+GCGTAATT"), None);
+        assert_eq!(assign(&demultiplexer, "@M03745:11:000000000-B54L5:2:2108:4127:8949 1:N:0:2"), None);
+    }
+
+    #[test]
+    fn test_classify() {
+        let sheet = parse_sample_sheet("[Data]\nSample_ID,Sample_Name,index\nS1,liver,TAAGGCGA\nS2,heart,TAAGGCTT\n").unwrap();
+        let demultiplexer = Demultiplexer::new(&sheet, 1);
+        let read = |barcode: &str| {
+            parse_sequence_identifier(&format!("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:{}", barcode)).unwrap()
+        };
+        let classify = |barcode: &str| demultiplexer.classify(&read(barcode));
+        assert_eq!(classify("TAAGGCTC"), Assignment::Sample("heart"));
+        let mut names = match classify("TAAGGCGT") {
+            Assignment::Ambiguous(names) => names,
+            other => panic!("{:?}", other)
+        };
+        names.sort();
+        assert_eq!(names, vec!["heart", "liver"]);
+        assert_eq!(demultiplexer.part(&read("TAAGGCGT")), AMBIGUOUS);
+        assert_eq!(demultiplexer.part(&read("TAAGGCGG")), "liver");
+        assert_eq!(classify("GGGGGGGG"), Assignment::Unassigned);
+        assert_eq!(classify("1"), Assignment::Unassigned);
+    }
+
+    #[test]
+    fn test_demultiplex() {
+        let dir = env::temp_dir().join(format!("illumina_coordinates_demux_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("Undetermined.fastq");
+        fs::write(&input, "@M03745:11:000000000-B54L5:2:2108:4127:8949 1:N:0:CGTACTAG+GCGTAAGA\nACGT\n+\nFFFF\n\
+                           @M03745:11:000000000-B54L5:2:2108:4128:8949 1:N:0:GGGGGGGG+GCGTAAGA\nACGT\n+\nFFFF\n").unwrap();
+        let sheet = parse_sample_sheet(SHEET).unwrap();
+        let counts = demultiplex(&input, &dir, &Demultiplexer::new(&sheet, 1)).unwrap();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&part_path(&input, &dir, "lung_left")], 1);
+        assert_eq!(counts[&part_path(&input, &dir, UNASSIGNED)], 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_part_collisions() {
+        let demultiplexer = |names: (&str, &str)| {
+            let sheet = format!("[Data]\nSample_ID,Sample_Name,index\nS1,{},TAAGGCGA\nS2,{},CGTACTAG\n", names.0, names.1);
+            Demultiplexer::new(&parse_sample_sheet(&sheet).unwrap(), 1)
+        };
+        let reads = "@M03745:11:000000000-B54L5:2:2108:4127:8949 1:N:0:CGTACTAG\nACGT\n+\nFFFF\n";
+        let dir = env::temp_dir().join(format!("illumina_coordinates_demux_collisions_{}", std::process::id()));
+        for &names in &[("a/b", "a_b"), ("liver", "unassigned"), ("ambiguous", "liver"), ("lung left", "lung/left")] {
+            assert!(demultiplexer(names).check_parts().is_err(), "{:?}", names);
+            let result = demultiplex_reader(reads.as_bytes(), false, "Undetermined.fastq", &dir, &demultiplexer(names));
+            assert!(result.is_err(), "{:?}", names);
+        }
+        assert!(!dir.exists());
+        assert!(demultiplexer(("a/b", "a-b")).check_parts().is_ok());
+        // A sample in several lanes is one sample, written to one file
+        assert!(Demultiplexer::new(&parse_sample_sheet(SHEET.replace("heart", "liver").as_str()).unwrap(), 1).check_parts().is_ok());
+    }
+}
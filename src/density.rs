@@ -0,0 +1,246 @@
+//! Histograms of where clusters are within a tile.
+//!
+//! A histogram divides a tile into a grid of equally sized bins and counts the clusters in each.
+//! Clusters are spread fairly evenly over a healthy tile, so bins with unusually few or many
+//! clusters, or an unusual share of bad reads, point to a problem with the flow cell.
+
+#[cfg(feature = "ndarray")]
+use ndarray::{Array2, Array3};
+use geometry::TileExtent;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Counts of clusters in a grid of bins covering a tile. Coordinates outside the tile extent are
+/// counted in the nearest bin.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::density::DensityHistogram;
+/// use illumina_coordinates::geometry;
+///
+/// fn main() {
+///     let mut histogram = DensityHistogram::new(geometry::MISEQ, 10, 10);
+///     histogram.add(4127, 8949);
+///     histogram.add(4128, 8950);
+///     assert_eq!(histogram.bin_of(4127, 8949), (1, 2));
+///     assert_eq!(histogram.count(1, 2), 2);
+///     assert_eq!(histogram.total(), 2);
+/// }
+/// ```
+pub struct DensityHistogram {
+    extent: TileExtent,
+    columns: usize,
+    rows: usize,
+    counts: Vec<u32>
+}
+
+impl DensityHistogram {
+    /// Creates an empty histogram that divides the tile into `columns` bins along x and `rows`
+    /// bins along y. Both must be at least 1.
+    pub fn new(extent: TileExtent, columns: usize, rows: usize) -> DensityHistogram {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+        DensityHistogram { extent, columns, rows, counts: vec![0; columns * rows] }
+    }
+
+    /// The tile extent the bins cover
+    pub fn extent(&self) -> TileExtent {
+        self.extent
+    }
+
+    /// The number of bins along x
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// The number of bins along y
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The column and row of the bin that the coordinates fall in
+    pub fn bin_of(&self, x: u16, y: u16) -> (usize, usize) {
+        (bin(x, self.extent.x_min, self.extent.x_max, self.columns),
+         bin(y, self.extent.y_min, self.extent.y_max, self.rows))
+    }
+
+    /// The range of coordinates covered by a bin, as `(x_min, x_max, y_min, y_max)`
+    pub fn bin_bounds(&self, column: usize, row: usize) -> (u16, u16, u16, u16) {
+        let (x_min, x_max) = bounds(column, self.extent.x_min, self.extent.x_max, self.columns);
+        let (y_min, y_max) = bounds(row, self.extent.y_min, self.extent.y_max, self.rows);
+        (x_min, x_max, y_min, y_max)
+    }
+
+    /// Counts a cluster
+    pub fn add(&mut self, x: u16, y: u16) {
+        let (column, row) = self.bin_of(x, y);
+        self.counts[row * self.columns + column] += 1;
+    }
+
+    /// The number of clusters counted in a bin
+    pub fn count(&self, column: usize, row: usize) -> u32 {
+        self.counts[row * self.columns + column]
+    }
+
+    /// The number of clusters counted in all bins
+    pub fn total(&self) -> u64 {
+        self.counts.iter().map(|&count| u64::from(count)).sum()
+    }
+
+    /// The counts of all bins in row-major order, so the count of a bin is at
+    /// `row * columns + column`. With the shape `(rows, columns)` this can be handed to array
+    /// crates without copying bin by bin.
+    pub fn counts(&self) -> &[u32] {
+        &self.counts
+    }
+
+    /// The counts of all bins as an array indexed by `[row, column]`. Needs the `ndarray`
+    /// feature.
+    #[cfg(feature = "ndarray")]
+    pub fn to_array(&self) -> Array2<u32> {
+        Array2::from_shape_vec((self.rows, self.columns), self.counts.clone())
+            .expect("a histogram has a count for every bin")
+    }
+}
+
+/// Stacks histograms with the same number of bins into one row-major buffer, with the shape
+/// `(histograms, rows, columns)`. Returns `None` if there are no histograms or their grids
+/// differ.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::density::{self, DensityHistogram};
+/// use illumina_coordinates::geometry;
+///
+/// fn main() {
+///     let mut first = DensityHistogram::new(geometry::MISEQ, 3, 2);
+///     first.add(0, 0);
+///     let second = DensityHistogram::new(geometry::MISEQ, 3, 2);
+///     let (counts, shape) = density::stack(&[first, second]).unwrap();
+///     assert_eq!(shape, (2, 2, 3));
+///     assert_eq!(counts, vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+/// }
+/// ```
+pub fn stack<'a, I>(histograms: I) -> Option<(Vec<u32>, (usize, usize, usize))>
+    where I: IntoIterator<Item=&'a DensityHistogram> {
+    let mut histograms = histograms.into_iter();
+    let first = histograms.next()?;
+    let mut counts = first.counts.clone();
+    let mut count = 1;
+    for histogram in histograms {
+        if (histogram.columns, histogram.rows) != (first.columns, first.rows) {
+            return None;
+        }
+        counts.extend_from_slice(&histogram.counts);
+        count += 1;
+    }
+    Some((counts, (count, first.rows, first.columns)))
+}
+
+/// Stacks histograms with the same number of bins into an array indexed by
+/// `[histogram, row, column]`, like `stack`. Needs the `ndarray` feature.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::density::{self, DensityHistogram};
+/// use illumina_coordinates::geometry;
+///
+/// fn main() {
+///     let mut first = DensityHistogram::new(geometry::MISEQ, 3, 2);
+///     first.add(0, 0);
+///     let second = DensityHistogram::new(geometry::MISEQ, 3, 2);
+///     let counts = density::stack_array(&[first, second]).unwrap();
+///     assert_eq!(counts.shape(), &[2, 2, 3]);
+///     assert_eq!((counts[[0, 0, 0]], counts[[1, 0, 0]]), (1, 0));
+/// }
+/// ```
+#[cfg(feature = "ndarray")]
+pub fn stack_array<'a, I>(histograms: I) -> Option<Array3<u32>>
+    where I: IntoIterator<Item=&'a DensityHistogram> {
+    let (counts, shape) = stack(histograms)?;
+    Some(Array3::from_shape_vec(shape, counts).expect("stacked histograms have a count for every bin"))
+}
+
+fn bin(value: u16, min: u16, max: u16, bins: usize) -> usize {
+    let value = value.max(min).min(max);
+    let width = usize::from(max - min) + 1;
+    usize::from(value - min) * bins / width
+}
+
+fn bounds(index: usize, min: u16, max: u16, bins: usize) -> (u16, u16) {
+    let width = usize::from(max - min) + 1;
+    // The first coordinate in the bin is the smallest whose bin isn't before it
+    let start = |index: usize| match index {
+        0 => usize::from(min),
+        _ => usize::from(min) + (index * width - 1) / bins + 1
+    };
+    (start(index) as u16, (start(index + 1) - 1) as u16)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geometry;
+
+    #[test]
+    fn test_bins() {
+        let extent = TileExtent { x_min: 0, x_max: 9, y_min: 10, y_max: 12 };
+        let mut histogram = DensityHistogram::new(extent, 3, 2);
+        for x in 0..10 {
+            histogram.add(x, 11);
+        }
+        assert_eq!((0..3).map(|column| histogram.count(column, 0)).collect::<Vec<_>>(), vec![4, 3, 3]);
+        assert_eq!(histogram.bin_bounds(0, 0), (0, 3, 10, 11));
+        assert_eq!(histogram.bin_bounds(2, 1), (7, 9, 12, 12));
+        for column in 0..3 {
+            let (x_min, x_max, _, _) = histogram.bin_bounds(column, 0);
+            assert!((x_min..=x_max).all(|x| histogram.bin_of(x, 10).0 == column));
+        }
+        histogram.add(60000, 0);
+        assert_eq!(histogram.count(2, 0), 4);
+        assert_eq!(histogram.total(), 11);
+    }
+
+    #[test]
+    fn test_full_range() {
+        let extent = TileExtent { x_min: 0, x_max: 65535, y_min: 0, y_max: 65535 };
+        let histogram = DensityHistogram::new(extent, 7, 1);
+        assert_eq!(histogram.bin_of(65535, 65535), (6, 0));
+        assert_eq!(histogram.bin_bounds(6, 0).1, 65535);
+        assert_eq!(DensityHistogram::new(geometry::HISEQ, 0, 0).columns(), 1);
+    }
+
+    #[test]
+    fn test_counts_and_stack() {
+        let extent = TileExtent { x_min: 0, x_max: 9, y_min: 0, y_max: 9 };
+        let mut histogram = DensityHistogram::new(extent, 2, 3);
+        histogram.add(9, 4);
+        assert_eq!(histogram.counts(), &[0, 0, 0, 1, 0, 0][..]);
+        let (counts, shape) = stack(vec![&histogram, &histogram]).unwrap();
+        assert_eq!((counts.len(), shape), (12, (2, 3, 2)));
+        assert_eq!(counts[9], 1);
+        assert!(stack(&[histogram, DensityHistogram::new(extent, 3, 2)]).is_none());
+        assert!(stack(&[]).is_none());
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_arrays() {
+        let extent = TileExtent { x_min: 0, x_max: 9, y_min: 0, y_max: 9 };
+        let mut histogram = DensityHistogram::new(extent, 2, 3);
+        histogram.add(9, 4);
+        let counts = histogram.to_array();
+        assert_eq!(counts.shape(), &[3, 2]);
+        assert_eq!((counts[[1, 1]], counts.sum()), (1, 1));
+        let counts = stack_array(vec![&DensityHistogram::new(extent, 2, 3), &histogram]).unwrap();
+        assert_eq!(counts.shape(), &[2, 3, 2]);
+        assert_eq!((counts[[0, 1, 1]], counts[[1, 1, 1]]), (0, 1));
+        assert!(stack_array(&[histogram, DensityHistogram::new(extent, 3, 2)]).is_none());
+    }
+}
@@ -0,0 +1,269 @@
+//! Estimates the number of distinct cluster positions and barcodes in a stream of reads.
+//!
+//! Counting distinct values exactly means remembering every one of them. HyperLogLog instead
+//! keeps a few kilobytes of registers and estimates the count to within a couple of percent.
+//! Comparing the distinct positions with the number of reads gives the rate of exact duplicates,
+//! and the number of distinct barcodes in Undetermined Reads shows whether unassigned reads come
+//! from a few unlisted samples or from noise, all in a single pass.
+
+use position::position_hash;
+use subsample::hash;
+use super::{IlluminaError, SequenceIdentifier};
+
+// The smallest and largest numbers of bits used to pick a register
+const MIN_PRECISION: u8 = 4;
+const MAX_PRECISION: u8 = 18;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A HyperLogLog sketch of a set of hashed values, as described by Flajolet et al. with the
+/// small-range correction of Heule et al.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::distinct::HyperLogLog;
+/// use std::collections::hash_map::DefaultHasher;
+/// use std::hash::{Hash, Hasher};
+///
+/// fn main() {
+///     let mut sketch = HyperLogLog::new(12);
+///     for value in 0..10_000u64 {
+///         let mut hasher = DefaultHasher::new();
+///         value.hash(&mut hasher);
+///         sketch.add_hash(hasher.finish());
+///     }
+///     assert!((sketch.estimate() - 10_000.0).abs() < 10_000.0 * 3.0 * sketch.relative_error());
+/// }
+/// ```
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>
+}
+
+impl HyperLogLog {
+    /// Creates a sketch with `2^precision` registers, one byte each. Precisions are clamped to
+    /// between 4 and 18.
+    pub fn new(precision: u8) -> HyperLogLog {
+        let precision = if precision > MAX_PRECISION { MAX_PRECISION } else { precision.max(MIN_PRECISION) };
+        HyperLogLog { precision, registers: vec![0; 1 << precision] }
+    }
+
+    /// The number of bits used to pick a register
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// Adds a value by its hash, which must be spread evenly over all 64 bits
+    pub fn add_hash(&mut self, hash: u64) {
+        let index = (hash >> (64 - self.precision)) as usize;
+        // The set bit stops the count of leading zeros at the end of the remaining bits
+        let rest = hash << self.precision | 1 << (self.precision - 1);
+        let rank = rest.leading_zeros() as u8 + 1;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    /// The estimated number of distinct values added
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m)
+        };
+        let sum: f64 = self.registers.iter().map(|&rank| 1.0 / (1u64 << rank) as f64).sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate while many registers are empty
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        }
+    }
+
+    /// The standard error of estimates, relative to the true count
+    pub fn relative_error(&self) -> f64 {
+        1.04 / (self.registers.len() as f64).sqrt()
+    }
+
+    /// Adds the values of another sketch, which must have the same precision
+    pub fn merge(&mut self, other: &HyperLogLog) -> Result<(), IlluminaError> {
+        if other.precision != self.precision {
+            return Err(IlluminaError::FormatError(
+                format!("can't merge sketches of precision {} and {}", self.precision, other.precision)));
+        }
+        for (rank, &other_rank) in self.registers.iter_mut().zip(&other.registers) {
+            *rank = (*rank).max(other_rank);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Estimates the distinct positions and barcodes of the reads in a run. Positions are hashed by
+/// `position::encode_position`, which leaves out the flow cell, so reads should come from one
+/// run.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::distinct::DistinctEstimator;
+/// use illumina_coordinates::parse_sequence_identifier;
+///
+/// fn main() {
+///     let mut estimator = DistinctEstimator::new(14);
+///     for line in &["@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:TAAGGCGA",
+///                   "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:TAAGGCGA",
+///                   "@M03745:11:000000000-B54L5:1:2108:4128:8950 1:N:0:GGGGGGGG",
+///                   "@M03745:11:000000000-B54L5:1:2108:4129:8951 1:N:0:1"] {
+///         estimator.add(&parse_sequence_identifier(line).unwrap());
+///     }
+///     assert_eq!(estimator.distinct_positions().round(), 3.0);
+///     assert_eq!(estimator.distinct_barcodes().round(), 2.0);
+///     assert_eq!(estimator.duplicate_rate().map(|rate| (rate * 100.0).round()), Some(25.0));
+/// }
+/// ```
+pub struct DistinctEstimator {
+    reads: u64,
+    barcode_reads: u64,
+    positions: HyperLogLog,
+    barcodes: HyperLogLog
+}
+
+impl DistinctEstimator {
+    /// Creates an estimator whose sketches have the given precision
+    pub fn new(precision: u8) -> DistinctEstimator {
+        DistinctEstimator {
+            reads: 0,
+            barcode_reads: 0,
+            positions: HyperLogLog::new(precision),
+            barcodes: HyperLogLog::new(precision)
+        }
+    }
+
+    /// Counts a read. Only reads whose sample is an index sequence count towards the barcodes.
+    pub fn add(&mut self, seq_id: &SequenceIdentifier) {
+        self.reads += 1;
+        self.positions.add_hash(position_hash(seq_id));
+        if let Some(barcode) = seq_id.sample.as_sequence() {
+            self.barcode_reads += 1;
+            self.barcodes.add_hash(hash(0, barcode.as_bytes()));
+        }
+    }
+
+    /// The number of reads counted
+    pub fn reads(&self) -> u64 {
+        self.reads
+    }
+
+    /// The number of reads whose sample was an index sequence
+    pub fn barcode_reads(&self) -> u64 {
+        self.barcode_reads
+    }
+
+    /// The estimated number of distinct positions
+    pub fn distinct_positions(&self) -> f64 {
+        self.positions.estimate()
+    }
+
+    /// The estimated number of distinct index sequences
+    pub fn distinct_barcodes(&self) -> f64 {
+        self.barcodes.estimate()
+    }
+
+    /// The estimated fraction of reads at a position that an earlier read was at, or `None` if
+    /// there were no reads. Reads 1 and 2 of a cluster share a position, so reads should come
+    /// from one read of the run.
+    pub fn duplicate_rate(&self) -> Option<f64> {
+        if self.reads == 0 {
+            None
+        } else {
+            Some((1.0 - self.distinct_positions() / self.reads as f64).max(0.0))
+        }
+    }
+
+    /// The sketch of the positions
+    pub fn positions(&self) -> &HyperLogLog {
+        &self.positions
+    }
+
+    /// The sketch of the barcodes
+    pub fn barcodes(&self) -> &HyperLogLog {
+        &self.barcodes
+    }
+
+    /// Adds the reads counted by another estimator, such as one for another file of the run. The
+    /// estimators must have the same precision.
+    pub fn merge(&mut self, other: &DistinctEstimator) -> Result<(), IlluminaError> {
+        self.positions.merge(&other.positions)?;
+        self.barcodes.merge(&other.barcodes)?;
+        self.reads += other.reads;
+        self.barcode_reads += other.barcode_reads;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+    use subsample::mix;
+
+    #[test]
+    fn test_estimate() {
+        assert_eq!(HyperLogLog::new(10).estimate(), 0.0);
+        assert_eq!(HyperLogLog::new(0).precision(), 4);
+        for &count in &[10u64, 1000, 100_000, 1_000_000] {
+            let mut sketch = HyperLogLog::new(14);
+            for value in 0..count {
+                sketch.add_hash(mix(value));
+                sketch.add_hash(mix(value));
+            }
+            let error = (sketch.estimate() - count as f64).abs() / count as f64;
+            assert!(error < 3.0 * sketch.relative_error(), "{} {}", count, error);
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let (mut first, mut second) = (HyperLogLog::new(12), HyperLogLog::new(12));
+        for value in 0..20_000u64 {
+            if value < 15_000 {
+                first.add_hash(mix(value));
+            }
+            if value >= 5000 {
+                second.add_hash(mix(value));
+            }
+        }
+        first.merge(&second).unwrap();
+        assert!((first.estimate() - 20_000.0).abs() < 20_000.0 * 3.0 * first.relative_error());
+        assert!(first.merge(&HyperLogLog::new(10)).is_err());
+    }
+
+    #[test]
+    fn test_distinct_estimator() {
+        let mut estimator = DistinctEstimator::new(12);
+        assert_eq!(estimator.duplicate_rate(), None);
+        let seq_id = parse_sequence_identifier("@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:TAAGGCGA+CTCTCTAT").unwrap();
+        for x in 0..2000 {
+            let mut read = seq_id.clone();
+            read.x = x % 1000;
+            estimator.add(&read);
+        }
+        assert_eq!(estimator.reads(), 2000);
+        assert_eq!(estimator.barcode_reads(), 2000);
+        assert_eq!(estimator.distinct_barcodes().round(), 1.0);
+        let rate = estimator.duplicate_rate().unwrap();
+        assert!((rate - 0.5).abs() < 0.05, "{}", rate);
+        let other = estimator.clone();
+        estimator.merge(&other).unwrap();
+        assert_eq!(estimator.reads(), 4000);
+        assert!(estimator.merge(&DistinctEstimator::new(10)).is_err());
+    }
+}
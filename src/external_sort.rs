@@ -0,0 +1,291 @@
+//! Sorts the reads of a run by position using a bounded amount of memory.
+//!
+//! Finding optical duplicates in one pass needs the reads of each tile together and in order of
+//! y, so that only the reads of a narrow band have to be remembered. A whole run is far too big to
+//! sort in memory, so `ExternalSorter` sorts records in chunks, writes each chunk to a temporary
+//! file and merges the chunks when every record has been added.
+//!
+//! Records are sorted by lane, tile, y and x. The sort is stable, so reads at the same position
+//! keep the order they were added in.
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::iter::Map;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::vec;
+use fastq::{FastqReader, Record};
+use gzip;
+use merge::Merge;
+use split::PartWriter;
+use super::{IlluminaError, SequenceIdentifier, TileId};
+
+// Numbers the sorters of this process, so that their chunk files don't clash
+static SORTERS: AtomicUsize = AtomicUsize::new(0);
+
+/// The order records are sorted in: the tile, which starts with the lane, then y and x
+pub type SortKey = (TileId, u16, u16);
+
+/// The key a read is sorted by
+pub fn sort_key(seq_id: &SequenceIdentifier) -> SortKey {
+    (seq_id.tile_id(), seq_id.y, seq_id.x)
+}
+
+type Keyed = (SortKey, Record);
+type ChunkStream = Map<FastqReader<BufReader<File>>, fn(Result<Record, IlluminaError>) -> Result<Keyed, IlluminaError>>;
+
+/// Pairs a record read back from a chunk with its key
+fn keyed(record: Result<Record, IlluminaError>) -> Result<Keyed, IlluminaError> {
+    let record = record?;
+    Ok((sort_key(&record.sequence_identifier()?), record))
+}
+
+/// The key of a keyed record
+fn key_of(keyed: &Keyed) -> SortKey {
+    keyed.0
+}
+
+/// Temporary chunk files, which are deleted when they're dropped
+#[derive(Debug, Default)]
+struct Chunks(Vec<PathBuf>);
+
+impl Drop for Chunks {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Sorts FASTQ records by position, keeping at most about `max_bytes` of them in memory and
+/// writing the rest to temporary files
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::external_sort::ExternalSorter;
+/// use illumina_coordinates::fastq::FastqReader;
+///
+/// fn main() {
+///     let data = "@A00123:8:H3KJ7DSXX:1:1102:1000:1000 1:N:0:0\nACGT\n+\nFFFF\n\
+///                 @A00123:8:H3KJ7DSXX:1:1101:2000:3000 1:N:0:0\nACGT\n+\nFFFF\n\
+///                 @A00123:8:H3KJ7DSXX:1:1101:3000:2000 1:N:0:0\nACGT\n+\nFFFF\n";
+///     let mut sorter = ExternalSorter::new(std::env::temp_dir(), 1 << 20);
+///     for record in FastqReader::new(data.as_bytes()) {
+///         sorter.add(record.unwrap()).unwrap();
+///     }
+///     let x: Vec<u16> = sorter.finish().unwrap()
+///         .map(|record| record.unwrap().sequence_identifier().unwrap().x)
+///         .collect();
+///     assert_eq!(x, vec![3000, 2000, 1000]);
+/// }
+/// ```
+pub struct ExternalSorter {
+    temp_dir: PathBuf,
+    max_bytes: usize,
+    id: usize,
+    buffer: Vec<Keyed>,
+    buffered_bytes: usize,
+    chunks: Chunks
+}
+
+impl ExternalSorter {
+    /// Creates a sorter that writes its temporary files to `temp_dir`
+    pub fn new<P: AsRef<Path>>(temp_dir: P, max_bytes: usize) -> ExternalSorter {
+        ExternalSorter {
+            temp_dir: temp_dir.as_ref().to_path_buf(),
+            max_bytes,
+            id: SORTERS.fetch_add(1, Ordering::Relaxed),
+            buffer: vec![],
+            buffered_bytes: 0,
+            chunks: Chunks::default()
+        }
+    }
+
+    /// Adds a record, writing the records in memory to a temporary file if they've reached the
+    /// limit
+    pub fn add(&mut self, record: Record) -> Result<(), IlluminaError> {
+        let key = sort_key(&record.sequence_identifier()?);
+        self.buffered_bytes += mem::size_of::<Keyed>() + record.header.len() + record.sequence.len() + record.quality.len();
+        self.buffer.push((key, record));
+        if self.buffered_bytes >= self.max_bytes {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// The number of temporary files written so far
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.0.len()
+    }
+
+    /// Sorts the records in memory and writes them to a new chunk file
+    fn spill(&mut self) -> Result<(), IlluminaError> {
+        let path = self.temp_dir.join(format!("illumina_coordinates_sort_{}_{}_{}.fastq",
+                                              std::process::id(), self.id, self.chunks.0.len()));
+        // The file is deleted with the others even if writing it fails
+        self.chunks.0.push(path.clone());
+        self.buffer.sort_by_key(key_of);
+        let mut writer = BufWriter::new(File::create(&path)?);
+        for keyed in &self.buffer {
+            keyed.1.write_to(&mut writer)?;
+        }
+        writer.flush()?;
+        self.buffer.clear();
+        self.buffered_bytes = 0;
+        Ok(())
+    }
+
+    /// Finishes adding records and returns them in sorted order. If every record fit in memory,
+    /// nothing is written to disk.
+    pub fn finish(mut self) -> Result<SortedRecords, IlluminaError> {
+        if self.chunks.0.is_empty() {
+            self.buffer.sort_by_key(key_of);
+            let records = mem::take(&mut self.buffer);
+            return Ok(SortedRecords { inner: Sorted::Memory(records.into_iter()), _chunks: Chunks::default() });
+        }
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+        let mut streams = Vec::with_capacity(self.chunks.0.len());
+        for path in &self.chunks.0 {
+            let reader = FastqReader::new(BufReader::new(File::open(path)?));
+            streams.push(reader.map(keyed as fn(Result<Record, IlluminaError>) -> Result<Keyed, IlluminaError>));
+        }
+        let chunks = mem::take(&mut self.chunks);
+        Ok(SortedRecords { inner: Sorted::Merged(Merge::new(streams, key_of)), _chunks: chunks })
+    }
+}
+
+/// Where sorted records come from
+enum Sorted {
+    Memory(vec::IntoIter<Keyed>),
+    Merged(Merge<ChunkStream, Keyed, SortKey, fn(&Keyed) -> SortKey>)
+}
+
+/// The records of an `ExternalSorter` in sorted order. Its temporary files are deleted when it's
+/// dropped.
+pub struct SortedRecords {
+    inner: Sorted,
+    _chunks: Chunks
+}
+
+impl Iterator for SortedRecords {
+    type Item = Result<Record, IlluminaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inner {
+            Sorted::Memory(ref mut records) => records.next().map(|(_, record)| Ok(record)),
+            Sorted::Merged(ref mut records) => records.next().map(|keyed| keyed.map(|(_, record)| record))
+        }
+    }
+}
+
+/// Sorts a FASTQ file by position, writing the sorted records to `output`, which is compressed as
+/// BGZF if the input is gzipped. At most about `max_bytes` of records are kept in memory, and the
+/// rest are written to temporary files in `temp_dir`. Returns the number of records.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::external_sort::sort_fastq;
+///
+/// fn main() {
+///     let records = sort_fastq("reads.fastq.gz", "sorted.fastq.gz", "/scratch", 1 << 30).unwrap();
+///     println!("sorted {} records", records);
+/// }
+/// ```
+pub fn sort_fastq<P, Q, T>(input: P, output: Q, temp_dir: T, max_bytes: usize) -> Result<u64, IlluminaError>
+    where P: AsRef<Path>, Q: AsRef<Path>, T: AsRef<Path> {
+    let compressed = gzip::is_gzipped(&input)?;
+    let mut sorter = ExternalSorter::new(temp_dir, max_bytes);
+    for record in FastqReader::new(gzip::open(&input)?) {
+        sorter.add(record?)?;
+    }
+    let mut writer = PartWriter::create(output, compressed)?;
+    let mut count = 0;
+    for record in sorter.finish()? {
+        record?.write_to(&mut writer)?;
+        count += 1;
+    }
+    writer.finish()?;
+    Ok(count)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn record(name: &str, sequence: &str) -> Record {
+        Record {
+            header: format!("@A00123:8:H3KJ7DSXX:{} 1:N:0:0", name),
+            sequence: sequence.to_string(),
+            quality: "F".repeat(sequence.len())
+        }
+    }
+
+    fn names() -> Vec<&'static str> {
+        vec!["2:1101:10:10", "1:1102:10:10", "1:1101:30:20", "1:1101:20:20", "1:1101:50:5", "1:1101:20:20",
+             "1:2101:1:1"]
+    }
+
+    fn sorted(max_bytes: usize) -> (Vec<String>, usize) {
+        let dir = env::temp_dir().join(format!("illumina_coordinates_external_sort_{}_{}", std::process::id(), max_bytes));
+        fs::create_dir_all(&dir).unwrap();
+        let mut sorter = ExternalSorter::new(&dir, max_bytes);
+        for (index, name) in names().into_iter().enumerate() {
+            sorter.add(record(name, &"ACGT"[index % 4..])).unwrap();
+        }
+        let chunks = sorter.chunk_count();
+        let records: Vec<String> = sorter.finish().unwrap()
+            .map(|record| {
+                let record = record.unwrap();
+                format!("{} {}", &record.header[20..record.header.find(' ').unwrap()], record.sequence)
+            })
+            .collect();
+        // The chunk files are gone once the sorted records are dropped
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+        (records, chunks)
+    }
+
+    #[test]
+    fn test_sort() {
+        let expected = ["1:1101:50:5 ACGT", "1:1101:20:20 T", "1:1101:20:20 CGT", "1:1101:30:20 GT", "1:1102:10:10 CGT",
+                        "1:2101:1:1 GT", "2:1101:10:10 ACGT"];
+        let (in_memory, chunks) = sorted(1 << 20);
+        assert_eq!(chunks, 0);
+        assert_eq!(in_memory, expected);
+        let (spilled, chunks) = sorted(1);
+        assert_eq!(chunks, 7);
+        assert_eq!(spilled, expected);
+    }
+
+    #[test]
+    fn test_sort_fastq() {
+        let dir = env::temp_dir().join(format!("illumina_coordinates_sort_fastq_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("reads.fastq");
+        let output = dir.join("sorted.fastq");
+        let mut text = String::new();
+        for name in names() {
+            let mut line = vec![];
+            record(name, "ACGT").write_to(&mut line).unwrap();
+            text.push_str(&String::from_utf8(line).unwrap());
+        }
+        fs::write(&input, text).unwrap();
+        assert_eq!(sort_fastq(&input, &output, &dir, 200).unwrap(), 7);
+        let sorted: Vec<SortKey> = FastqReader::new(gzip::open(&output).unwrap())
+            .map(|record| sort_key(&record.unwrap().sequence_identifier().unwrap()))
+            .collect();
+        assert_eq!(sorted.len(), 7);
+        assert!(sorted.windows(2).all(|pair| pair[0] <= pair[1]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
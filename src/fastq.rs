@@ -0,0 +1,265 @@
+//! Reads and writes FASTQ records.
+
+use std::io::{self, BufRead, Write};
+#[cfg(feature = "noodles-fastq")]
+use std::convert::TryFrom;
+use header_format::HeaderFormat;
+#[cfg(feature = "noodles-fastq")]
+use noodles_fastq;
+use super::{parse_sequence_identifier, IlluminaError, SequenceIdentifier};
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// A single FASTQ record. Line endings are not included, and the separator line is always
+/// written as a bare `+`.
+pub struct Record {
+    /// The header line, including the leading `@`
+    pub header: String,
+    /// The bases of the read
+    pub sequence: String,
+    /// The quality scores of the read
+    pub quality: String
+}
+
+impl Record {
+    /// Parses the sequence identifier in the header line
+    pub fn sequence_identifier(&self) -> Result<SequenceIdentifier, IlluminaError> {
+        parse_sequence_identifier(&self.header)
+    }
+
+    /// Parses the sequence identifier in the header line, which is in the given format
+    pub fn sequence_identifier_with<F: HeaderFormat + ?Sized>(&self, format: &F)
+            -> Result<SequenceIdentifier, IlluminaError> {
+        format.parse(&self.header)
+    }
+
+    /// The mean of the quality scores, which are Phred scores offset by 33. Reads with no bases
+    /// have a mean of 0.
+    pub fn mean_quality(&self) -> f64 {
+        if self.quality.is_empty() {
+            return 0.0;
+        }
+        let total: u64 = self.quality.bytes().map(|score| u64::from(score.saturating_sub(33))).sum();
+        total as f64 / self.quality.len() as f64
+    }
+
+    /// Writes the record in FASTQ format
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "{}\n{}\n+\n{}", self.header, self.sequence, self.quality)
+    }
+}
+
+/// Reads FASTQ records from a buffered reader. The reader never reads past the end of the
+/// current record, so the position of the underlying reader can be used to locate records.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::fastq::FastqReader;
+///
+/// fn main() {
+///     let data = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\nACGT\n+\nFFFF\n";
+///     let mut reader = FastqReader::new(data.as_bytes());
+///     let record = reader.next().unwrap().unwrap();
+///     assert_eq!(record.sequence, "ACGT");
+///     assert_eq!(record.sequence_identifier().unwrap().x, 4127);
+///     assert!(reader.next().is_none());
+/// }
+/// ```
+pub struct FastqReader<R: BufRead> {
+    inner: R,
+    separator: String
+}
+
+impl<R: BufRead> FastqReader<R> {
+    /// Creates a reader
+    pub fn new(inner: R) -> FastqReader<R> {
+        FastqReader { inner, separator: String::new() }
+    }
+
+    /// Reads the next record into `record`, reusing its allocations. Returns false at the end of
+    /// the input.
+    pub fn read_record(&mut self, record: &mut Record) -> Result<bool, IlluminaError> {
+        if !read_line(&mut self.inner, &mut record.header)? {
+            return Ok(false);
+        }
+        if !record.header.starts_with('@') {
+            return Err(IlluminaError::FormatError(format!("FASTQ header doesn't start with @: {}", record.header)));
+        }
+        let complete = read_line(&mut self.inner, &mut record.sequence)?
+            && read_line(&mut self.inner, &mut self.separator)?
+            && read_line(&mut self.inner, &mut record.quality)?;
+        if !complete {
+            return Err(IlluminaError::FormatError(format!("FASTQ record is truncated: {}", record.header)));
+        }
+        if !self.separator.starts_with('+') {
+            return Err(IlluminaError::FormatError(format!("FASTQ record has no + line: {}", record.header)));
+        }
+        if record.sequence.len() != record.quality.len() {
+            return Err(IlluminaError::FormatError(
+                format!("FASTQ sequence and quality have different lengths: {}", record.header)));
+        }
+        Ok(true)
+    }
+
+    /// Returns a reference to the underlying reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying reader
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Returns the underlying reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> Result<bool, IlluminaError> {
+    line.clear();
+    if reader.read_line(line)? == 0 {
+        return Ok(false);
+    }
+    let length = line.trim_end_matches(&['\n', '\r'][..]).len();
+    line.truncate(length);
+    Ok(true)
+}
+
+impl<R: BufRead> Iterator for FastqReader<R> {
+    type Item = Result<Record, IlluminaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record = Record::default();
+        match self.read_record(&mut record) {
+            Ok(true) => Some(Ok(record)),
+            Ok(false) => None,
+            Err(error) => Some(Err(error))
+        }
+    }
+}
+
+
+/// Wraps a noodles-fastq reader, parsing the sequence identifier of each record it reads. Needs
+/// the `noodles-fastq` feature.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::fastq::NoodlesReader;
+///
+/// fn main() {
+///     let data = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\nACGT\n+\nFFFF\n";
+///     let mut reader = NoodlesReader::new(data.as_bytes());
+///     let (record, seq_id) = reader.next().unwrap().unwrap();
+///     assert_eq!(record.sequence(), b"ACGT");
+///     assert_eq!(seq_id.x, 4127);
+///     assert!(reader.next().is_none());
+/// }
+/// ```
+#[cfg(feature = "noodles-fastq")]
+pub struct NoodlesReader<R: BufRead> {
+    inner: noodles_fastq::io::Reader<R>
+}
+
+#[cfg(feature = "noodles-fastq")]
+impl<R: BufRead> NoodlesReader<R> {
+    /// Creates a reader
+    pub fn new(inner: R) -> NoodlesReader<R> {
+        NoodlesReader { inner: noodles_fastq::io::Reader::new(inner) }
+    }
+
+    /// Reads the next record into `record`, reusing its allocations, and parses its sequence
+    /// identifier. Returns None at the end of the input.
+    pub fn read_record(&mut self, record: &mut noodles_fastq::Record)
+            -> Result<Option<SequenceIdentifier>, IlluminaError> {
+        if self.inner.read_record(record)? == 0 {
+            return Ok(None);
+        }
+        SequenceIdentifier::try_from(&*record).map(Some)
+    }
+
+    /// Returns a reference to the noodles reader
+    pub fn get_ref(&self) -> &noodles_fastq::io::Reader<R> {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the noodles reader
+    pub fn get_mut(&mut self) -> &mut noodles_fastq::io::Reader<R> {
+        &mut self.inner
+    }
+
+    /// Returns the noodles reader
+    pub fn into_inner(self) -> noodles_fastq::io::Reader<R> {
+        self.inner
+    }
+}
+
+#[cfg(feature = "noodles-fastq")]
+impl<R: BufRead> Iterator for NoodlesReader<R> {
+    type Item = Result<(noodles_fastq::Record, SequenceIdentifier), IlluminaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record = noodles_fastq::Record::default();
+        match self.read_record(&mut record) {
+            Ok(Some(seq_id)) => Some(Ok((record, seq_id))),
+            Ok(None) => None,
+            Err(error) => Some(Err(error))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use header_format::ReadNameFormat;
+
+    const FASTQ: &str = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\r\nACGT\r\n+\r\nFFFF\r\n\
+                         @M03745:11:000000000-B54L5:1:2108:4128:8950 1:N:0:0\nTTTT\n+M03745\n####";
+
+    #[test]
+    fn test_read_records() {
+        let records: Vec<Record> = FastqReader::new(FASTQ.as_bytes()).map(|record| record.unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].header, "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0");
+        assert_eq!(records[0].quality, "FFFF");
+        assert_eq!(records[1].sequence, "TTTT");
+        assert_eq!(records[1].quality, "####");
+        assert_eq!(records[1].sequence_identifier().unwrap().y, 8950);
+        assert!(records[1].sequence_identifier_with(&ReadNameFormat).is_err());
+        assert_eq!((records[0].mean_quality(), records[1].mean_quality()), (37.0, 2.0));
+        assert_eq!(Record::default().mean_quality(), 0.0);
+    }
+
+    #[cfg(feature = "noodles-fastq")]
+    #[test]
+    fn test_noodles_reader() {
+        let records: Vec<_> = NoodlesReader::new(FASTQ.as_bytes()).map(|record| record.unwrap()).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].1, parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap());
+        assert_eq!(records[1].0.quality_scores(), b"####");
+        assert_eq!(SequenceIdentifier::try_from(&records[1].0).unwrap(), records[1].1);
+        let mut reader = NoodlesReader::new(&b"@read1\nACGT\n+\nFFFF\n@read2\nACGT\n"[..]);
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_write_record() {
+        let record = FastqReader::new(FASTQ.as_bytes()).next().unwrap().unwrap();
+        let mut output = vec![];
+        record.write_to(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\nACGT\n+\nFFFF\n");
+    }
+
+    #[test]
+    fn test_malformed_records() {
+        assert!(FastqReader::new("M03745\nACGT\n+\nFFFF\n".as_bytes()).next().unwrap().is_err());
+        assert!(FastqReader::new("@M03745\nACGT\n".as_bytes()).next().unwrap().is_err());
+        assert!(FastqReader::new("@M03745\nACGT\n-\nFFFF\n".as_bytes()).next().unwrap().is_err());
+        assert!(FastqReader::new("@M03745\nACGT\n+\nFFF\n".as_bytes()).next().unwrap().is_err());
+    }
+}
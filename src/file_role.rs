@@ -0,0 +1,180 @@
+//! Infers which read of a run a FASTQ file holds, to catch misnamed files before a pipeline
+//! pairs them up wrongly.
+//!
+//! Headers give the read number, but index reads don't always say that they're index reads.
+//! They're short, and bcl2fastq and BCL Convert write the index they read into the sample field
+//! of every header, so the sequences of an index file match the sample of their own headers.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::BufRead;
+use barcode::hamming_distance;
+use fastq::{FastqReader, Record};
+use super::IlluminaError;
+
+// Index reads are at most this long; the shortest biological reads are longer
+const MAX_INDEX_LENGTH: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// The read of a run that a file holds, as in the `R1`, `R2`, `I1` and `I2` of file names
+pub enum FileRole {
+    /// A biological read, numbered from 1
+    Read(u8),
+    /// An index read, numbered from 1
+    Index(u8)
+}
+
+impl FileRole {
+    /// The role given in a file name following Illumina's convention, such as
+    /// `Sample_S1_L001_I1_001.fastq.gz`, or `None` if the name has no role
+    pub fn from_file_name(name: &str) -> Option<FileRole> {
+        name.rsplit(&['_', '.'][..]).find_map(|part| {
+            let number = part.get(1..).and_then(|number| number.parse().ok())?;
+            match part.as_bytes()[0] {
+                b'R' => Some(FileRole::Read(number)),
+                b'I' => Some(FileRole::Index(number)),
+                _ => None
+            }
+        })
+    }
+}
+
+impl fmt::Display for FileRole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FileRole::Read(number) => write!(f, "R{}", number),
+            FileRole::Index(number) => write!(f, "I{}", number)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// What the first records of a file say about its role
+pub struct RoleEvidence {
+    /// The number of records sampled
+    pub records: u64,
+    /// The number of records with each read number in their header
+    pub read_numbers: BTreeMap<u8, u64>,
+    /// The length of the longest sequence
+    pub max_length: usize,
+    /// The number of records whose sequences match the first and second indexes of their
+    /// sample, with at most one mismatch
+    pub index_matches: [u64; 2]
+}
+
+impl RoleEvidence {
+    /// Adds a record to the evidence
+    pub fn add(&mut self, record: &Record) -> Result<(), IlluminaError> {
+        let seq_id = record.sequence_identifier()?;
+        self.records += 1;
+        *self.read_numbers.entry(seq_id.read).or_insert(0) += 1;
+        self.max_length = self.max_length.max(record.sequence.len());
+        let indexes = match seq_id.sample.index_pair() {
+            Some((first, second)) => [Some(first), Some(second)],
+            None => [seq_id.sample.as_sequence(), None]
+        };
+        for (matches, index) in self.index_matches.iter_mut().zip(&indexes) {
+            if let Some(index) = *index {
+                if hamming_distance(&record.sequence, index).unwrap_or(usize::MAX) <= 1 {
+                    *matches += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The read number of the headers, or `None` if they don't agree
+    pub fn read_number(&self) -> Option<u8> {
+        if self.read_numbers.len() == 1 {
+            self.read_numbers.keys().next().cloned()
+        } else {
+            None
+        }
+    }
+
+    /// Whether the records look like an index read: most sequences match an index of their
+    /// sample, or every sequence is short enough to be an index
+    pub fn is_index(&self) -> bool {
+        self.records > 0
+            && (self.index_matches.iter().any(|&matches| matches * 2 > self.records) || self.max_length <= MAX_INDEX_LENGTH)
+    }
+
+    /// The role of the file, or `None` if there were no records or their read numbers don't
+    /// agree. Index reads are numbered by the index they match, falling back to the read number
+    /// of the headers.
+    pub fn role(&self) -> Option<FileRole> {
+        if !self.is_index() {
+            return self.read_number().map(FileRole::Read);
+        }
+        if self.index_matches[1] > self.index_matches[0] && self.index_matches[1] * 2 > self.records {
+            Some(FileRole::Index(2))
+        } else if self.index_matches[0] * 2 > self.records {
+            Some(FileRole::Index(1))
+        } else {
+            self.read_number().map(FileRole::Index)
+        }
+    }
+}
+
+/// Reads up to `max_records` records from the start of a FASTQ file and weighs up which read of
+/// the run they're from
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::file_role::{infer_role, FileRole};
+///
+/// fn main() {
+///     let index = "@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:TAAGGCGA+CTCTCTAT\nCTCTCTAT\n+\nFFFFFFFF\n";
+///     let evidence = infer_role(index.as_bytes(), 1000).unwrap();
+///     assert_eq!(evidence.role(), Some(FileRole::Index(2)));
+///     assert_eq!(FileRole::from_file_name("Sample_S1_L001_I2_001.fastq.gz"), evidence.role());
+/// }
+/// ```
+pub fn infer_role<R: BufRead>(reader: R, max_records: u64) -> Result<RoleEvidence, IlluminaError> {
+    let mut reader = FastqReader::new(reader);
+    let mut record = Record::default();
+    let mut evidence = RoleEvidence::default();
+    while evidence.records < max_records && reader.read_record(&mut record)? {
+        evidence.add(&record)?;
+    }
+    Ok(evidence)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evidence(records: &[(&str, &str)]) -> RoleEvidence {
+        let text: String = records.iter()
+            .map(|&(comment, sequence)| format!("@A00123:8:H3KJ7DSXX:1:1101:1000:1000 {}\n{}\n+\n{}\n",
+                                                comment, sequence, "F".repeat(sequence.len())))
+            .collect();
+        infer_role(text.as_bytes(), 100).unwrap()
+    }
+
+    #[test]
+    fn test_infer_role() {
+        let read = "ACGTACGTACGTACGTACGTACGTACGTACGTACGT";
+        assert_eq!(evidence(&[("2:N:0:TAAGGCGA+CTCTCTAT", read)]).role(), Some(FileRole::Read(2)));
+        assert_eq!(evidence(&[("1:N:0:2", read), ("2:N:0:2", read)]).role(), None);
+        let index1 = evidence(&[("1:N:0:TAAGGCGA+CTCTCTAT", "TAAGGCGA"), ("1:N:0:TAAGGCGA+CTCTCTAT", "TAAGGCGT")]);
+        assert_eq!(index1.index_matches, [2, 0]);
+        assert_eq!(index1.role(), Some(FileRole::Index(1)));
+        assert_eq!(evidence(&[("1:N:0:TAAGGCGA", "TAAGGCGA")]).role(), Some(FileRole::Index(1)));
+        // Short reads without indexes in their headers are index reads numbered by their headers
+        assert_eq!(evidence(&[("2:N:0:1", "GGGGGGGG")]).role(), Some(FileRole::Index(2)));
+        assert_eq!(evidence(&[]).role(), None);
+    }
+
+    #[test]
+    fn test_from_file_name() {
+        assert_eq!(FileRole::from_file_name("Undetermined_S0_L001_R1_001.fastq.gz"), Some(FileRole::Read(1)));
+        assert_eq!(FileRole::from_file_name("reads_R2.fastq"), Some(FileRole::Read(2)));
+        assert_eq!(FileRole::from_file_name("Sample_S1_L002_I1_001.fastq.gz"), Some(FileRole::Index(1)));
+        assert_eq!(FileRole::from_file_name("reads.fastq.gz"), None);
+        assert_eq!(FileRole::Index(2).to_string(), "I2");
+    }
+}
@@ -0,0 +1,251 @@
+//! Predicates for selecting reads based on their sequence identifiers.
+
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use super::{IlluminaError, SequenceIdentifier, TileId};
+use geometry::{lane_coordinates, FlowCellLayout, TileExtent};
+
+/// Flags reads whose clusters lie close to the edge of their tile. Edge clusters have
+/// systematically worse quality, so some pipelines prefer to drop them.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::filter::EdgeFilter;
+/// use illumina_coordinates::geometry;
+///
+/// fn main() {
+///     let filter = EdgeFilter::new(geometry::MISEQ, 1000);
+///     let line = "@M03745:11:000000000-B54L5:1:2108:1127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(&line).unwrap();
+///     assert!(filter.is_near_edge(&seq_id));
+/// }
+/// ```
+pub struct EdgeFilter {
+    extent: TileExtent,
+    margin: u16,
+}
+
+impl EdgeFilter {
+    /// Creates a filter that flags reads within `margin` units of the edge of a tile with the
+    /// given extent
+    pub fn new(extent: TileExtent, margin: u16) -> EdgeFilter {
+        EdgeFilter { extent, margin }
+    }
+
+    /// Whether the read's cluster lies within the margin of the tile edge
+    pub fn is_near_edge(&self, seq_id: &SequenceIdentifier) -> bool {
+        self.extent.is_near_edge(seq_id.x, seq_id.y, self.margin)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The part of the flow cell a region is on, which sets the coordinates its bounds are in
+enum Area {
+    Tile { lane: Option<u8>, tile: u32 },
+    Lane { layout: FlowCellLayout, extent: TileExtent, lane: u8, side: u8 }
+}
+
+/// Selects reads whose clusters lie within a rectangle, for example to pull out the reads under
+/// an artifact seen in a picture of the flow cell. The bounds are inclusive.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::filter::RegionFilter;
+///
+/// fn main() {
+///     let filter = RegionFilter::tile(Some(1), 2108, (4000, 5000), (8000, 9000));
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(&line).unwrap();
+///     assert!(filter.contains(&seq_id));
+/// }
+/// ```
+pub struct RegionFilter {
+    area: Area,
+    x: (u32, u32),
+    y: (u32, u32)
+}
+
+impl RegionFilter {
+    /// Creates a filter for a rectangle on a tile, given by its tile field such as 2108. If no
+    /// lane is given, the rectangle is selected on that tile in every lane.
+    pub fn tile(lane: Option<u8>, tile: u32, x: (u32, u32), y: (u32, u32)) -> RegionFilter {
+        RegionFilter { area: Area::Tile { lane, tile }, x, y }
+    }
+
+    /// Creates a filter for a rectangle on one side of a lane, in the coordinates given by
+    /// `geometry::lane_coordinates`, so that the rectangle can span several tiles
+    pub fn lane(layout: FlowCellLayout, extent: TileExtent, lane: u8, side: u8, x: (u32, u32), y: (u32, u32))
+            -> RegionFilter {
+        RegionFilter { area: Area::Lane { layout, extent, lane, side }, x, y }
+    }
+
+    /// Whether the read's cluster lies within the rectangle
+    pub fn contains(&self, seq_id: &SequenceIdentifier) -> bool {
+        let (x, y) = match self.area {
+            Area::Tile { lane, tile } => {
+                if (lane.is_some() && lane != Some(seq_id.lane)) || tile != seq_id.tile_field() {
+                    return false;
+                }
+                (u32::from(seq_id.x), u32::from(seq_id.y))
+            },
+            Area::Lane { layout, extent, lane, side } => {
+                if lane != seq_id.lane || side != seq_id.side {
+                    return false;
+                }
+                match lane_coordinates(&layout, extent, seq_id.tile_id(), seq_id.x, seq_id.y) {
+                    Some(coordinates) => coordinates,
+                    None => return false
+                }
+            }
+        };
+        self.x.0 <= x && x <= self.x.1 && self.y.0 <= y && y <= self.y.1
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// Tiles whose reads should be left out, such as the outliers found by
+/// `StatsCollector::count_outliers`. Tiles are identified by their lane and tile field, e.g. 2108.
+///
+/// Blacklists are saved as tab-separated text, with a `lane` and `tile` header row and one row
+/// per tile, so they can also be written by hand or by other tools. Blank lines and lines
+/// starting with `#` are ignored.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::filter::TileBlacklist;
+///
+/// fn main() {
+///     let blacklist = TileBlacklist::read("lane\ttile\n1\t2108\n".as_bytes()).unwrap();
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(&line).unwrap();
+///     assert!(blacklist.contains(&seq_id));
+///     let mut saved = vec![];
+///     blacklist.write(&mut saved).unwrap();
+///     assert_eq!(saved, b"lane\ttile\n1\t2108\n");
+/// }
+/// ```
+pub struct TileBlacklist {
+    tiles: BTreeSet<(u8, u32)>
+}
+
+impl TileBlacklist {
+    /// Creates an empty blacklist
+    pub fn new() -> TileBlacklist {
+        TileBlacklist::default()
+    }
+
+    /// Adds a tile
+    pub fn insert(&mut self, tile: TileId) {
+        self.tiles.insert((tile.lane, tile.tile_field()));
+    }
+
+    /// Whether the read's tile is on the blacklist
+    pub fn contains(&self, seq_id: &SequenceIdentifier) -> bool {
+        self.tiles.contains(&(seq_id.lane, seq_id.tile_field()))
+    }
+
+    /// The number of tiles on the blacklist
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// Whether the blacklist has no tiles
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Writes the blacklist as tab-separated text, in order of lane and tile
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "lane\ttile")?;
+        for &(lane, tile) in &self.tiles {
+            writeln!(writer, "{}\t{}", lane, tile)?;
+        }
+        writer.flush()
+    }
+
+    /// Reads a blacklist written by `write`. The header row is optional.
+    pub fn read<R: BufRead>(reader: R) -> Result<TileBlacklist, IlluminaError> {
+        let mut blacklist = TileBlacklist::new();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line == "lane\ttile" {
+                continue;
+            }
+            let mut fields = line.split('\t');
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(lane), Some(tile), None) => blacklist.tiles.insert((lane.trim().parse()?, tile.trim().parse()?)),
+                _ => return Err(IlluminaError::FormatError(format!("expected a lane and a tile: {}", line)))
+            };
+        }
+        Ok(blacklist)
+    }
+}
+
+impl std::iter::FromIterator<TileId> for TileBlacklist {
+    fn from_iter<I: IntoIterator<Item=TileId>>(tiles: I) -> TileBlacklist {
+        let mut blacklist = TileBlacklist::new();
+        for tile in tiles {
+            blacklist.insert(tile);
+        }
+        blacklist
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geometry;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_edge_filter() {
+        let filter = EdgeFilter::new(geometry::MISEQ, 1000);
+        let center = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+        let edge = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:29500 1:N:0:0").unwrap();
+        assert!(!filter.is_near_edge(&center));
+        assert!(filter.is_near_edge(&edge));
+    }
+
+    #[test]
+    fn test_region_filter() {
+        let read = |name: &str| parse_sequence_identifier(&format!("@M03745:11:000000000-B54L5:{} 1:N:0:0", name)).unwrap();
+        let filter = RegionFilter::tile(None, 2108, (4000, 5000), (8000, 9000));
+        assert!(filter.contains(&read("2:2108:4000:9000")));
+        assert!(!filter.contains(&read("1:2108:3999:8500")));
+        assert!(!filter.contains(&read("1:2109:4500:8500")));
+        assert!(!RegionFilter::tile(Some(2), 2108, (4000, 5000), (8000, 9000)).contains(&read("1:2108:4500:8500")));
+
+        let layout = FlowCellLayout { lanes: 1, sides: 2, swaths: 1, cameras: 1, tiles_per_swath: 19 };
+        let filter = RegionFilter::lane(layout, geometry::MISEQ, 1, 2, (0, 1000), (28000, 30000));
+        assert!(filter.contains(&read("1:2101:1500:29500")));
+        assert!(filter.contains(&read("1:2102:1500:1500")));
+        assert!(!filter.contains(&read("1:1102:1500:1500")));
+        assert!(!filter.contains(&read("1:2103:1500:1500")));
+    }
+
+    #[test]
+    fn test_tile_blacklist() {
+        let read = |name: &str| parse_sequence_identifier(&format!("@NS500358:204:HTN5KAFXY:{} 1:N:0:0", name)).unwrap();
+        let blacklist: TileBlacklist = vec![read("1:11101:1000:1000").tile_id(), read("2:23612:1000:1000").tile_id()].into_iter().collect();
+        assert_eq!(blacklist.len(), 2);
+        assert!(blacklist.contains(&read("1:11101:5000:5000")));
+        assert!(!blacklist.contains(&read("2:11101:5000:5000")));
+        let mut saved = vec![];
+        blacklist.write(&mut saved).unwrap();
+        assert_eq!(String::from_utf8(saved.clone()).unwrap(), "lane\ttile\n1\t11101\n2\t23612\n");
+        assert_eq!(TileBlacklist::read(&saved[..]).unwrap(), blacklist);
+        assert_eq!(TileBlacklist::read("# suspect tiles\n\n2\t23612\r\n1\t11101\n".as_bytes()).unwrap(), blacklist);
+        assert!(TileBlacklist::read("1\n".as_bytes()).is_err());
+        assert!(TileBlacklist::read("1\t2108\t3\n".as_bytes()).is_err());
+        assert!(TileBlacklist::read("1\ttile\n".as_bytes()).is_err());
+        assert!(TileBlacklist::new().is_empty());
+    }
+}
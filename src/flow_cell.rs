@@ -0,0 +1,249 @@
+//! Identification of the type of flow cell a run used.
+//!
+//! Flow cell IDs encode the kind of flow cell: MiSeq IDs have a letter after the
+//! `000000000-` prefix that depends on the reagent kit, MiniSeq IDs start with `000H`, iSeq IDs
+//! are eight characters, a dash and four digits, and other platforms end their IDs with a
+//! four-character code such as `DSXX` for NovaSeq S4. The layouts are the standard ones for each
+//! type, so they describe the tiles a run should have without needing `RunInfo.xml`. Runs that
+//! were set up to image only part of the flow cell will have fewer.
+
+use geometry::FlowCellLayout;
+use instrument::InstrumentModel;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A type of flow cell
+pub enum FlowCellType {
+    /// MiSeq Reagent Nano Kit v2
+    MiSeqNano,
+    /// MiSeq Reagent Micro Kit v2
+    MiSeqMicro,
+    /// MiSeq Reagent Kit v2
+    MiSeqV2,
+    /// MiSeq Reagent Kit v3
+    MiSeqV3,
+    /// MiniSeq Mid or High Output
+    MiniSeq,
+    /// iSeq 100 i1
+    ISeq,
+    /// HiSeq 2500 Rapid Run, v1 or v2
+    HiSeqRapid,
+    /// HiSeq 2000 and 2500 High Output v3
+    HiSeqHighOutputV3,
+    /// HiSeq 2500 High Output v4
+    HiSeqHighOutputV4,
+    /// HiSeq 3000 and 4000
+    HiSeq4000,
+    /// HiSeq X, v2 or v2.5
+    HiSeqX,
+    /// NextSeq 500 and 550 Mid Output
+    NextSeqMidOutput,
+    /// NextSeq 500 and 550 High Output
+    NextSeqHighOutput,
+    /// NovaSeq 6000 SP or S1, which use flow cells of the same kind
+    NovaSeqS1,
+    /// NovaSeq 6000 S2
+    NovaSeqS2,
+    /// NovaSeq 6000 S4
+    NovaSeqS4
+}
+
+const MISEQ_PREFIX: &str = "000000000-";
+const MINISEQ_PREFIX: &str = "000H";
+
+// The last four characters of flow cell IDs on platforms other than the MiSeq
+const SUFFIXES: [(&str, FlowCellType); 13] = [
+    ("ADXX", FlowCellType::HiSeqRapid),
+    ("BCXX", FlowCellType::HiSeqRapid),
+    ("ACXX", FlowCellType::HiSeqHighOutputV3),
+    ("ANXX", FlowCellType::HiSeqHighOutputV4),
+    ("BBXX", FlowCellType::HiSeq4000),
+    ("ALXX", FlowCellType::HiSeqX),
+    ("CCXX", FlowCellType::HiSeqX),
+    ("AFXX", FlowCellType::NextSeqMidOutput),
+    ("AGXX", FlowCellType::NextSeqHighOutput),
+    ("BGXX", FlowCellType::NextSeqHighOutput),
+    ("DRXX", FlowCellType::NovaSeqS1),
+    ("DMXX", FlowCellType::NovaSeqS2),
+    ("DSXX", FlowCellType::NovaSeqS4)
+];
+
+impl FlowCellType {
+    /// Infers the type from the flow cell ID, or returns `None` if it isn't recognized
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::flow_cell::FlowCellType;
+    ///
+    /// fn main() {
+    ///     let flow_cell = FlowCellType::from_flow_cell_id("000000000-B54L5").unwrap();
+    ///     assert_eq!(flow_cell, FlowCellType::MiSeqV3);
+    ///     assert_eq!(flow_cell.layout().tiles_per_lane(), 38);
+    ///     assert_eq!(FlowCellType::from_flow_cell_id("HFWJ3DSXX"), Some(FlowCellType::NovaSeqS4));
+    /// }
+    /// ```
+    pub fn from_flow_cell_id(flow_cell_id: &str) -> Option<FlowCellType> {
+        if let Some(serial) = flow_cell_id.strip_prefix(MISEQ_PREFIX) {
+            return match serial.chars().next() {
+                Some('A') => Some(FlowCellType::MiSeqV2),
+                Some('B') | Some('C') => Some(FlowCellType::MiSeqV3),
+                Some('D') => Some(FlowCellType::MiSeqNano),
+                Some('G') => Some(FlowCellType::MiSeqMicro),
+                _ => None
+            };
+        }
+        if flow_cell_id.starts_with(MINISEQ_PREFIX) && flow_cell_id.len() == 9 {
+            return Some(FlowCellType::MiniSeq);
+        }
+        let bytes = flow_cell_id.as_bytes();
+        if bytes.len() == 13 && bytes[8] == b'-' && bytes[..8].iter().all(u8::is_ascii_alphanumeric)
+            && bytes[9..].iter().all(u8::is_ascii_digit) {
+            return Some(FlowCellType::ISeq);
+        }
+        // Nearly all other flow cell IDs are nine characters, and the code follows a five-character
+        // serial number. NextSeq IDs can end in Y rather than X.
+        let code = flow_cell_id.get(5..).filter(|_| flow_cell_id.len() == 9)?;
+        let code = match code.get(..3) {
+            Some(start) if code.ends_with('Y') => format!("{}X", start),
+            _ => code.to_string()
+        };
+        SUFFIXES.iter()
+            .find(|&&(suffix, _)| suffix == code)
+            .map(|&(_, flow_cell)| flow_cell)
+    }
+
+    /// The range of a flow cell ID that holds the serial number of the flow cell, leaving out the
+    /// parts that identify its type
+    pub(crate) fn serial_number_range(flow_cell_id: &str) -> (usize, usize) {
+        let length = flow_cell_id.len();
+        match FlowCellType::from_flow_cell_id(flow_cell_id) {
+            Some(FlowCellType::MiSeqNano) | Some(FlowCellType::MiSeqMicro) | Some(FlowCellType::MiSeqV2)
+                | Some(FlowCellType::MiSeqV3) => (MISEQ_PREFIX.len() + 1, length),
+            Some(FlowCellType::MiniSeq) => (MINISEQ_PREFIX.len(), length),
+            Some(flow_cell) if flow_cell.code().is_some() => (0, 5),
+            _ => (0, length)
+        }
+    }
+
+    /// The four-character code at the end of IDs of this type of flow cell, such as `DSXX`.
+    /// MiSeq, MiniSeq and iSeq flow cell IDs don't have one.
+    pub fn code(&self) -> Option<&'static str> {
+        SUFFIXES.iter()
+            .find(|&&(_, flow_cell)| flow_cell == *self)
+            .map(|&(suffix, _)| suffix)
+    }
+
+    /// The standard arrangement of tiles on this type of flow cell
+    pub fn layout(&self) -> FlowCellLayout {
+        let (lanes, sides, swaths, cameras, tiles_per_swath) = match *self {
+            FlowCellType::MiSeqNano => (1, 2, 1, 1, 1),
+            FlowCellType::MiSeqMicro => (1, 2, 1, 1, 2),
+            FlowCellType::MiSeqV2 => (1, 2, 1, 1, 14),
+            FlowCellType::MiSeqV3 => (1, 2, 1, 1, 19),
+            // MiniSeqs image a single lane with cameras 1 and 2, and iSeqs image one side only
+            FlowCellType::MiniSeq => (1, 2, 3, 2, 12),
+            FlowCellType::ISeq => (1, 1, 1, 1, 16),
+            FlowCellType::HiSeqRapid => (2, 2, 2, 1, 16),
+            FlowCellType::HiSeqHighOutputV3 => (8, 2, 3, 1, 16),
+            FlowCellType::HiSeqHighOutputV4 => (8, 2, 2, 1, 16),
+            FlowCellType::HiSeq4000 => (8, 2, 2, 1, 28),
+            FlowCellType::HiSeqX => (8, 2, 2, 1, 24),
+            // Six cameras image each swath, three of them on each pair of lanes
+            FlowCellType::NextSeqMidOutput | FlowCellType::NextSeqHighOutput => (4, 2, 3, 3, 12),
+            FlowCellType::NovaSeqS1 => (2, 2, 2, 1, 78),
+            FlowCellType::NovaSeqS2 => (2, 2, 4, 1, 78),
+            FlowCellType::NovaSeqS4 => (4, 2, 4, 1, 88)
+        };
+        FlowCellLayout { lanes, sides, swaths, cameras, tiles_per_swath }
+    }
+
+    /// The model of sequencer this type of flow cell is used in
+    pub fn instrument(&self) -> InstrumentModel {
+        match *self {
+            FlowCellType::MiSeqNano | FlowCellType::MiSeqMicro | FlowCellType::MiSeqV2
+                | FlowCellType::MiSeqV3 => InstrumentModel::MiSeq,
+            FlowCellType::MiniSeq => InstrumentModel::MiniSeq,
+            FlowCellType::ISeq => InstrumentModel::ISeq,
+            FlowCellType::HiSeqRapid | FlowCellType::HiSeqHighOutputV3
+                | FlowCellType::HiSeqHighOutputV4 => InstrumentModel::HiSeq,
+            FlowCellType::HiSeq4000 => InstrumentModel::HiSeq4000,
+            FlowCellType::HiSeqX => InstrumentModel::HiSeqX,
+            FlowCellType::NextSeqMidOutput | FlowCellType::NextSeqHighOutput => InstrumentModel::NextSeq,
+            FlowCellType::NovaSeqS1 | FlowCellType::NovaSeqS2 | FlowCellType::NovaSeqS4 => InstrumentModel::NovaSeq
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_from_flow_cell_id() {
+        for &(flow_cell_id, flow_cell) in &[("000000000-D2K5T", FlowCellType::MiSeqNano),
+                                           ("000000000-G6LBN", FlowCellType::MiSeqMicro),
+                                           ("000000000-A7F4K", FlowCellType::MiSeqV2),
+                                           ("H7K2JBCXX", FlowCellType::HiSeqRapid),
+                                           ("C6PNVANXX", FlowCellType::HiSeqHighOutputV4),
+                                           ("HTN5KAFXY", FlowCellType::NextSeqMidOutput),
+                                           ("H2VGLBGXY", FlowCellType::NextSeqHighOutput),
+                                           ("HFWJ3DRXX", FlowCellType::NovaSeqS1),
+                                           ("HFWJ3DMXX", FlowCellType::NovaSeqS2)] {
+            assert_eq!(FlowCellType::from_flow_cell_id(flow_cell_id), Some(flow_cell), "{}", flow_cell_id);
+        }
+        for flow_cell_id in &["", "000000000-", "000000000-Z1234", "HFWJ3DZXX", "HFWJ3DSXXX", "HFWJéSXX"] {
+            assert_eq!(FlowCellType::from_flow_cell_id(flow_cell_id), None, "{}", flow_cell_id);
+        }
+    }
+
+    #[test]
+    fn test_layout() {
+        let seq_id = parse_sequence_identifier("@A00123:8:HFWJ3DSXX:1:1101:1000:1000 1:N:0:0").unwrap();
+        let flow_cell = seq_id.flow_cell_type().unwrap();
+        assert_eq!(flow_cell.instrument(), seq_id.instrument().unwrap());
+        assert_eq!(flow_cell.layout().lanes, 4);
+        assert_eq!(flow_cell.layout().tile_count(), 2816);
+        assert_eq!(FlowCellType::MiSeqNano.layout().tiles_per_lane(), 2);
+    }
+
+    #[test]
+    fn test_iseq_and_miniseq() {
+        let seq_id = parse_sequence_identifier("@FS10000123:14:BNT40323-1214:1:1116:22110:30515 1:N:0:1").unwrap();
+        assert_eq!(seq_id.instrument(), Some(InstrumentModel::ISeq));
+        assert_eq!(seq_id.flow_cell_type(), Some(FlowCellType::ISeq));
+        assert!(seq_id.flow_cell_layout().unwrap().contains(seq_id.tile_id()));
+        assert!(!seq_id.instrument().unwrap().tile_extent().unwrap().is_near_edge(seq_id.x, seq_id.y, 0));
+
+        let seq_id = parse_sequence_identifier("@MN00858:34:000H2JYHH:1:21212:12860:1047 1:N:0:1").unwrap();
+        assert_eq!(seq_id.instrument(), Some(InstrumentModel::MiniSeq));
+        assert_eq!(seq_id.flow_cell_type(), Some(FlowCellType::MiniSeq));
+        assert_eq!((seq_id.side, seq_id.swath, seq_id.camera, seq_id.tile), (2, 1, Some(2), 12));
+        assert!(seq_id.flow_cell_layout().unwrap().contains(seq_id.tile_id()));
+        assert_eq!(seq_id.to_string(), "@MN00858:34:000H2JYHH:1:21212:12860:1047 1:N:0:1");
+    }
+
+    #[test]
+    fn test_code() {
+        assert_eq!(FlowCellType::NovaSeqS4.code(), Some("DSXX"));
+        assert_eq!(FlowCellType::HiSeqRapid.code(), Some("ADXX"));
+        assert_eq!(FlowCellType::MiSeqV3.code(), None);
+    }
+
+    #[test]
+    fn test_novaseq_layouts() {
+        let tiles = |line: &str| {
+            let seq_id = parse_sequence_identifier(line).unwrap();
+            seq_id.flow_cell_layout().unwrap().contains(seq_id.tile_id())
+        };
+        assert!(tiles("@A00123:8:HFWJ3DRXX:2:2278:1000:1000 1:N:0:0"));
+        assert!(!tiles("@A00123:8:HFWJ3DRXX:2:2279:1000:1000 1:N:0:0"));
+        assert!(!tiles("@A00123:8:HFWJ3DRXX:2:2378:1000:1000 1:N:0:0"));
+        assert!(!tiles("@A00123:8:HFWJ3DRXX:3:1101:1000:1000 1:N:0:0"));
+        assert!(tiles("@A00123:8:HFWJ3DMXX:2:2478:1000:1000 1:N:0:0"));
+        assert!(tiles("@A00123:8:HFWJ3DSXX:4:1488:1000:1000 1:N:0:0"));
+        assert!(!tiles("@A00123:8:HFWJ3DSXX:4:1501:1000:1000 1:N:0:0"));
+    }
+}
@@ -0,0 +1,323 @@
+//! Streams of realistic synthetic sequence identifiers, for benchmarks and for testing tools that
+//! consume FASTQ files. Enabled by the `testing` feature.
+//!
+//! Unlike the values from `testing::Arbitrary`, generated identifiers look like they came from a
+//! real run: the sequencer and flow cell IDs are in the format of the chosen flow cell type, every
+//! read is on one of its tiles, and coordinates are within the instrument's tile extent. Mock
+//! FASTQ files can be generated with chosen cluster densities and optical duplicate rates, along
+//! with the truth about which reads are duplicates, for testing QC and duplicate-marking tools.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use fastq::Record;
+use flow_cell::FlowCellType;
+use geometry::{self, TileExtent};
+use instrument::InstrumentModel;
+use testing::Rng;
+use super::{IdString, Sample, SequenceIdentifier, TileId};
+
+const DIGITS: &[u8] = b"0123456789";
+const SERIAL_CHARACTERS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ0123456789";
+const BASES: &[u8] = b"ACGT";
+
+/// An endless stream of reads from one run on a flow cell of the chosen type. Reads are spread
+/// evenly over the flow cell's tiles, and are first reads that passed filtering, from sample 1.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::flow_cell::FlowCellType;
+/// use illumina_coordinates::generate::Generator;
+/// use illumina_coordinates::validate::validate;
+///
+/// fn main() {
+///     let generator = Generator::new(FlowCellType::NovaSeqS4, 42);
+///     for seq_id in generator.take(100) {
+///         assert_eq!(seq_id.flow_cell_type(), Some(FlowCellType::NovaSeqS4));
+///         assert!(validate(&seq_id).is_empty());
+///     }
+/// }
+/// ```
+pub struct Generator {
+    rng: Rng,
+    template: SequenceIdentifier,
+    tiles: Vec<TileId>,
+    extent: TileExtent
+}
+
+impl Generator {
+    /// Creates a generator for a run on the type of flow cell. The same seed always produces the
+    /// same run.
+    pub fn new(flow_cell: FlowCellType, seed: u64) -> Generator {
+        let mut rng = Rng::new(seed);
+        let model = flow_cell.instrument();
+        let sequencer_id = format!("{}{}", sequencer_prefix(model), rng.string(DIGITS, 5, 5));
+        let flow_cell_id = flow_cell_id(flow_cell, &mut rng);
+        let template = SequenceIdentifier {
+            sequencer_id: IdString::from(sequencer_id),
+            run_count: 1 + rng.below(999) as u16,
+            flow_cell_id: IdString::from(flow_cell_id),
+            lane: 1,
+            side: 1,
+            swath: 1,
+            camera: None,
+            tile: 1,
+            x: 0,
+            y: 0,
+            read: 1,
+            is_filtered: false,
+            control_number: 0,
+            sample: Sample::Number(1)
+        };
+        // The extents of HiSeq 3000, 4000 and X tiles aren't known, so other HiSeqs' are used
+        let extent = model.tile_extent().unwrap_or(geometry::HISEQ);
+        Generator { rng, template, tiles: flow_cell.layout().tiles(), extent }
+    }
+
+    /// The ID of the generated sequencer
+    pub fn sequencer_id(&self) -> &str {
+        &self.template.sequencer_id
+    }
+
+    /// The ID of the generated flow cell
+    pub fn flow_cell_id(&self) -> &str {
+        &self.template.flow_cell_id
+    }
+
+    /// The tiles reads are generated on
+    pub fn tiles(&self) -> &[TileId] {
+        &self.tiles
+    }
+
+    /// The range of coordinates reads are generated in
+    pub fn extent(&self) -> TileExtent {
+        self.extent
+    }
+
+    /// Generates a read on the tile
+    pub fn read_on(&mut self, tile: TileId) -> SequenceIdentifier {
+        let x = self.coordinate(self.extent.x_min, self.extent.x_max);
+        let y = self.coordinate(self.extent.y_min, self.extent.y_max);
+        SequenceIdentifier {
+            lane: tile.lane,
+            side: tile.side,
+            swath: tile.swath,
+            camera: tile.camera,
+            tile: tile.tile,
+            x,
+            y,
+            ..self.template.clone()
+        }
+    }
+
+    fn coordinate(&mut self, min: u16, max: u16) -> u16 {
+        min + self.rng.below(u64::from(max - min) + 1) as u16
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// How to lay out the clusters of a mock FASTQ file
+pub struct MockOptions {
+    /// The number of clusters on particular tiles
+    pub clusters_per_tile: HashMap<TileId, u32>,
+    /// The number of clusters on tiles that aren't in `clusters_per_tile`
+    pub default_clusters_per_tile: u32,
+    /// The number of bases in each read
+    pub read_length: usize,
+    /// The probability that a cluster has an optical duplicate
+    pub optical_duplicate_rate: f64,
+    /// The farthest an optical duplicate can be from its original in x and in y
+    pub optical_duplicate_distance: u16
+}
+
+impl Default for MockOptions {
+    fn default() -> MockOptions {
+        MockOptions {
+            clusters_per_tile: HashMap::new(),
+            default_clusters_per_tile: 100,
+            read_length: 50,
+            optical_duplicate_rate: 0.0,
+            optical_duplicate_distance: 100
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A read written to a mock FASTQ file, with the truth about where it came from
+pub struct MockRecord {
+    /// The identifier in the record's header
+    pub seq_id: SequenceIdentifier,
+    /// The record as it was written
+    pub record: Record,
+    /// For optical duplicates, the index of the record that was duplicated
+    pub duplicate_of: Option<usize>
+}
+
+impl Generator {
+    /// Generates the reads of a mock FASTQ file, tile by tile. Each optical duplicate has the same
+    /// sequence as its original and follows it directly.
+    pub fn mock_records(&mut self, options: &MockOptions) -> Vec<MockRecord> {
+        let mut records = vec![];
+        for i in 0..self.tiles.len() {
+            let tile = self.tiles[i];
+            let clusters = options.clusters_per_tile.get(&tile).cloned().unwrap_or(options.default_clusters_per_tile);
+            for _ in 0..clusters {
+                let seq_id = self.read_on(tile);
+                let sequence = self.rng.string(BASES, options.read_length as u64, options.read_length as u64);
+                let original = records.len();
+                let duplicate = if self.rng.chance(options.optical_duplicate_rate) {
+                    Some(self.nearby(&seq_id, options.optical_duplicate_distance))
+                } else {
+                    None
+                };
+                records.push(self.mock_record(seq_id, sequence.clone(), None));
+                if let Some(duplicate) = duplicate {
+                    records.push(self.mock_record(duplicate, sequence, Some(original)));
+                }
+            }
+        }
+        records
+    }
+
+    /// Writes a mock FASTQ file, returning the records that were written
+    pub fn write_mock_fastq<W: Write>(&mut self, writer: &mut W, options: &MockOptions) -> io::Result<Vec<MockRecord>> {
+        let records = self.mock_records(options);
+        for record in &records {
+            record.record.write_to(writer)?;
+        }
+        Ok(records)
+    }
+
+    fn mock_record(&self, seq_id: SequenceIdentifier, sequence: String, duplicate_of: Option<usize>) -> MockRecord {
+        let quality = "F".repeat(sequence.len());
+        let record = Record { header: seq_id.to_string(), sequence, quality };
+        MockRecord { seq_id, record, duplicate_of }
+    }
+
+    /// A read at a different position within `distance` of the read in x and in y
+    fn nearby(&mut self, seq_id: &SequenceIdentifier, distance: u16) -> SequenceIdentifier {
+        let distance = distance.max(1);
+        let offset = |rng: &mut Rng, value: u16, min: u16, max: u16, at_least: u16| {
+            let delta = at_least + rng.below(u64::from(distance - at_least) + 1) as u16;
+            // Go the other way if the duplicate would be off the tile
+            match (rng.chance(0.5), value.checked_add(delta), value.checked_sub(delta)) {
+                (true, Some(up), _) if up <= max => up,
+                (_, _, Some(down)) if down >= min => down,
+                (_, Some(up), _) => up.min(max),
+                _ => value
+            }
+        };
+        let x = offset(&mut self.rng, seq_id.x, self.extent.x_min, self.extent.x_max, 1);
+        let y = offset(&mut self.rng, seq_id.y, self.extent.y_min, self.extent.y_max, 0);
+        SequenceIdentifier { x, y, ..seq_id.clone() }
+    }
+}
+
+impl Iterator for Generator {
+    type Item = SequenceIdentifier;
+
+    fn next(&mut self) -> Option<SequenceIdentifier> {
+        let tile = self.tiles[self.rng.below(self.tiles.len() as u64) as usize];
+        Some(self.read_on(tile))
+    }
+}
+
+/// The letters instrument IDs of the model start with
+fn sequencer_prefix(model: InstrumentModel) -> &'static str {
+    match model {
+        InstrumentModel::MiSeq => "M",
+        InstrumentModel::MiniSeq => "MN",
+        InstrumentModel::ISeq => "FS",
+        InstrumentModel::HiSeq => "D",
+        InstrumentModel::HiSeq4000 => "K",
+        InstrumentModel::HiSeqX => "E",
+        InstrumentModel::NextSeq => "NB",
+        InstrumentModel::NextSeq2000 => "VH",
+        InstrumentModel::NovaSeq => "A",
+        InstrumentModel::NovaSeqX => "LH"
+    }
+}
+
+/// A random ID in the format of the type of flow cell
+fn flow_cell_id(flow_cell: FlowCellType, rng: &mut Rng) -> String {
+    let kit = match flow_cell {
+        FlowCellType::MiSeqV2 => Some('A'),
+        FlowCellType::MiSeqV3 => Some('B'),
+        FlowCellType::MiSeqNano => Some('D'),
+        FlowCellType::MiSeqMicro => Some('G'),
+        _ => None
+    };
+    if let Some(kit) = kit {
+        return format!("000000000-{}{}", kit, rng.string(SERIAL_CHARACTERS, 4, 4));
+    }
+    match flow_cell {
+        FlowCellType::MiniSeq => format!("000H{}", rng.string(SERIAL_CHARACTERS, 5, 5)),
+        FlowCellType::ISeq => format!("{}-{}", rng.string(SERIAL_CHARACTERS, 8, 8), rng.string(DIGITS, 4, 4)),
+        _ => format!("H{}{}", rng.string(SERIAL_CHARACTERS, 4, 4), flow_cell.code().unwrap_or("XXXX"))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fastq::FastqReader;
+    use parse_sequence_identifier;
+    use validate::validate;
+
+    const FLOW_CELLS: [FlowCellType; 16] = [
+        FlowCellType::MiSeqNano, FlowCellType::MiSeqMicro, FlowCellType::MiSeqV2, FlowCellType::MiSeqV3,
+        FlowCellType::MiniSeq, FlowCellType::ISeq, FlowCellType::HiSeqRapid, FlowCellType::HiSeqHighOutputV3,
+        FlowCellType::HiSeqHighOutputV4, FlowCellType::HiSeq4000, FlowCellType::HiSeqX,
+        FlowCellType::NextSeqMidOutput, FlowCellType::NextSeqHighOutput, FlowCellType::NovaSeqS1,
+        FlowCellType::NovaSeqS2, FlowCellType::NovaSeqS4
+    ];
+
+    #[test]
+    fn test_generate() {
+        for &flow_cell in &FLOW_CELLS {
+            let generator = Generator::new(flow_cell, 1);
+            assert_eq!(FlowCellType::from_flow_cell_id(generator.flow_cell_id()), Some(flow_cell));
+            assert_eq!(InstrumentModel::from_sequencer_id(generator.sequencer_id()), Some(flow_cell.instrument()));
+            for seq_id in generator.take(200) {
+                assert!(validate(&seq_id).is_empty(), "{}", seq_id);
+                assert_eq!(parse_sequence_identifier(&seq_id.to_string()).unwrap(), seq_id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mock_records() {
+        let mut generator = Generator::new(FlowCellType::MiSeqNano, 3);
+        let tiles = generator.tiles().to_vec();
+        let mut options = MockOptions { optical_duplicate_rate: 0.5, default_clusters_per_tile: 1000, ..MockOptions::default() };
+        options.clusters_per_tile.insert(tiles[1], 10);
+        let mut output = vec![];
+        let records = generator.write_mock_fastq(&mut output, &options).unwrap();
+        let originals = records.iter().filter(|record| record.duplicate_of.is_none()).count();
+        assert_eq!(originals, 1010);
+        assert!(records.len() > 1300 && records.len() < 1700);
+        for (i, record) in records.iter().enumerate() {
+            assert!(validate(&record.seq_id).is_empty());
+            assert_eq!(record.record.sequence.len(), 50);
+            if let Some(original) = record.duplicate_of {
+                let original = &records[original];
+                assert_eq!(original.record.sequence, record.record.sequence, "{}", i);
+                assert_eq!(original.seq_id.tile_id(), record.seq_id.tile_id());
+                assert!(original.seq_id.x != record.seq_id.x);
+                assert!((i32::from(original.seq_id.x) - i32::from(record.seq_id.x)).abs() <= 100);
+                assert!((i32::from(original.seq_id.y) - i32::from(record.seq_id.y)).abs() <= 100);
+            }
+        }
+        let written: Vec<Record> = FastqReader::new(&output[..]).map(|record| record.unwrap()).collect();
+        assert_eq!(written, records.into_iter().map(|record| record.record).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_seeds_are_reproducible() {
+        let first: Vec<_> = Generator::new(FlowCellType::MiSeqV3, 7).take(10).collect();
+        assert_eq!(Generator::new(FlowCellType::MiSeqV3, 7).take(10).collect::<Vec<_>>(), first);
+        assert!(Generator::new(FlowCellType::MiSeqV3, 8).take(10).collect::<Vec<_>>() != first);
+    }
+}
@@ -0,0 +1,388 @@
+//! Physical layout of Illumina flow cells.
+//!
+//! Coordinates in sequence identifiers are given in arbitrary units that differ between
+//! platforms. The extents below are the ranges observed in practice and are approximate.
+//!
+//! Coordinates can be converted to microns with the size of a pixel in the tile images. The pixel
+//! sizes below are approximate too, so distances in microns are only good to about 10%.
+
+use super::TileId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The range of x- and y-coordinates that clusters within a single tile can have
+pub struct TileExtent {
+    /// The smallest x-coordinate in a tile
+    pub x_min: u16,
+    /// The largest x-coordinate in a tile
+    pub x_max: u16,
+    /// The smallest y-coordinate in a tile
+    pub y_min: u16,
+    /// The largest y-coordinate in a tile
+    pub y_max: u16,
+}
+
+/// Tile extent of MiSeq flow cells
+pub const MISEQ: TileExtent = TileExtent { x_min: 1000, x_max: 30000, y_min: 1000, y_max: 30000 };
+
+/// Tile extent of HiSeq flow cells. Tiles are much longer than they are wide, and the
+/// y-coordinates of the longest tiles do not fit in the range this crate can represent
+pub const HISEQ: TileExtent = TileExtent { x_min: 1000, x_max: 21000, y_min: 1000, y_max: 65535 };
+
+/// Tile extent of NextSeq flow cells
+pub const NEXTSEQ: TileExtent = TileExtent { x_min: 1000, x_max: 26000, y_min: 1000, y_max: 26000 };
+
+/// Tile extent of MiniSeq flow cells, which are imaged like NextSeq flow cells
+pub const MINISEQ: TileExtent = NEXTSEQ;
+
+/// Tile extent of iSeq flow cells
+pub const ISEQ: TileExtent = TileExtent { x_min: 1000, x_max: 32000, y_min: 1000, y_max: 32000 };
+
+/// Tile extent of NovaSeq flow cells
+pub const NOVASEQ: TileExtent = TileExtent { x_min: 1000, x_max: 32000, y_min: 1000, y_max: 37000 };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The arrangement of tiles on a flow cell. Every lane has the same tiles: each side of a lane is
+/// divided into swaths, and each swath into tiles. On the NextSeq, each swath is imaged by
+/// several cameras, and each camera's segment of the swath is divided into tiles.
+pub struct FlowCellLayout {
+    /// The number of lanes
+    pub lanes: u8,
+    /// The number of imaged sides, either 1 or 2
+    pub sides: u8,
+    /// The number of swaths on each side of a lane
+    pub swaths: u8,
+    /// The number of camera segments in each swath of a lane. This is 1 except on the NextSeq and
+    /// MiniSeq, where cameras are numbered consecutively across each pair of lanes: lanes 1 and 2 are
+    /// imaged by cameras 1 to 3, and lanes 3 and 4 by cameras 4 to 6.
+    pub cameras: u8,
+    /// The number of tiles in each swath, or in each camera segment of a swath
+    pub tiles_per_swath: u8
+}
+
+impl FlowCellLayout {
+    /// The number of tiles in each lane
+    pub fn tiles_per_lane(&self) -> u32 {
+        u32::from(self.sides) * u32::from(self.swaths) * u32::from(self.cameras) * u32::from(self.tiles_per_swath)
+    }
+
+    /// The number of tiles on the whole flow cell
+    pub fn tile_count(&self) -> u32 {
+        u32::from(self.lanes) * self.tiles_per_lane()
+    }
+
+    /// Every tile on the flow cell, in order of lane, side, swath, camera and tile
+    pub fn tiles(&self) -> Vec<TileId> {
+        let mut tiles = Vec::with_capacity(self.tile_count() as usize);
+        for lane in 1..=self.lanes {
+            for side in 1..=self.sides {
+                for swath in 1..=self.swaths {
+                    for segment in 0..self.cameras {
+                        // Cameras are numbered across each pair of lanes
+                        let camera = if self.cameras == 1 {
+                            None
+                        } else {
+                            Some((lane - 1) / 2 * self.cameras + segment + 1)
+                        };
+                        for tile in 1..=self.tiles_per_swath {
+                            tiles.push(TileId { lane, side, swath, camera, tile });
+                        }
+                    }
+                }
+            }
+        }
+        tiles
+    }
+
+    /// Whether the tile is one of the tiles on the flow cell. Lanes, sides, swaths, cameras and
+    /// tiles are all numbered from 1.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::flow_cell::FlowCellType;
+    ///
+    /// fn main() {
+    ///     let layout = FlowCellType::NovaSeqS4.layout();
+    ///     let line = "@A00123:8:HFWJ3DSXX:4:2488:1000:1000 1:N:0:0";
+    ///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+    ///     assert!(layout.contains(seq_id.tile_id()));
+    ///     let line = "@A00123:8:HFWJ3DSXX:4:2489:1000:1000 1:N:0:0";
+    ///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+    ///     assert!(!layout.contains(seq_id.tile_id()));
+    /// }
+    /// ```
+    pub fn contains(&self, tile: TileId) -> bool {
+        let in_range = |value: u8, count: u8| value >= 1 && value <= count;
+        let camera_matches = match tile.camera {
+            Some(camera) => self.cameras > 1 && camera >= 1
+                && (camera - 1) / self.cameras == (tile.lane.max(1) - 1) / 2,
+            None => self.cameras == 1
+        };
+        in_range(tile.lane, self.lanes)
+            && in_range(tile.side, self.sides)
+            && in_range(tile.swath, self.swaths)
+            && in_range(tile.tile, self.tiles_per_swath)
+            && camera_matches
+    }
+}
+
+/// Approximate size of a pixel in MiSeq tile images, in microns
+pub const MISEQ_MICRONS_PER_PIXEL: f64 = 0.37;
+
+/// Approximate size of a pixel in HiSeq 1000, 1500, 2000 and 2500 tile images, in microns
+pub const HISEQ_MICRONS_PER_PIXEL: f64 = 0.375;
+
+/// Approximate size of a pixel in NextSeq 500 and 550 and MiniSeq tile images, in microns
+pub const NEXTSEQ_MICRONS_PER_PIXEL: f64 = 0.6;
+
+/// Approximate size of a pixel in NovaSeq 6000 tile images, in microns
+pub const NOVASEQ_MICRONS_PER_PIXEL: f64 = 0.325;
+
+/// Converts a coordinate from a sequence identifier to a position in pixels in the tile image.
+/// bcl2fastq and BCL Convert write the cluster's position in pixels multiplied by 10, plus 1000.
+pub fn to_pixels(coordinate: u16) -> f64 {
+    (f64::from(coordinate) - 1000.0) / 10.0
+}
+
+/// Converts a coordinate from a sequence identifier to microns from the edge of the tile image
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::geometry::{to_microns, MISEQ_MICRONS_PER_PIXEL};
+///
+/// fn main() {
+///     assert_eq!(to_microns(1000, MISEQ_MICRONS_PER_PIXEL), 0.0);
+///     assert!((to_microns(2000, MISEQ_MICRONS_PER_PIXEL) - 37.0).abs() < 1e-9);
+/// }
+/// ```
+pub fn to_microns(coordinate: u16, microns_per_pixel: f64) -> f64 {
+    to_pixels(coordinate) * microns_per_pixel
+}
+
+/// The distance in microns between two clusters on the same tile
+pub fn distance_microns(a: (u16, u16), b: (u16, u16), microns_per_pixel: f64) -> f64 {
+    let dx = (f64::from(a.0) - f64::from(b.0)) / 10.0;
+    let dy = (f64::from(a.1) - f64::from(b.1)) / 10.0;
+    (dx * dx + dy * dy).sqrt() * microns_per_pixel
+}
+
+/// The tiles that share an edge with a tile: the tiles before and after it along its swath, and
+/// the tiles beside it in the neighbouring swaths on the same side of the lane. On the NextSeq
+/// and MiniSeq, each camera images the next segment of the swath, so the last tile of one camera
+/// borders the first tile of the next. Tiles on the other side of the flow cell, or in other
+/// lanes, never border each other. A tile that isn't on the flow cell has no neighbours.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::TileId;
+/// use illumina_coordinates::flow_cell::FlowCellType;
+/// use illumina_coordinates::geometry::adjacent_tiles;
+///
+/// fn main() {
+///     let layout = FlowCellType::HiSeqHighOutputV4.layout();
+///     let tile = TileId { lane: 3, side: 1, swath: 1, camera: None, tile: 16 };
+///     let neighbours: Vec<u32> = adjacent_tiles(&layout, tile).iter().map(|tile| tile.tile_field()).collect();
+///     assert_eq!(neighbours, vec![1115, 1216]);
+/// }
+/// ```
+pub fn adjacent_tiles(layout: &FlowCellLayout, tile: TileId) -> Vec<TileId> {
+    if !layout.contains(tile) {
+        return vec![];
+    }
+    let mut neighbours = vec![];
+    let (first_camera, position) = position_along_swath(layout, tile);
+    let length = u16::from(layout.cameras) * u16::from(layout.tiles_per_swath);
+    let at = |swath: u8, position: u16| {
+        let segment = (position / u16::from(layout.tiles_per_swath)) as u8;
+        TileId {
+            swath,
+            camera: first_camera.map(|first| first + segment),
+            tile: (position % u16::from(layout.tiles_per_swath)) as u8 + 1,
+            ..tile
+        }
+    };
+    if position > 0 {
+        neighbours.push(at(tile.swath, position - 1));
+    }
+    if position + 1 < length {
+        neighbours.push(at(tile.swath, position + 1));
+    }
+    if tile.swath > 1 {
+        neighbours.push(at(tile.swath - 1, position));
+    }
+    if tile.swath < layout.swaths {
+        neighbours.push(at(tile.swath + 1, position));
+    }
+    neighbours
+}
+
+/// The first camera of the tile's lane, for sequencers that number tiles by camera, and the
+/// position of the tile along its swath, counting from 0 across camera segments
+fn position_along_swath(layout: &FlowCellLayout, tile: TileId) -> (Option<u8>, u16) {
+    // Cameras are numbered across each pair of lanes, so their first camera depends on the lane
+    let first_camera = tile.camera.map(|_| (tile.lane - 1) / 2 * layout.cameras + 1);
+    let segment = match (tile.camera, first_camera) {
+        (Some(camera), Some(first)) => camera - first,
+        _ => 0
+    };
+    (first_camera, u16::from(segment) * u16::from(layout.tiles_per_swath) + u16::from(tile.tile) - 1)
+}
+
+/// Converts coordinates within a tile to coordinates on its side of the lane, placing the swaths
+/// side by side along x and the tiles of each swath one after another along y, in the same units
+/// as the coordinates in sequence identifiers. Neighbouring tiles overlap slightly when they're
+/// imaged, so the same cluster imaged on two tiles ends up close to, but not exactly at, the same
+/// position. Returns `None` for tiles that aren't on the flow cell, or if the extent's minimums
+/// are above its maximums.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::TileId;
+/// use illumina_coordinates::geometry::{lane_coordinates, FlowCellLayout, MISEQ};
+///
+/// fn main() {
+///     let layout = FlowCellLayout { lanes: 1, sides: 2, swaths: 2, cameras: 1, tiles_per_swath: 14 };
+///     let tile = TileId { lane: 1, side: 1, swath: 2, camera: None, tile: 3 };
+///     assert_eq!(lane_coordinates(&layout, MISEQ, tile, 1000, 1500), Some((29001, 58502)));
+/// }
+/// ```
+pub fn lane_coordinates(layout: &FlowCellLayout, extent: TileExtent, tile: TileId, x: u16, y: u16)
+        -> Option<(u32, u32)> {
+    if !layout.contains(tile) {
+        return None;
+    }
+    let (_, position) = position_along_swath(layout, tile);
+    let width = u32::from(extent.x_max.checked_sub(extent.x_min)?) + 1;
+    let height = u32::from(extent.y_max.checked_sub(extent.y_min)?) + 1;
+    let x = u32::from(x.max(extent.x_min).min(extent.x_max) - extent.x_min);
+    let y = u32::from(y.max(extent.y_min).min(extent.y_max) - extent.y_min);
+    Some((u32::from(tile.swath - 1) * width + x, u32::from(position) * height + y))
+}
+
+impl TileExtent {
+    /// Whether the point lies within `margin` units of any edge of the tile. Points outside
+    /// the tile entirely are also considered to be near the edge.
+    pub fn is_near_edge(&self, x: u16, y: u16, margin: u16) -> bool {
+        x < self.x_min.saturating_add(margin)
+            || x > self.x_max.saturating_sub(margin)
+            || y < self.y_min.saturating_add(margin)
+            || y > self.y_max.saturating_sub(margin)
+    }
+
+    /// The approximate area of the tile in square millimetres
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::geometry::{MISEQ, MISEQ_MICRONS_PER_PIXEL};
+    ///
+    /// fn main() {
+    ///     let area = MISEQ.area_mm2(MISEQ_MICRONS_PER_PIXEL);
+    ///     assert!(area > 1.1 && area < 1.2);
+    /// }
+    /// ```
+    pub fn area_mm2(&self, microns_per_pixel: f64) -> f64 {
+        let width = to_microns(self.x_max, microns_per_pixel) - to_microns(self.x_min, microns_per_pixel);
+        let height = to_microns(self.y_max, microns_per_pixel) - to_microns(self.y_min, microns_per_pixel);
+        width * height / 1e6
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_near_edge() {
+        assert!(MISEQ.is_near_edge(1200, 15000, 500));
+        assert!(MISEQ.is_near_edge(15000, 29600, 500));
+        assert!(!MISEQ.is_near_edge(15000, 15000, 500));
+        assert!(!MISEQ.is_near_edge(1500, 29500, 500));
+    }
+
+    #[test]
+    fn test_contains() {
+        let nextseq = FlowCellLayout { lanes: 4, sides: 2, swaths: 3, cameras: 3, tiles_per_swath: 12 };
+        let tile = |lane, side, swath, camera, tile| TileId { lane, side, swath, camera, tile };
+        assert!(nextseq.contains(tile(1, 2, 3, Some(3), 12)));
+        assert!(nextseq.contains(tile(4, 1, 1, Some(6), 1)));
+        assert!(!nextseq.contains(tile(4, 1, 1, Some(3), 1)));
+        assert!(!nextseq.contains(tile(1, 1, 1, None, 1)));
+        assert!(!nextseq.contains(tile(5, 1, 1, Some(1), 1)));
+        let miseq = FlowCellLayout { lanes: 1, sides: 2, swaths: 1, cameras: 1, tiles_per_swath: 19 };
+        assert!(miseq.contains(tile(1, 2, 1, None, 19)));
+        assert!(!miseq.contains(tile(1, 2, 1, None, 0)));
+        assert!(!miseq.contains(tile(1, 2, 2, None, 1)));
+        assert!(!miseq.contains(tile(1, 1, 1, Some(1), 1)));
+    }
+
+    #[test]
+    fn test_tiles() {
+        let nextseq = FlowCellLayout { lanes: 4, sides: 2, swaths: 3, cameras: 3, tiles_per_swath: 12 };
+        let tiles = nextseq.tiles();
+        assert_eq!(tiles.len() as u32, nextseq.tile_count());
+        assert!(tiles.iter().all(|&tile| nextseq.contains(tile)));
+        assert_eq!(tiles.last(), Some(&TileId { lane: 4, side: 2, swath: 3, camera: Some(6), tile: 12 }));
+        let miseq = FlowCellLayout { lanes: 1, sides: 2, swaths: 1, cameras: 1, tiles_per_swath: 19 };
+        assert_eq!(miseq.tiles()[19], TileId { lane: 1, side: 2, swath: 1, camera: None, tile: 1 });
+    }
+
+    #[test]
+    fn test_adjacent_tiles() {
+        let tile = |lane, side, swath, camera, tile| TileId { lane, side, swath, camera, tile };
+        let miseq = FlowCellLayout { lanes: 1, sides: 2, swaths: 1, cameras: 1, tiles_per_swath: 19 };
+        assert_eq!(adjacent_tiles(&miseq, tile(1, 2, 1, None, 1)), vec![tile(1, 2, 1, None, 2)]);
+        assert_eq!(adjacent_tiles(&miseq, tile(1, 1, 1, None, 8)), vec![tile(1, 1, 1, None, 7), tile(1, 1, 1, None, 9)]);
+        assert!(adjacent_tiles(&miseq, tile(1, 1, 1, None, 20)).is_empty());
+        let nextseq = FlowCellLayout { lanes: 4, sides: 2, swaths: 3, cameras: 3, tiles_per_swath: 12 };
+        assert_eq!(adjacent_tiles(&nextseq, tile(3, 1, 2, Some(4), 12)),
+                   vec![tile(3, 1, 2, Some(4), 11), tile(3, 1, 2, Some(5), 1), tile(3, 1, 1, Some(4), 12),
+                        tile(3, 1, 3, Some(4), 12)]);
+        assert_eq!(adjacent_tiles(&nextseq, tile(1, 1, 1, Some(3), 12)),
+                   vec![tile(1, 1, 1, Some(3), 11), tile(1, 1, 2, Some(3), 12)]);
+    }
+
+    #[test]
+    fn test_lane_coordinates() {
+        let nextseq = FlowCellLayout { lanes: 4, sides: 2, swaths: 3, cameras: 3, tiles_per_swath: 12 };
+        let extent = TileExtent { x_min: 1000, x_max: 1999, y_min: 1000, y_max: 1099 };
+        let tile = TileId { lane: 3, side: 1, swath: 1, camera: Some(5), tile: 2 };
+        assert_eq!(lane_coordinates(&nextseq, extent, tile, 1000, 1000), Some((0, 1300)));
+        let tile = TileId { lane: 3, side: 1, swath: 3, camera: Some(4), tile: 12 };
+        assert_eq!(lane_coordinates(&nextseq, extent, tile, 5000, 0), Some((2999, 1100)));
+        let tile = TileId { lane: 3, side: 1, swath: 3, camera: Some(1), tile: 12 };
+        assert_eq!(lane_coordinates(&nextseq, extent, tile, 1000, 1000), None);
+        let tile = TileId { lane: 3, side: 1, swath: 1, camera: Some(5), tile: 2 };
+        let inverted = TileExtent { x_min: 2000, x_max: 1000, ..extent };
+        assert_eq!(lane_coordinates(&nextseq, inverted, tile, 1500, 1000), None);
+        let inverted = TileExtent { y_min: 2000, y_max: 1000, ..extent };
+        assert_eq!(lane_coordinates(&nextseq, inverted, tile, 1500, 1000), None);
+    }
+
+    #[test]
+    fn test_microns() {
+        assert_eq!(to_pixels(1000), 0.0);
+        assert_eq!(to_pixels(1005), 0.5);
+        assert_eq!(to_pixels(0), -100.0);
+        assert!((to_microns(11000, NOVASEQ_MICRONS_PER_PIXEL) - 325.0).abs() < 1e-9);
+        assert!((distance_microns((1000, 1000), (1030, 1040), 1.0) - 5.0).abs() < 1e-9);
+        let extent = TileExtent { x_min: 1000, x_max: 11000, y_min: 1000, y_max: 21000 };
+        assert!((extent.area_mm2(1.0) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_is_near_edge_outside_tile() {
+        assert!(MISEQ.is_near_edge(500, 15000, 0));
+        assert!(MISEQ.is_near_edge(15000, 31000, 0));
+        assert!(!MISEQ.is_near_edge(1000, 30000, 0));
+    }
+}
@@ -0,0 +1,206 @@
+//! Reads gzip-compressed files, including files of several concatenated gzip members such as BGZF.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use bgzf::{is_gzip, FEXTRA, GZIP_MAGIC};
+use deflate::{inflate_block, BitReader, Crc32, WINDOW_SIZE};
+
+const FHCRC: u8 = 2;
+const FNAME: u8 = 8;
+const FCOMMENT: u8 = 16;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Decompresses a gzip stream as it's read
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use std::io::{BufRead, Write};
+/// use illumina_coordinates::bgzf::BgzfWriter;
+/// use illumina_coordinates::gzip::GzipReader;
+///
+/// fn main() {
+///     let mut writer = BgzfWriter::new(vec![]);
+///     writer.write_all(b"first\nsecond\n").unwrap();
+///     let compressed = writer.finish().unwrap();
+///
+///     let lines: Vec<String> = GzipReader::new(&compressed[..]).lines().map(|line| line.unwrap()).collect();
+///     assert_eq!(lines, vec!["first", "second"]);
+/// }
+/// ```
+pub struct GzipReader<R: BufRead> {
+    reader: BitReader<R>,
+    out: Vec<u8>,
+    position: usize,
+    in_member: bool,
+    crc: Crc32,
+    size: u32
+}
+
+impl<R: BufRead> GzipReader<R> {
+    /// Creates a reader
+    pub fn new(inner: R) -> GzipReader<R> {
+        GzipReader {
+            reader: BitReader::new(inner),
+            out: vec![],
+            position: 0,
+            in_member: false,
+            crc: Crc32::new(),
+            size: 0
+        }
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        self.reader.aligned_byte()
+    }
+
+    fn read_header(&mut self) -> io::Result<()> {
+        let mut header = [0u8; 10];
+        for byte in &mut header {
+            *byte = self.byte()?;
+        }
+        if header[0..2] != GZIP_MAGIC || header[2] != 8 {
+            return Err(invalid("not a gzip member"));
+        }
+        let flags = header[3];
+        if flags & FEXTRA != 0 {
+            let length = u16::from(self.byte()?) | u16::from(self.byte()?) << 8;
+            for _ in 0..length {
+                self.byte()?;
+            }
+        }
+        for &flag in &[FNAME, FCOMMENT] {
+            if flags & flag != 0 {
+                while self.byte()? != 0 {}
+            }
+        }
+        if flags & FHCRC != 0 {
+            self.byte()?;
+            self.byte()?;
+        }
+        Ok(())
+    }
+
+    fn read_trailer(&mut self) -> io::Result<()> {
+        let mut trailer = [0u32; 8];
+        for byte in &mut trailer {
+            *byte = u32::from(self.byte()?);
+        }
+        let crc = trailer[0] | trailer[1] << 8 | trailer[2] << 16 | trailer[3] << 24;
+        let size = trailer[4] | trailer[5] << 8 | trailer[6] << 16 | trailer[7] << 24;
+        if crc != self.crc.sum() {
+            return Err(invalid("gzip member failed its CRC check"));
+        }
+        if size != self.size {
+            return Err(invalid("gzip member has the wrong uncompressed size"));
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> BufRead for GzipReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.position == self.out.len() {
+            // Only the last window of output is needed to resolve back-references
+            if self.out.len() > 2 * WINDOW_SIZE {
+                let excess = self.out.len() - WINDOW_SIZE;
+                self.out.drain(..excess);
+                self.position = self.out.len();
+            }
+            if !self.in_member {
+                if self.reader.at_end()? {
+                    break;
+                }
+                self.read_header()?;
+                self.in_member = true;
+                self.crc = Crc32::new();
+                self.size = 0;
+            }
+            let start = self.out.len();
+            let is_final = inflate_block(&mut self.reader, &mut self.out)?;
+            self.crc.update(&self.out[start..]);
+            self.size = self.size.wrapping_add((self.out.len() - start) as u32);
+            if is_final {
+                self.read_trailer()?;
+                self.in_member = false;
+            }
+        }
+        Ok(&self.out[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.out.len());
+    }
+}
+
+impl<R: BufRead> Read for GzipReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let available = self.fill_buf()?;
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+/// Opens a file for reading, decompressing it if it's gzipped
+pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn BufRead>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let compressed = is_gzip(reader.fill_buf()?);
+    if compressed {
+        Ok(Box::new(GzipReader::new(reader)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Whether a file is gzipped, judging by its first bytes
+pub fn is_gzipped<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let mut header = vec![];
+    File::open(path)?.take(2).read_to_end(&mut header)?;
+    Ok(is_gzip(&header))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use bgzf::BgzfWriter;
+
+    #[test]
+    fn test_read_members() {
+        // A member with a file name and a single fixed-code block, followed by a BGZF file
+        let mut data = vec![0x1f, 0x8b, 8, FNAME, 0, 0, 0, 0, 0, 0xff];
+        data.extend_from_slice(b"hello.txt\0");
+        data.extend_from_slice(&[0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x27, 0x01]);
+        data.extend_from_slice(&::deflate::crc32(b"hello hello hello hello").to_le_bytes());
+        data.extend_from_slice(&23u32.to_le_bytes());
+        let mut writer = BgzfWriter::new(vec![]);
+        let long: Vec<u8> = (0..100_000u32).map(|i| b"ACGT"[(i.wrapping_mul(2_654_435_761) >> 30) as usize]).collect();
+        writer.write_all(&long).unwrap();
+        data.extend(writer.finish().unwrap());
+
+        let mut out = vec![];
+        GzipReader::new(&data[..]).read_to_end(&mut out).unwrap();
+        assert_eq!(&out[..23], b"hello hello hello hello");
+        assert_eq!(out[23..], long[..]);
+    }
+
+    #[test]
+    fn test_corrupt_member() {
+        let mut data = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff, 0x03, 0x00];
+        data.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        let mut out = vec![];
+        assert!(GzipReader::new(&data[..]).read_to_end(&mut out).is_err());
+        assert!(GzipReader::new(&data[..4]).read_to_end(&mut out).is_err());
+    }
+}
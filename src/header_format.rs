@@ -0,0 +1,280 @@
+//! Pluggable formats for FASTQ headers.
+//!
+//! Every format parses headers into a `SequenceIdentifier`, so the rest of the crate works the
+//! same whichever format a file uses. Files whose headers were rewritten by in-house tools can
+//! be supported by implementing `HeaderFormat` for the rewritten format.
+//!
+//! Identifiers can also be written back out in each format with `HeaderStyle`, for tools that
+//! only understand one of them.
+
+use std::convert::TryFrom;
+use options::ParseOptions;
+use read_name;
+use super::{parse_number, parse_read_name_borrowed, parse_sequence_identifier_with, parse_tile_field, IlluminaError, Sample, SequenceIdentifier};
+
+/// A format of FASTQ header
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::{IlluminaError, SequenceIdentifier};
+/// use illumina_coordinates::header_format::{detect_format, HeaderFormat, IlluminaFormat};
+///
+/// /// Headers that had a run name added at the start, like "@run42|M03745:11:..."
+/// struct RunPrefixed;
+///
+/// impl HeaderFormat for RunPrefixed {
+///     fn parse(&self, header: &str) -> Result<SequenceIdentifier, IlluminaError> {
+///         let start = header.find('|').ok_or(IlluminaError::SplitError)?;
+///         IlluminaFormat::default().parse(&header[start + 1..])
+///     }
+/// }
+///
+/// fn main() {
+///     let formats: [&dyn HeaderFormat; 2] = [&IlluminaFormat::default(), &RunPrefixed];
+///     let header = "@run42|M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let format = detect_format(header, &formats).unwrap();
+///     assert_eq!(format.parse(header).unwrap().x, 4127);
+/// }
+/// ```
+pub trait HeaderFormat {
+    /// Parses a header, which may include the leading `@` and trailing whitespace
+    fn parse(&self, header: &str) -> Result<SequenceIdentifier, IlluminaError>;
+
+    /// Whether the header is in this format. By default, this is whether it parses.
+    fn detect(&self, header: &str) -> bool {
+        self.parse(header).is_ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// The format Illumina's software writes, as parsed by `parse_sequence_identifier_with`
+pub struct IlluminaFormat {
+    /// The rules to relax when parsing
+    pub options: ParseOptions
+}
+
+impl IlluminaFormat {
+    /// Creates the format with the given options
+    pub fn new(options: ParseOptions) -> IlluminaFormat {
+        IlluminaFormat { options }
+    }
+}
+
+impl HeaderFormat for IlluminaFormat {
+    fn parse(&self, header: &str) -> Result<SequenceIdentifier, IlluminaError> {
+        parse_sequence_identifier_with(header, &self.options)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Headers that only have the read name, the half of the identifier before the space, as written
+/// by tools that convert from BAM or download from the SRA. A `/1` or `/2` suffix gives the read
+/// number, which is 1 otherwise. Reads are taken to be unfiltered, non-control reads of sample 0.
+pub struct ReadNameFormat;
+
+impl HeaderFormat for ReadNameFormat {
+    fn parse(&self, header: &str) -> Result<SequenceIdentifier, IlluminaError> {
+        let trimmed = header.trim();
+        let trimmed = trimmed.strip_prefix('@').unwrap_or(trimmed);
+        if trimmed.contains(char::is_whitespace) {
+            return Err(IlluminaError::SplitError);
+        }
+        let name = read_name::normalize(trimmed);
+        let mut seq_id = parse_read_name_borrowed(name)?.into_owned();
+        if trimmed.ends_with("/2") {
+            seq_id.read = 2;
+        }
+        Ok(seq_id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Headers written by the Illumina pipeline before CASAVA 1.8, like
+/// `@HWUSI-EAS100R:6:73:941:1973#0/1`: the sequencer ID, lane, tile number, coordinates, then the
+/// sample number or index sequence after a `#` and the read number after a `/`. These headers
+/// don't record the run count or the flow cell ID, so reads are given the ones in the format.
+/// Reads are taken to be unfiltered, non-control reads.
+///
+/// Genome Analyzer tiles are numbered along the lane from 1 to 120, so tile numbers below 1000
+/// are kept as they are, with side and swath 0. Larger numbers, written by HiSeqs running the
+/// older pipeline, are split into the side, swath and tile like CASAVA 1.8 tile fields: 2108 is
+/// side 2, swath 1, tile 8.
+pub struct LegacyFormat {
+    /// The run count to give reads
+    pub run_count: u16,
+    /// The flow cell ID to give reads
+    pub flow_cell_id: String
+}
+
+impl LegacyFormat {
+    /// Creates the format, giving reads the run count and flow cell ID
+    pub fn new<S: Into<String>>(run_count: u16, flow_cell_id: S) -> LegacyFormat {
+        LegacyFormat { run_count, flow_cell_id: flow_cell_id.into() }
+    }
+}
+
+impl HeaderFormat for LegacyFormat {
+    fn parse(&self, header: &str) -> Result<SequenceIdentifier, IlluminaError> {
+        let trimmed = header.trim();
+        let trimmed = trimmed.strip_prefix('@').unwrap_or(trimmed);
+        let hash = trimmed.find('#').ok_or(IlluminaError::SplitError)?;
+        let (name, suffix) = (&trimmed[..hash], &trimmed[hash + 1..]);
+        let (sample, read) = match suffix.find('/') {
+            Some(slash) => (&suffix[..slash], parse_number(&suffix[slash + 1..])?),
+            None => (suffix, 1)
+        };
+        let fields: Vec<&str> = name.split(':').collect();
+        if fields.len() != 5 || sample.is_empty() || sample.contains(char::is_whitespace) {
+            return Err(IlluminaError::SplitError);
+        }
+        let tile_number: u32 = parse_number(fields[2])?;
+        let (side, swath, camera, tile) = if tile_number < 1000 {
+            (0, 0, None, u8::try_from(tile_number).map_err(|_| IlluminaError::ParseError)?)
+        } else {
+            parse_tile_field(&tile_number.to_string(), fields[0])?
+        };
+        Ok(SequenceIdentifier {
+            sequencer_id: fields[0].into(),
+            run_count: self.run_count,
+            flow_cell_id: self.flow_cell_id.as_str().into(),
+            lane: parse_number(fields[1])?,
+            side,
+            swath,
+            camera,
+            tile,
+            x: parse_number(fields[3])?,
+            y: parse_number(fields[4])?,
+            read,
+            is_filtered: false,
+            control_number: 0,
+            sample: Sample::from_field(sample)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A format to write headers in
+pub enum HeaderStyle {
+    /// The format of CASAVA 1.8 and later, as written by `Display`
+    Casava,
+    /// The format from before CASAVA 1.8, as parsed by `LegacyFormat`. The run count, flow cell
+    /// ID, filter flag and control number are left out.
+    Legacy,
+    /// The read name with a `/1` or `/2` suffix for the read number, without the comment half,
+    /// as parsed by `ReadNameFormat`. Only the read number is kept from the comment.
+    ReadName
+}
+
+impl HeaderStyle {
+    /// Writes the identifier as a header in this style, including the leading `@`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::header_format::HeaderStyle;
+    ///
+    /// fn main() {
+    ///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 2:N:0:ACGT";
+    ///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+    ///     assert_eq!(HeaderStyle::Legacy.header(&seq_id), "@M03745:1:2108:4127:8949#ACGT/2");
+    ///     assert_eq!(HeaderStyle::ReadName.header(&seq_id), "@M03745:11:000000000-B54L5:1:2108:4127:8949/2");
+    /// }
+    /// ```
+    pub fn header(&self, seq_id: &SequenceIdentifier) -> String {
+        match *self {
+            HeaderStyle::Casava => seq_id.to_string(),
+            HeaderStyle::Legacy => format!("@{}:{}:{}:{}:{}#{}/{}", seq_id.sequencer_id, seq_id.lane, seq_id.tile_field(),
+                                           seq_id.x, seq_id.y, seq_id.sample, seq_id.read),
+            HeaderStyle::ReadName => {
+                let header = seq_id.to_string();
+                let end = header.find(' ').unwrap_or(header.len());
+                format!("{}/{}", &header[..end], seq_id.read)
+            }
+        }
+    }
+}
+
+/// Returns the first of the formats that the header is in
+pub fn detect_format<'a>(header: &str, formats: &[&'a dyn HeaderFormat]) -> Option<&'a dyn HeaderFormat> {
+    formats.iter().find(|format| format.detect(header)).cloned()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use barcode::Barcode;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_read_name_format() {
+        let expected = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+        assert_eq!(ReadNameFormat.parse("@M03745:11:000000000-B54L5:1:2108:4127:8949\n").unwrap(), expected);
+        assert_eq!(ReadNameFormat.parse("M03745:11:000000000-B54L5:1:2108:4127:8949/1").unwrap(), expected);
+        assert_eq!(ReadNameFormat.parse("M03745:11:000000000-B54L5:1:2108:4127:8949/2").unwrap().read, 2);
+        assert!(!ReadNameFormat.detect("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0"));
+        assert!(!ReadNameFormat.detect("@M03745:11:000000000-B54L5:1:2108"));
+    }
+
+    #[test]
+    fn test_legacy_format() {
+        let format = LegacyFormat::new(11, "000000000-B54L5");
+        let seq_id = format.parse("@M03745:1:2108:4127:8949#0/2\n").unwrap();
+        assert_eq!(seq_id, parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 2:N:0:0").unwrap());
+        assert_eq!(format.parse("@M03745:1:2108:4127:8949#ACGT").unwrap().sample, Sample::Sequence(Barcode::new("ACGT").unwrap()));
+        assert_eq!(HeaderStyle::Legacy.header(&seq_id), "@M03745:1:2108:4127:8949#0/2");
+        for header in &["@M03745:1:2108:4127:8949", "@M03745:1:2108:4127#0/1", "@M03745:1:2108:4127:8949#/1",
+                        "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0"] {
+            assert!(!format.detect(header), "{}", header);
+        }
+    }
+
+    #[test]
+    fn test_legacy_tile_numbers() {
+        // Genome Analyzer and early HiSeq tiles are plain numbers
+        let format = LegacyFormat::new(0, "unknown");
+        let tiles = |seq_id: &SequenceIdentifier| (seq_id.side, seq_id.swath, seq_id.camera, seq_id.tile);
+        let seq_id = format.parse("@HWUSI-EAS100R:6:73:941:1973#0/1").unwrap();
+        assert_eq!(tiles(&seq_id), (0, 0, None, 73));
+        assert_eq!((seq_id.lane, seq_id.x, seq_id.y, seq_id.read), (6, 941, 1973, 1));
+        assert_eq!(HeaderStyle::Legacy.header(&seq_id), "@HWUSI-EAS100R:6:73:941:1973#0/1");
+        let seq_id = format.parse("@HWI-ST999:2:1:1229:1000#0/1").unwrap();
+        assert_eq!(tiles(&seq_id), (0, 0, None, 1));
+        assert_eq!(HeaderStyle::Legacy.header(&seq_id), "@HWI-ST999:2:1:1229:1000#0/1");
+        let seq_id = format.parse("@HWUSI-EAS100R:6:120:941:1973#ACGT/1").unwrap();
+        assert_eq!(tiles(&seq_id), (0, 0, None, 120));
+        assert_eq!(seq_id.sample.as_sequence(), Some("ACGT"));
+        assert_eq!(HeaderStyle::Legacy.header(&seq_id), "@HWUSI-EAS100R:6:120:941:1973#ACGT/1");
+        // Camera digits are kept for sequencers that have them
+        let seq_id = format.parse("@NS500358:1:11203:20886:1073#0/2").unwrap();
+        assert_eq!(tiles(&seq_id), (1, 1, Some(2), 3));
+        for header in &["@HWUSI-EAS100R:6:300:941:1973#0/1", "@HWUSI-EAS100R:6:+73:941:1973#0/1",
+                        "@HWUSI-EAS100R:6:73:941:1973#0/+1", "@HWUSI-EAS100R:6::941:1973#0/1"] {
+            assert!(format.parse(header).is_err(), "{}", header);
+        }
+    }
+
+    #[test]
+    fn test_header_style() {
+        let seq_id = parse_sequence_identifier("@NS500358:204:HTN5KAFXY:1:11101:20886:1073 1:Y:0:2").unwrap();
+        assert_eq!(HeaderStyle::Casava.header(&seq_id), "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 1:Y:0:2");
+        let read_name = HeaderStyle::ReadName.header(&seq_id);
+        assert_eq!(read_name, "@NS500358:204:HTN5KAFXY:1:11101:20886:1073/1");
+        assert_eq!(ReadNameFormat.parse(&read_name).unwrap().tile_id(), seq_id.tile_id());
+        let legacy = HeaderStyle::Legacy.header(&seq_id);
+        assert_eq!(LegacyFormat::new(204, "HTN5KAFXY").parse(&legacy).unwrap().tile_id(), seq_id.tile_id());
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let formats: [&dyn HeaderFormat; 2] = [&IlluminaFormat::default(), &ReadNameFormat];
+        let header = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+        assert!(detect_format(header, &formats).unwrap().parse(header).is_ok());
+        let header = "@M03745:11:000000000-B54L5:1:2108:4127:8949/2";
+        assert_eq!(detect_format(header, &formats).unwrap().parse(header).unwrap().read, 2);
+        assert!(detect_format("@read1", &formats).is_none());
+    }
+}
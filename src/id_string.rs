@@ -0,0 +1,240 @@
+//! A string type for sequencer and flow cell IDs that stores short strings inline.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::str;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The longest string stored without allocating. Sequencer and flow cell IDs are at most 15
+/// characters in practice, and 22 keeps the whole type the size of a `String`.
+pub const INLINE_CAPACITY: usize = 22;
+
+#[derive(Clone)]
+enum Repr {
+    Inline { length: u8, bytes: [u8; INLINE_CAPACITY] },
+    Heap(Box<str>)
+}
+
+#[derive(Clone)]
+/// An immutable string that stores up to `INLINE_CAPACITY` bytes inline, so that creating one
+/// from a short ID doesn't allocate. Longer strings are stored on the heap.
+///
+/// It dereferences to `str` and compares equal to `str` and `String`, so in most code it can be
+/// used as if it were a `String`.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::id_string::IdString;
+///
+/// fn main() {
+///     let id = IdString::from("HTN5KAFXY");
+///     assert!(id.is_inline());
+///     assert_eq!(id, "HTN5KAFXY");
+///     assert_eq!(id.len(), 9);
+///     assert_eq!(id.to_lowercase(), "htn5kafxy");
+/// }
+/// ```
+pub struct IdString(Repr);
+
+impl IdString {
+    /// Creates an empty string
+    pub fn new() -> IdString {
+        IdString(Repr::Inline { length: 0, bytes: [0; INLINE_CAPACITY] })
+    }
+
+    /// The string as a `&str`
+    pub fn as_str(&self) -> &str {
+        match self.0 {
+            Repr::Inline { length, ref bytes } => {
+                // Inline bytes are only ever copied from a `&str`, and always in full
+                match str::from_utf8(&bytes[..length as usize]) {
+                    Ok(text) => text,
+                    Err(_) => unreachable!("IdString holds invalid UTF-8")
+                }
+            },
+            Repr::Heap(ref text) => text
+        }
+    }
+
+    /// Whether the string is stored inline rather than on the heap
+    pub fn is_inline(&self) -> bool {
+        match self.0 {
+            Repr::Inline { .. } => true,
+            Repr::Heap(_) => false
+        }
+    }
+}
+
+impl Default for IdString {
+    fn default() -> IdString {
+        IdString::new()
+    }
+}
+
+impl<'a> From<&'a str> for IdString {
+    fn from(text: &'a str) -> IdString {
+        if text.len() > INLINE_CAPACITY {
+            return IdString(Repr::Heap(text.into()));
+        }
+        let mut bytes = [0; INLINE_CAPACITY];
+        bytes[..text.len()].copy_from_slice(text.as_bytes());
+        IdString(Repr::Inline { length: text.len() as u8, bytes })
+    }
+}
+
+impl From<String> for IdString {
+    fn from(text: String) -> IdString {
+        if text.len() > INLINE_CAPACITY {
+            IdString(Repr::Heap(text.into_boxed_str()))
+        } else {
+            IdString::from(text.as_str())
+        }
+    }
+}
+
+impl From<IdString> for String {
+    fn from(text: IdString) -> String {
+        match text.0 {
+            Repr::Heap(text) => text.into_string(),
+            Repr::Inline { .. } => text.as_str().to_string()
+        }
+    }
+}
+
+impl Deref for IdString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for IdString {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for IdString {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for IdString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for IdString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl PartialEq for IdString {
+    fn eq(&self, other: &IdString) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for IdString {}
+
+impl PartialEq<str> for IdString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for IdString {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for IdString {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialEq<IdString> for String {
+    fn eq(&self, other: &IdString) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialEq<IdString> for &str {
+    fn eq(&self, other: &IdString) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl PartialOrd for IdString {
+    fn partial_cmp(&self, other: &IdString) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IdString {
+    fn cmp(&self, other: &IdString) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for IdString {
+    // Hashes like a `str`, as `Borrow<str>` requires
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for IdString {
+    // Serializes as a string
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for IdString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<IdString, D::Error> {
+        String::deserialize(deserializer).map(IdString::from)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_inline_and_heap() {
+        let short = IdString::from("000000000-B54L5");
+        assert!(short.is_inline());
+        assert_eq!(short, "000000000-B54L5".to_string());
+        let long = IdString::from("a flow cell ID that's much too long".to_string());
+        assert!(!long.is_inline());
+        assert_eq!(&*long, "a flow cell ID that's much too long");
+        assert_eq!(String::from(long.clone()), "a flow cell ID that's much too long");
+        assert!(short < long);
+        assert_eq!(IdString::new(), "");
+        assert_eq!(format!("{:?} {}", short, short), "\"000000000-B54L5\" 000000000-B54L5");
+    }
+
+    #[test]
+    fn test_borrow() {
+        let mut counts = HashMap::new();
+        counts.insert(IdString::from("M03745"), 1);
+        assert_eq!(counts.get("M03745"), Some(&1));
+    }
+}
@@ -0,0 +1,398 @@
+//! A positional index of FASTQ files, mapping each tile to the locations of its records.
+//!
+//! The index is built by scanning the file once and is saved as a small sidecar file next to it.
+//! Afterwards, the records from particular tiles can be read without decompressing or parsing
+//! the rest of the file. Uncompressed and BGZF-compressed files can be indexed; ordinary gzip
+//! files can't, since they don't support random access.
+//!
+//! Sequencers write reads tile by tile, so each tile usually occupies a single contiguous range
+//! of the file. Files where tiles are interleaved produce larger indexes but still work.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use bgzf::{is_bgzf, is_gzip, BgzfReader};
+use fastq::{FastqReader, Record};
+use super::{IlluminaError, TileId};
+
+const MAGIC: &[u8] = b"TIX2";
+// Version 1 indexes predate cameras, and have no camera byte in their entries
+const MAGIC_V1: &[u8] = b"TIX1";
+// Written in place of the camera for tiles that don't have one
+const NO_CAMERA: u8 = 0xff;
+
+/// The extension appended to a FASTQ file's path to get the path of its index
+pub const INDEX_EXTENSION: &str = "tix";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// A run of consecutive records from the same tile
+pub struct TileRange {
+    /// The tile the records came from
+    pub tile: TileId,
+    /// The offset of the first record: a byte offset for uncompressed files, or a virtual offset
+    /// for BGZF files
+    pub offset: u64,
+    /// The number of records in the run
+    pub records: u64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The locations of the records from each tile in a FASTQ file
+pub struct FastqIndex {
+    /// Whether the indexed file is BGZF-compressed
+    pub is_bgzf: bool,
+    /// The runs of records, in the order they appear in the file
+    pub ranges: Vec<TileRange>
+}
+
+impl FastqIndex {
+    /// Scans a FASTQ file and builds an index of it
+    pub fn build<P: AsRef<Path>>(fastq: P) -> Result<FastqIndex, IlluminaError> {
+        let source = Source::open(fastq.as_ref())?;
+        let is_bgzf = match source {
+            Source::Bgzf(_) => true,
+            Source::Plain { .. } => false
+        };
+        let mut reader = FastqReader::new(source);
+        let mut record = Record::default();
+        let mut ranges: Vec<TileRange> = vec![];
+        loop {
+            let offset = reader.get_ref().offset();
+            if !reader.read_record(&mut record)? {
+                break;
+            }
+            let tile = record.sequence_identifier()?.tile_id();
+            match ranges.last_mut() {
+                Some(range) if range.tile == tile => {
+                    range.records += 1;
+                    continue;
+                },
+                _ => {}
+            }
+            ranges.push(TileRange { tile, offset, records: 1 });
+        }
+        Ok(FastqIndex { is_bgzf, ranges })
+    }
+
+    /// The distinct tiles in the file, in sorted order
+    pub fn tiles(&self) -> Vec<TileId> {
+        let mut tiles: Vec<TileId> = self.ranges.iter().map(|range| range.tile).collect();
+        tiles.sort();
+        tiles.dedup();
+        tiles
+    }
+
+    /// The number of records from the tile
+    pub fn records(&self, tile: TileId) -> u64 {
+        self.ranges.iter().filter(|range| range.tile == tile).map(|range| range.records).sum()
+    }
+
+    /// Writes the index in its binary format
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[self.is_bgzf as u8])?;
+        writer.write_all(&(self.ranges.len() as u64).to_le_bytes())?;
+        for range in &self.ranges {
+            let camera = range.tile.camera.unwrap_or(NO_CAMERA);
+            writer.write_all(&[range.tile.lane, range.tile.side, range.tile.swath, camera, range.tile.tile])?;
+            writer.write_all(&range.offset.to_le_bytes())?;
+            writer.write_all(&range.records.to_le_bytes())?;
+        }
+        writer.flush()
+    }
+
+    /// Reads an index written by `write`
+    pub fn read<R: Read>(mut reader: R) -> Result<FastqIndex, IlluminaError> {
+        let mut header = [0u8; 13];
+        reader.read_exact(&mut header)?;
+        let has_camera = match &header[..4] {
+            magic if magic == MAGIC => true,
+            magic if magic == MAGIC_V1 => false,
+            _ => return Err(IlluminaError::FormatError("not a FASTQ tile index".to_string()))
+        };
+        let is_bgzf = header[4] == 1;
+        let count = le_u64(&header[5..13]);
+        let mut ranges = vec![];
+        let mut entry = [0u8; 21];
+        let entry = if has_camera { &mut entry[..] } else { &mut entry[1..] };
+        for _ in 0..count {
+            reader.read_exact(entry)?;
+            let (camera, rest) = if has_camera {
+                (Some(entry[3]).filter(|&camera| camera != NO_CAMERA), &entry[4..])
+            } else {
+                (None, &entry[3..])
+            };
+            ranges.push(TileRange {
+                tile: TileId { lane: entry[0], side: entry[1], swath: entry[2], camera, tile: rest[0] },
+                offset: le_u64(&rest[1..9]),
+                records: le_u64(&rest[9..17])
+            });
+        }
+        Ok(FastqIndex { is_bgzf, ranges })
+    }
+
+    /// Saves the index to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), IlluminaError> {
+        let file = File::create(path)?;
+        Ok(self.write(BufWriter::new(file))?)
+    }
+
+    /// Loads an index from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<FastqIndex, IlluminaError> {
+        let file = File::open(path)?;
+        FastqIndex::read(BufReader::new(file))
+    }
+}
+
+/// The path of the sidecar index for a FASTQ file, e.g. `reads.fastq.gz.tix`
+pub fn index_path<P: AsRef<Path>>(fastq: P) -> PathBuf {
+    let mut path = fastq.as_ref().as_os_str().to_os_string();
+    path.push(".");
+    path.push(INDEX_EXTENSION);
+    PathBuf::from(path)
+}
+
+fn le_u64(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |value, &byte| value << 8 | u64::from(byte))
+}
+
+/// Reads the records from selected tiles of an indexed FASTQ file
+///
+/// # Example
+///
+/// ```rust,no_run
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::index::{index_path, FastqIndex, IndexedFastqReader};
+///
+/// fn main() {
+///     let index = FastqIndex::build("reads.fastq.gz").unwrap();
+///     index.save(index_path("reads.fastq.gz")).unwrap();
+///
+///     let mut reader = IndexedFastqReader::open("reads.fastq.gz").unwrap();
+///     let tile = reader.index().tiles()[0];
+///     for record in reader.fetch(&[tile]) {
+///         println!("{}", record.unwrap().header);
+///     }
+/// }
+/// ```
+pub struct IndexedFastqReader {
+    reader: FastqReader<Source>,
+    index: FastqIndex
+}
+
+impl IndexedFastqReader {
+    /// Opens a FASTQ file along with its sidecar index
+    pub fn open<P: AsRef<Path>>(fastq: P) -> Result<IndexedFastqReader, IlluminaError> {
+        let index = FastqIndex::load(index_path(&fastq))?;
+        IndexedFastqReader::with_index(fastq, index)
+    }
+
+    /// Opens a FASTQ file using an index that has already been loaded
+    pub fn with_index<P: AsRef<Path>>(fastq: P, index: FastqIndex) -> Result<IndexedFastqReader, IlluminaError> {
+        let source = Source::open(fastq.as_ref())?;
+        let is_bgzf = match source {
+            Source::Bgzf(_) => true,
+            Source::Plain { .. } => false
+        };
+        if is_bgzf != index.is_bgzf {
+            return Err(IlluminaError::FormatError("index was built for a different file".to_string()));
+        }
+        Ok(IndexedFastqReader { reader: FastqReader::new(source), index })
+    }
+
+    /// The index of the file
+    pub fn index(&self) -> &FastqIndex {
+        &self.index
+    }
+
+    /// Iterates over the records from the given tiles, in the order they appear in the file
+    pub fn fetch(&mut self, tiles: &[TileId]) -> TileRecords<'_> {
+        let ranges: Vec<TileRange> = self.index.ranges.iter()
+            .filter(|range| tiles.contains(&range.tile))
+            .cloned()
+            .collect();
+        TileRecords { reader: &mut self.reader, ranges, next_range: 0, remaining: 0 }
+    }
+}
+
+/// An iterator over the records from selected tiles, created by `IndexedFastqReader::fetch`
+pub struct TileRecords<'a> {
+    reader: &'a mut FastqReader<Source>,
+    ranges: Vec<TileRange>,
+    next_range: usize,
+    remaining: u64
+}
+
+impl<'a> Iterator for TileRecords<'a> {
+    type Item = Result<Record, IlluminaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.remaining == 0 {
+            let range = *self.ranges.get(self.next_range)?;
+            self.next_range += 1;
+            if let Err(error) = self.reader.get_mut().seek(range.offset) {
+                return Some(Err(error.into()));
+            }
+            self.remaining = range.records;
+        }
+        self.remaining -= 1;
+        let mut record = Record::default();
+        match self.reader.read_record(&mut record) {
+            Ok(true) => Some(Ok(record)),
+            Ok(false) => Some(Err(IlluminaError::FormatError("FASTQ file ended before the index said it would".to_string()))),
+            Err(error) => Some(Err(error))
+        }
+    }
+}
+
+/// An uncompressed or BGZF-compressed FASTQ file that knows its position
+enum Source {
+    Plain { reader: BufReader<File>, offset: u64 },
+    Bgzf(BgzfReader<File>)
+}
+
+impl Source {
+    fn open(path: &Path) -> Result<Source, IlluminaError> {
+        let mut file = File::open(path)?;
+        let mut header = vec![];
+        (&mut file).take(18).read_to_end(&mut header)?;
+        file.seek(SeekFrom::Start(0))?;
+        if is_bgzf(&header) {
+            Ok(Source::Bgzf(BgzfReader::new(file)))
+        } else if is_gzip(&header) {
+            Err(IlluminaError::FormatError("only BGZF-compressed files can be indexed, not ordinary gzip".to_string()))
+        } else {
+            Ok(Source::Plain { reader: BufReader::new(file), offset: 0 })
+        }
+    }
+
+    fn offset(&self) -> u64 {
+        match *self {
+            Source::Plain { offset, .. } => offset,
+            Source::Bgzf(ref reader) => reader.virtual_offset()
+        }
+    }
+
+    fn seek(&mut self, position: u64) -> io::Result<()> {
+        match *self {
+            Source::Plain { ref mut reader, ref mut offset } => {
+                reader.seek(SeekFrom::Start(position))?;
+                *offset = position;
+                Ok(())
+            },
+            Source::Bgzf(ref mut reader) => reader.seek_virtual(position)
+        }
+    }
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Source::Plain { ref mut reader, ref mut offset } => {
+                let n = reader.read(buf)?;
+                *offset += n as u64;
+                Ok(n)
+            },
+            Source::Bgzf(ref mut reader) => reader.read(buf)
+        }
+    }
+}
+
+impl BufRead for Source {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match *self {
+            Source::Plain { ref mut reader, .. } => reader.fill_buf(),
+            Source::Bgzf(ref mut reader) => reader.fill_buf()
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        match *self {
+            Source::Plain { ref mut reader, ref mut offset } => {
+                reader.consume(amount);
+                *offset += amount as u64;
+            },
+            Source::Bgzf(ref mut reader) => reader.consume(amount)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use bgzf::tests::stored_block;
+
+    fn record(tile: &str, y: u32) -> String {
+        format!("@M03745:11:000000000-B54L5:1:{}:4127:{} 1:N:0:0\nACGT\n+\nFFFF\n", tile, y)
+    }
+
+    fn fastq() -> Vec<String> {
+        vec![record("1101", 1000), record("1101", 1001), record("1102", 1002), record("2101", 1003), record("1101", 1004)]
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("illumina_coordinates_index_{}_{}", std::process::id(), name))
+    }
+
+    fn check_index(path: &Path, is_bgzf: bool) {
+        let index = FastqIndex::build(path).unwrap();
+        assert_eq!(index.is_bgzf, is_bgzf);
+        assert_eq!(index.ranges.len(), 4);
+        let tile_1101 = TileId { lane: 1, side: 1, swath: 1, camera: None, tile: 1 };
+        let tile_2101 = TileId { lane: 1, side: 2, swath: 1, camera: None, tile: 1 };
+        assert_eq!(index.records(tile_1101), 3);
+        assert_eq!(index.tiles().len(), 3);
+
+        index.save(index_path(path)).unwrap();
+        let mut reader = IndexedFastqReader::open(path).unwrap();
+        assert_eq!(reader.index(), &index);
+        let ys: Vec<u16> = reader.fetch(&[tile_1101, tile_2101])
+            .map(|record| record.unwrap().sequence_identifier().unwrap().y)
+            .collect();
+        assert_eq!(ys, vec![1000, 1001, 1003, 1004]);
+        fs::remove_file(index_path(path)).unwrap();
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_index_plain() {
+        let path = temp_path("plain.fastq");
+        fs::write(&path, fastq().concat()).unwrap();
+        check_index(&path, false);
+    }
+
+    #[test]
+    fn test_index_bgzf() {
+        let path = temp_path("blocks.fastq.gz");
+        let records = fastq();
+        // Split the records across blocks so that some start in the middle of a block
+        let mut data = stored_block(records[..2].concat().as_bytes());
+        data.extend(stored_block(records[2..].concat().as_bytes()));
+        data.extend(stored_block(b""));
+        fs::write(&path, data).unwrap();
+        check_index(&path, true);
+    }
+
+    #[test]
+    fn test_read_write_index() {
+        let mut index = FastqIndex {
+            is_bgzf: true,
+            ranges: vec![TileRange { tile: TileId { lane: 2, side: 1, swath: 2, camera: None, tile: 19 }, offset: 1 << 40 | 5, records: 7 },
+                         TileRange { tile: TileId { lane: 2, side: 1, swath: 2, camera: Some(3), tile: 4 }, offset: 9, records: 1 }]
+        };
+        let mut data = vec![];
+        index.write(&mut data).unwrap();
+        assert_eq!(FastqIndex::read(&data[..]).unwrap(), index);
+
+        // Version 1 entries have no camera byte
+        let mut data = b"TIX1\x01\x01\x00\x00\x00\x00\x00\x00\x00\x02\x01\x02\x13".to_vec();
+        data.extend_from_slice(&(1u64 << 40 | 5).to_le_bytes());
+        data.extend_from_slice(&7u64.to_le_bytes());
+        index.ranges.truncate(1);
+        assert_eq!(FastqIndex::read(&data[..]).unwrap(), index);
+        assert!(FastqIndex::read(&b"TIX0"[..]).is_err());
+    }
+}
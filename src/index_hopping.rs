@@ -0,0 +1,147 @@
+//! Detection of index hopping in dual-indexed runs.
+//!
+//! When index hopping occurs, a read carries the i7 index of one sample and the i5 index of
+//! another. Such reads end up in Undetermined Reads with both indexes recognizable but paired
+//! incorrectly. Counting them gives the hopping rate of each lane.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use barcode::BarcodeMatcher;
+use super::SequenceIdentifier;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// Index combinations seen on a single lane
+pub struct LaneHopping {
+    /// Reads whose index pair matched an expected pair
+    pub expected: u64,
+    /// Reads where both indexes were expected, but not as a pair
+    pub hopped: u64,
+    /// Reads where at least one index couldn't be recognized
+    pub unrecognized: u64,
+    /// The number of reads with each hopped (i7, i5) combination
+    pub hopped_pairs: HashMap<(String, String), u64>
+}
+
+impl LaneHopping {
+    /// The fraction of reads with recognizable indexes that had a hopped combination
+    pub fn hopping_rate(&self) -> f64 {
+        let recognized = self.expected + self.hopped;
+        if recognized == 0 {
+            0.0
+        } else {
+            self.hopped as f64 / recognized as f64
+        }
+    }
+}
+
+/// Counts expected and unexpected index combinations per lane. Indexes are read from
+/// `Sample::Sequence` values in the form `i7+i5`; reads with sample numbers or single indexes
+/// are ignored.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::index_hopping::IndexHoppingCounter;
+///
+/// fn main() {
+///     let mut counter = IndexHoppingCounter::new(vec![("AAAA", "CCCC"), ("GGGG", "TTTT")], 0);
+///     for line in &["@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:AAAA+CCCC",
+///                   "@A00123:8:H3KJ7DSXX:1:1101:1000:1200 1:N:0:AAAA+TTTT"] {
+///         counter.add(&illumina_coordinates::parse_sequence_identifier(line).unwrap());
+///     }
+///     assert_eq!(counter.lanes()[&1].hopped, 1);
+///     assert_eq!(counter.lanes()[&1].hopping_rate(), 0.5);
+/// }
+/// ```
+pub struct IndexHoppingCounter {
+    expected_pairs: HashSet<(String, String)>,
+    i7_matcher: BarcodeMatcher,
+    i5_matcher: BarcodeMatcher,
+    lanes: BTreeMap<u8, LaneHopping>
+}
+
+impl IndexHoppingCounter {
+    /// Creates a counter for the expected (i7, i5) index pairs. Each index may differ from an
+    /// expected one by up to `max_mismatches` and still be recognized.
+    pub fn new<I, S>(expected_pairs: I, max_mismatches: usize) -> IndexHoppingCounter
+        where I: IntoIterator<Item=(S, S)>, S: Into<String> {
+        let expected_pairs: HashSet<(String, String)> = expected_pairs.into_iter()
+            .map(|(i7, i5)| (i7.into(), i5.into()))
+            .collect();
+        let i7s: HashSet<&String> = expected_pairs.iter().map(|(i7, _)| i7).collect();
+        let i5s: HashSet<&String> = expected_pairs.iter().map(|(_, i5)| i5).collect();
+        let i7_matcher = BarcodeMatcher::new(i7s.into_iter().cloned(), max_mismatches);
+        let i5_matcher = BarcodeMatcher::new(i5s.into_iter().cloned(), max_mismatches);
+        IndexHoppingCounter { expected_pairs, i7_matcher, i5_matcher, lanes: BTreeMap::new() }
+    }
+
+    /// Counts the index combination of a read
+    pub fn add(&mut self, seq_id: &SequenceIdentifier) {
+        let (i7, i5) = match seq_id.sample.index_pair() {
+            Some(pair) => pair,
+            None => return
+        };
+        let lane = self.lanes.entry(seq_id.lane).or_default();
+        let (i7, i5) = match (self.i7_matcher.assign(i7), self.i5_matcher.assign(i5)) {
+            (Some(i7), Some(i5)) => (i7, i5),
+            _ => {
+                lane.unrecognized += 1;
+                return;
+            }
+        };
+        let pair = (i7.to_string(), i5.to_string());
+        if self.expected_pairs.contains(&pair) {
+            lane.expected += 1;
+        } else {
+            lane.hopped += 1;
+            *lane.hopped_pairs.entry(pair).or_insert(0) += 1;
+        }
+    }
+
+    /// The counts for each lane that had at least one dual-indexed read
+    pub fn lanes(&self) -> &BTreeMap<u8, LaneHopping> {
+        &self.lanes
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    fn counter_with(lines: &[&str]) -> IndexHoppingCounter {
+        let mut counter = IndexHoppingCounter::new(vec![("AAAAAAAA", "CCCCCCCC"), ("GGGGGGGG", "TTTTTTTT")], 1);
+        for line in lines {
+            counter.add(&parse_sequence_identifier(line).unwrap());
+        }
+        counter
+    }
+
+    #[test]
+    fn test_counts_per_lane() {
+        let counter = counter_with(&[
+            "@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:AAAAAAAA+CCCCCCCC",
+            "@A00123:8:H3KJ7DSXX:1:1101:1000:1100 1:N:0:AAAAAAAT+CCCCCCCC",
+            "@A00123:8:H3KJ7DSXX:1:1101:1000:1200 1:N:0:AAAAAAAA+TTTTTTTT",
+            "@A00123:8:H3KJ7DSXX:1:1101:1000:1300 1:N:0:ACGTACGT+TTTTTTTT",
+            "@A00123:8:H3KJ7DSXX:2:1101:1000:1000 1:N:0:GGGGGGGG+CCCCCCCC",
+            "@A00123:8:H3KJ7DSXX:2:1101:1000:1100 1:N:0:GGGGGGGG",
+            "@A00123:8:H3KJ7DSXX:2:1101:1000:1200 1:N:0:1",
+        ]);
+        let lane1 = &counter.lanes()[&1];
+        assert_eq!(lane1.expected, 2);
+        assert_eq!(lane1.hopped, 1);
+        assert_eq!(lane1.unrecognized, 1);
+        assert_eq!(lane1.hopped_pairs[&("AAAAAAAA".to_string(), "TTTTTTTT".to_string())], 1);
+        let lane2 = &counter.lanes()[&2];
+        assert_eq!(lane2.expected, 0);
+        assert_eq!(lane2.hopped, 1);
+        assert_eq!(lane2.hopping_rate(), 1.0);
+    }
+
+    #[test]
+    fn test_hopping_rate_without_reads() {
+        assert_eq!(LaneHopping::default().hopping_rate(), 0.0);
+    }
+}
@@ -0,0 +1,169 @@
+//! Identification of the sequencing platform that produced a read.
+//!
+//! Illumina gives each model of sequencer its own prefix for instrument IDs, so the model can be
+//! inferred from the sequencer ID in every read without needing the run folder.
+
+use geometry::{self, TileExtent};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A model, or a family of closely related models, of Illumina sequencer
+pub enum InstrumentModel {
+    /// MiSeq, with instrument IDs starting with M
+    MiSeq,
+    /// MiniSeq, with instrument IDs starting with MN
+    MiniSeq,
+    /// iSeq 100, with instrument IDs starting with FS
+    ISeq,
+    /// HiSeq 1000, 1500, 2000 and 2500, with instrument IDs starting with D, C or SN
+    HiSeq,
+    /// HiSeq 3000 and 4000, with instrument IDs starting with J or K
+    HiSeq4000,
+    /// HiSeq X, with instrument IDs starting with E
+    HiSeqX,
+    /// NextSeq 500 and 550, with instrument IDs starting with NS or NB
+    NextSeq,
+    /// NextSeq 1000 and 2000, with instrument IDs starting with VH
+    NextSeq2000,
+    /// NovaSeq 6000, with instrument IDs starting with A
+    NovaSeq,
+    /// NovaSeq X, with instrument IDs starting with LH
+    NovaSeqX
+}
+
+// Longer prefixes come before the shorter prefixes they start with
+const PREFIXES: [(&str, InstrumentModel); 13] = [
+    ("MN", InstrumentModel::MiniSeq),
+    ("M", InstrumentModel::MiSeq),
+    ("FS", InstrumentModel::ISeq),
+    ("SN", InstrumentModel::HiSeq),
+    ("D", InstrumentModel::HiSeq),
+    ("C", InstrumentModel::HiSeq),
+    ("J", InstrumentModel::HiSeq4000),
+    ("K", InstrumentModel::HiSeq4000),
+    ("E", InstrumentModel::HiSeqX),
+    ("NS", InstrumentModel::NextSeq),
+    ("NB", InstrumentModel::NextSeq),
+    ("VH", InstrumentModel::NextSeq2000),
+    ("LH", InstrumentModel::NovaSeqX)
+];
+
+impl InstrumentModel {
+    /// Infers the model from the sequencer ID, or returns `None` if the prefix isn't recognized
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::instrument::InstrumentModel;
+    ///
+    /// fn main() {
+    ///     assert_eq!(InstrumentModel::from_sequencer_id("M03745"), Some(InstrumentModel::MiSeq));
+    ///     assert_eq!(InstrumentModel::from_sequencer_id("NB501234"), Some(InstrumentModel::NextSeq));
+    ///     assert_eq!(InstrumentModel::from_sequencer_id("my-sequencer"), None);
+    /// }
+    /// ```
+    pub fn from_sequencer_id(sequencer_id: &str) -> Option<InstrumentModel> {
+        // NovaSeq 6000 IDs are A followed by digits, which keeps them apart from other IDs
+        // starting with A
+        if sequencer_id.len() > 1 && sequencer_id.starts_with('A')
+            && sequencer_id[1..].bytes().all(|byte| byte.is_ascii_digit()) {
+            return Some(InstrumentModel::NovaSeq);
+        }
+        PREFIXES.iter()
+            .find(|&&(prefix, _)| sequencer_id.starts_with(prefix))
+            // The prefix must be followed by the instrument's serial number
+            .filter(|&&(prefix, _)| sequencer_id[prefix.len()..].starts_with(|c: char| c.is_ascii_digit()))
+            .map(|&(_, model)| model)
+    }
+
+    /// Whether the model uses patterned flow cells, where clusters grow in a regular grid of
+    /// nanowells rather than wherever DNA happened to attach
+    pub fn has_patterned_flow_cell(&self) -> bool {
+        match *self {
+            InstrumentModel::MiSeq | InstrumentModel::MiniSeq | InstrumentModel::HiSeq
+                | InstrumentModel::NextSeq => false,
+            InstrumentModel::ISeq | InstrumentModel::HiSeq4000 | InstrumentModel::HiSeqX
+                | InstrumentModel::NextSeq2000 | InstrumentModel::NovaSeq | InstrumentModel::NovaSeqX => true
+        }
+    }
+
+    /// The distance within which two clusters are usually considered optical duplicates: 100 for
+    /// unpatterned flow cells and 2500 for patterned ones, following Picard's recommendations
+    pub fn optical_duplicate_distance(&self) -> u16 {
+        if self.has_patterned_flow_cell() { 2500 } else { 100 }
+    }
+
+    /// Whether the model numbers tiles by the camera that imaged them, writing five-digit tile
+    /// fields such as `11203` for camera 2, tile 3. The NextSeq 500 and 550 and the MiniSeq,
+    /// which share their optics, do.
+    pub fn has_cameras(&self) -> bool {
+        matches!(*self, InstrumentModel::NextSeq | InstrumentModel::MiniSeq)
+    }
+
+    /// The approximate size of a pixel in tile images, in microns, if it's known for this model
+    pub fn microns_per_pixel(&self) -> Option<f64> {
+        match *self {
+            InstrumentModel::MiSeq => Some(geometry::MISEQ_MICRONS_PER_PIXEL),
+            InstrumentModel::HiSeq => Some(geometry::HISEQ_MICRONS_PER_PIXEL),
+            InstrumentModel::MiniSeq | InstrumentModel::NextSeq => Some(geometry::NEXTSEQ_MICRONS_PER_PIXEL),
+            InstrumentModel::NovaSeq => Some(geometry::NOVASEQ_MICRONS_PER_PIXEL),
+            _ => None
+        }
+    }
+
+    /// The approximate area of a tile in square millimetres, if both the tile extent and the pixel
+    /// size are known for this model
+    pub fn tile_area_mm2(&self) -> Option<f64> {
+        match (self.tile_extent(), self.microns_per_pixel()) {
+            (Some(extent), Some(microns_per_pixel)) => Some(extent.area_mm2(microns_per_pixel)),
+            _ => None
+        }
+    }
+
+    /// The range of coordinates within a tile, if it's known for this model
+    pub fn tile_extent(&self) -> Option<TileExtent> {
+        match *self {
+            InstrumentModel::MiSeq => Some(geometry::MISEQ),
+            InstrumentModel::MiniSeq => Some(geometry::MINISEQ),
+            InstrumentModel::ISeq => Some(geometry::ISEQ),
+            InstrumentModel::HiSeq => Some(geometry::HISEQ),
+            InstrumentModel::NextSeq => Some(geometry::NEXTSEQ),
+            InstrumentModel::NovaSeq => Some(geometry::NOVASEQ),
+            _ => None
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_from_sequencer_id() {
+        for &(sequencer_id, model) in &[("M03745", InstrumentModel::MiSeq), ("MN00123", InstrumentModel::MiniSeq),
+                                       ("FS10000456", InstrumentModel::ISeq), ("D00360", InstrumentModel::HiSeq),
+                                       ("SN7001234", InstrumentModel::HiSeq), ("K00123", InstrumentModel::HiSeq4000),
+                                       ("E00456", InstrumentModel::HiSeqX), ("NS500358", InstrumentModel::NextSeq),
+                                       ("NB501234", InstrumentModel::NextSeq), ("VH00123", InstrumentModel::NextSeq2000),
+                                       ("A00123", InstrumentModel::NovaSeq), ("LH00456", InstrumentModel::NovaSeqX)] {
+            assert_eq!(InstrumentModel::from_sequencer_id(sequencer_id), Some(model), "{}", sequencer_id);
+        }
+        for sequencer_id in &["", "A", "ABC", "MX123", "Névé", "HWI-ST1234"] {
+            assert_eq!(InstrumentModel::from_sequencer_id(sequencer_id), None, "{}", sequencer_id);
+        }
+    }
+
+    #[test]
+    fn test_instrument() {
+        let seq_id = parse_sequence_identifier("@A00123:8:HFWJ3DSXX:1:1101:1000:1000 1:N:0:0").unwrap();
+        let model = seq_id.instrument().unwrap();
+        assert!(model.has_patterned_flow_cell());
+        assert_eq!(model.optical_duplicate_distance(), 2500);
+        assert_eq!(model.tile_extent(), Some(geometry::NOVASEQ));
+        assert_eq!(model.microns_per_pixel(), Some(geometry::NOVASEQ_MICRONS_PER_PIXEL));
+        assert!(model.tile_area_mm2().unwrap() > 1.0);
+        assert_eq!(InstrumentModel::ISeq.tile_area_mm2(), None);
+    }
+}
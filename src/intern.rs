@@ -0,0 +1,161 @@
+//! Interning of sequencer and flow cell IDs, which are nearly always the same for every read in a
+//! file. Interned identifiers share a single allocation for each distinct ID instead of
+//! allocating two strings per read.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use id_string::IdString;
+use super::{parse_sequence_identifier_borrowed, IlluminaError, Sample, SequenceIdentifier};
+
+#[derive(Debug, Clone, Default)]
+/// Stores one shared copy of each distinct string it's given
+pub struct Interner {
+    strings: HashSet<Arc<str>>
+}
+
+impl Interner {
+    /// Creates an empty interner
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    /// Returns the shared copy of the string, creating it if this is the first time it's been seen
+    pub fn intern(&mut self, text: &str) -> Arc<str> {
+        if let Some(shared) = self.strings.get(text) {
+            return shared.clone();
+        }
+        let shared: Arc<str> = Arc::from(text);
+        self.strings.insert(shared.clone());
+        shared
+    }
+
+    /// The number of distinct strings
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Whether no strings have been interned
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Parses a sequence identifier, interning its sequencer and flow cell IDs
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use std::sync::Arc;
+    /// use illumina_coordinates::intern::Interner;
+    ///
+    /// fn main() {
+    ///     let mut interner = Interner::new();
+    ///     let first = interner.parse("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+    ///     let second = interner.parse("@M03745:11:000000000-B54L5:1:2108:4130:9012 1:N:0:0").unwrap();
+    ///     assert!(Arc::ptr_eq(&first.flow_cell_id, &second.flow_cell_id));
+    ///     assert_eq!(interner.len(), 2);
+    /// }
+    /// ```
+    pub fn parse(&mut self, text: &str) -> Result<InternedSequenceIdentifier, IlluminaError> {
+        let seq_id = parse_sequence_identifier_borrowed(text)?;
+        Ok(InternedSequenceIdentifier {
+            sequencer_id: self.intern(&seq_id.sequencer_id),
+            run_count: seq_id.run_count,
+            flow_cell_id: self.intern(&seq_id.flow_cell_id),
+            lane: seq_id.lane,
+            side: seq_id.side,
+            swath: seq_id.swath,
+            camera: seq_id.camera,
+            tile: seq_id.tile,
+            x: seq_id.x,
+            y: seq_id.y,
+            read: seq_id.read,
+            is_filtered: seq_id.is_filtered,
+            control_number: seq_id.control_number,
+            sample: seq_id.sample
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A parsed sequence identifier whose sequencer and flow cell IDs are shared with other
+/// identifiers parsed by the same `Interner`
+pub struct InternedSequenceIdentifier {
+    /// ID of the sequencing machine
+    pub sequencer_id: Arc<str>,
+    /// The number of sequencing runs this machine has performed
+    pub run_count: u16,
+    /// ID of the flow cell, printed on the side of the glass slide
+    pub flow_cell_id: Arc<str>,
+    /// Lane number. For MiSeqs, this is always 1
+    pub lane: u8,
+    /// The near or far side off the flow cell surface
+    pub side: u8,
+    /// The row within a lane, if wide enough. For MiSeqs, this is always 1
+    pub swath: u8,
+    /// The camera that imaged the tile, for sequencers that number tiles by camera
+    pub camera: Option<u8>,
+    /// The positional order of the region where the cluster is located
+    pub tile: u8,
+    /// The x-coordinate of the cluster
+    pub x: u16,
+    /// The y-coordinate of the cluster
+    pub y: u16,
+    /// The read number
+    pub read: u8,
+    /// Whether the read was filtered for low quality (Y=filtered)
+    pub is_filtered: bool,
+    /// Indicates the type of control, 0 = not a control read
+    pub control_number: u16,
+    /// Number from sample sheet, or the sequence if the read is in Undetermined Reads
+    pub sample: Sample
+}
+
+impl From<InternedSequenceIdentifier> for SequenceIdentifier {
+    fn from(seq_id: InternedSequenceIdentifier) -> SequenceIdentifier {
+        SequenceIdentifier {
+            sequencer_id: IdString::from(&*seq_id.sequencer_id),
+            run_count: seq_id.run_count,
+            flow_cell_id: IdString::from(&*seq_id.flow_cell_id),
+            lane: seq_id.lane,
+            side: seq_id.side,
+            swath: seq_id.swath,
+            camera: seq_id.camera,
+            tile: seq_id.tile,
+            x: seq_id.x,
+            y: seq_id.y,
+            read: seq_id.read,
+            is_filtered: seq_id.is_filtered,
+            control_number: seq_id.control_number,
+            sample: seq_id.sample
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_intern() {
+        let mut interner = Interner::new();
+        let a = interner.intern("HTN5KAFXY");
+        let copy = String::from("HTN5KAFXY");
+        let b = interner.intern(&copy);
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &interner.intern("000000000-B54L5")));
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn test_parse() {
+        let line = "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC";
+        let mut interner = Interner::new();
+        let seq_id = interner.parse(line).unwrap();
+        assert_eq!(&*seq_id.sequencer_id, "NS500358");
+        assert_eq!(SequenceIdentifier::from(seq_id), parse_sequence_identifier(line).unwrap());
+        assert!(interner.parse("@NS500358:204").is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! Reads the binary InterOp files that Illumina sequencers write during a run. Only
+//! `TileMetricsOut.bin` is supported, which holds the sequencer's own count of clusters on each
+//! tile, so that statistics computed from sequence identifiers can be checked against it.
+//!
+//! Tiles are identified by their full tile number as printed in sequence identifiers, e.g. 2108.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use super::IlluminaError;
+
+const CLUSTER_DENSITY: u16 = 100;
+const PF_CLUSTER_DENSITY: u16 = 101;
+const CLUSTER_COUNT: u16 = 102;
+const PF_CLUSTER_COUNT: u16 = 103;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// Metrics that the sequencer recorded for a single tile. Metrics that weren't present in the
+/// file are `None`.
+pub struct TileMetrics {
+    /// Lane number
+    pub lane: u16,
+    /// The full tile number, e.g. 2108
+    pub tile: u32,
+    /// Clusters per square millimetre
+    pub cluster_density: Option<f32>,
+    /// Clusters passing filter per square millimetre
+    pub pf_cluster_density: Option<f32>,
+    /// The number of clusters on the tile
+    pub cluster_count: Option<f32>,
+    /// The number of clusters on the tile that passed filter
+    pub pf_cluster_count: Option<f32>
+}
+
+impl TileMetrics {
+    /// The fraction of clusters on the tile that passed filter
+    pub fn pf_fraction(&self) -> Option<f32> {
+        match (self.pf_cluster_count, self.cluster_count) {
+            (Some(pf), Some(total)) if total > 0.0 => Some(pf / total),
+            _ => None
+        }
+    }
+}
+
+/// Reads a `TileMetricsOut.bin` file
+pub fn read_tile_metrics<P: AsRef<Path>>(path: P) -> Result<Vec<TileMetrics>, IlluminaError> {
+    let file = File::open(path)?;
+    parse_tile_metrics(BufReader::new(file))
+}
+
+/// Parses the contents of a `TileMetricsOut.bin` file. Versions 2 and 3 of the format are
+/// supported. The metrics are returned ordered by lane and then tile.
+pub fn parse_tile_metrics<R: Read>(mut reader: R) -> Result<Vec<TileMetrics>, IlluminaError> {
+    let mut contents = vec![];
+    reader.read_to_end(&mut contents)?;
+    if contents.len() < 2 {
+        return Err(IlluminaError::FormatError("TileMetricsOut.bin has no header".to_string()));
+    }
+    let version = contents[0];
+    let record_size = contents[1] as usize;
+    let mut tiles: BTreeMap<(u16, u32), TileMetrics> = BTreeMap::new();
+    match (version, record_size) {
+        (2, 10) => {
+            for record in contents[2..].chunks(record_size) {
+                if record.len() != record_size {
+                    return Err(truncated());
+                }
+                let lane = le_u16(&record[0..2]);
+                let tile = u32::from(le_u16(&record[2..4]));
+                let value = le_f32(&record[6..10]);
+                let metrics = entry(&mut tiles, lane, tile);
+                match le_u16(&record[4..6]) {
+                    CLUSTER_DENSITY => metrics.cluster_density = Some(value),
+                    PF_CLUSTER_DENSITY => metrics.pf_cluster_density = Some(value),
+                    CLUSTER_COUNT => metrics.cluster_count = Some(value),
+                    PF_CLUSTER_COUNT => metrics.pf_cluster_count = Some(value),
+                    _ => {}
+                }
+            }
+        },
+        (3, 15) => {
+            if contents.len() < 6 {
+                return Err(truncated());
+            }
+            let area = le_f32(&contents[2..6]);
+            for record in contents[6..].chunks(record_size) {
+                if record.len() != record_size {
+                    return Err(truncated());
+                }
+                // Records with other codes hold per-read metrics, which we don't use
+                if record[6] != b't' {
+                    continue;
+                }
+                let lane = le_u16(&record[0..2]);
+                let tile = le_u32(&record[2..6]);
+                let cluster_count = le_f32(&record[7..11]);
+                let pf_cluster_count = le_f32(&record[11..15]);
+                let metrics = entry(&mut tiles, lane, tile);
+                metrics.cluster_count = Some(cluster_count);
+                metrics.pf_cluster_count = Some(pf_cluster_count);
+                if area > 0.0 {
+                    metrics.cluster_density = Some(cluster_count / area);
+                    metrics.pf_cluster_density = Some(pf_cluster_count / area);
+                }
+            }
+        },
+        _ => return Err(IlluminaError::FormatError(
+            format!("unsupported TileMetricsOut.bin version {} with record size {}", version, record_size)))
+    }
+    Ok(tiles.values().cloned().collect())
+}
+
+fn entry(tiles: &mut BTreeMap<(u16, u32), TileMetrics>, lane: u16, tile: u32) -> &mut TileMetrics {
+    tiles.entry((lane, tile)).or_insert_with(|| TileMetrics { lane, tile, ..Default::default() })
+}
+
+fn truncated() -> IlluminaError {
+    IlluminaError::FormatError("TileMetricsOut.bin ends with a partial record".to_string())
+}
+
+fn le_u16(bytes: &[u8]) -> u16 {
+    u16::from(bytes[0]) | u16::from(bytes[1]) << 8
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    u32::from(le_u16(&bytes[0..2])) | u32::from(le_u16(&bytes[2..4])) << 16
+}
+
+fn le_f32(bytes: &[u8]) -> f32 {
+    f32::from_bits(le_u32(bytes))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v2_record(lane: u16, tile: u16, code: u16, value: f32) -> Vec<u8> {
+        let mut record = vec![];
+        record.extend_from_slice(&lane.to_le_bytes());
+        record.extend_from_slice(&tile.to_le_bytes());
+        record.extend_from_slice(&code.to_le_bytes());
+        record.extend_from_slice(&value.to_bits().to_le_bytes());
+        record
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        let mut contents = vec![2, 10];
+        contents.extend(v2_record(1, 2108, CLUSTER_COUNT, 1000.0));
+        contents.extend(v2_record(1, 2108, PF_CLUSTER_COUNT, 900.0));
+        contents.extend(v2_record(1, 1101, CLUSTER_DENSITY, 1200.0));
+        contents.extend(v2_record(1, 1101, 200, 0.1));
+        let metrics = parse_tile_metrics(&contents[..]).unwrap();
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[0].tile, 1101);
+        assert_eq!(metrics[0].cluster_density, Some(1200.0));
+        assert_eq!(metrics[0].cluster_count, None);
+        assert_eq!(metrics[1].tile, 2108);
+        assert_eq!(metrics[1].pf_fraction(), Some(0.9));
+    }
+
+    #[test]
+    fn test_parse_v3() {
+        let mut contents = vec![3, 15];
+        contents.extend_from_slice(&2.0f32.to_bits().to_le_bytes());
+        contents.extend_from_slice(&1u16.to_le_bytes());
+        contents.extend_from_slice(&2108u32.to_le_bytes());
+        contents.push(b't');
+        contents.extend_from_slice(&1000.0f32.to_bits().to_le_bytes());
+        contents.extend_from_slice(&500.0f32.to_bits().to_le_bytes());
+        contents.extend_from_slice(&1u16.to_le_bytes());
+        contents.extend_from_slice(&2108u32.to_le_bytes());
+        contents.push(b'r');
+        contents.extend_from_slice(&1u32.to_le_bytes());
+        contents.extend_from_slice(&98.0f32.to_bits().to_le_bytes());
+        let metrics = parse_tile_metrics(&contents[..]).unwrap();
+        assert_eq!(metrics, vec![TileMetrics {
+            lane: 1,
+            tile: 2108,
+            cluster_density: Some(500.0),
+            pf_cluster_density: Some(250.0),
+            cluster_count: Some(1000.0),
+            pf_cluster_count: Some(500.0)
+        }]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_tile_metrics(&[2u8][..]).is_err());
+        assert!(parse_tile_metrics(&[4u8, 10][..]).is_err());
+        assert!(parse_tile_metrics(&[2u8, 10, 1, 0, 1][..]).is_err());
+    }
+}
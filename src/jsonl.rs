@@ -0,0 +1,193 @@
+//! Writes parsed sequence identifiers as JSON Lines: one JSON object per line, per identifier.
+//! Records are written as they arrive, so arbitrarily large files can be piped into `jq` or log
+//! pipelines without loading them into memory. The camera is `null` for tiles that don't have
+//! one. Field names and whether the sample is split into a number and a sequence field are
+//! chosen with `OutputOptions`.
+
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+use options::OutputOptions;
+use super::{Sample, SequenceIdentifier};
+
+/// Writes sequence identifiers as JSON objects, one per line. Sample numbers are written as
+/// numbers and sample sequences as strings.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::jsonl::JsonLinesWriter;
+///
+/// fn main() {
+///     let line = "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 1:N:0:TAAGGC";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     let mut writer = JsonLinesWriter::new(vec![]);
+///     writer.write(&seq_id).unwrap();
+///     let output = String::from_utf8(writer.into_inner()).unwrap();
+///     assert!(output.starts_with(r#"{"sequencer_id":"NS500358","run_count":204,"#));
+///     assert!(output.ends_with("\"sample\":\"TAAGGC\"}\n"));
+/// }
+/// ```
+pub struct JsonLinesWriter<W: Write> {
+    writer: W,
+    options: OutputOptions
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    /// Creates a writer that uses the default output options
+    pub fn new(writer: W) -> JsonLinesWriter<W> {
+        JsonLinesWriter::with_options(writer, OutputOptions::default())
+    }
+
+    /// Creates a writer that names and shapes fields according to the options
+    pub fn with_options(writer: W, options: OutputOptions) -> JsonLinesWriter<W> {
+        JsonLinesWriter { writer, options }
+    }
+
+    /// Writes a single sequence identifier as a line of JSON
+    pub fn write(&mut self, seq_id: &SequenceIdentifier) -> io::Result<()> {
+        writeln!(self.writer, "{}", to_json_with(seq_id, &self.options))
+    }
+
+    /// Flushes the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Writes all of the sequence identifiers as JSON Lines
+pub fn to_jsonl<'a, W, I>(writer: W, seq_ids: I) -> io::Result<()>
+    where W: Write, I: IntoIterator<Item=&'a SequenceIdentifier> {
+    let mut writer = JsonLinesWriter::new(writer);
+    for seq_id in seq_ids {
+        writer.write(seq_id)?;
+    }
+    writer.flush()
+}
+
+/// Formats a sequence identifier as a single JSON object
+pub fn to_json(seq_id: &SequenceIdentifier) -> String {
+    to_json_with(seq_id, &OutputOptions::default())
+}
+
+/// Formats a sequence identifier as a single JSON object, with fields named and shaped according
+/// to the options
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::jsonl::to_json_with;
+/// use illumina_coordinates::options::{FieldNaming, OutputOptions};
+///
+/// fn main() {
+///     let line = "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 1:N:0:TAAGGC";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     let options = OutputOptions { naming: FieldNaming::CamelCase, split_sample: true };
+///     let json = to_json_with(&seq_id, &options);
+///     assert!(json.starts_with(r#"{"sequencerId":"NS500358","runCount":204,"#));
+///     assert!(json.ends_with(r#""sampleNumber":null,"sampleSequence":"TAAGGC"}"#));
+/// }
+/// ```
+pub fn to_json_with(seq_id: &SequenceIdentifier, options: &OutputOptions) -> String {
+    let mut values = vec![
+        escape(&seq_id.sequencer_id),
+        seq_id.run_count.to_string(),
+        escape(&seq_id.flow_cell_id),
+        seq_id.lane.to_string(),
+        seq_id.side.to_string(),
+        seq_id.swath.to_string(),
+        seq_id.camera.map(|camera| camera.to_string()).unwrap_or_else(|| "null".to_string()),
+        seq_id.tile.to_string(),
+        seq_id.x.to_string(),
+        seq_id.y.to_string(),
+        seq_id.read.to_string(),
+        seq_id.is_filtered.to_string(),
+        seq_id.control_number.to_string()
+    ];
+    match seq_id.sample {
+        Sample::Number(n) if options.split_sample => values.extend(vec![n.to_string(), "null".to_string()]),
+        Sample::Sequence(ref sequence) if options.split_sample => values.extend(vec!["null".to_string(), escape(sequence)]),
+        Sample::Other(ref text) if options.split_sample => values.extend(vec!["null".to_string(), escape(text)]),
+        Sample::Number(n) => values.push(n.to_string()),
+        Sample::Sequence(ref sequence) => values.push(escape(sequence)),
+        Sample::Other(ref text) => values.push(escape(text))
+    }
+    let mut json = String::from("{");
+    for (index, (name, value)) in options.field_names().into_iter().zip(values).enumerate() {
+        if index > 0 {
+            json.push(',');
+        }
+        let _ = write!(json, "\"{}\":{}", name, value);
+    }
+    json.push('}');
+    json
+}
+
+/// Formats a string as a quoted JSON string
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            },
+            c => escaped.push(c)
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use options::FieldNaming;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_to_jsonl() {
+        let seq_ids = vec![
+            parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap(),
+            parse_sequence_identifier("@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC").unwrap(),
+        ];
+        let mut output = vec![];
+        to_jsonl(&mut output, &seq_ids).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines, vec![
+            r#"{"sequencer_id":"M03745","run_count":11,"flow_cell_id":"000000000-B54L5","lane":1,"side":2,"swath":1,"camera":null,"tile":8,"x":4127,"y":8949,"read":1,"is_filtered":false,"control_number":0,"sample":0}"#,
+            r#"{"sequencer_id":"NS500358","run_count":204,"flow_cell_id":"HTN5KAFXY","lane":1,"side":1,"swath":1,"camera":1,"tile":1,"x":20886,"y":1073,"read":2,"is_filtered":true,"control_number":0,"sample":"TAAGGC"}"#,
+        ]);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("M03745"), "\"M03745\"");
+        assert_eq!(escape("a\"b\\c"), "\"a\\\"b\\\\c\"");
+        assert_eq!(escape("\u{1}"), "\"\\u0001\"");
+    }
+
+    #[test]
+    fn test_to_json_with() {
+        let seq_id = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:3").unwrap();
+        let split = OutputOptions { split_sample: true, ..OutputOptions::new() };
+        assert!(to_json_with(&seq_id, &split).ends_with(r#""control_number":0,"sample_number":3,"sample_sequence":null}"#));
+        let camel = OutputOptions { naming: FieldNaming::CamelCase, ..OutputOptions::new() };
+        assert_eq!(to_json_with(&seq_id, &camel),
+                   r#"{"sequencerId":"M03745","runCount":11,"flowCellId":"000000000-B54L5","lane":1,"side":2,"swath":1,"camera":null,"tile":8,"x":4127,"y":8949,"read":1,"isFiltered":false,"controlNumber":0,"sample":3}"#);
+        assert_eq!(to_json_with(&seq_id, &OutputOptions::new()), to_json(&seq_id));
+    }
+}
@@ -0,0 +1,232 @@
+//! A sequence identifier that defers parsing each field until it's needed.
+
+use super::{parse_number, parse_tile_field, IlluminaError, Sample, SequenceIdentifier};
+
+const SEQUENCER_ID: usize = 0;
+const RUN_COUNT: usize = 1;
+const FLOW_CELL_ID: usize = 2;
+const LANE: usize = 3;
+const TILE: usize = 4;
+const X: usize = 5;
+const Y: usize = 6;
+const READ: usize = 7;
+const IS_FILTERED: usize = 8;
+const CONTROL_NUMBER: usize = 9;
+const SAMPLE: usize = 10;
+const FIELD_COUNT: usize = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// A sequence identifier that only records where each field is when it's created, and parses a
+/// field when its accessor is called. This is faster than `parse_sequence_identifier` for code
+/// that only looks at a field or two of each read.
+///
+/// Creating one checks that the identifier has the right number of fields, so accessors only fail
+/// if their own field is malformed.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::lazy::LazySequenceIdentifier;
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = LazySequenceIdentifier::new(line).unwrap();
+///     assert_eq!(seq_id.tile().unwrap(), 8);
+///     assert_eq!(seq_id.x().unwrap(), 4127);
+///     assert_eq!(seq_id.flow_cell_id(), "000000000-B54L5");
+/// }
+/// ```
+pub struct LazySequenceIdentifier<'a> {
+    text: &'a str,
+    fields: [(u32, u32); FIELD_COUNT]
+}
+
+impl<'a> LazySequenceIdentifier<'a> {
+    /// Finds the fields of a sequence identifier without parsing them
+    pub fn new(text: &'a str) -> Result<LazySequenceIdentifier<'a>, IlluminaError> {
+        let text = text.trim();
+        // Tab-separated fields after the comment are extra tags
+        let text = match text.find(' ') {
+            Some(space) => &text[..text[space..].find('\t').map_or(text.len(), |tab| space + tab)],
+            None => text
+        };
+        let mut fields = [(0, 0); FIELD_COUNT];
+        let mut count = 0;
+        let mut start = 0;
+        for (position, byte) in text.bytes().enumerate() {
+            let is_separator = match byte {
+                b':' => true,
+                b' ' => {
+                    // The space separates the seven fields of the first half from the second half
+                    if count != TILE + 2 {
+                        return Err(IlluminaError::SplitError);
+                    }
+                    true
+                },
+                _ => false
+            };
+            if is_separator {
+                if count == FIELD_COUNT - 1 {
+                    return Err(IlluminaError::SplitError);
+                }
+                fields[count] = (start as u32, position as u32);
+                count += 1;
+                start = position + 1;
+            }
+        }
+        if count != FIELD_COUNT - 1 || !text.contains(' ') {
+            return Err(IlluminaError::SplitError);
+        }
+        fields[count] = (start as u32, text.len() as u32);
+        Ok(LazySequenceIdentifier { text, fields })
+    }
+
+    fn field(&self, index: usize) -> &'a str {
+        let (start, end) = self.fields[index];
+        &self.text[start as usize..end as usize]
+    }
+
+    /// The text of the identifier
+    pub fn as_str(&self) -> &'a str {
+        self.text
+    }
+
+    /// ID of the sequencing machine
+    pub fn sequencer_id(&self) -> Result<&'a str, IlluminaError> {
+        self.field(SEQUENCER_ID).get(1..).ok_or(IlluminaError::SplitError)
+    }
+
+    /// The number of sequencing runs this machine has performed
+    pub fn run_count(&self) -> Result<u16, IlluminaError> {
+        parse_number(self.field(RUN_COUNT))
+    }
+
+    /// ID of the flow cell
+    pub fn flow_cell_id(&self) -> &'a str {
+        self.field(FLOW_CELL_ID)
+    }
+
+    /// Lane number
+    pub fn lane(&self) -> Result<u8, IlluminaError> {
+        parse_number(self.field(LANE))
+    }
+
+    // The tile field is split the same way whichever part is asked for
+    fn tile_field(&self) -> Result<(u8, u8, Option<u8>, u8), IlluminaError> {
+        parse_tile_field(self.field(TILE), self.sequencer_id().unwrap_or(""))
+    }
+
+    /// The near or far side of the flow cell surface
+    pub fn side(&self) -> Result<u8, IlluminaError> {
+        Ok(self.tile_field()?.0)
+    }
+
+    /// The row within a lane
+    pub fn swath(&self) -> Result<u8, IlluminaError> {
+        Ok(self.tile_field()?.1)
+    }
+
+    /// The camera that imaged the tile, for sequencers that number tiles by camera
+    pub fn camera(&self) -> Result<Option<u8>, IlluminaError> {
+        Ok(self.tile_field()?.2)
+    }
+
+    /// The positional order of the tile within its swath
+    pub fn tile(&self) -> Result<u8, IlluminaError> {
+        Ok(self.tile_field()?.3)
+    }
+
+    /// The x-coordinate of the cluster
+    pub fn x(&self) -> Result<u16, IlluminaError> {
+        parse_number(self.field(X))
+    }
+
+    /// The y-coordinate of the cluster
+    pub fn y(&self) -> Result<u16, IlluminaError> {
+        parse_number(self.field(Y))
+    }
+
+    /// The read number
+    pub fn read(&self) -> Result<u8, IlluminaError> {
+        parse_number(self.field(READ))
+    }
+
+    /// Whether the read was filtered for low quality
+    pub fn is_filtered(&self) -> Result<bool, IlluminaError> {
+        match self.field(IS_FILTERED) {
+            "Y" => Ok(true),
+            "N" => Ok(false),
+            _ => Err(IlluminaError::ParseError)
+        }
+    }
+
+    /// The type of control, 0 = not a control read
+    pub fn control_number(&self) -> Result<u16, IlluminaError> {
+        parse_number(self.field(CONTROL_NUMBER))
+    }
+
+    /// Number from sample sheet, or the sequence if the read is in Undetermined Reads
+    pub fn sample(&self) -> Sample {
+        Sample::from_field(self.field(SAMPLE))
+    }
+
+    /// Parses every field
+    pub fn parse(&self) -> Result<SequenceIdentifier, IlluminaError> {
+        Ok(SequenceIdentifier {
+            sequencer_id: self.sequencer_id()?.into(),
+            run_count: self.run_count()?,
+            flow_cell_id: self.flow_cell_id().into(),
+            lane: self.lane()?,
+            side: self.side()?,
+            swath: self.swath()?,
+            camera: self.camera()?,
+            tile: self.tile()?,
+            x: self.x()?,
+            y: self.y()?,
+            read: self.read()?,
+            is_filtered: self.is_filtered()?,
+            control_number: self.control_number()?,
+            sample: self.sample()
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use barcode::Barcode;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_fields() {
+        let line = "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC\n";
+        let seq_id = LazySequenceIdentifier::new(line).unwrap();
+        assert_eq!(seq_id.sequencer_id().unwrap(), "NS500358");
+        assert_eq!(seq_id.run_count().unwrap(), 204);
+        assert_eq!(seq_id.side().unwrap(), 1);
+        assert_eq!(seq_id.swath().unwrap(), 1);
+        assert_eq!(seq_id.camera().unwrap(), Some(1));
+        assert_eq!(seq_id.tile().unwrap(), 1);
+        assert_eq!(seq_id.y().unwrap(), 1073);
+        assert!(seq_id.is_filtered().unwrap());
+        assert_eq!(seq_id.sample(), Sample::Sequence(Barcode::new("TAAGGC").unwrap()));
+        assert_eq!(seq_id.parse().unwrap(), parse_sequence_identifier(line).unwrap());
+    }
+
+    #[test]
+    fn test_malformed() {
+        for line in &["", "@M03745:11:000000000-B54L5:1:2108:4127:8949", "@M03745:11:000000000-B54L5:1:2108:4127 8949:1:N:0:0",
+                      "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0:0", "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0 0",
+                      "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0"] {
+            assert!(LazySequenceIdentifier::new(line).is_err(), "{}", line);
+            assert!(parse_sequence_identifier(line).is_err(), "{}", line);
+        }
+        // Only the malformed field fails
+        let seq_id = LazySequenceIdentifier::new("@M03745:11:000000000-B54L5:1:2é:4127:8949 1:N:0:0").unwrap();
+        assert_eq!(seq_id.x().unwrap(), 4127);
+        assert!(seq_id.swath().is_err());
+        assert!(seq_id.parse().is_err());
+    }
+}
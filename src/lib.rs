@@ -9,35 +9,285 @@
 
 #![crate_type="lib"]
 #![deny(warnings, missing_docs)]
-use std::convert::From;
+#[cfg(feature = "bincode")]
+extern crate bincode;
+#[cfg(feature = "bio")]
+extern crate bio;
+#[cfg(feature = "ndarray")]
+extern crate ndarray;
+#[cfg(feature = "noodles-fastq")]
+extern crate noodles_fastq;
+#[cfg(feature = "noodles-sam")]
+extern crate noodles_sam;
+#[cfg(feature = "polars")]
+extern crate polars;
+#[cfg(feature = "postcard")]
+extern crate postcard;
+#[cfg(feature = "testing")]
+extern crate proptest;
+#[cfg(feature = "testing")]
+extern crate quickcheck;
+#[cfg(feature = "rust-htslib")]
+extern crate rust_htslib;
+#[cfg(feature = "seq_io")]
+extern crate seq_io;
+#[cfg(feature = "serde")]
+extern crate serde;
+use std::borrow::Cow;
+use std::convert::{From, TryFrom};
+use std::error;
+use std::fmt;
 use std::result::Result;
+use std::io;
 use std::num;
+use std::str;
+use barcode::Barcode;
+use control::ControlFlags;
+use flow_cell::FlowCellType;
+use geometry::FlowCellLayout;
+use id_string::IdString;
+use instrument::InstrumentModel;
+use options::ParseOptions;
+use position::PositionKey;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+pub mod anonymize;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod artifacts;
+pub mod barcode;
+pub mod consistency;
+pub mod control;
+pub mod count_min;
+pub mod cross_tile;
+pub mod batch;
+pub mod bgzf;
+pub mod bloom;
+#[cfg(feature = "cache")]
+pub mod cache;
+#[cfg(feature = "csv")]
+pub mod csv;
+#[cfg(feature = "polars")]
+pub mod dataframe;
+mod deflate;
+pub mod dedupe;
+pub mod density;
+pub mod demux;
+pub mod distinct;
+pub mod external_sort;
+pub mod fastq;
+pub mod file_role;
+pub mod filter;
+pub mod flow_cell;
+#[cfg(feature = "testing")]
+pub mod generate;
+pub mod geometry;
+pub mod gzip;
+pub mod header_format;
+pub mod id_string;
+pub mod index;
+pub mod index_hopping;
+pub mod instrument;
+pub mod intern;
+pub mod interop;
+pub mod jsonl;
+pub mod lazy;
+pub mod merge;
+pub mod mmap;
+pub mod options;
+pub mod parallel;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod patterned;
+#[cfg(feature = "plotters")]
+pub mod plot;
+pub mod position;
+pub mod position_stream;
+pub mod read_group;
+pub mod read_name;
+pub mod recover;
+pub mod rewrite;
+pub mod run_info;
+pub mod sample_counts;
+pub mod sample_sheet;
+pub mod split;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stats;
+pub mod subsample;
+pub mod svg;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod validate;
 
-#[derive(Debug, PartialOrd, PartialEq)]
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// Sample numbers are either the number from the sample sheet or a sequence if the read was from
 /// the Undetermined Reads
 pub enum Sample {
     /// Sample number
     Number(u8),
     /// Sequence from Undetermined Reads
-    Sequence(String)
+    Sequence(Barcode),
+    /// Any other sample field, kept as written: a sample number above 255, or an index sequence
+    /// that isn't a barcode, such as one in lower case or with indexes joined by `-`
+    Other(String)
 }
 
-/// A parsed sequence identifier
+impl Sample {
+    /// Interprets the sample field of a sequence identifier: a number if it is one that fits, a
+    /// barcode if it's a valid one and as written otherwise
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::Sample;
+    ///
+    /// fn main() {
+    ///     assert_eq!(Sample::from_field("3"), Sample::Number(3));
+    ///     assert_eq!(Sample::from_field("TAAGGC+CTTAGG").as_barcode().map(|barcode| barcode.as_str()), Some("TAAGGC+CTTAGG"));
+    ///     assert_eq!(Sample::from_field("300"), Sample::Other("300".to_string()));
+    /// }
+    /// ```
+    pub fn from_field(field: &str) -> Sample {
+        if let Ok(n) = parse_number::<u8>(field) {
+            return Sample::Number(n);
+        }
+        match Barcode::new(field) {
+            Ok(barcode) => Sample::Sequence(barcode),
+            Err(_) => Sample::Other(field.to_string())
+        }
+    }
+
+    /// The sample number, if the sample is a number
+    pub fn as_number(&self) -> Option<u8> {
+        match *self {
+            Sample::Number(n) => Some(n),
+            Sample::Sequence(_) | Sample::Other(_) => None
+        }
+    }
+
+    /// The index sequence, if the sample is a sequence. Other sample fields are returned as
+    /// written, except for sample numbers too large for a `u8`, which aren't sequences.
+    pub fn as_sequence(&self) -> Option<&str> {
+        match *self {
+            Sample::Number(_) => None,
+            Sample::Sequence(ref sequence) => Some(sequence),
+            Sample::Other(ref text) if !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_digit()) => None,
+            Sample::Other(ref text) => Some(text)
+        }
+    }
+
+    /// The sample field as written, if the sample isn't a number that fits in a `u8`
+    pub fn as_text(&self) -> Option<&str> {
+        match *self {
+            Sample::Number(_) => None,
+            Sample::Sequence(ref sequence) => Some(sequence),
+            Sample::Other(ref text) => Some(text)
+        }
+    }
+
+    /// The barcode, if the sample is a sequence
+    pub fn as_barcode(&self) -> Option<&Barcode> {
+        match *self {
+            Sample::Sequence(ref barcode) => Some(barcode),
+            Sample::Number(_) | Sample::Other(_) => None
+        }
+    }
+
+    /// The i7 and i5 indexes, if the sample is a dual-index sequence such as `TAAGGC+CTTAGG`
+    pub fn index_pair(&self) -> Option<(&str, &str)> {
+        let sequence = self.as_sequence()?;
+        let plus = sequence.find('+')?;
+        Some((&sequence[..plus], &sequence[plus + 1..]))
+    }
+
+    /// The positions of the `N` bases in the index sequence, counting from 0, or an empty list if
+    /// the sample is a number. The `+` between dual indexes counts as a position, so positions
+    /// index into `as_sequence`. Many `N`s point to a poor-quality index read.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::barcode::Barcode;
+    /// use illumina_coordinates::Sample;
+    ///
+    /// fn main() {
+    ///     let sample = Sample::Sequence(Barcode::new("TANGGC+CTTAGN").unwrap());
+    ///     assert_eq!(sample.ambiguous_positions(), vec![2, 12]);
+    ///     assert_eq!(sample.ambiguous_count(), 2);
+    ///     assert!(sample.has_ambiguous());
+    ///     assert!(!Sample::Number(1).has_ambiguous());
+    /// }
+    /// ```
+    pub fn ambiguous_positions(&self) -> Vec<usize> {
+        self.as_sequence()
+            .map(|sequence| sequence.bytes().enumerate().filter(|&(_, base)| base == b'N').map(|(position, _)| position).collect())
+            .unwrap_or_default()
+    }
+
+    /// The number of `N` bases in the index sequence, or 0 if the sample is a number
+    pub fn ambiguous_count(&self) -> usize {
+        self.as_sequence().map_or(0, |sequence| sequence.bytes().filter(|&base| base == b'N').count())
+    }
+
+    /// Whether the index sequence has any `N` bases
+    pub fn has_ambiguous(&self) -> bool {
+        self.ambiguous_count() > 0
+    }
+
+    /// Whether the read is from the Undetermined Reads. Samples from the sample sheet are
+    /// numbered from 1, so this is the case for sample 0 as well as for sequences, but not for
+    /// sample numbers above 255.
+    pub fn is_undetermined(&self) -> bool {
+        match *self {
+            Sample::Number(n) => n == 0,
+            Sample::Sequence(_) => true,
+            Sample::Other(ref text) => text.parse::<u64>().is_err()
+        }
+    }
+}
+
+impl fmt::Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Sample::Number(n) => write!(f, "{}", n),
+            Sample::Sequence(ref sequence) => write!(f, "{}", sequence),
+            Sample::Other(ref text) => write!(f, "{}", text)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+/// A parsed sequence identifier. Its `Display` implementation always writes it in the CASAVA 1.8
+/// format, whichever format it was parsed from; `header_format::HeaderStyle::header` writes the
+/// other formats.
+///
+/// Fields may be added in minor releases, so outside this crate, identifiers can only be built
+/// by parsing or with `from_parts`. The fields are still public, but the getters of the same
+/// names are preferred, as the fields may become private in a future major release.
 pub struct SequenceIdentifier {
     /// ID of the sequencing machine
-    pub sequencer_id: String,
+    pub sequencer_id: IdString,
     /// The number of sequencing runs this machine has performed
     pub run_count: u16,
     /// ID of the flow cell, printed on the side of the glass slide
-    pub flow_cell_id: String,
+    pub flow_cell_id: IdString,
     /// Lane number. For MiSeqs, this is always 1
     pub lane: u8,
     /// The near or far side off the flow cell surface
     pub side: u8,
     /// The row within a lane, if wide enough. For MiSeqs, this is always 1
     pub swath: u8,
+    /// The camera that imaged the tile, for sequencers that number tiles by camera (the NextSeq
+    /// 500 and 550 and the MiniSeq). For other sequencers, this is `None`.
+    pub camera: Option<u8>,
     /// The positional order of the region where the cluster is located
     pub tile: u8,
     /// The x-coordinate of the cluster
@@ -48,27 +298,430 @@ pub struct SequenceIdentifier {
     pub read: u8,
     /// Whether the read was filtered for low quality (Y=filtered)
     pub is_filtered: bool,
-    /// Indicates the type of control, 0 = not a control read
-    pub control_number: u8,
+    /// Indicates the type of control, 0 = not a control read. This is a bitfield, which
+    /// `control_flags` decodes.
+    pub control_number: u16,
     /// Number from sample sheet, or the sequence if the read is in Undetermined Reads
     pub sample: Sample
 }
 
+impl SequenceIdentifier {
+    /// Creates an identifier for the first read of a cluster at the position, unfiltered, not a
+    /// control and sample 0. Returns an error if the identifier wouldn't parse back to the same
+    /// fields: the IDs must be non-empty without colons or whitespace, and the tile must be
+    /// writable into the tile field, with a camera only if the sequencer numbers tiles by camera.
+    /// Like the parser, a sequencer that numbers tiles by camera may have tiles of two digits
+    /// without one.
+    ///
+    /// Whether the position is on the flow cell isn't checked; `validate::validate` does that.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::{SequenceIdentifier, TileId};
+    ///
+    /// fn main() {
+    ///     let tile = TileId { lane: 1, side: 2, swath: 1, camera: None, tile: 8 };
+    ///     let seq_id = SequenceIdentifier::new("M03745", 11, "000000000-B54L5", tile, 4127, 8949).unwrap();
+    ///     assert_eq!(seq_id.to_string(), "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0");
+    ///     let moved = seq_id.with_position(tile, 4128, 8950).unwrap();
+    ///     assert_eq!(moved.x, 4128);
+    ///     assert!(SequenceIdentifier::new("M03745", 11, "000000000:B54L5", tile, 4127, 8949).is_err());
+    /// }
+    /// ```
+    pub fn new(sequencer_id: &str, run_count: u16, flow_cell_id: &str, tile: TileId, x: u16, y: u16)
+            -> Result<SequenceIdentifier, IlluminaError> {
+        for &(name, id) in &[("sequencer", sequencer_id), ("flow cell", flow_cell_id)] {
+            if id.is_empty() || id.contains(|c: char| c == ':' || c.is_whitespace()) {
+                return Err(IlluminaError::FormatError(format!("invalid {} ID: {:?}", name, id)));
+            }
+        }
+        let seq_id = SequenceIdentifier {
+            sequencer_id: IdString::from(sequencer_id),
+            run_count,
+            flow_cell_id: IdString::from(flow_cell_id),
+            lane: 1,
+            side: 1,
+            swath: 1,
+            camera: None,
+            tile: 1,
+            x: 0,
+            y: 0,
+            read: 1,
+            is_filtered: false,
+            control_number: 0,
+            sample: Sample::Number(0)
+        };
+        seq_id.with_position(tile, x, y)
+    }
+
+    /// Returns a copy of the identifier moved to another position, checking the tile like `new`
+    pub fn with_position(&self, tile: TileId, x: u16, y: u16) -> Result<SequenceIdentifier, IlluminaError> {
+        let has_cameras = matches!(self.instrument(), Some(model) if model.has_cameras());
+        let valid = tile.side <= 9 && tile.swath <= 9 && match tile.camera {
+            Some(camera) => has_cameras && camera <= 9 && tile.tile <= 99,
+            // A longer tile field would be read back with a camera
+            None => !has_cameras || tile.tile <= 99
+        };
+        if !valid {
+            return Err(IlluminaError::FormatError(format!("tile can't be written in a sequence identifier: {:?}", tile)));
+        }
+        Ok(SequenceIdentifier {
+            lane: tile.lane,
+            side: tile.side,
+            swath: tile.swath,
+            camera: tile.camera,
+            tile: tile.tile,
+            x,
+            y,
+            ..self.clone()
+        })
+    }
+
+    /// Creates an identifier for a read of the cluster
+    pub fn from_parts(cluster: ClusterKey, read: u8, is_filtered: bool, control_number: u16, sample: Sample)
+            -> SequenceIdentifier {
+        SequenceIdentifier {
+            sequencer_id: cluster.sequencer_id,
+            run_count: cluster.run_count,
+            flow_cell_id: cluster.flow_cell_id,
+            lane: cluster.lane,
+            side: cluster.side,
+            swath: cluster.swath,
+            camera: cluster.camera,
+            tile: cluster.tile,
+            x: cluster.x,
+            y: cluster.y,
+            read,
+            is_filtered,
+            control_number,
+            sample
+        }
+    }
+
+    /// Parses an identifier that a FASTQ library has already split into the read name and the
+    /// comment after the space, without joining them back together. With the `bio` feature,
+    /// rust-bio's `fastq::Record` converts through `TryFrom` this way. The name may start with `@`. Without a comment, the
+    /// read is taken to be an unfiltered, non-control first read of sample 0.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::SequenceIdentifier;
+    ///
+    /// fn main() {
+    ///     let seq_id = SequenceIdentifier::from_id_and_desc("M03745:11:000000000-B54L5:1:2108:4127:8949", Some("2:Y:0:0")).unwrap();
+    ///     assert_eq!((seq_id.x, seq_id.read, seq_id.is_filtered), (4127, 2, true));
+    ///     assert_eq!(SequenceIdentifier::from_id_and_desc("M03745:11:000000000-B54L5:1:2108:4127:8949", None).unwrap().read, 1);
+    /// }
+    /// ```
+    pub fn from_id_and_desc(id: &str, desc: Option<&str>) -> Result<SequenceIdentifier, IlluminaError> {
+        let id = id.trim();
+        let mut seq_id = parse_read_name_borrowed(id.strip_prefix('@').unwrap_or(id))?;
+        if let Some(desc) = desc {
+            let desc = desc.trim();
+            // Tab-separated fields after the comment are extra tags
+            let desc = &desc[..desc.find('\t').unwrap_or(desc.len())];
+            parse_comment(&mut seq_id, desc, &ParseOptions::default())?;
+        }
+        Ok(seq_id.into_owned())
+    }
+
+    /// Parses an identifier from the name and description of a FASTQ record as raw bytes, like
+    /// `from_id_and_desc`. An empty description is taken to mean there's no comment. With the
+    /// `noodles-fastq` feature, noodles' `fastq::Record` converts through `TryFrom` this way.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::SequenceIdentifier;
+    ///
+    /// fn main() {
+    ///     let name = b"M03745:11:000000000-B54L5:1:2108:4127:8949";
+    ///     let seq_id = SequenceIdentifier::from_name_and_description(name, b"1:N:0:ACGT").unwrap();
+    ///     assert_eq!(seq_id.sample.as_sequence(), Some("ACGT"));
+    ///     assert_eq!(SequenceIdentifier::from_name_and_description(name, b"").unwrap().y, 8949);
+    /// }
+    /// ```
+    pub fn from_name_and_description(name: &[u8], description: &[u8]) -> Result<SequenceIdentifier, IlluminaError> {
+        let text = |bytes| str::from_utf8(bytes)
+            .map_err(|_| IlluminaError::FormatError("sequence identifier isn't valid UTF-8".to_string()));
+        let description = text(description)?;
+        SequenceIdentifier::from_id_and_desc(text(name)?, if description.is_empty() { None } else { Some(description) })
+    }
+
+    /// ID of the sequencing machine
+    pub fn sequencer_id(&self) -> &str {
+        &self.sequencer_id
+    }
+
+    /// The number of sequencing runs this machine has performed
+    pub fn run_count(&self) -> u16 {
+        self.run_count
+    }
+
+    /// ID of the flow cell, printed on the side of the glass slide
+    pub fn flow_cell_id(&self) -> &str {
+        &self.flow_cell_id
+    }
+
+    /// Lane number. For MiSeqs, this is always 1
+    pub fn lane(&self) -> u8 {
+        self.lane
+    }
+
+    /// The near or far side off the flow cell surface
+    pub fn side(&self) -> u8 {
+        self.side
+    }
+
+    /// The row within a lane, if wide enough. For MiSeqs, this is always 1
+    pub fn swath(&self) -> u8 {
+        self.swath
+    }
+
+    /// The camera that imaged the tile, for sequencers that number tiles by camera
+    pub fn camera(&self) -> Option<u8> {
+        self.camera
+    }
+
+    /// The positional order of the region where the cluster is located
+    pub fn tile(&self) -> u8 {
+        self.tile
+    }
+
+    /// The x-coordinate of the cluster
+    pub fn x(&self) -> u16 {
+        self.x
+    }
+
+    /// The y-coordinate of the cluster
+    pub fn y(&self) -> u16 {
+        self.y
+    }
+
+    /// The read number
+    pub fn read(&self) -> u8 {
+        self.read
+    }
+
+    /// Whether the read was filtered for low quality (Y=filtered)
+    pub fn is_filtered(&self) -> bool {
+        self.is_filtered
+    }
+
+    /// Indicates the type of control, 0 = not a control read
+    pub fn control_number(&self) -> u16 {
+        self.control_number
+    }
+
+    /// Number from sample sheet, or the sequence if the read is in Undetermined Reads
+    pub fn sample(&self) -> &Sample {
+        &self.sample
+    }
+
+    /// Returns a key identifying the cluster this read came from. Mates from a paired-end run
+    /// have the same key, so it can be used to match them up in a `HashMap`.
+    pub fn cluster_key(&self) -> ClusterKey {
+        ClusterKey {
+            sequencer_id: self.sequencer_id.clone(),
+            run_count: self.run_count,
+            flow_cell_id: self.flow_cell_id.clone(),
+            lane: self.lane,
+            side: self.side,
+            swath: self.swath,
+            camera: self.camera,
+            tile: self.tile,
+            x: self.x,
+            y: self.y
+        }
+    }
+
+    /// Returns the tile this read came from
+    pub fn tile_id(&self) -> TileId {
+        TileId {
+            lane: self.lane,
+            side: self.side,
+            swath: self.swath,
+            camera: self.camera,
+            tile: self.tile
+        }
+    }
+
+    /// The tile field as it's written in the sequence identifier, combining the side, swath,
+    /// camera and tile, e.g. 2108 or 11101. Picard and samtools refer to tiles by this number.
+    pub fn tile_field(&self) -> u32 {
+        self.tile_id().tile_field()
+    }
+
+    /// Returns the flow cell, lane, tile and coordinates of the cluster, for joining with tables
+    /// built from other sources
+    pub fn position_key(&self) -> PositionKey {
+        PositionKey::from(self)
+    }
+
+    /// The ID of the read's SAM read group, `<flow cell>.<lane>`, as used by GATK and most
+    /// aligner wrappers. `read_group::ReadGroup` builds the whole `@RG` header line.
+    pub fn read_group_id(&self) -> String {
+        format!("{}.{}", self.flow_cell_id, self.lane)
+    }
+
+    /// The model of sequencer that produced this read, inferred from the sequencer ID
+    pub fn instrument(&self) -> Option<InstrumentModel> {
+        InstrumentModel::from_sequencer_id(&self.sequencer_id)
+    }
+
+    /// The type of flow cell the read was sequenced on, inferred from the flow cell ID
+    pub fn flow_cell_type(&self) -> Option<FlowCellType> {
+        FlowCellType::from_flow_cell_id(&self.flow_cell_id)
+    }
+
+    /// The standard layout of the read's flow cell, if its type can be inferred from the flow
+    /// cell ID. Whether the read's tile fits the layout is a quick check for garbled
+    /// identifiers.
+    pub fn flow_cell_layout(&self) -> Option<FlowCellLayout> {
+        self.flow_cell_type().map(|flow_cell| flow_cell.layout())
+    }
+
+    /// The control bits of the read
+    pub fn control_flags(&self) -> ControlFlags {
+        ControlFlags(self.control_number)
+    }
+
+    /// Whether the other read came from the same cluster but has a different read number
+    pub fn is_mate_of(&self, other: &SequenceIdentifier) -> bool {
+        self.read != other.read
+            && self.x == other.x
+            && self.y == other.y
+            && self.tile == other.tile
+            && self.camera == other.camera
+            && self.swath == other.swath
+            && self.side == other.side
+            && self.lane == other.lane
+            && self.run_count == other.run_count
+            && self.flow_cell_id == other.flow_cell_id
+            && self.sequencer_id == other.sequencer_id
+    }
+}
+
+impl fmt::Display for SequenceIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "@{}:{}:{}:{}:{}{}",
+               self.sequencer_id,
+               self.run_count,
+               self.flow_cell_id,
+               self.lane,
+               self.side,
+               self.swath)?;
+        if let Some(camera) = self.camera {
+            write!(f, "{}", camera)?;
+        }
+        write!(f, "{:02}:{}:{} {}:{}:{}:{}",
+               self.tile,
+               self.x,
+               self.y,
+               self.read,
+               if self.is_filtered { "Y" } else { "N" },
+               self.control_number,
+               self.sample)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// The fields of a sequence identifier that locate its cluster, ignoring the read number and
+/// filter flag. R1 and R2 mates have equal keys.
+pub struct ClusterKey {
+    /// ID of the sequencing machine
+    pub sequencer_id: IdString,
+    /// The number of sequencing runs this machine has performed
+    pub run_count: u16,
+    /// ID of the flow cell
+    pub flow_cell_id: IdString,
+    /// Lane number
+    pub lane: u8,
+    /// The near or far side off the flow cell surface
+    pub side: u8,
+    /// The row within a lane
+    pub swath: u8,
+    /// The camera that imaged the tile, for sequencers that number tiles by camera (the NextSeq
+    /// 500 and 550 and the MiniSeq). For other sequencers, this is `None`.
+    pub camera: Option<u8>,
+    /// The positional order of the region where the cluster is located
+    pub tile: u8,
+    /// The x-coordinate of the cluster
+    pub x: u16,
+    /// The y-coordinate of the cluster
+    pub y: u16
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// Identifies a single tile on a flow cell
+pub struct TileId {
+    /// Lane number
+    pub lane: u8,
+    /// The near or far side off the flow cell surface
+    pub side: u8,
+    /// The row within a lane
+    pub swath: u8,
+    /// The camera that imaged the tile, for sequencers that number tiles by camera (the NextSeq
+    /// 500 and 550 and the MiniSeq). For other sequencers, this is `None`.
+    pub camera: Option<u8>,
+    /// The positional order of the tile within its swath
+    pub tile: u8
+}
+
+impl TileId {
+    /// The tile field as it's written in sequence identifiers, e.g. 2108 or 11101
+    pub fn tile_field(&self) -> u32 {
+        let (scale, number) = match self.camera {
+            Some(camera) => (1000, u32::from(camera) * 100 + u32::from(self.tile)),
+            None => (if self.tile < 100 { 100 } else { 1000 }, u32::from(self.tile))
+        };
+        u32::from(self.side) * scale * 10 + u32::from(self.swath) * scale + number
+    }
+}
+
 #[derive(Debug)]
-/// Errors encountered when parsing FASTQ files
+/// Errors encountered when parsing FASTQ files and run metadata
 pub enum IlluminaError {
     /// We expected an integer but did not find one
     ParseError,
     /// The line was not structured as expected
-    SplitError
+    SplitError,
+    /// A file could not be read
+    IoError(io::Error),
+    /// A run metadata file was not structured as expected
+    FormatError(String)
+}
+
+impl fmt::Display for IlluminaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IlluminaError::ParseError => write!(f, "expected an integer"),
+            IlluminaError::SplitError => write!(f, "sequence identifier is not structured as expected"),
+            IlluminaError::IoError(ref error) => write!(f, "{}", error),
+            IlluminaError::FormatError(ref message) => write!(f, "{}", message)
+        }
+    }
 }
 
+impl error::Error for IlluminaError {}
+
 impl From<num::ParseIntError> for IlluminaError {
     fn from(_: num::ParseIntError) -> IlluminaError {
         IlluminaError::ParseError
     }
 }
 
+impl From<io::Error> for IlluminaError {
+    fn from(error: io::Error) -> IlluminaError {
+        IlluminaError::IoError(error)
+    }
+}
+
 /// Parses location information from an Illumina sequence identifier. This implementation is
 /// about 3x faster than using a regular expression.
 ///
@@ -88,6 +741,8 @@ impl From<num::ParseIntError> for IlluminaError {
 ///                     wide, and the first pass from left-to-right is swath one, then the returning pass on the other
 ///                     side of the lane is swath two
 ///                     the last two digits are the order of the tile. For MiSeqs, this is a number from 1 to 19
+///                     NextSeq 500s and 550s and MiniSeqs write five digits, with the camera that imaged the tile
+///                     before the last two
 ///
 /// 4127                the x-position of the read in the tile, in arbitrary units
 ///
@@ -103,6 +758,12 @@ impl From<num::ParseIntError> for IlluminaError {
 ///
 /// See https://help.basespace.illumina.com/articles/descriptive/fastq-files/ for more information.
 ///
+/// Fields after a tab at the end of the identifier, such as the SAM tags that `samtools fastq -T`
+/// appends, are ignored.
+///
+/// Malformed input of any kind, including truncated fields, non-ASCII text and numbers too large
+/// for their field, returns an error. This function never panics.
+///
 /// # Example
 ///
 /// ```rust
@@ -128,62 +789,302 @@ impl From<num::ParseIntError> for IlluminaError {
 /// }
 /// ```
 pub fn parse_sequence_identifier(text: &str) -> Result<SequenceIdentifier, IlluminaError> {
-    let halves: Vec<&str> = text.trim().split(' ').collect();
-    if halves.len() != 2 {
-        return Err(IlluminaError::SplitError)
+    parse_sequence_identifier_borrowed(text).map(|seq_id| seq_id.into_owned())
+}
+
+/// Parses a sequence identifier like `parse_sequence_identifier`, relaxing the rules that the
+/// options turn off
+pub fn parse_sequence_identifier_with(text: &str, options: &ParseOptions) -> Result<SequenceIdentifier, IlluminaError> {
+    parse_sequence_identifier_borrowed_with(text, options).map(|seq_id| seq_id.into_owned())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A parsed sequence identifier whose sequencer and flow cell IDs borrow from the text it was
+/// parsed from, so that parsing doesn't allocate for them. Call `into_owned` to keep it beyond
+/// the lifetime of the text.
+pub struct BorrowedSequenceIdentifier<'a> {
+    /// ID of the sequencing machine
+    pub sequencer_id: Cow<'a, str>,
+    /// The number of sequencing runs this machine has performed
+    pub run_count: u16,
+    /// ID of the flow cell, printed on the side of the glass slide
+    pub flow_cell_id: Cow<'a, str>,
+    /// Lane number. For MiSeqs, this is always 1
+    pub lane: u8,
+    /// The near or far side off the flow cell surface
+    pub side: u8,
+    /// The row within a lane, if wide enough. For MiSeqs, this is always 1
+    pub swath: u8,
+    /// The camera that imaged the tile, for sequencers that number tiles by camera (the NextSeq
+    /// 500 and 550 and the MiniSeq). For other sequencers, this is `None`.
+    pub camera: Option<u8>,
+    /// The positional order of the region where the cluster is located
+    pub tile: u8,
+    /// The x-coordinate of the cluster
+    pub x: u16,
+    /// The y-coordinate of the cluster
+    pub y: u16,
+    /// The read number
+    pub read: u8,
+    /// Whether the read was filtered for low quality (Y=filtered)
+    pub is_filtered: bool,
+    /// Indicates the type of control, 0 = not a control read. This is a bitfield, which
+    /// `control_flags` decodes.
+    pub control_number: u16,
+    /// Number from sample sheet, or the sequence if the read is in Undetermined Reads
+    pub sample: Sample
+}
+
+/// Another name for `BorrowedSequenceIdentifier`, matching the `RefRecord` naming of readers such
+/// as seq_io
+pub type SequenceIdentifierRef<'a> = BorrowedSequenceIdentifier<'a>;
+
+impl<'a> BorrowedSequenceIdentifier<'a> {
+    /// Converts to a `SequenceIdentifier`, copying the IDs if they're borrowed
+    pub fn into_owned(self) -> SequenceIdentifier {
+        SequenceIdentifier {
+            sequencer_id: IdString::from(self.sequencer_id.as_ref()),
+            run_count: self.run_count,
+            flow_cell_id: IdString::from(self.flow_cell_id.as_ref()),
+            lane: self.lane,
+            side: self.side,
+            swath: self.swath,
+            camera: self.camera,
+            tile: self.tile,
+            x: self.x,
+            y: self.y,
+            read: self.read,
+            is_filtered: self.is_filtered,
+            control_number: self.control_number,
+            sample: self.sample
+        }
     }
-    let left: Vec<&str> = halves[0].split(':').collect();
-    let right: Vec<&str> = halves[1].split(':').collect();
-    if left.len() != 7 {
+
+    /// Parses the header of a FASTQ record as raw bytes, with or without the leading `@`, without
+    /// copying the IDs out of it. Readers such as seq_io give headers this way, without the `@`,
+    /// through `RefRecord::head()`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use std::borrow::Cow;
+    /// use illumina_coordinates::BorrowedSequenceIdentifier;
+    ///
+    /// fn main() {
+    ///     let head = b"M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+    ///     let seq_id = BorrowedSequenceIdentifier::from_head(head).unwrap();
+    ///     assert_eq!(seq_id.flow_cell_id, Cow::Borrowed("000000000-B54L5"));
+    ///     assert_eq!(seq_id.x, 4127);
+    /// }
+    /// ```
+    ///
+    /// With the `seq_io` feature, seq_io's `fastq::RefRecord` converts through `TryFrom` this way.
+    pub fn from_head(head: &'a [u8]) -> Result<BorrowedSequenceIdentifier<'a>, IlluminaError> {
+        let text = str::from_utf8(head)
+            .map_err(|_| IlluminaError::FormatError("sequence identifier isn't valid UTF-8".to_string()))?;
+        let options = ParseOptions::default();
+        let (left, right) = split_halves(text.trim(), &options).ok_or(IlluminaError::SplitError)?;
+        let mut seq_id = parse_read_name_borrowed(left.strip_prefix('@').unwrap_or(left))?;
+        parse_comment(&mut seq_id, right, &options)?;
+        Ok(seq_id)
+    }
+}
+
+impl<'a> From<BorrowedSequenceIdentifier<'a>> for SequenceIdentifier {
+    fn from(seq_id: BorrowedSequenceIdentifier<'a>) -> SequenceIdentifier {
+        seq_id.into_owned()
+    }
+}
+
+impl<'a> TryFrom<&'a str> for SequenceIdentifier {
+    type Error = IlluminaError;
+
+    fn try_from(text: &'a str) -> Result<SequenceIdentifier, IlluminaError> {
+        parse_sequence_identifier(text)
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for SequenceIdentifier {
+    type Error = IlluminaError;
+
+    fn try_from(bytes: &'a [u8]) -> Result<SequenceIdentifier, IlluminaError> {
+        let text = str::from_utf8(bytes)
+            .map_err(|_| IlluminaError::FormatError("sequence identifier isn't valid UTF-8".to_string()))?;
+        parse_sequence_identifier(text)
+    }
+}
+
+#[cfg(feature = "bio")]
+impl<'a> TryFrom<&'a bio::io::fastq::Record> for SequenceIdentifier {
+    type Error = IlluminaError;
+
+    fn try_from(record: &'a bio::io::fastq::Record) -> Result<SequenceIdentifier, IlluminaError> {
+        SequenceIdentifier::from_id_and_desc(record.id(), record.desc())
+    }
+}
+
+#[cfg(feature = "noodles-fastq")]
+impl<'a> TryFrom<&'a noodles_fastq::Record> for SequenceIdentifier {
+    type Error = IlluminaError;
+
+    fn try_from(record: &'a noodles_fastq::Record) -> Result<SequenceIdentifier, IlluminaError> {
+        SequenceIdentifier::from_name_and_description(record.name(), record.description())
+    }
+}
+
+#[cfg(feature = "seq_io")]
+impl<'a, 'r> TryFrom<&'a seq_io::fastq::RefRecord<'r>> for SequenceIdentifierRef<'a> {
+    type Error = IlluminaError;
+
+    fn try_from(record: &'a seq_io::fastq::RefRecord<'r>) -> Result<SequenceIdentifierRef<'a>, IlluminaError> {
+        use seq_io::fastq::Record;
+        SequenceIdentifierRef::from_head(record.head())
+    }
+}
+
+/// Parses a sequence identifier like `parse_sequence_identifier`, but without copying the
+/// sequencer and flow cell IDs out of the text. This is faster when only some identifiers need
+/// to be kept.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use std::borrow::Cow;
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier_borrowed(line).unwrap();
+///     assert_eq!(seq_id.sequencer_id, Cow::Borrowed("M03745"));
+///     let owned = seq_id.into_owned();
+///     assert_eq!(owned.flow_cell_id, "000000000-B54L5");
+/// }
+/// ```
+pub fn parse_sequence_identifier_borrowed(text: &str) -> Result<BorrowedSequenceIdentifier<'_>, IlluminaError> {
+    parse_sequence_identifier_borrowed_with(text, &ParseOptions::default())
+}
+
+/// Splits an identifier into the halves before and after the space. Some aligners and renaming
+/// tools replace the space with an underscore, so if there's no space, the first underscore in the
+/// y-coordinate field is used instead.
+fn split_halves<'a>(text: &'a str, options: &ParseOptions) -> Option<(&'a str, &'a str)> {
+    let separators: &[char] = if options.tolerant_whitespace { &[' ', '\t'] } else { &[' '] };
+    let (end, right) = match text.find(separators) {
+        Some(end) if options.tolerant_whitespace => (end, text[end..].trim_start_matches(separators)),
+        Some(end) => (end, &text[end + 1..]),
+        None => {
+            let y_start = text.match_indices(':').nth(5)?.0 + 1;
+            let underscore = y_start + text[y_start..].find('_')?;
+            return Some((&text[..underscore], &text[underscore + 1..]));
+        }
+    };
+    // Tab-separated fields after the comment are extra tags
+    let right = &right[..right.find('\t').unwrap_or(right.len())];
+    if right.contains(separators) {
+        return None;
+    }
+    Some((&text[..end], right))
+}
+
+/// Parses a sequence identifier like `parse_sequence_identifier_borrowed`, relaxing the rules
+/// that the options turn off
+pub fn parse_sequence_identifier_borrowed_with<'a>(text: &'a str, options: &ParseOptions)
+        -> Result<BorrowedSequenceIdentifier<'a>, IlluminaError> {
+    let (left, right) = split_halves(text.trim(), options).ok_or(IlluminaError::SplitError)?;
+    // Slicing with get() rather than split_at() means short or non-ASCII fields are errors
+    // rather than panics
+    let mut seq_id = parse_read_name_borrowed(left.get(1..).ok_or(IlluminaError::SplitError)?)?;
+    parse_comment(&mut seq_id, right, options)?;
+    Ok(seq_id)
+}
+
+/// Parses the half of a sequence identifier after the space into the read, filter flag, control
+/// number and sample fields
+pub(crate) fn parse_comment(seq_id: &mut BorrowedSequenceIdentifier, right: &str, options: &ParseOptions) -> Result<(), IlluminaError> {
+    // Whitespace would end the comment when the identifier is written out again
+    if right.contains(char::is_whitespace) {
         return Err(IlluminaError::SplitError);
     }
+    let right: Vec<&str> = right.split(':').collect();
     if right.len() != 4 {
         return Err(IlluminaError::SplitError);
     }
-    let sequencer_id = left[0].split_at(1).1.to_string();
-    let run_count = left[1].parse::<u16>()?;
-    let flow_cell_id = left[2].to_string();
-    let lane = left[3].parse::<u8>()?;
-    let (side, remainder) = left[4].split_at(1);
-    let (swath, tile) = remainder.split_at(1);
-    let side = side.parse::<u8>()?;
-    let swath = swath.parse::<u8>()?;
-    let tile = tile.parse::<u8>()?;
-    let x = left[5].parse::<u16>()?;
-    let y = left[6].parse::<u16>()?;
-
-    let read = right[0].parse::<u8>()?;
-    let is_filtered = match right[1] {
-        "Y" => true,
-        "N" => false,
-        _ => return Err(IlluminaError::ParseError)
-    };
-    let control_number= right[2].parse::<u8>()?;
-    let sample = right[3].parse::<u8>();
-    let sample = match sample {
-        Ok(n) => Sample::Number(n),
-        Err(_) => Sample::Sequence(String::from(right[3]))
-    };
+    seq_id.read = parse_number(right[0])?;
+    seq_id.is_filtered = options.filter_flag(right[1]).ok_or(IlluminaError::ParseError)?;
+    seq_id.control_number = parse_number(right[2])?;
+    seq_id.sample = Sample::from_field(right[3]);
+    Ok(())
+}
+
+/// Parses a numeric field, which must be all ASCII digits. `str::parse` also accepts a leading
+/// `+`, which would be lost when the identifier is written out again.
+pub(crate) fn parse_number<T: str::FromStr>(field: &str) -> Result<T, IlluminaError> {
+    if field.is_empty() || !field.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(IlluminaError::ParseError);
+    }
+    field.parse().map_err(|_| IlluminaError::ParseError)
+}
+
+/// Splits a tile field into its side, swath, camera and tile. NextSeq and MiniSeq tile fields
+/// have an extra digit for the camera, as in `11203` for camera 2, tile 3, unless they have only
+/// four digits. Other fields must have a side and swath digit and a tile of two or three digits.
+/// Fields that wouldn't be written the same way again, such as `110101` or `11+1`, are errors.
+pub(crate) fn parse_tile_field(field: &str, sequencer_id: &str) -> Result<(u8, u8, Option<u8>, u8), IlluminaError> {
+    // Slicing with get() means short or non-ASCII fields are errors rather than panics
+    let digits = |start: usize, end: usize| field.get(start..end).ok_or(IlluminaError::ParseError).and_then(parse_number);
+    let has_cameras = matches!(InstrumentModel::from_sequencer_id(sequencer_id), Some(model) if model.has_cameras());
+    match field.len() {
+        5 if has_cameras => Ok((digits(0, 1)?, digits(1, 2)?, Some(digits(2, 3)?), digits(3, 5)?)),
+        4 => Ok((digits(0, 1)?, digits(1, 2)?, None, digits(2, 4)?)),
+        5 => {
+            let tile = digits(2, 5)?;
+            // Tiles below 100 are written with two digits
+            if tile < 100 {
+                return Err(IlluminaError::ParseError);
+            }
+            Ok((digits(0, 1)?, digits(1, 2)?, None, tile))
+        },
+        _ => Err(IlluminaError::ParseError)
+    }
+}
+
+/// Parses the half of a sequence identifier before the space, which locates the cluster, without
+/// the leading `@`. The fields from the other half are given the values of an unfiltered first read of sample 0.
+pub(crate) fn parse_read_name_borrowed(left: &str) -> Result<BorrowedSequenceIdentifier<'_>, IlluminaError> {
+    let left: Vec<&str> = left.split(':').collect();
+    if left.len() != 7 {
+        return Err(IlluminaError::SplitError);
+    }
+    let sequencer_id = Cow::Borrowed(left[0]);
+    let run_count = parse_number(left[1])?;
+    let flow_cell_id = Cow::Borrowed(left[2]);
+    let lane = parse_number(left[3])?;
+    let (side, swath, camera, tile) = parse_tile_field(left[4], &sequencer_id)?;
+    let x = parse_number(left[5])?;
+    let y = parse_number(left[6])?;
 
-    Ok(SequenceIdentifier {
+    Ok(BorrowedSequenceIdentifier {
         sequencer_id,
         run_count,
         flow_cell_id,
         lane,
         side,
         swath,
+        camera,
         tile,
         x,
         y,
-        read,
-        is_filtered,
-        control_number,
-        sample
+        read: 1,
+        is_filtered: false,
+        control_number: 0,
+        sample: Sample::Number(0)
     })
 }
 
 
 #[cfg(test)]
+#[allow(clippy::needless_borrow, clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -204,6 +1105,7 @@ mod tests {
         assert_eq!(seq_id.is_filtered, false);
         assert_eq!(seq_id.control_number, 0);
         assert_eq!(seq_id.sample, Sample::Number(0));
+        assert_eq!(seq_id.tile_field(), 2108);
     }
     
     #[test]
@@ -236,13 +1138,162 @@ mod tests {
         assert_eq!(seq_id.lane, 1);
         assert_eq!(seq_id.side, 1);
         assert_eq!(seq_id.swath, 1);
-        assert_eq!(seq_id.tile, 101);
+        assert_eq!(seq_id.camera, Some(1));
+        assert_eq!(seq_id.tile, 1);
         assert_eq!(seq_id.x, 20886);
         assert_eq!(seq_id.y, 1073);
         assert_eq!(seq_id.read, 1);
         assert_eq!(seq_id.is_filtered, false);
         assert_eq!(seq_id.control_number, 0);
-        assert_eq!(seq_id.sample, Sample::Sequence("TAAGGC".into()));
+        assert_eq!(seq_id.sample, Sample::Sequence(Barcode::new("TAAGGC").unwrap()));
+    }
+
+    #[test]
+    fn test_parse_nextseq_camera() {
+        // Tiles imaged by cameras 3 to 6 have tile fields too large for the MiSeq layout
+        let seq_id = parse_sequence_identifier("@NB501234:12:H2VGLBGXY:4:23612:11289:19567 1:N:0:1").unwrap();
+        assert_eq!((seq_id.side, seq_id.swath, seq_id.camera, seq_id.tile), (2, 3, Some(6), 12));
+        assert_eq!(seq_id.tile_id().camera, Some(6));
+        assert_eq!(seq_id.tile_field(), 23612);
+        // Other sequencers don't have cameras, even with five-digit tile fields
+        let seq_id = parse_sequence_identifier("@custom:12:H2VGLBGXY:4:21112:11289:19567 1:N:0:1").unwrap();
+        assert_eq!((seq_id.camera, seq_id.tile), (None, 112));
+        assert_eq!(seq_id.tile_field(), 21112);
+        assert_eq!(seq_id.to_string(), "@custom:12:H2VGLBGXY:4:21112:11289:19567 1:N:0:1");
+    }
+
+    #[test]
+    fn test_parse_underscore_separator() {
+        let seq_id = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949_1:N:0:0").unwrap();
+        assert_eq!(seq_id, parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap());
+        assert_eq!(seq_id.to_string(), "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0");
+        // Underscores elsewhere don't count
+        let seq_id = parse_sequence_identifier("@my_sequencer:11:000000000-B54L5:1:2108:4127:8949_2:N:0:0").unwrap();
+        assert_eq!(seq_id.sequencer_id, "my_sequencer");
+        assert_eq!(seq_id.read, 2);
+        for line in &["@my_sequencer:11:000000000-B54L5:1:2108:4127:8949", "@M03745:11:000000000-B54L5:1:2108:4127:8949_1:N:0:0 x",
+                      "@M03745:11:000000000-B54L5:1:2108:4127_8949:1:N:0:0"] {
+            assert!(parse_sequence_identifier(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_parse_whitespace() {
+        let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+        let expected = parse_sequence_identifier(line).unwrap();
+        assert_eq!(parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\r\n").unwrap(), expected);
+        let options = ParseOptions { tolerant_whitespace: true, ..ParseOptions::default() };
+        for line in &["@M03745:11:000000000-B54L5:1:2108:4127:8949\t1:N:0:0", "@M03745:11:000000000-B54L5:1:2108:4127:8949  \t 1:N:0:0\r\n"] {
+            assert!(parse_sequence_identifier(line).is_err(), "{}", line);
+            assert_eq!(parse_sequence_identifier_with(line, &options).unwrap(), expected, "{}", line);
+        }
+        assert_eq!(parse_sequence_identifier_with(line, &options).unwrap(), expected);
+        assert!(parse_sequence_identifier_with("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0 extra", &options).is_err());
+        // Tags after a tab are ignored
+        for line in &["@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\tBC:Z:TAAGGC", "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\tduplicate"] {
+            assert_eq!(parse_sequence_identifier(line).unwrap(), expected, "{}", line);
+            assert_eq!(parse_sequence_identifier_with(line, &options).unwrap(), expected, "{}", line);
+            assert_eq!(lazy::LazySequenceIdentifier::new(line).unwrap().parse().unwrap(), expected, "{}", line);
+        }
+        assert!(parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0 BC:Z:TAAGGC\tduplicate").is_err());
+    }
+
+    #[test]
+    fn test_from_parts() {
+        let seq_id = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 2:Y:0:TAAGGC").unwrap();
+        let rebuilt = SequenceIdentifier::from_parts(seq_id.cluster_key(), 2, true, 0, Sample::Sequence(Barcode::new("TAAGGC").unwrap()));
+        assert_eq!(rebuilt, seq_id);
+        assert_eq!((rebuilt.sequencer_id(), rebuilt.flow_cell_id(), rebuilt.tile(), rebuilt.read()),
+                   ("M03745", "000000000-B54L5", 8, 2));
+        assert_eq!(rebuilt.sample().as_sequence(), Some("TAAGGC"));
+    }
+
+    #[test]
+    fn test_new() {
+        let tile = TileId { lane: 4, side: 2, swath: 3, camera: Some(6), tile: 12 };
+        let seq_id = SequenceIdentifier::new("NB501234", 12, "H2VGLBGXY", tile, 11289, 19567).unwrap();
+        assert_eq!(seq_id.to_string(), "@NB501234:12:H2VGLBGXY:4:23612:11289:19567 1:N:0:0");
+        assert_eq!(parse_sequence_identifier(&seq_id.to_string()).unwrap(), seq_id);
+        let invalid = [TileId { camera: None, tile: 100, ..tile }, TileId { tile: 100, ..tile }, TileId { side: 10, ..tile },
+                       TileId { camera: Some(10), ..tile }];
+        for &tile in &invalid {
+            assert!(seq_id.with_position(tile, 1, 1).is_err(), "{:?}", tile);
+        }
+        assert!(SequenceIdentifier::new("M03745", 11, "000000000-B54L5", tile, 1, 1).is_err());
+        assert!(SequenceIdentifier::new("", 11, "000000000-B54L5", TileId { camera: None, ..tile }, 1, 1).is_err());
+        assert!(SequenceIdentifier::new("M03745", 11, "000 B54L5", TileId { camera: None, ..tile }, 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_new_without_camera() {
+        // NextSeq tile fields without a camera digit parse with no camera
+        let seq_id = parse_sequence_identifier("@NB501234:8:H3KJ7BGXX:1:1101:1000:1000 1:N:0:0").unwrap();
+        assert_eq!(seq_id.camera, None);
+        let moved = seq_id.with_position(seq_id.tile_id(), 5, 5).unwrap();
+        assert_eq!(moved.to_string(), "@NB501234:8:H3KJ7BGXX:1:1101:5:5 1:N:0:0");
+        assert_eq!(parse_sequence_identifier(&moved.to_string()).unwrap(), moved);
+        let new = SequenceIdentifier::new("NB501234", 8, "H3KJ7BGXX", seq_id.tile_id(), 1000, 1000).unwrap();
+        assert_eq!(new, seq_id);
+    }
+
+    #[test]
+    fn test_sample() {
+        let number = Sample::Number(3);
+        assert_eq!((number.as_number(), number.as_sequence(), number.index_pair()), (Some(3), None, None));
+        assert!(!number.is_undetermined());
+        assert!(Sample::Number(0).is_undetermined());
+        let single = Sample::Sequence(Barcode::new("TAAGGC").unwrap());
+        assert_eq!((single.as_number(), single.as_sequence(), single.index_pair()), (None, Some("TAAGGC"), None));
+        assert!(single.is_undetermined());
+        assert_eq!(Sample::Sequence(Barcode::new("TAAGGC+CTTAGG").unwrap()).index_pair(), Some(("TAAGGC", "CTTAGG")));
+        // Sample fields that are neither numbers nor barcodes are kept as written
+        for &field in &["300", "acgtac", "GATCAG-TTAGGC", "sample1", "TAAGGC+"] {
+            let line = format!("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:{}", field);
+            let seq_id = parse_sequence_identifier(&line).unwrap();
+            assert_eq!(seq_id.sample, Sample::Other(field.to_string()));
+            assert_eq!(seq_id.to_string(), line);
+        }
+        assert!(!Sample::Other("300".to_string()).is_undetermined());
+        // Sample numbers too large for a u8 aren't sequences
+        let large = Sample::Other("300".to_string());
+        assert_eq!((large.as_sequence(), large.as_text(), large.index_pair(), large.ambiguous_count()), (None, Some("300"), None, 0));
+        assert_eq!((number.as_text(), single.as_text()), (None, Some("TAAGGC")));
+        assert_eq!(Sample::Other("acgtac".to_string()).as_sequence(), Some("acgtac"));
+        assert!(Sample::Other("acgtac".to_string()).is_undetermined());
+        assert_eq!(Sample::Other("acgtac+ttaggc".to_string()).index_pair(), Some(("acgtac", "ttaggc")));
+    }
+
+    #[test]
+    fn test_ambiguous_bases() {
+        assert_eq!((Sample::Number(3).ambiguous_positions(), Sample::Number(3).ambiguous_count()), (vec![], 0));
+        let clean = Sample::Sequence(Barcode::new("TAAGGC+CTTAGG").unwrap());
+        assert_eq!((clean.ambiguous_count(), clean.has_ambiguous()), (0, false));
+        let all = Sample::Sequence(Barcode::new("NNNN").unwrap());
+        assert_eq!((all.ambiguous_positions(), all.ambiguous_count(), all.has_ambiguous()), (vec![0, 1, 2, 3], 4, true));
+    }
+
+    #[test]
+    fn test_cluster_key() {
+        let r1 = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+        let r2 = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 2:Y:0:0").unwrap();
+        let other = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8950 2:N:0:0").unwrap();
+        assert_eq!(r1.cluster_key(), r2.cluster_key());
+        assert!(r1.cluster_key() != other.cluster_key());
+
+        let mut mates = std::collections::HashMap::new();
+        mates.insert(r1.cluster_key(), r1);
+        assert!(mates.contains_key(&r2.cluster_key()));
+    }
+
+    #[test]
+    fn test_is_mate_of() {
+        let r1 = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+        let r2 = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 2:N:0:0").unwrap();
+        let other = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2109:4127:8949 2:N:0:0").unwrap();
+        assert!(r1.is_mate_of(&r2));
+        assert!(r2.is_mate_of(&r1));
+        assert!(!r1.is_mate_of(&r1));
+        assert!(!r1.is_mate_of(&other));
     }
 
     #[test]
@@ -250,4 +1301,146 @@ mod tests {
         let result = parse_sequence_identifier("CACGACGACTAGCTACGGACGCGGCACGACGCAG");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_parse_non_canonical_fields() {
+        // Fields that would be written differently, or for the wrong instrument, are errors
+        for line in &["@M03745:11:000000000-B54L5:1:11+101:4127:8949 1:N:0:0",
+                      "@M03745:11:000000000-B54L5:1:+108:4127:8949 1:N:0:0",
+                      "@M03745:11:000000000-B54L5:1:210:4127:8949 1:N:0:0",
+                      "@M03745:11:000000000-B54L5:1:21008:4127:8949 1:N:0:0",
+                      "@NB501234:12:H2VGLBGXY:4:110101:11289:19567 1:N:0:1",
+                      "@NB501234:12:H2VGLBGXY:4:111:11289:19567 1:N:0:1",
+                      "@M03745:+11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+                      "@M03745:11:000000000-B54L5:+1:2108:4127:8949 1:N:0:0",
+                      "@M03745:11:000000000-B54L5:1:2108:+4127:8949 1:N:0:0",
+                      "@M03745:11:000000000-B54L5:1:2108:4127:8949 +1:N:0:0",
+                      "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:-0:0"] {
+            assert!(parse_sequence_identifier(line).is_err(), "{}", line);
+            assert!(lazy::LazySequenceIdentifier::new(line).unwrap().parse().is_err(), "{}", line);
+        }
+        let name = b"M03745:11:000000000-B54L5:1:2108:4127:8949";
+        assert!(SequenceIdentifier::from_name_and_description(name, b"1:N:0:TAAGGC GG").is_err());
+        assert!(SequenceIdentifier::from_name_and_description(name, b"1:N:0:TAAGGC\t").is_ok());
+        assert!(SequenceIdentifier::from_name_and_description(name, b"1:N:0:TAAGGC\tduplicate").is_ok());
+        assert!(SequenceIdentifier::from_name_and_description(name, "1:N:0:TAAGGC\u{3000}GG".as_bytes()).is_err());
+        let seq_id = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:+3").unwrap();
+        assert_eq!(seq_id.sample, Sample::Other("+3".to_string()));
+        assert_eq!(parse_sequence_identifier(&seq_id.to_string()).unwrap(), seq_id);
+    }
+
+    #[test]
+    fn test_display() {
+        for line in &["@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+                      "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC+CTTAGG",
+                      "@NB501234:12:H2VGLBGXY:4:23612:11289:19567 1:N:0:1"] {
+            let seq_id = parse_sequence_identifier(line).unwrap();
+            assert_eq!(&seq_id.to_string(), line);
+            assert_eq!(parse_sequence_identifier(&seq_id.to_string()).unwrap(), seq_id);
+        }
+    }
+
+    #[test]
+    fn test_parse_borrowed() {
+        let line = "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC".to_string();
+        let borrowed = parse_sequence_identifier_borrowed(&line).unwrap();
+        match borrowed.flow_cell_id {
+            Cow::Borrowed(id) => assert_eq!(id, "HTN5KAFXY"),
+            Cow::Owned(_) => panic!("flow cell ID was copied")
+        }
+        assert_eq!(borrowed.tile, 1);
+        let owned: SequenceIdentifier = borrowed.into();
+        drop(line);
+        assert_eq!(owned.sequencer_id, "NS500358");
+        assert!(parse_sequence_identifier_borrowed("@M03745:11").is_err());
+    }
+
+    #[test]
+    fn test_try_from() {
+        let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+        let expected = parse_sequence_identifier(line).unwrap();
+        assert_eq!(SequenceIdentifier::try_from(line).unwrap(), expected);
+        assert_eq!(SequenceIdentifier::try_from(line.as_bytes()).unwrap(), expected);
+        assert!(SequenceIdentifier::try_from(&b"@M03745:11:\xff"[..]).is_err());
+        assert!(SequenceIdentifier::try_from("@M03745:11").is_err());
+    }
+
+    #[test]
+    fn test_from_id_and_desc() {
+        let expected = parse_sequence_identifier("@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC").unwrap();
+        assert_eq!(SequenceIdentifier::from_id_and_desc("NS500358:204:HTN5KAFXY:1:11101:20886:1073", Some("2:Y:0:TAAGGC")).unwrap(), expected);
+        assert_eq!(SequenceIdentifier::from_id_and_desc("@NS500358:204:HTN5KAFXY:1:11101:20886:1073", Some("2:Y:0:TAAGGC\n")).unwrap(), expected);
+        assert!(SequenceIdentifier::from_id_and_desc("NS500358:204:HTN5KAFXY:1:11101:20886:1073", Some("2:Y:0")).is_err());
+        assert!(SequenceIdentifier::from_id_and_desc("NS500358:204:HTN5KAFXY:1:11101:20886", None).is_err());
+        let name = b"NS500358:204:HTN5KAFXY:1:11101:20886:1073";
+        assert_eq!(SequenceIdentifier::from_name_and_description(name, b"2:Y:0:TAAGGC").unwrap(), expected);
+        assert_eq!(SequenceIdentifier::from_name_and_description(name, b"").unwrap().read, 1);
+        assert!(SequenceIdentifier::from_name_and_description(name, b"2:Y:0:\xff").is_err());
+        let head = b"NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC";
+        assert_eq!(BorrowedSequenceIdentifier::from_head(head).unwrap().into_owned(), expected);
+        assert_eq!(BorrowedSequenceIdentifier::from_head(b"@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC\r").unwrap().into_owned(), expected);
+        assert!(BorrowedSequenceIdentifier::from_head(b"NS500358:204:HTN5KAFXY:1:11101:20886:1073").is_err());
+    }
+
+    #[cfg(feature = "seq_io")]
+    #[test]
+    fn test_seq_io_record() {
+        use seq_io::fastq::Reader;
+        let text = "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC\nACGT\n+\nFFFF\n@read1\nACGT\n+\nFFFF\n";
+        let mut reader = Reader::new(text.as_bytes());
+        {
+            let record = reader.next().unwrap().unwrap();
+            let seq_id = SequenceIdentifierRef::try_from(&record).unwrap();
+            assert_eq!(seq_id.flow_cell_id, Cow::Borrowed("HTN5KAFXY"));
+            assert_eq!(seq_id.into_owned(), parse_sequence_identifier("@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC").unwrap());
+        }
+        let record = reader.next().unwrap().unwrap();
+        assert!(SequenceIdentifierRef::try_from(&record).is_err());
+    }
+
+    #[cfg(feature = "bio")]
+    #[test]
+    fn test_bio_record() {
+        use bio::io::fastq;
+        let expected = parse_sequence_identifier("@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC").unwrap();
+        let text = "@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC\nACGT\n+\nFFFF\n";
+        let record = fastq::Reader::new(text.as_bytes()).records().next().unwrap().unwrap();
+        assert_eq!(SequenceIdentifier::try_from(&record).unwrap(), expected);
+        let record = fastq::Record::with_attrs("NS500358:204:HTN5KAFXY:1:11101:20886:1073", None, b"ACGT", b"FFFF");
+        assert_eq!(SequenceIdentifier::try_from(&record).unwrap().read, 1);
+        let record = fastq::Record::with_attrs("read1", Some("1:N:0:0"), b"ACGT", b"FFFF");
+        assert!(SequenceIdentifier::try_from(&record).is_err());
+    }
+
+    #[test]
+    fn test_malformed_input_is_an_error() {
+        let lines = [
+            "",
+            " ",
+            ":::::: :::",
+            "@M03745:11:000000000-B54L5:1::4127:8949 1:N:0:0",
+            "@M03745:11:000000000-B54L5:1:2:4127:8949 1:N:0:0",
+            "@M03745:11:000000000-B54L5:1:21:4127:8949 1:N:0:0",
+            "@M03745:11:000000000-B54L5:1:é108:4127:8949 1:N:0:0",
+            "@M03745:11:000000000-B54L5:1:2é08:4127:8949 1:N:0:0",
+            "é:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+            ":11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+            "@M03745:11:000000000-B54L5:1:2108:4127:99999999999999999999 1:N:0:0",
+            "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:99999:0",
+            "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:X:0:0",
+        ];
+        for line in lines.iter() {
+            assert!(parse_sequence_identifier(line).is_err(), "{}", line);
+        }
+
+        // Every truncation and single-character corruption of a valid identifier
+        let valid = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+        for (i, _) in valid.char_indices() {
+            let _ = parse_sequence_identifier(&valid[..i]);
+            for replacement in &["é", ":", " ", "", "\u{0}", "99999"] {
+                let corrupted = format!("{}{}{}", &valid[..i], replacement, &valid[i + 1..]);
+                let _ = parse_sequence_identifier(&corrupted);
+            }
+        }
+    }
 }
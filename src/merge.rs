@@ -0,0 +1,163 @@
+//! Merges streams of reads that are each sorted by position into one sorted stream.
+//!
+//! Files sorted by position, such as one per lane, can be processed as a whole run by merging
+//! them, without reading any of them into memory. Only the next read of each stream is held at a
+//! time. Reads at the same position, such as read 1 and read 2 of a cluster, come out in the
+//! order of their streams.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use position::Position;
+use super::{IlluminaError, SequenceIdentifier};
+
+/// An iterator that merges streams sorted by a key into a single sorted stream.
+///
+/// An error from any stream is passed on and ends the merge, since the rest of that stream can't
+/// be placed. So does a stream whose keys go backwards, which isn't sorted.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::merge::merge_by_position;
+/// use illumina_coordinates::parse_sequence_identifier;
+///
+/// fn main() {
+///     let lane1 = "@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:0\n@A00123:8:H3KJ7DSXX:1:1102:1000:1000 1:N:0:0";
+///     let lane2 = "@A00123:8:H3KJ7DSXX:2:1101:1000:1000 1:N:0:0";
+///     let again = "@A00123:8:H3KJ7DSXX:1:1101:2000:1000 1:N:0:0";
+///     let streams = vec![lane2.lines().map(parse_sequence_identifier),
+///                        lane1.lines().map(parse_sequence_identifier),
+///                        again.lines().map(parse_sequence_identifier)];
+///     let merged: Vec<(u8, u32, u16)> = merge_by_position(streams)
+///         .map(|seq_id| seq_id.map(|seq_id| (seq_id.lane, seq_id.tile_field(), seq_id.x)))
+///         .collect::<Result<_, _>>()
+///         .unwrap();
+///     assert_eq!(merged, vec![(1, 1101, 1000), (1, 1101, 2000), (1, 1102, 1000), (2, 1101, 1000)]);
+/// }
+/// ```
+pub struct Merge<I, T, K, F> {
+    streams: Vec<I>,
+    heads: Vec<Option<T>>,
+    last_keys: Vec<Option<K>>,
+    heap: BinaryHeap<Reverse<(K, usize)>>,
+    key: F,
+    error: Option<IlluminaError>,
+    started: bool,
+    finished: bool
+}
+
+impl<I, T, K, F> Merge<I, T, K, F>
+    where I: Iterator<Item=Result<T, IlluminaError>>,
+          K: Ord + Clone,
+          F: FnMut(&T) -> K {
+    /// Merges streams that are each sorted by `key`
+    pub fn new(streams: Vec<I>, key: F) -> Merge<I, T, K, F> {
+        let count = streams.len();
+        Merge {
+            streams,
+            heads: (0..count).map(|_| None).collect(),
+            last_keys: (0..count).map(|_| None).collect(),
+            heap: BinaryHeap::with_capacity(count),
+            key,
+            error: None,
+            started: false,
+            finished: false
+        }
+    }
+
+    /// Reads the next item of a stream into its head, keeping any error for later
+    fn advance(&mut self, stream: usize) {
+        match self.streams[stream].next() {
+            Some(Ok(item)) => {
+                let key = (self.key)(&item);
+                if let Some(ref last) = self.last_keys[stream] {
+                    if key < *last {
+                        self.error = Some(IlluminaError::FormatError(format!("stream {} isn't sorted", stream + 1)));
+                        return;
+                    }
+                }
+                self.last_keys[stream] = Some(key.clone());
+                self.heads[stream] = Some(item);
+                self.heap.push(Reverse((key, stream)));
+            }
+            Some(Err(error)) => self.error = Some(error),
+            None => {}
+        }
+    }
+}
+
+impl<I, T, K, F> Iterator for Merge<I, T, K, F>
+    where I: Iterator<Item=Result<T, IlluminaError>>,
+          K: Ord + Clone,
+          F: FnMut(&T) -> K {
+    type Item = Result<T, IlluminaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        if !self.started {
+            self.started = true;
+            for stream in 0..self.streams.len() {
+                self.advance(stream);
+            }
+        }
+        if let Some(error) = self.error.take() {
+            self.finished = true;
+            return Some(Err(error));
+        }
+        let Reverse((_, stream)) = self.heap.pop()?;
+        let item = self.heads[stream].take();
+        self.advance(stream);
+        item.map(Ok)
+    }
+}
+
+/// Merges streams of sequence identifiers that are each sorted by position, in the order of
+/// `position::Position`, which is also the order of `position::encode_position`
+pub fn merge_by_position<I>(streams: Vec<I>) -> Merge<I, SequenceIdentifier, Position, fn(&SequenceIdentifier) -> Position>
+    where I: Iterator<Item=Result<SequenceIdentifier, IlluminaError>> {
+    Merge::new(streams, |seq_id| Position::from(seq_id))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(keys: &[u32]) -> ::std::vec::IntoIter<Result<u32, IlluminaError>> {
+        keys.iter().map(|&key| Ok(key)).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn test_merge() {
+        let merged: Vec<u32> = Merge::new(vec![stream(&[1, 4, 4, 9]), stream(&[]), stream(&[2, 4, 10])], |&key| key)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(merged, [1, 2, 4, 4, 4, 9, 10]);
+        let empty: Vec<::std::vec::IntoIter<Result<u32, IlluminaError>>> = vec![];
+        assert_eq!(Merge::new(empty, |&key| key).count(), 0);
+    }
+
+    #[test]
+    fn test_ties_keep_stream_order() {
+        let streams = vec![stream(&[10, 20]), stream(&[11, 21])];
+        let merged: Vec<u32> = Merge::new(streams, |&key| key / 10).map(Result::unwrap).collect();
+        assert_eq!(merged, [10, 11, 20, 21]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut merged = Merge::new(vec![stream(&[1, 3]), stream(&[5, 2])], |&key| key);
+        assert_eq!(merged.next().unwrap().unwrap(), 1);
+        assert_eq!(merged.next().unwrap().unwrap(), 3);
+        assert_eq!(merged.next().unwrap().unwrap(), 5);
+        assert!(merged.next().unwrap().is_err());
+        assert!(merged.next().is_none());
+        let failing = vec![Ok(1), Err(IlluminaError::ParseError), Ok(2)].into_iter();
+        let results: Vec<Result<u32, IlluminaError>> = Merge::new(vec![failing], |&key| key).collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[1].is_err());
+    }
+}
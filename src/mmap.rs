@@ -0,0 +1,256 @@
+//! Parses the headers of an uncompressed FASTQ file by memory-mapping it, without copying the
+//! file into buffers or the headers into strings.
+//!
+//! Mapping is supported on 64-bit Unix systems. Elsewhere, the file is read into memory in one
+//! go instead, which is slower but behaves the same.
+
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+use std::str;
+use batch::SequenceIdentifierBatch;
+use bgzf::is_gzip;
+use super::{parse_sequence_identifier_borrowed, BorrowedSequenceIdentifier, IlluminaError};
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+mod sys {
+    use std::fs::File;
+    use std::io;
+    use std::os::raw::{c_int, c_void};
+    use std::os::unix::io::AsRawFd;
+
+    const PROT_READ: c_int = 1;
+    const MAP_PRIVATE: c_int = 2;
+
+    extern "C" {
+        fn mmap(address: *mut c_void, length: usize, protection: c_int, flags: c_int, fd: c_int,
+                offset: i64) -> *mut c_void;
+        fn munmap(address: *mut c_void, length: usize) -> c_int;
+    }
+
+    /// A read-only mapping of a whole file
+    pub struct Map {
+        address: *mut c_void,
+        length: usize
+    }
+
+    impl Map {
+        /// Maps `length` bytes of the file, which must not be zero
+        pub fn new(file: &File, length: usize) -> io::Result<Map> {
+            // The mapping stays valid after the file is closed
+            let address = unsafe {
+                mmap(::std::ptr::null_mut(), length, PROT_READ, MAP_PRIVATE, file.as_raw_fd(), 0)
+            };
+            if address as isize == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Map { address, length })
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            unsafe { ::std::slice::from_raw_parts(self.address as *const u8, self.length) }
+        }
+    }
+
+    impl Drop for Map {
+        fn drop(&mut self) {
+            unsafe {
+                munmap(self.address, self.length);
+            }
+        }
+    }
+
+    // The mapping is read-only, so it can be shared between threads like a `&[u8]`
+    unsafe impl Send for Map {}
+    unsafe impl Sync for Map {}
+}
+
+enum Contents {
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    Mapped(sys::Map),
+    Read(Vec<u8>)
+}
+
+/// The contents of a file, memory-mapped where possible. It dereferences to `[u8]`.
+///
+/// The file must not be modified while it's mapped: on most systems, changes show up in the
+/// mapping, and truncating the file makes reading the removed part crash the process.
+pub struct MappedFile {
+    contents: Contents
+}
+
+impl MappedFile {
+    /// Maps the file at `path` into memory
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<MappedFile> {
+        let mut file = File::open(path)?;
+        let length = file.metadata()?.len() as usize;
+        MappedFile::map(&mut file, length)
+    }
+
+    #[cfg(all(unix, target_pointer_width = "64"))]
+    fn map(file: &mut File, length: usize) -> io::Result<MappedFile> {
+        // Empty mappings aren't allowed
+        if length == 0 {
+            return Ok(MappedFile { contents: Contents::Read(vec![]) });
+        }
+        Ok(MappedFile { contents: Contents::Mapped(sys::Map::new(file, length)?) })
+    }
+
+    #[cfg(not(all(unix, target_pointer_width = "64")))]
+    fn map(file: &mut File, length: usize) -> io::Result<MappedFile> {
+        use std::io::Read;
+        let mut data = Vec::with_capacity(length);
+        file.read_to_end(&mut data)?;
+        Ok(MappedFile { contents: Contents::Read(data) })
+    }
+
+    /// Whether the file is actually memory-mapped, rather than read into memory
+    pub fn is_mapped(&self) -> bool {
+        match self.contents {
+            #[cfg(all(unix, target_pointer_width = "64"))]
+            Contents::Mapped(_) => true,
+            Contents::Read(_) => false
+        }
+    }
+
+    /// Iterates over the sequence identifiers in the file, which must be an uncompressed FASTQ
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::mmap::MappedFile;
+    ///
+    /// fn main() {
+    ///     let path = std::env::temp_dir().join("illumina_coordinates_mmap_example.fastq");
+    ///     std::fs::write(&path, "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\nACGT\n+\nFFFF\n").unwrap();
+    ///     let file = MappedFile::open(&path).unwrap();
+    ///     let x: Vec<u16> = file.sequence_identifiers().map(|seq_id| seq_id.unwrap().x).collect();
+    ///     assert_eq!(x, vec![4127]);
+    ///     std::fs::remove_file(&path).unwrap();
+    /// }
+    /// ```
+    pub fn sequence_identifiers(&self) -> SequenceIdentifiers<'_> {
+        SequenceIdentifiers { data: self, position: 0 }
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self.contents {
+            #[cfg(all(unix, target_pointer_width = "64"))]
+            Contents::Mapped(ref map) => map.as_slice(),
+            Contents::Read(ref data) => data
+        }
+    }
+}
+
+/// An iterator over the sequence identifiers in an uncompressed FASTQ file, which borrow their
+/// sequencer and flow cell IDs from the file's contents. It stops after the first error.
+pub struct SequenceIdentifiers<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> SequenceIdentifiers<'a> {
+    /// The next line, without its line ending
+    fn line(&mut self) -> Option<&'a [u8]> {
+        if self.position >= self.data.len() {
+            return None;
+        }
+        let rest = &self.data[self.position..];
+        let (line, length) = match rest.iter().position(|&byte| byte == b'\n') {
+            Some(end) => (&rest[..end], end + 1),
+            None => (rest, rest.len())
+        };
+        self.position += length;
+        Some(match line.last() {
+            Some(&b'\r') => &line[..line.len() - 1],
+            _ => line
+        })
+    }
+
+    fn parse_record(&mut self) -> Option<Result<BorrowedSequenceIdentifier<'a>, IlluminaError>> {
+        if self.position == 0 && is_gzip(self.data) {
+            return Some(Err(IlluminaError::FormatError("FASTQ file is compressed".to_string())));
+        }
+        let header = self.line()?;
+        // Sequence and quality scores are skipped without being checked
+        let complete = self.line().is_some() && matches!(self.line(), Some(line) if line.starts_with(b"+"))
+            && self.line().is_some();
+        let header = match str::from_utf8(header) {
+            Ok(header) => header,
+            Err(_) => return Some(Err(IlluminaError::FormatError("FASTQ header isn't valid UTF-8".to_string())))
+        };
+        if !header.starts_with('@') {
+            return Some(Err(IlluminaError::FormatError(format!("FASTQ header doesn't start with @: {}", header))));
+        }
+        if !complete {
+            return Some(Err(IlluminaError::FormatError(format!("FASTQ record is truncated: {}", header))));
+        }
+        Some(parse_sequence_identifier_borrowed(header))
+    }
+}
+
+impl<'a> Iterator for SequenceIdentifiers<'a> {
+    type Item = Result<BorrowedSequenceIdentifier<'a>, IlluminaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.parse_record();
+        if let Some(Err(_)) = result {
+            self.position = self.data.len();
+        }
+        result
+    }
+}
+
+/// Memory-maps an uncompressed FASTQ file and parses the sequence identifier of every record
+/// into a batch. This is the fastest way to parse a whole file that's on a local disk.
+pub fn parse_file_mmap<P: AsRef<Path>>(path: P) -> Result<SequenceIdentifierBatch, IlluminaError> {
+    let file = MappedFile::open(path)?;
+    let mut batch = SequenceIdentifierBatch::new();
+    for seq_id in file.sequence_identifiers() {
+        batch.push_borrowed(&seq_id?);
+    }
+    Ok(batch)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use parse_sequence_identifier;
+
+    const FASTQ: &str = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\nACGT\n+\nFFFF\n\
+                         @NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC\r\nAC\r\n+\r\nFF";
+
+    #[test]
+    fn test_parse_file_mmap() {
+        let path = env::temp_dir().join("illumina_coordinates_test_parse_file_mmap.fastq");
+        fs::write(&path, FASTQ).unwrap();
+        let batch = parse_file_mmap(&path).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch.get(1).unwrap(),
+                   parse_sequence_identifier("@NS500358:204:HTN5KAFXY:1:11101:20886:1073 2:Y:0:TAAGGC").unwrap());
+        assert_eq!(MappedFile::open(&path).unwrap().is_mapped(), cfg!(all(unix, target_pointer_width = "64")));
+
+        fs::write(&path, "").unwrap();
+        assert!(parse_file_mmap(&path).unwrap().is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_malformed() {
+        let records = SequenceIdentifiers { data: b"@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\nACGT\n", position: 0 };
+        assert_eq!(records.map(|seq_id| seq_id.is_err()).collect::<Vec<_>>(), vec![true]);
+        let records = SequenceIdentifiers { data: b"ACGT\nACGT\n+\nFFFF\n", position: 0 };
+        assert!(records.collect::<Vec<_>>()[0].is_err());
+        let records = SequenceIdentifiers { data: &[0x1f, 0x8b, 8, 0], position: 0 };
+        assert!(records.collect::<Vec<_>>()[0].is_err());
+    }
+}
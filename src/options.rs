@@ -0,0 +1,160 @@
+//! Options that make parsing accept sequence identifiers that other tools have rewritten.
+//!
+//! Parsing is strict by default, so that corrupted files are noticed. Each option relaxes one
+//! rule, for files that are known to have been through tools that rewrite headers.
+//!
+//! `OutputOptions` choose the names and shape of the fields in CSV and JSON Lines output, to
+//! match the schema that a downstream tool expects.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Options for `parse_sequence_identifier_with`. The default options are the ones
+/// `parse_sequence_identifier` uses.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::parse_sequence_identifier_with;
+/// use illumina_coordinates::options::ParseOptions;
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:y:0:0";
+///     let options = ParseOptions { tolerant_filter_flag: true, ..ParseOptions::default() };
+///     assert!(parse_sequence_identifier_with(line, &options).unwrap().is_filtered);
+///     assert!(parse_sequence_identifier_with(line, &ParseOptions::default()).is_err());
+/// }
+/// ```
+pub struct ParseOptions {
+    /// Accept `y` and `n`, and `1` and `0`, as well as `Y` and `N` for the filter flag
+    pub tolerant_filter_flag: bool,
+    /// Accept tabs and runs of spaces and tabs, as well as a single space, between the two halves
+    /// of the identifier. Line endings and surrounding whitespace are always ignored.
+    pub tolerant_whitespace: bool
+}
+
+impl ParseOptions {
+    /// Creates the default, strict options
+    pub fn new() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    /// Decodes a filter flag, returning `None` if it isn't valid
+    pub(crate) fn filter_flag(&self, flag: &str) -> Option<bool> {
+        match flag {
+            "Y" => Some(true),
+            "N" => Some(false),
+            "y" | "1" if self.tolerant_filter_flag => Some(true),
+            "n" | "0" if self.tolerant_filter_flag => Some(false),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How field names are written
+pub enum FieldNaming {
+    /// The names of the fields of `SequenceIdentifier`, such as `flow_cell_id`
+    SnakeCase,
+    /// Names such as `flowCellId`
+    CamelCase
+}
+
+// The snake case and camel case names of the fields before the sample
+const FIELD_NAMES: [(&str, &str); 13] = [
+    ("sequencer_id", "sequencerId"),
+    ("run_count", "runCount"),
+    ("flow_cell_id", "flowCellId"),
+    ("lane", "lane"),
+    ("side", "side"),
+    ("swath", "swath"),
+    ("camera", "camera"),
+    ("tile", "tile"),
+    ("x", "x"),
+    ("y", "y"),
+    ("read", "read"),
+    ("is_filtered", "isFiltered"),
+    ("control_number", "controlNumber")
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Options for the names and shape of fields in CSV and JSON Lines output. The default options
+/// give the fields the names they have in `SequenceIdentifier`, with the sample in one field.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::options::{FieldNaming, OutputOptions};
+///
+/// fn main() {
+///     let options = OutputOptions { naming: FieldNaming::CamelCase, split_sample: true };
+///     let names = options.field_names();
+///     assert_eq!(names[2], "flowCellId");
+///     assert_eq!(&names[13..], &["sampleNumber", "sampleSequence"]);
+///     assert_eq!(OutputOptions::default().field_names().last(), Some(&"sample"));
+/// }
+/// ```
+pub struct OutputOptions {
+    /// How field names are written
+    pub naming: FieldNaming,
+    /// Write the sample as two fields, `sample_number` and `sample_sequence`, only one of which
+    /// has a value, instead of one `sample` field that holds either
+    pub split_sample: bool
+}
+
+impl Default for OutputOptions {
+    fn default() -> OutputOptions {
+        OutputOptions { naming: FieldNaming::SnakeCase, split_sample: false }
+    }
+}
+
+impl OutputOptions {
+    /// Creates the default options
+    pub fn new() -> OutputOptions {
+        OutputOptions::default()
+    }
+
+    /// The names of the fields, in the order they're written
+    pub fn field_names(&self) -> Vec<&'static str> {
+        let camel_case = self.naming == FieldNaming::CamelCase;
+        let mut names: Vec<&'static str> = FIELD_NAMES.iter()
+            .map(|&(snake, camel)| if camel_case { camel } else { snake })
+            .collect();
+        match (self.split_sample, camel_case) {
+            (false, _) => names.push("sample"),
+            (true, false) => names.extend_from_slice(&["sample_number", "sample_sequence"]),
+            (true, true) => names.extend_from_slice(&["sampleNumber", "sampleSequence"])
+        }
+        names
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_flag() {
+        let strict = ParseOptions::new();
+        let tolerant = ParseOptions { tolerant_filter_flag: true, ..ParseOptions::new() };
+        for &(flag, expected) in &[("Y", Some(true)), ("N", Some(false)), ("y", None), ("0", None), ("", None)] {
+            assert_eq!(strict.filter_flag(flag), expected, "{}", flag);
+        }
+        for &(flag, expected) in &[("y", Some(true)), ("1", Some(true)), ("n", Some(false)), ("0", Some(false)), ("yes", None)] {
+            assert_eq!(tolerant.filter_flag(flag), expected, "{}", flag);
+        }
+    }
+
+    #[test]
+    fn test_field_names() {
+        let default = OutputOptions::new().field_names();
+        assert_eq!(default.len(), 14);
+        assert_eq!((default[0], default[11], default[13]), ("sequencer_id", "is_filtered", "sample"));
+        let split = OutputOptions { split_sample: true, ..OutputOptions::new() }.field_names();
+        assert_eq!(&split[..13], &default[..13]);
+        assert_eq!(&split[13..], &["sample_number", "sample_sequence"]);
+        let camel = OutputOptions { naming: FieldNaming::CamelCase, ..OutputOptions::new() }.field_names();
+        assert_eq!((camel[0], camel[12], camel[13]), ("sequencerId", "controlNumber", "sample"));
+    }
+}
@@ -0,0 +1,434 @@
+//! Reads and writes compressed files on several threads, so that reading and writing keep up with
+//! the disk on machines with more than one core.
+//!
+//! The blocks of a BGZF file are independent, so they're decompressed and compressed in
+//! parallel. Other gzip files can only be decompressed in order, so they're decompressed on a
+//! single background thread while the caller parses what has already been decompressed. Only a
+//! few blocks per thread are in flight at once, so memory use doesn't grow with the file.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use bgzf::{compress_block, decompress_block, is_bgzf, is_gzip, read_raw_block, MAX_BLOCK_DATA};
+use gzip::GzipReader;
+
+/// The size of the chunks passed between threads by `ReadAhead`
+const CHUNK_SIZE: usize = 0x10000;
+
+/// How many blocks can be waiting for each thread
+const BLOCKS_PER_THREAD: usize = 4;
+
+// A block of data, and where to send the block once it's been compressed or decompressed
+type Job = (Vec<u8>, SyncSender<io::Result<Vec<u8>>>);
+
+// A compressed or decompressed block that may not be ready yet
+type Pending = Receiver<io::Result<Vec<u8>>>;
+
+/// Starts the threads that compress or decompress blocks, which stop once the returned sender is
+/// dropped
+fn start_workers(threads: usize, work: fn(&[u8]) -> io::Result<Vec<u8>>) -> SyncSender<Job> {
+    let (sender, jobs) = sync_channel::<Job>(threads);
+    let jobs = Arc::new(Mutex::new(jobs));
+    for _ in 0..threads {
+        let jobs = jobs.clone();
+        thread::spawn(move || loop {
+            let job = match jobs.lock() {
+                Ok(jobs) => jobs.recv(),
+                Err(_) => return
+            };
+            match job {
+                Ok((data, result)) => {
+                    let _ = result.send(work(&data));
+                },
+                Err(_) => return
+            }
+        });
+    }
+    sender
+}
+
+/// Submits a block to the workers, returning where its result will arrive
+fn submit(jobs: &SyncSender<Job>, data: Vec<u8>) -> io::Result<Pending> {
+    let (sender, pending) = sync_channel(1);
+    jobs.send((data, sender)).map_err(|_| stopped())?;
+    Ok(pending)
+}
+
+/// Waits for a block to be compressed or decompressed
+fn wait(pending: &Pending) -> io::Result<Vec<u8>> {
+    pending.recv().unwrap_or_else(|_| Err(stopped()))
+}
+
+fn stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "a compression thread stopped unexpectedly")
+}
+
+fn decompress(compressed: &[u8]) -> io::Result<Vec<u8>> {
+    let mut block = Vec::with_capacity(MAX_BLOCK_DATA);
+    decompress_block(compressed, &mut block)?;
+    Ok(block)
+}
+
+fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut block = vec![];
+    compress_block(data, &mut block);
+    Ok(block)
+}
+
+/// Decompresses a BGZF file, decompressing the blocks in parallel
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use std::io::{Cursor, Read, Write};
+/// use illumina_coordinates::bgzf::BgzfWriter;
+/// use illumina_coordinates::parallel::ParallelBgzfReader;
+///
+/// fn main() {
+///     let mut writer = BgzfWriter::new(vec![]);
+///     writer.write_all(b"@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\n").unwrap();
+///     let compressed = writer.finish().unwrap();
+///
+///     let mut text = String::new();
+///     ParallelBgzfReader::new(Cursor::new(compressed), 4).read_to_string(&mut text).unwrap();
+///     assert!(text.starts_with("@M03745"));
+/// }
+/// ```
+pub struct ParallelBgzfReader {
+    blocks: Receiver<Pending>,
+    block: Vec<u8>,
+    position: usize
+}
+
+impl ParallelBgzfReader {
+    /// Starts reading the BGZF data on a background thread, decompressing it on `threads` more
+    pub fn new<R: Read + Send + 'static>(mut inner: R, threads: usize) -> ParallelBgzfReader {
+        let threads = threads.max(1);
+        let jobs = start_workers(threads, decompress);
+        let (sender, blocks) = sync_channel(threads * BLOCKS_PER_THREAD);
+        thread::spawn(move || loop {
+            let mut compressed = vec![];
+            let pending = match read_raw_block(&mut inner, &mut compressed) {
+                Ok(Some(_)) => match submit(&jobs, compressed) {
+                    Ok(pending) => pending,
+                    Err(_) => return
+                },
+                Ok(None) => return,
+                Err(error) => {
+                    let (result, pending) = sync_channel(1);
+                    let _ = result.send(Err(error));
+                    let _ = sender.send(pending);
+                    return;
+                }
+            };
+            if sender.send(pending).is_err() {
+                return;
+            }
+        });
+        ParallelBgzfReader { blocks, block: vec![], position: 0 }
+    }
+}
+
+impl BufRead for ParallelBgzfReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.position == self.block.len() {
+            match self.blocks.recv() {
+                Ok(pending) => {
+                    self.block = wait(&pending)?;
+                    self.position = 0;
+                },
+                Err(_) => break
+            }
+        }
+        Ok(&self.block[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.block.len());
+    }
+}
+
+impl Read for ParallelBgzfReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let available = self.fill_buf()?;
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+/// Reads from the inner reader on a background thread, so that its data is ready by the time
+/// it's needed. Wrapping a `GzipReader` decompresses on the background thread.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use std::io::BufRead;
+/// use illumina_coordinates::parallel::ReadAhead;
+///
+/// fn main() {
+///     let lines: Vec<String> = ReadAhead::new(&b"first\nsecond\n"[..]).lines().map(|line| line.unwrap()).collect();
+///     assert_eq!(lines, vec!["first", "second"]);
+/// }
+/// ```
+pub struct ReadAhead {
+    chunks: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    position: usize
+}
+
+impl ReadAhead {
+    /// Starts reading on a background thread
+    pub fn new<R: Read + Send + 'static>(mut inner: R) -> ReadAhead {
+        let (sender, chunks) = sync_channel(BLOCKS_PER_THREAD);
+        thread::spawn(move || loop {
+            let mut chunk = vec![0; CHUNK_SIZE];
+            let result = match inner.read(&mut chunk) {
+                Ok(0) => return,
+                Ok(n) => {
+                    chunk.truncate(n);
+                    Ok(chunk)
+                },
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => Err(error)
+            };
+            let failed = result.is_err();
+            if sender.send(result).is_err() || failed {
+                return;
+            }
+        });
+        ReadAhead { chunks, chunk: vec![], position: 0 }
+    }
+}
+
+impl BufRead for ReadAhead {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position == self.chunk.len() {
+            if let Ok(chunk) = self.chunks.recv() {
+                self.chunk = chunk?;
+                self.position = 0;
+            }
+        }
+        Ok(&self.chunk[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.chunk.len());
+    }
+}
+
+impl Read for ReadAhead {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let available = self.fill_buf()?;
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+/// Compresses data into BGZF blocks like `BgzfWriter`, compressing the blocks in parallel. The
+/// blocks are written in order.
+///
+/// `finish` must be called once all the data has been written, to write the last blocks and the
+/// end-of-file marker.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use std::io::{Read, Write};
+/// use illumina_coordinates::bgzf::BgzfReader;
+/// use illumina_coordinates::parallel::ParallelBgzfWriter;
+///
+/// fn main() {
+///     let mut writer = ParallelBgzfWriter::new(vec![], 4);
+///     writer.write_all(b"@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\n").unwrap();
+///     let compressed = writer.finish().unwrap();
+///
+///     let mut text = String::new();
+///     BgzfReader::new(&compressed[..]).read_to_string(&mut text).unwrap();
+///     assert!(text.starts_with("@M03745"));
+/// }
+/// ```
+pub struct ParallelBgzfWriter<W: Write> {
+    inner: W,
+    data: Vec<u8>,
+    jobs: SyncSender<Job>,
+    pending: VecDeque<Pending>,
+    max_pending: usize
+}
+
+impl<W: Write> ParallelBgzfWriter<W> {
+    /// Creates a writer that compresses on `threads` background threads
+    pub fn new(inner: W, threads: usize) -> ParallelBgzfWriter<W> {
+        let threads = threads.max(1);
+        ParallelBgzfWriter {
+            inner,
+            data: Vec::with_capacity(MAX_BLOCK_DATA),
+            jobs: start_workers(threads, compress),
+            pending: VecDeque::new(),
+            max_pending: threads * BLOCKS_PER_THREAD
+        }
+    }
+
+    /// Sends the buffered data to be compressed, writing out the oldest blocks if too many are
+    /// waiting
+    fn submit_block(&mut self) -> io::Result<()> {
+        if self.data.is_empty() {
+            return Ok(());
+        }
+        let data = ::std::mem::replace(&mut self.data, Vec::with_capacity(MAX_BLOCK_DATA));
+        self.pending.push_back(submit(&self.jobs, data)?);
+        while self.pending.len() > self.max_pending {
+            self.write_oldest()?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the oldest block to be compressed and writes it
+    fn write_oldest(&mut self) -> io::Result<()> {
+        if let Some(pending) = self.pending.pop_front() {
+            self.inner.write_all(&wait(&pending)?)?;
+        }
+        Ok(())
+    }
+
+    /// Writes any buffered data and the end-of-file marker, and returns the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.submit_block()?;
+        while !self.pending.is_empty() {
+            self.write_oldest()?;
+        }
+        // The end-of-file marker is an empty block
+        self.inner.write_all(&compress(&[])?)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ParallelBgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(MAX_BLOCK_DATA - self.data.len());
+        self.data.extend_from_slice(&buf[..n]);
+        if self.data.len() == MAX_BLOCK_DATA {
+            self.submit_block()?;
+        }
+        Ok(n)
+    }
+
+    /// Writes the buffered data as a block and waits for every block to be written, so a flush
+    /// ends the current block early
+    fn flush(&mut self) -> io::Result<()> {
+        self.submit_block()?;
+        while !self.pending.is_empty() {
+            self.write_oldest()?;
+        }
+        self.inner.flush()
+    }
+}
+
+/// Wraps a reader to decompress it if it's gzipped, using up to `threads` threads. BGZF files are
+/// decompressed in parallel, and other gzip files on a background thread if `threads` is more
+/// than 1. Also returns whether the data was gzipped.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use std::io::{BufRead, Cursor, Write};
+/// use illumina_coordinates::bgzf::BgzfWriter;
+/// use illumina_coordinates::parallel::decompress_reader;
+///
+/// fn main() {
+///     let mut writer = BgzfWriter::new(vec![]);
+///     writer.write_all(b"first\nsecond\n").unwrap();
+///     let compressed = writer.finish().unwrap();
+///
+///     let (reader, gzipped) = decompress_reader(Cursor::new(compressed), 4).unwrap();
+///     assert!(gzipped);
+///     assert_eq!(reader.lines().count(), 2);
+/// }
+/// ```
+pub fn decompress_reader<R: Read + Send + 'static>(mut inner: R, threads: usize)
+    -> io::Result<(Box<dyn BufRead>, bool)> {
+    // Pipes can return less than was asked for, so read the header in as many calls as it takes
+    let mut start = vec![];
+    Read::by_ref(&mut inner).take(18).read_to_end(&mut start)?;
+    let bgzf = is_bgzf(&start);
+    let gzipped = is_gzip(&start);
+    let inner = Cursor::new(start).chain(inner);
+    let reader: Box<dyn BufRead> = match (gzipped, threads > 1) {
+        (true, true) if bgzf => Box::new(ParallelBgzfReader::new(inner, threads)),
+        (true, true) => Box::new(ReadAhead::new(GzipReader::new(BufReader::new(inner)))),
+        (true, false) => Box::new(GzipReader::new(BufReader::new(inner))),
+        (false, _) => Box::new(BufReader::new(inner))
+    };
+    Ok((reader, gzipped))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bgzf::{BgzfReader, BgzfWriter};
+
+    fn text() -> Vec<u8> {
+        (0..5000).map(|i| format!("@M03745:11:000000000-B54L5:1:2108:{}:8949 1:N:0:0\nACGT\n+\nFFFF\n", i))
+            .collect::<String>().into_bytes()
+    }
+
+    #[test]
+    fn test_parallel_bgzf_writer() {
+        let text = text();
+        let mut writer = ParallelBgzfWriter::new(vec![], 3);
+        for chunk in text.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        let compressed = writer.finish().unwrap();
+        let mut expected = BgzfWriter::new(vec![]);
+        for chunk in text.chunks(1000) {
+            expected.write_all(chunk).unwrap();
+        }
+        assert_eq!(compressed, expected.finish().unwrap());
+        let mut decompressed = vec![];
+        BgzfReader::new(&compressed[..]).read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, text);
+    }
+
+    #[test]
+    fn test_decompress_reader() {
+        let text = text();
+        let mut writer = BgzfWriter::new(vec![]);
+        writer.write_all(&text).unwrap();
+        let compressed = writer.finish().unwrap();
+        for &threads in &[1, 4] {
+            let (mut reader, gzipped) = decompress_reader(Cursor::new(compressed.clone()), threads).unwrap();
+            let mut decompressed = vec![];
+            reader.read_to_end(&mut decompressed).unwrap();
+            assert!(gzipped);
+            assert_eq!(decompressed, text);
+        }
+        let (mut reader, gzipped) = decompress_reader(Cursor::new(text.clone()), 4).unwrap();
+        let mut plain = vec![];
+        reader.read_to_end(&mut plain).unwrap();
+        assert!(!gzipped);
+        assert_eq!(plain, text);
+
+        let mut truncated = compressed.clone();
+        truncated.truncate(compressed.len() / 2);
+        let (mut reader, _) = decompress_reader(Cursor::new(truncated), 4).unwrap();
+        assert!(reader.read_to_end(&mut vec![]).is_err());
+    }
+}
@@ -0,0 +1,464 @@
+//! Writes parsed sequence identifiers as a Parquet file with typed columns. For run-scale
+//! analysis, columnar output is far more practical than text.
+//!
+//! Files are written uncompressed with PLAIN encoding, which every Parquet reader supports. Each
+//! field of `SequenceIdentifier` becomes a column of the same name; integer fields are stored as
+//! unsigned `INT32` columns, the camera is nullable, and the sample is stored as a string.
+//!
+//! The output loads straight into a data frame, e.g. with `ParquetReader::new(Cursor::new(bytes))`
+//! in Polars or `pandas.read_parquet` in Python.
+//!
+//! The tests in `compat/` read the output back with the reference `parquet` crate and check the
+//! type and value of every column.
+
+use std::io::{self, Write};
+use super::SequenceIdentifier;
+
+const MAGIC: &[u8] = b"PAR1";
+
+/// The number of rows buffered in memory before they're written out as a row group
+pub const DEFAULT_ROW_GROUP_SIZE: usize = 1_000_000;
+
+// Physical types
+const BOOLEAN: i32 = 0;
+const INT32: i32 = 1;
+const BYTE_ARRAY: i32 = 6;
+
+// Converted types
+const UTF8: i32 = 0;
+const UINT_8: i32 = 11;
+const UINT_16: i32 = 12;
+
+// Encodings
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+
+// Repetition types
+const REQUIRED: i32 = 0;
+const OPTIONAL: i32 = 1;
+
+struct Column {
+    name: &'static str,
+    physical_type: i32,
+    converted_type: Option<i32>,
+    repetition_type: i32
+}
+
+const COLUMNS: [Column; 14] = [
+    Column { name: "sequencer_id", physical_type: BYTE_ARRAY, converted_type: Some(UTF8), repetition_type: REQUIRED },
+    Column { name: "run_count", physical_type: INT32, converted_type: Some(UINT_16), repetition_type: REQUIRED },
+    Column { name: "flow_cell_id", physical_type: BYTE_ARRAY, converted_type: Some(UTF8), repetition_type: REQUIRED },
+    Column { name: "lane", physical_type: INT32, converted_type: Some(UINT_8), repetition_type: REQUIRED },
+    Column { name: "side", physical_type: INT32, converted_type: Some(UINT_8), repetition_type: REQUIRED },
+    Column { name: "swath", physical_type: INT32, converted_type: Some(UINT_8), repetition_type: REQUIRED },
+    Column { name: "camera", physical_type: INT32, converted_type: Some(UINT_8), repetition_type: OPTIONAL },
+    Column { name: "tile", physical_type: INT32, converted_type: Some(UINT_8), repetition_type: REQUIRED },
+    Column { name: "x", physical_type: INT32, converted_type: Some(UINT_16), repetition_type: REQUIRED },
+    Column { name: "y", physical_type: INT32, converted_type: Some(UINT_16), repetition_type: REQUIRED },
+    Column { name: "read", physical_type: INT32, converted_type: Some(UINT_8), repetition_type: REQUIRED },
+    Column { name: "is_filtered", physical_type: BOOLEAN, converted_type: None, repetition_type: REQUIRED },
+    Column { name: "control_number", physical_type: INT32, converted_type: Some(UINT_16), repetition_type: REQUIRED },
+    Column { name: "sample", physical_type: BYTE_ARRAY, converted_type: Some(UTF8), repetition_type: REQUIRED },
+];
+
+const CAMERA: usize = 6;
+const IS_FILTERED: usize = 11;
+
+struct ColumnChunk {
+    offset: u64,
+    size: u64
+}
+
+struct RowGroup {
+    columns: Vec<ColumnChunk>,
+    rows: usize
+}
+
+/// Writes sequence identifiers to a Parquet file. Rows are buffered and written in row groups;
+/// `finish` must be called to write the file footer.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::parquet::ParquetWriter;
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     let mut writer = ParquetWriter::new(vec![]).unwrap();
+///     writer.write(&seq_id).unwrap();
+///     let output = writer.finish().unwrap();
+///     assert!(output.starts_with(b"PAR1") && output.ends_with(b"PAR1"));
+/// }
+/// ```
+pub struct ParquetWriter<W: Write> {
+    writer: W,
+    offset: u64,
+    row_group_size: usize,
+    buffers: Vec<Vec<u8>>,
+    is_filtered: Vec<bool>,
+    has_camera: Vec<bool>,
+    rows: usize,
+    row_groups: Vec<RowGroup>
+}
+
+impl<W: Write> ParquetWriter<W> {
+    /// Creates a writer that uses the default row group size
+    pub fn new(writer: W) -> io::Result<ParquetWriter<W>> {
+        ParquetWriter::with_row_group_size(writer, DEFAULT_ROW_GROUP_SIZE)
+    }
+
+    /// Creates a writer that writes a row group every `row_group_size` rows
+    pub fn with_row_group_size(mut writer: W, row_group_size: usize) -> io::Result<ParquetWriter<W>> {
+        writer.write_all(MAGIC)?;
+        Ok(ParquetWriter {
+            writer,
+            offset: MAGIC.len() as u64,
+            row_group_size: row_group_size.max(1),
+            buffers: COLUMNS.iter().map(|_| vec![]).collect(),
+            is_filtered: vec![],
+            has_camera: vec![],
+            rows: 0,
+            row_groups: vec![]
+        })
+    }
+
+    /// Writes a single sequence identifier as a row
+    pub fn write(&mut self, seq_id: &SequenceIdentifier) -> io::Result<()> {
+        write_string(&mut self.buffers[0], &seq_id.sequencer_id);
+        write_int(&mut self.buffers[1], seq_id.run_count.into());
+        write_string(&mut self.buffers[2], &seq_id.flow_cell_id);
+        write_int(&mut self.buffers[3], seq_id.lane.into());
+        write_int(&mut self.buffers[4], seq_id.side.into());
+        write_int(&mut self.buffers[5], seq_id.swath.into());
+        if let Some(camera) = seq_id.camera {
+            write_int(&mut self.buffers[CAMERA], camera.into());
+        }
+        self.has_camera.push(seq_id.camera.is_some());
+        write_int(&mut self.buffers[7], seq_id.tile.into());
+        write_int(&mut self.buffers[8], seq_id.x.into());
+        write_int(&mut self.buffers[9], seq_id.y.into());
+        write_int(&mut self.buffers[10], seq_id.read.into());
+        self.is_filtered.push(seq_id.is_filtered);
+        write_int(&mut self.buffers[12], seq_id.control_number.into());
+        write_string(&mut self.buffers[13], &seq_id.sample.to_string());
+        self.rows += 1;
+        if self.rows >= self.row_group_size {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    /// Writes any buffered rows and the file footer, and returns the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.rows > 0 {
+            self.write_row_group()?;
+        }
+        let metadata = self.file_metadata();
+        self.writer.write_all(&metadata)?;
+        self.writer.write_all(&(metadata.len() as u32).to_le_bytes())?;
+        self.writer.write_all(MAGIC)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_row_group(&mut self) -> io::Result<()> {
+        self.buffers[IS_FILTERED] = pack_bits(&self.is_filtered);
+        self.is_filtered.clear();
+        // Definition levels of the nullable column precede its values, as a single bit-packed run
+        // with a length prefix
+        let mut levels = vec![];
+        let packed = pack_bits(&self.has_camera);
+        let mut run_header = CompactWriter::new();
+        run_header.varint((packed.len() as u64) << 1 | 1);
+        levels.extend_from_slice(&((run_header.bytes.len() + packed.len()) as u32).to_le_bytes());
+        levels.extend_from_slice(&run_header.bytes);
+        levels.extend_from_slice(&packed);
+        levels.extend_from_slice(&self.buffers[CAMERA]);
+        self.buffers[CAMERA] = levels;
+        self.has_camera.clear();
+
+        let mut columns = vec![];
+        for buffer in &mut self.buffers {
+            let mut header = CompactWriter::new();
+            header.i32_field(1, 0);
+            header.i32_field(2, buffer.len() as i32);
+            header.i32_field(3, buffer.len() as i32);
+            header.struct_begin(5);
+            header.i32_field(1, self.rows as i32);
+            header.i32_field(2, PLAIN);
+            header.i32_field(3, RLE);
+            header.i32_field(4, RLE);
+            header.struct_end();
+            header.stop();
+            self.writer.write_all(&header.bytes)?;
+            self.writer.write_all(buffer)?;
+            let size = (header.bytes.len() + buffer.len()) as u64;
+            columns.push(ColumnChunk { offset: self.offset, size });
+            self.offset += size;
+            buffer.clear();
+        }
+        self.row_groups.push(RowGroup { columns, rows: self.rows });
+        self.rows = 0;
+        Ok(())
+    }
+
+    fn file_metadata(&self) -> Vec<u8> {
+        let mut metadata = CompactWriter::new();
+        metadata.i32_field(1, 1);
+        metadata.list_begin(2, STRUCT, COLUMNS.len() + 1);
+        metadata.element_begin();
+        metadata.binary_field(4, b"schema");
+        metadata.i32_field(5, COLUMNS.len() as i32);
+        metadata.element_end();
+        for column in &COLUMNS {
+            metadata.element_begin();
+            metadata.i32_field(1, column.physical_type);
+            metadata.i32_field(3, column.repetition_type);
+            metadata.binary_field(4, column.name.as_bytes());
+            if let Some(converted_type) = column.converted_type {
+                metadata.i32_field(6, converted_type);
+            }
+            metadata.element_end();
+        }
+        let total_rows: usize = self.row_groups.iter().map(|row_group| row_group.rows).sum();
+        metadata.i64_field(3, total_rows as i64);
+        metadata.list_begin(4, STRUCT, self.row_groups.len());
+        for row_group in &self.row_groups {
+            metadata.element_begin();
+            metadata.list_begin(1, STRUCT, COLUMNS.len());
+            for (column, chunk) in COLUMNS.iter().zip(&row_group.columns) {
+                metadata.element_begin();
+                metadata.i64_field(2, chunk.offset as i64);
+                metadata.struct_begin(3);
+                metadata.i32_field(1, column.physical_type);
+                metadata.list_begin(2, I32, 2);
+                metadata.varint(zigzag(PLAIN.into()));
+                metadata.varint(zigzag(RLE.into()));
+                metadata.list_begin(3, BINARY, 1);
+                metadata.binary(column.name.as_bytes());
+                metadata.i32_field(4, 0);
+                metadata.i64_field(5, row_group.rows as i64);
+                metadata.i64_field(6, chunk.size as i64);
+                metadata.i64_field(7, chunk.size as i64);
+                metadata.i64_field(9, chunk.offset as i64);
+                metadata.struct_end();
+                metadata.element_end();
+            }
+            let size: u64 = row_group.columns.iter().map(|chunk| chunk.size).sum();
+            metadata.i64_field(2, size as i64);
+            metadata.i64_field(3, row_group.rows as i64);
+            metadata.element_end();
+        }
+        metadata.binary_field(6, b"illumina_coordinates");
+        metadata.stop();
+        metadata.bytes
+    }
+}
+
+/// Writes all of the sequence identifiers as a Parquet file, and returns the underlying writer
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::parquet::write_parquet;
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_ids = vec![illumina_coordinates::parse_sequence_identifier(line).unwrap()];
+///     let bytes = write_parquet(vec![], &seq_ids).unwrap();
+///     assert!(bytes.starts_with(b"PAR1"));
+/// }
+/// ```
+pub fn write_parquet<'a, W, I>(writer: W, seq_ids: I) -> io::Result<W>
+    where W: Write, I: IntoIterator<Item=&'a SequenceIdentifier> {
+    let mut writer = ParquetWriter::new(writer)?;
+    for seq_id in seq_ids {
+        writer.write(seq_id)?;
+    }
+    writer.finish()
+}
+
+/// Packs booleans into bytes, eight at a time, least significant bit first
+fn pack_bits(values: &[bool]) -> Vec<u8> {
+    values.chunks(8)
+        .map(|chunk| chunk.iter().enumerate().fold(0u8, |byte, (i, &value)| byte | (value as u8) << i))
+        .collect()
+}
+
+fn write_int(buffer: &mut Vec<u8>, value: i32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+// Thrift compact protocol types
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Encodes the Thrift structures in Parquet file metadata and page headers, using the compact
+/// protocol
+struct CompactWriter {
+    bytes: Vec<u8>,
+    last_field: i16,
+    parents: Vec<i16>
+}
+
+impl CompactWriter {
+    fn new() -> CompactWriter {
+        CompactWriter { bytes: vec![], last_field: 0, parents: vec![] }
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn field_header(&mut self, id: i16, field_type: u8) {
+        let delta = id - self.last_field;
+        if delta > 0 && delta <= 15 {
+            self.bytes.push((delta as u8) << 4 | field_type);
+        } else {
+            self.bytes.push(field_type);
+            self.varint(zigzag(id.into()));
+        }
+        self.last_field = id;
+    }
+
+    fn i32_field(&mut self, id: i16, value: i32) {
+        self.field_header(id, I32);
+        self.varint(zigzag(value.into()));
+    }
+
+    fn i64_field(&mut self, id: i16, value: i64) {
+        self.field_header(id, I64);
+        self.varint(zigzag(value));
+    }
+
+    fn binary(&mut self, value: &[u8]) {
+        self.varint(value.len() as u64);
+        self.bytes.extend_from_slice(value);
+    }
+
+    fn binary_field(&mut self, id: i16, value: &[u8]) {
+        self.field_header(id, BINARY);
+        self.binary(value);
+    }
+
+    fn list_begin(&mut self, id: i16, element_type: u8, size: usize) {
+        self.field_header(id, LIST);
+        if size < 15 {
+            self.bytes.push((size as u8) << 4 | element_type);
+        } else {
+            self.bytes.push(0xf0 | element_type);
+            self.varint(size as u64);
+        }
+    }
+
+    fn struct_begin(&mut self, id: i16) {
+        self.field_header(id, STRUCT);
+        self.element_begin();
+    }
+
+    fn struct_end(&mut self) {
+        self.element_end();
+    }
+
+    /// Starts a struct that is an element of a list, and so has no field header
+    fn element_begin(&mut self) {
+        self.parents.push(self.last_field);
+        self.last_field = 0;
+    }
+
+    fn element_end(&mut self) {
+        self.stop();
+        self.last_field = self.parents.pop().unwrap_or(0);
+    }
+
+    fn stop(&mut self) {
+        self.bytes.push(0);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    fn footer(output: &[u8]) -> &[u8] {
+        let length_start = output.len() - 8;
+        let mut length = [0u8; 4];
+        length.copy_from_slice(&output[length_start..length_start + 4]);
+        &output[length_start - u32::from_le_bytes(length) as usize..length_start]
+    }
+
+    #[test]
+    fn test_compact_writer() {
+        let mut writer = CompactWriter::new();
+        writer.i32_field(1, 1);
+        writer.i32_field(2, -1);
+        writer.i64_field(20, 300);
+        writer.struct_begin(21);
+        writer.binary_field(1, b"ab");
+        writer.struct_end();
+        writer.stop();
+        assert_eq!(writer.bytes, vec![0x15, 0x02, 0x15, 0x01, 0x06, 0x28, 0xd8, 0x04, 0x1c, 0x18, 0x02, b'a', b'b', 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_write_parquet() {
+        let seq_id = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:Y:0:0").unwrap();
+        let mut writer = ParquetWriter::with_row_group_size(vec![], 2).unwrap();
+        for _ in 0..3 {
+            writer.write(&seq_id).unwrap();
+        }
+        let output = writer.finish().unwrap();
+        assert_eq!(&output[..4], MAGIC);
+        assert_eq!(&output[output.len() - 4..], MAGIC);
+        let footer = footer(&output);
+        assert_eq!(footer.last(), Some(&0));
+        assert!(footer.windows(b"flow_cell_id".len()).any(|window| window == b"flow_cell_id"));
+
+        // The x column of the first row group holds two PLAIN-encoded values
+        let x = 4127i32.to_le_bytes();
+        let plain: Vec<u8> = x.iter().chain(x.iter()).cloned().collect();
+        assert!(output.windows(plain.len()).any(|window| window == &plain[..]));
+    }
+
+    #[test]
+    fn test_write_empty() {
+        let output = ParquetWriter::new(vec![]).unwrap().finish().unwrap();
+        assert_eq!(write_parquet(vec![], &[]).unwrap(), output);
+        assert_eq!(&output[..4], MAGIC);
+        assert_eq!(&output[output.len() - 4..], MAGIC);
+        assert!(!footer(&output).is_empty());
+    }
+
+    #[test]
+    fn test_write_camera() {
+        let mut writer = ParquetWriter::new(vec![]).unwrap();
+        for line in &["@NB501234:12:H2VGLBGXY:4:23612:11289:19567 1:N:0:1", "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:Y:0:0"] {
+            writer.write(&parse_sequence_identifier(line).unwrap()).unwrap();
+        }
+        let output = writer.finish().unwrap();
+        // A length prefix, a bit-packed run of one group, the levels of the two rows, and the one camera
+        let camera = [2, 0, 0, 0, 3, 0b01, 6, 0, 0, 0];
+        assert!(output.windows(camera.len()).any(|window| window == camera));
+    }
+}
@@ -0,0 +1,554 @@
+//! Support for patterned flow cells, where clusters grow in a regular grid of nanowells.
+//!
+//! On a patterned flow cell every cluster sits in a well, so the coordinates of clusters only take
+//! the values of the well centres. On other flow cells clusters grow wherever DNA happened to
+//! attach, and their coordinates take any value. Which kind of flow cell produced a run matters
+//! when interpreting duplicates: patterned flow cells produce many more duplicates, including in
+//! neighbouring wells, than unpatterned ones.
+//!
+//! The wells are laid out in a hexagonal grid: the rows are evenly spaced, and every other row is
+//! offset by half the spacing of the wells within a row. A `WellGrid` maps coordinates back to the
+//! row and column of the well, so that duplicates in the same well can be told apart from
+//! duplicates in neighbouring wells.
+//!
+//! Counting the distinct wells that reads came from also estimates how many of a tile's wells
+//! are occupied, which approximates the "% Occupied" metric of Sequencing Analysis Viewer for
+//! runs without InterOp files.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use geometry::TileExtent;
+use super::{SequenceIdentifier, TileId};
+
+// Tiles with fewer reads than this don't tell patterned and unpatterned flow cells apart
+const MIN_READS_PER_TILE: u64 = 500;
+// Reads using fewer than this fraction of the distinct values expected from random coordinates
+// are taken to be on a grid
+const MAX_PATTERNED_FRACTION: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// Guesses whether reads came from a patterned flow cell by how many distinct x- and y-coordinates
+/// they use. Reads on an unpatterned flow cell use about as many distinct values as reads with
+/// uniformly random coordinates would, while reads on a patterned flow cell reuse the coordinates
+/// of the rows and columns of wells.
+///
+/// This is useful when the sequencer ID doesn't identify the instrument, for example because it
+/// was rewritten. When it does, `InstrumentModel::has_patterned_flow_cell` is more reliable.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::parse_sequence_identifier;
+/// use illumina_coordinates::patterned::PatternDetector;
+///
+/// fn main() {
+///     let mut detector = PatternDetector::new();
+///     for row in 0..50 {
+///         for column in 0..50 {
+///             let (x, y) = (1000 + column * 24, 1000 + row * 21);
+///             let line = format!("@A00123:8:H3KJ7DSXX:1:1101:{}:{} 1:N:0:0", x, y);
+///             detector.add(&parse_sequence_identifier(&line).unwrap());
+///         }
+///     }
+///     assert_eq!(detector.is_patterned(), Some(true));
+/// }
+/// ```
+pub struct PatternDetector {
+    tiles: BTreeMap<TileId, TileValues>
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+struct TileValues {
+    reads: u64,
+    xs: HashSet<u16>,
+    ys: HashSet<u16>
+}
+
+impl PatternDetector {
+    /// Creates a detector that hasn't seen any reads
+    pub fn new() -> PatternDetector {
+        PatternDetector::default()
+    }
+
+    /// Adds the coordinates of a read
+    pub fn add(&mut self, seq_id: &SequenceIdentifier) {
+        let values = self.tiles.entry(seq_id.tile_id()).or_default();
+        values.reads += 1;
+        values.xs.insert(seq_id.x);
+        values.ys.insert(seq_id.y);
+    }
+
+    /// The fraction of the distinct coordinates expected from uniformly random positions that
+    /// the reads used, over the tiles with enough reads to tell. Values near 1 suggest an
+    /// unpatterned flow cell, and much smaller values a patterned one. Returns `None` if no tile
+    /// has enough reads.
+    pub fn distinct_fraction(&self) -> Option<f64> {
+        let (mut observed, mut expected) = (0.0, 0.0);
+        for values in self.tiles.values().filter(|values| values.reads >= MIN_READS_PER_TILE) {
+            for axis in &[&values.xs, &values.ys] {
+                observed += axis.len() as f64;
+                expected += expected_distinct(axis, values.reads);
+            }
+        }
+        if expected > 0.0 {
+            Some(observed / expected)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the reads look like they came from a patterned flow cell, or `None` if there
+    /// aren't enough reads on any tile to tell
+    pub fn is_patterned(&self) -> Option<bool> {
+        self.distinct_fraction().map(|fraction| fraction < MAX_PATTERNED_FRACTION)
+    }
+}
+
+/// The number of distinct values expected among `reads` values drawn uniformly from the range
+/// spanned by the observed values
+fn expected_distinct(values: &HashSet<u16>, reads: u64) -> f64 {
+    let (min, max) = values.iter().fold((u16::MAX, 0), |(min, max), &value| (min.min(value), max.max(value)));
+    if min > max {
+        return 0.0;
+    }
+    let range = f64::from(max - min) + 1.0;
+    range * (1.0 - (1.0 - 1.0 / range).powf(reads as f64))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// The row and column of a nanowell within a tile. In odd rows, column `c` sits between columns
+/// `c` and `c + 1` of the even rows.
+pub struct Well {
+    /// The row of the well, counting from the smallest y-coordinate
+    pub row: u32,
+    /// The column of the well within its row, counting from the smallest x-coordinate
+    pub column: u32
+}
+
+impl Well {
+    /// Whether the wells are next to each other in the grid. Each well has six neighbours: two in
+    /// its own row and two in each of the rows above and below it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::patterned::Well;
+    ///
+    /// fn main() {
+    ///     let well = Well { row: 2, column: 5 };
+    ///     assert!(well.is_adjacent(&Well { row: 2, column: 6 }));
+    ///     assert!(well.is_adjacent(&Well { row: 3, column: 4 }));
+    ///     assert!(!well.is_adjacent(&Well { row: 3, column: 6 }));
+    ///     assert!(!well.is_adjacent(&well));
+    /// }
+    /// ```
+    pub fn is_adjacent(&self, other: &Well) -> bool {
+        if self.row == other.row {
+            return self.column.max(other.column) - self.column.min(other.column) == 1;
+        }
+        if self.row.max(other.row) - self.row.min(other.row) != 1 {
+            return false;
+        }
+        // The wells of odd rows are shifted half a column towards larger x-coordinates
+        let (even, odd) = if self.row & 1 == 0 { (self, other) } else { (other, self) };
+        odd.column == even.column || odd.column + 1 == even.column
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The positions of the wells of a patterned flow cell, in the units of the coordinates in
+/// sequence identifiers. The spacing of the wells differs between flow cells and between the
+/// versions of the software that wrote the coordinates, so it's usually estimated from the reads
+/// with `WellGrid::estimate`.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::patterned::{Well, WellGrid};
+///
+/// fn main() {
+///     let grid = WellGrid { x_origin: 1000.0, y_origin: 1000.0, column_spacing: 24.0, row_spacing: 21.0 };
+///     assert_eq!(grid.well(1048, 1000), Well { row: 0, column: 2 });
+///     assert_eq!(grid.well(1061, 1022), Well { row: 1, column: 2 });
+/// }
+/// ```
+pub struct WellGrid {
+    /// The x-coordinate of the first well in the even rows
+    pub x_origin: f64,
+    /// The y-coordinate of the first row
+    pub y_origin: f64,
+    /// The distance between neighbouring wells in a row
+    pub column_spacing: f64,
+    /// The distance between neighbouring rows
+    pub row_spacing: f64
+}
+
+impl WellGrid {
+    /// Estimates the grid from the coordinates of reads on a single tile. The row spacing is the
+    /// most common gap between neighbouring y-coordinates, and the column spacing the most
+    /// common gap between neighbouring x-coordinates within a row. Returns `None` if the reads
+    /// don't cover at least two rows and two columns.
+    pub fn estimate<I: IntoIterator<Item=(u16, u16)>>(positions: I) -> Option<WellGrid> {
+        let positions: Vec<(u16, u16)> = positions.into_iter().collect();
+        let row_spacing = most_common_gap(positions.iter().map(|&(_, y)| y))?;
+        let y_origin = f64::from(positions.iter().map(|&(_, y)| y).min()?);
+        let mut rows: HashMap<u32, Vec<u16>> = HashMap::new();
+        for &(x, y) in &positions {
+            let row = ((f64::from(y) - y_origin) / row_spacing).round() as u32;
+            rows.entry(row).or_default().push(x);
+        }
+        let mut gaps = HashMap::new();
+        for xs in rows.values() {
+            for gap in gaps_between(xs.iter().cloned()) {
+                *gaps.entry(gap).or_insert(0) += 1;
+            }
+        }
+        let column_spacing = f64::from(mode(&gaps)?);
+        let first_even = rows.iter()
+            .filter(|&(row, _)| row & 1 == 0)
+            .filter_map(|(_, xs)| xs.iter().min())
+            .min();
+        let x_origin = match first_even {
+            Some(&x) => f64::from(x),
+            None => f64::from(*rows.values().flatten().min()?) - column_spacing / 2.0
+        };
+        Some(WellGrid { x_origin, y_origin, column_spacing, row_spacing })
+    }
+
+    /// The well nearest the coordinates. Coordinates before the first row or column are placed
+    /// in it.
+    pub fn well(&self, x: u16, y: u16) -> Well {
+        let row = ((f64::from(y) - self.y_origin) / self.row_spacing).round().max(0.0);
+        let shift = if row as u32 & 1 == 1 { self.column_spacing / 2.0 } else { 0.0 };
+        let column = ((f64::from(x) - self.x_origin - shift) / self.column_spacing).round().max(0.0);
+        Well { row: row as u32, column: column as u32 }
+    }
+
+    /// The number of rows and of wells in each row that fit within the extent, counting from the
+    /// origin of the grid
+    pub fn dimensions(&self, extent: TileExtent) -> (u32, u32) {
+        let count = |max: u16, origin: f64, spacing: f64| {
+            if f64::from(max) < origin || spacing <= 0.0 {
+                0
+            } else {
+                ((f64::from(max) - origin) / spacing).floor() as u32 + 1
+            }
+        };
+        (count(extent.y_max, self.y_origin, self.row_spacing), count(extent.x_max, self.x_origin, self.column_spacing))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Counts of the kinds of duplicates on a tile
+pub struct TileDuplicates {
+    /// The number of reads
+    pub reads: u64,
+    /// Duplicates in the same well as an earlier copy, which usually come from the well being
+    /// imaged or called twice
+    pub same_well: u64,
+    /// Duplicates in a well next to an earlier copy, which come from ExAmp seeding a neighbouring
+    /// well with the same molecule, known as pad hopping
+    pub pad_hopping: u64,
+    /// Duplicates further away but within the optical duplicate distance of an earlier copy
+    pub optical: u64,
+    /// Duplicates far from every earlier copy, usually from PCR
+    pub other: u64
+}
+
+impl TileDuplicates {
+    /// The fraction of reads that are pad-hopping duplicates
+    pub fn pad_hopping_rate(&self) -> f64 {
+        if self.reads == 0 {
+            0.0
+        } else {
+            self.pad_hopping as f64 / self.reads as f64
+        }
+    }
+}
+
+/// Finds duplicate reads on patterned flow cells and sorts them by how far each is from the
+/// nearest earlier read with the same sequence: in the same well, in a neighbouring well, within
+/// the optical duplicate distance, or further away. Reads with the same sequence are only
+/// compared when they're on the same tile.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::parse_sequence_identifier;
+/// use illumina_coordinates::patterned::{PadHoppingDetector, WellGrid};
+///
+/// fn main() {
+///     let grid = WellGrid { x_origin: 1000.0, y_origin: 1000.0, column_spacing: 24.0, row_spacing: 21.0 };
+///     let mut detector = PadHoppingDetector::new(grid, 2500);
+///     for &(x, sequence) in &[(1000, "ACGT"), (1024, "ACGT"), (1048, "TTTT")] {
+///         let line = format!("@A00123:8:H3KJ7DSXX:1:1101:{}:1000 1:N:0:0", x);
+///         detector.add(&parse_sequence_identifier(&line).unwrap(), sequence.as_bytes());
+///     }
+///     let tile = detector.tiles().values().next().cloned().unwrap();
+///     assert_eq!(tile.pad_hopping, 1);
+///     assert_eq!(tile.pad_hopping_rate(), 1.0 / 3.0);
+/// }
+/// ```
+pub struct PadHoppingDetector {
+    grid: WellGrid,
+    optical_distance: u16,
+    tiles: BTreeMap<TileId, TileDuplicates>,
+    copies: HashMap<(TileId, Vec<u8>), Copies>
+}
+
+// The positions of the reads seen so far with a sequence on a tile
+type Copies = Vec<(u16, u16)>;
+
+impl PadHoppingDetector {
+    /// Creates a detector for wells laid out on `grid`. Reads within `optical_distance` of each
+    /// other, in each of x and y, count as optical duplicates if they aren't in the same or
+    /// neighbouring wells.
+    pub fn new(grid: WellGrid, optical_distance: u16) -> PadHoppingDetector {
+        PadHoppingDetector { grid, optical_distance, tiles: BTreeMap::new(), copies: HashMap::new() }
+    }
+
+    /// Counts a read with the given sequence
+    pub fn add(&mut self, seq_id: &SequenceIdentifier, sequence: &[u8]) {
+        let (grid, distance, tile) = (self.grid, self.optical_distance, seq_id.tile_id());
+        let counts = self.tiles.entry(tile).or_default();
+        counts.reads += 1;
+        let position = (seq_id.x, seq_id.y);
+        let earlier = self.copies.entry((tile, sequence.to_vec())).or_default();
+        if !earlier.is_empty() {
+            let well = grid.well(position.0, position.1);
+            let wells: Vec<Well> = earlier.iter().map(|&(x, y)| grid.well(x, y)).collect();
+            let is_near = |&(x, y): &(u16, u16)| {
+                x.max(position.0) - x.min(position.0) <= distance && y.max(position.1) - y.min(position.1) <= distance
+            };
+            if wells.contains(&well) {
+                counts.same_well += 1;
+            } else if wells.iter().any(|other| other.is_adjacent(&well)) {
+                counts.pad_hopping += 1;
+            } else if earlier.iter().any(is_near) {
+                counts.optical += 1;
+            } else {
+                counts.other += 1;
+            }
+        }
+        earlier.push(position);
+    }
+
+    /// The duplicate counts for each tile that had at least one read
+    pub fn tiles(&self) -> &BTreeMap<TileId, TileDuplicates> {
+        &self.tiles
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// How many of the wells of a tile had a read
+pub struct TileOccupancy {
+    /// The number of reads
+    pub reads: u64,
+    /// The number of distinct wells with at least one read
+    pub occupied: u64,
+    /// The number of wells on the tile
+    pub wells: u64
+}
+
+impl TileOccupancy {
+    /// The fraction of the wells that had a read
+    pub fn fraction(&self) -> f64 {
+        if self.wells == 0 {
+            0.0
+        } else {
+            self.occupied as f64 / self.wells as f64
+        }
+    }
+}
+
+/// Estimates the fraction of each tile's wells that are occupied by a cluster, from the wells
+/// that reads came from. Sequencing Analysis Viewer counts a well as occupied whether or not its
+/// cluster passed filtering, so the estimate only matches its "% Occupied" when the reads
+/// include those that failed the filter; from files of passing reads alone, it's a lower bound.
+///
+/// Each tile's wells are tracked with one bit per well, so reads can arrive in any order.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::geometry::TileExtent;
+/// use illumina_coordinates::parse_sequence_identifier;
+/// use illumina_coordinates::patterned::{OccupancyEstimator, WellGrid};
+///
+/// fn main() {
+///     let grid = WellGrid { x_origin: 1000.0, y_origin: 1000.0, column_spacing: 24.0, row_spacing: 21.0 };
+///     // Ten rows of ten wells
+///     let extent = TileExtent { x_min: 1000, x_max: 1216, y_min: 1000, y_max: 1189 };
+///     let mut estimator = OccupancyEstimator::new(grid, extent);
+///     for &(x, y) in &[(1000, 1000), (1001, 1001), (1024, 1000), (1012, 1021)] {
+///         let line = format!("@A00123:8:H3KJ7DSXX:1:1101:{}:{} 1:N:0:0", x, y);
+///         estimator.add(&parse_sequence_identifier(&line).unwrap());
+///     }
+///     let tile = estimator.tiles().values().next().cloned().unwrap();
+///     assert_eq!((tile.reads, tile.occupied, tile.wells), (4, 3, 100));
+///     assert_eq!(tile.fraction(), 0.03);
+/// }
+/// ```
+pub struct OccupancyEstimator {
+    grid: WellGrid,
+    rows: u32,
+    columns: u32,
+    tiles: BTreeMap<TileId, (TileOccupancy, Vec<u64>)>
+}
+
+impl OccupancyEstimator {
+    /// Creates an estimator for tiles with wells laid out on `grid` within `extent`. The extent
+    /// of a model's tiles can be found with `InstrumentModel::tile_extent`, or the largest
+    /// coordinates of the reads can be used.
+    pub fn new(grid: WellGrid, extent: TileExtent) -> OccupancyEstimator {
+        let (rows, columns) = grid.dimensions(extent);
+        OccupancyEstimator { grid, rows, columns, tiles: BTreeMap::new() }
+    }
+
+    /// Counts a read. Reads outside the extent are counted as reads but not as occupying a well.
+    pub fn add(&mut self, seq_id: &SequenceIdentifier) {
+        let wells = u64::from(self.rows) * u64::from(self.columns);
+        let (occupancy, occupied) = self.tiles.entry(seq_id.tile_id())
+            .or_insert_with(|| (TileOccupancy { wells, ..TileOccupancy::default() }, vec![0; ((wells + 63) >> 6) as usize]));
+        occupancy.reads += 1;
+        let well = self.grid.well(seq_id.x, seq_id.y);
+        if well.row >= self.rows || well.column >= self.columns {
+            return;
+        }
+        let index = u64::from(well.row) * u64::from(self.columns) + u64::from(well.column);
+        let (word, bit) = ((index / 64) as usize, 1 << (index % 64));
+        if occupied[word] & bit == 0 {
+            occupied[word] |= bit;
+            occupancy.occupied += 1;
+        }
+    }
+
+    /// The occupancy of each tile that had at least one read
+    pub fn tiles(&self) -> BTreeMap<TileId, TileOccupancy> {
+        self.tiles.iter().map(|(&tile, &(occupancy, _))| (tile, occupancy)).collect()
+    }
+}
+
+/// The gaps between neighbouring distinct values
+fn gaps_between<I: Iterator<Item=u16>>(values: I) -> Vec<u16> {
+    let mut values: Vec<u16> = values.collect();
+    values.sort_unstable();
+    values.dedup();
+    values.windows(2).map(|pair| pair[1] - pair[0]).collect()
+}
+
+fn most_common_gap<I: Iterator<Item=u16>>(values: I) -> Option<f64> {
+    let mut gaps = HashMap::new();
+    for gap in gaps_between(values) {
+        *gaps.entry(gap).or_insert(0) += 1;
+    }
+    mode(&gaps).map(f64::from)
+}
+
+/// The most common gap, preferring the smallest of equally common ones
+fn mode(gaps: &HashMap<u16, u32>) -> Option<u16> {
+    gaps.iter().max_by_key(|&(&gap, &count)| (count, u16::MAX - gap)).map(|(&gap, _)| gap)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    fn detector_with<I: IntoIterator<Item=(u16, u16)>>(positions: I) -> PatternDetector {
+        let mut detector = PatternDetector::new();
+        for (x, y) in positions {
+            let line = format!("@A00123:8:H3KJ7DSXX:1:1101:{}:{} 1:N:0:0", x, y);
+            detector.add(&parse_sequence_identifier(&line).unwrap());
+        }
+        detector
+    }
+
+    #[test]
+    fn test_random_positions() {
+        let mut state = 17u64;
+        let mut next = move || {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            1000 + (state >> 33) as u16 % 29000
+        };
+        let detector = detector_with((0..5000).map(|_| (next(), next())));
+        assert!(detector.distinct_fraction().unwrap() > 0.9);
+        assert_eq!(detector.is_patterned(), Some(false));
+    }
+
+    #[test]
+    fn test_hexagonal_grid() {
+        // Every other row of wells is offset by half a column
+        let positions = (0..100u16).flat_map(|row| (0..100u16).map(move |column| {
+            (1000 + column * 24 + (row % 2) * 12, 1000 + row * 21)
+        }));
+        let detector = detector_with(positions);
+        assert!(detector.distinct_fraction().unwrap() < 0.1);
+        assert_eq!(detector.is_patterned(), Some(true));
+    }
+
+    #[test]
+    fn test_estimate_well_grid() {
+        let positions: Vec<(u16, u16)> = (0..20u16).flat_map(|row| (0..20u16).map(move |column| {
+            (1030 + column * 24 + (row % 2) * 12, 1005 + row * 21)
+        })).collect();
+        let grid = WellGrid::estimate(positions.iter().cloned()).unwrap();
+        assert_eq!(grid, WellGrid { x_origin: 1030.0, y_origin: 1005.0, column_spacing: 24.0, row_spacing: 21.0 });
+        assert_eq!(grid.well(1030, 1005), Well { row: 0, column: 0 });
+        assert_eq!(grid.well(1090, 1026), Well { row: 1, column: 2 });
+        assert_eq!(grid.well(1092, 1027), Well { row: 1, column: 2 });
+        assert_eq!(grid.well(0, 0), Well { row: 0, column: 0 });
+        assert!(grid.well(1030, 1005).is_adjacent(&grid.well(1042, 1026)));
+        assert_eq!(WellGrid::estimate(vec![(1000, 1000), (1024, 1000)]), None);
+    }
+
+    #[test]
+    fn test_pad_hopping() {
+        let grid = WellGrid { x_origin: 1000.0, y_origin: 1000.0, column_spacing: 24.0, row_spacing: 21.0 };
+        let mut detector = PadHoppingDetector::new(grid, 100);
+        let reads = [(1101, 1000, 1000, "AAAA"), (1101, 1001, 1001, "AAAA"), (1101, 1012, 1021, "AAAA"),
+                     (1101, 1072, 1000, "AAAA"), (1101, 3000, 3000, "AAAA"), (1102, 1024, 1000, "AAAA"),
+                     (1101, 1024, 1000, "CCCC")];
+        for &(tile, x, y, sequence) in &reads {
+            let line = format!("@A00123:8:H3KJ7DSXX:1:{}:{}:{} 1:N:0:0", tile, x, y);
+            detector.add(&parse_sequence_identifier(&line).unwrap(), sequence.as_bytes());
+        }
+        let tiles: Vec<TileDuplicates> = detector.tiles().values().cloned().collect();
+        assert_eq!(tiles, vec![TileDuplicates { reads: 6, same_well: 1, pad_hopping: 1, optical: 1, other: 1 },
+                               TileDuplicates { reads: 1, ..TileDuplicates::default() }]);
+        assert_eq!(TileDuplicates::default().pad_hopping_rate(), 0.0);
+    }
+
+    #[test]
+    fn test_occupancy() {
+        let grid = WellGrid { x_origin: 1000.0, y_origin: 1000.0, column_spacing: 24.0, row_spacing: 21.0 };
+        let extent = TileExtent { x_min: 1000, x_max: 3399, y_min: 1000, y_max: 3099 };
+        assert_eq!(grid.dimensions(extent), (100, 100));
+        assert_eq!(grid.dimensions(TileExtent { x_max: 999, ..extent }), (100, 0));
+        let mut estimator = OccupancyEstimator::new(grid, extent);
+        // Every third well of the hexagonal grid, each read twice, and a read beyond the extent
+        for row in 0..100u16 {
+            for column in (0..100u16).filter(|column| (row + column) % 3 == 0) {
+                let line = format!("@A00123:8:H3KJ7DSXX:1:1101:{}:{} 1:N:0:0", 1000 + column * 24 + (row % 2) * 12, 1000 + row * 21);
+                estimator.add(&parse_sequence_identifier(&line).unwrap());
+                estimator.add(&parse_sequence_identifier(&line).unwrap());
+            }
+        }
+        estimator.add(&parse_sequence_identifier("@A00123:8:H3KJ7DSXX:1:1101:9000:9000 1:N:0:0").unwrap());
+        let tile = estimator.tiles()[&TileId { lane: 1, side: 1, swath: 1, camera: None, tile: 1 }];
+        assert_eq!((tile.reads, tile.occupied, tile.wells), (6669, 3334, 10000));
+        assert!((tile.fraction() - 0.3334).abs() < 1e-9);
+        assert_eq!(TileOccupancy::default().fraction(), 0.0);
+    }
+
+    #[test]
+    fn test_too_few_reads() {
+        assert_eq!(detector_with((0..100).map(|i| (1000 + i, 1000))).is_patterned(), None);
+        assert_eq!(PatternDetector::new().distinct_fraction(), None);
+    }
+}
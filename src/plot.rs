@@ -0,0 +1,177 @@
+//! Scatter plots of cluster positions within a tile, written as PNG images. Enabled by the
+//! `plotters` feature.
+//!
+//! Bubbles, scratches and smudges on the flow cell leave holes or streaks in the positions of
+//! clusters, or patches of reads that failed the filter, which are obvious at a glance. The
+//! images are encoded with the crate's own DEFLATE encoder, so no imaging library is needed.
+
+use std::io::{self, Write};
+use deflate::{deflate, Crc32};
+use geometry::TileExtent;
+use super::SequenceIdentifier;
+
+const BACKGROUND: [u8; 3] = [255, 255, 255];
+const READ_COLOR: [u8; 3] = [0, 0, 0];
+const PASSED_COLOR: [u8; 3] = [31, 119, 180];
+const FILTERED_COLOR: [u8; 3] = [214, 39, 40];
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The size and colors of a scatter plot
+pub struct ScatterOptions {
+    /// The width of the image in pixels
+    pub width: u32,
+    /// The height of the image in pixels
+    pub height: u32,
+    /// Draw reads that passed the filter in blue and reads that failed it in red, rather than
+    /// drawing every read in black
+    pub color_by_filter: bool
+}
+
+impl Default for ScatterOptions {
+    fn default() -> ScatterOptions {
+        ScatterOptions { width: 1000, height: 1000, color_by_filter: false }
+    }
+}
+
+/// Writes a PNG image of the positions of the reads, which should all come from the same tile.
+/// The tile extent is scaled to fill the image, with the smallest x- and y-coordinates at the top
+/// left, and reads outside the extent are left out.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::geometry;
+/// use illumina_coordinates::plot::{write_tile_png, ScatterOptions};
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     let mut png = vec![];
+///     let options = ScatterOptions { color_by_filter: true, ..ScatterOptions::default() };
+///     write_tile_png(&mut png, &[seq_id], geometry::MISEQ, &options).unwrap();
+///     assert!(png.starts_with(b"\x89PNG"));
+/// }
+/// ```
+pub fn write_tile_png<'a, W, I>(mut writer: W, seq_ids: I, extent: TileExtent, options: &ScatterOptions)
+        -> io::Result<()>
+    where W: Write, I: IntoIterator<Item=&'a SequenceIdentifier> {
+    if options.width == 0 || options.height == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "image must not be empty"));
+    }
+    let width = options.width as usize;
+    let height = options.height as usize;
+    // Each row starts with a byte choosing the PNG filter, which is always none
+    let row_length = 1 + 3 * width;
+    let mut pixels = vec![0u8; row_length * height];
+    for row in pixels.chunks_mut(row_length) {
+        for pixel in row[1..].chunks_mut(3) {
+            pixel.copy_from_slice(&BACKGROUND);
+        }
+    }
+    let scale = |value: u16, min: u16, max: u16, size: usize| {
+        let fraction = f64::from(value - min) / f64::from((max - min).max(1));
+        ((fraction * size as f64) as usize).min(size - 1)
+    };
+    for seq_id in seq_ids {
+        if seq_id.x < extent.x_min || seq_id.x > extent.x_max || seq_id.y < extent.y_min || seq_id.y > extent.y_max {
+            continue;
+        }
+        let column = scale(seq_id.x, extent.x_min, extent.x_max, width);
+        let row = scale(seq_id.y, extent.y_min, extent.y_max, height);
+        let color = match (options.color_by_filter, seq_id.is_filtered) {
+            (false, _) => READ_COLOR,
+            (true, false) => PASSED_COLOR,
+            (true, true) => FILTERED_COLOR
+        };
+        let start = row * row_length + 1 + 3 * column;
+        pixels[start..start + 3].copy_from_slice(&color);
+    }
+
+    writer.write_all(&PNG_SIGNATURE)?;
+    let mut header = vec![];
+    header.extend_from_slice(&options.width.to_be_bytes());
+    header.extend_from_slice(&options.height.to_be_bytes());
+    // 8-bit RGB, with the standard compression and filtering and no interlacing
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(&mut writer, b"IHDR", &header)?;
+    write_chunk(&mut writer, b"IDAT", &zlib(&pixels))?;
+    write_chunk(&mut writer, b"IEND", &[])
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    let mut crc = Crc32::new();
+    crc.update(kind);
+    crc.update(data);
+    writer.write_all(&crc.sum().to_be_bytes())
+}
+
+/// Wraps a DEFLATE stream in the zlib format PNG uses
+fn zlib(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32 KiB window and no preset dictionary
+    let mut out = vec![0x78, 0x01];
+    deflate(data, &mut out);
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deflate::inflate;
+    use geometry;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_write_tile_png() {
+        let seq_ids: Vec<SequenceIdentifier> = ["@M03745:11:000000000-B54L5:1:2108:1000:1000 1:N:0:0",
+                                                "@M03745:11:000000000-B54L5:1:2108:30000:30000 1:Y:0:0",
+                                                "@M03745:11:000000000-B54L5:1:2108:31000:1000 1:N:0:0"]
+            .iter().map(|line| parse_sequence_identifier(line).unwrap()).collect();
+        let options = ScatterOptions { width: 4, height: 2, color_by_filter: true };
+        let mut png = vec![];
+        write_tile_png(&mut png, &seq_ids, geometry::MISEQ, &options).unwrap();
+        assert_eq!(&png[..8], &PNG_SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 4, 0, 0, 0, 2]);
+        let idat_length = u32::from_be_bytes([png[33], png[34], png[35], png[36]]) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let zlib = &png[41..41 + idat_length];
+        let mut pixels = vec![];
+        inflate(&zlib[2..zlib.len() - 4], &mut pixels).unwrap();
+        // The third read is outside the tile
+        let mut expected = vec![0];
+        expected.extend_from_slice(&PASSED_COLOR);
+        expected.extend_from_slice(&[255; 9]);
+        expected.push(0);
+        expected.extend_from_slice(&[255; 9]);
+        expected.extend_from_slice(&FILTERED_COLOR);
+        assert_eq!(pixels, expected);
+        assert_eq!(&zlib[zlib.len() - 4..], &adler32(&pixels).to_be_bytes());
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+    }
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(&[255; 100_000]), 0x149a_302c);
+    }
+}
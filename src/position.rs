@@ -0,0 +1,238 @@
+//! Compact encoding of cluster positions.
+//!
+//! Duplicate-marking tools often need to remember every cluster position in a run. Packing the
+//! position into a `u64` lets billions of them fit in a `HashSet<u64>` instead of storing whole
+//! identifiers. The packed values sort in the same order as (lane, side, swath, camera, tile, x,
+//! y).
+
+use std::fmt;
+use std::str::FromStr;
+use id_string::IdString;
+use subsample::{hash, mix};
+use super::{IlluminaError, SequenceIdentifier};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// The location of a cluster within a flow cell
+pub struct Position {
+    /// Lane number
+    pub lane: u8,
+    /// The near or far side off the flow cell surface
+    pub side: u8,
+    /// The row within a lane
+    pub swath: u8,
+    /// The camera that imaged the tile, for sequencers that number tiles by camera
+    pub camera: Option<u8>,
+    /// The positional order of the region where the cluster is located
+    pub tile: u8,
+    /// The x-coordinate of the cluster
+    pub x: u16,
+    /// The y-coordinate of the cluster
+    pub y: u16
+}
+
+impl<'a> From<&'a SequenceIdentifier> for Position {
+    fn from(seq_id: &'a SequenceIdentifier) -> Position {
+        Position {
+            lane: seq_id.lane,
+            side: seq_id.side,
+            swath: seq_id.swath,
+            camera: seq_id.camera,
+            tile: seq_id.tile,
+            x: seq_id.x,
+            y: seq_id.y
+        }
+    }
+}
+
+/// Packs the lane, side, swath, camera, tile and coordinates of a read into 64 bits. The swath
+/// and camera share a byte, so the swath must be at most 15 and the camera at most 14. Parsed
+/// identifiers always fit, since both are single digits; other values are an error.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::position::{decode_position, encode_position};
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     let position = decode_position(encode_position(&seq_id).unwrap());
+///     assert_eq!(position.tile, 8);
+///     assert_eq!(position.x, 4127);
+/// }
+/// ```
+pub fn encode_position(seq_id: &SequenceIdentifier) -> Result<u64, IlluminaError> {
+    let camera = match seq_id.camera {
+        Some(camera) => camera.checked_add(1).filter(|&camera| camera <= 0xf),
+        None => Some(0)
+    };
+    let camera = match camera {
+        Some(camera) if seq_id.swath <= 0xf => camera,
+        _ => return Err(IlluminaError::FormatError(
+            format!("swath {} and camera {:?} can't be packed into a position", seq_id.swath, seq_id.camera)))
+    };
+    Ok(u64::from(seq_id.lane) << 56
+        | u64::from(seq_id.side) << 48
+        | u64::from(seq_id.swath) << 44
+        | u64::from(camera) << 40
+        | u64::from(seq_id.tile) << 32
+        | u64::from(seq_id.x) << 16
+        | u64::from(seq_id.y))
+}
+
+/// A well-mixed hash of a read's position: of its packed position when it can be packed, and of
+/// each field otherwise
+pub(crate) fn position_hash(seq_id: &SequenceIdentifier) -> u64 {
+    match encode_position(seq_id) {
+        Ok(position) => mix(position),
+        Err(_) => {
+            let (x, y) = (seq_id.x.to_le_bytes(), seq_id.y.to_le_bytes());
+            hash(0, &[seq_id.lane, seq_id.side, seq_id.swath, seq_id.camera.is_some() as u8,
+                      seq_id.camera.unwrap_or(0), seq_id.tile, x[0], x[1], y[0], y[1]])
+        }
+    }
+}
+
+/// Unpacks a value created by `encode_position`
+pub fn decode_position(encoded: u64) -> Position {
+    Position {
+        lane: (encoded >> 56) as u8,
+        side: (encoded >> 48) as u8,
+        swath: (encoded >> 44) as u8 & 0xf,
+        camera: match (encoded >> 40) as u8 & 0xf {
+            0 => None,
+            camera => Some(camera - 1)
+        },
+        tile: (encoded >> 32) as u8,
+        x: (encoded >> 16) as u16,
+        y: encoded as u16
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// The flow cell, lane, tile and coordinates of a cluster, for joining tables of reads from
+/// different sources. Its text form, `FC:L:T:X:Y`, is the end of the read name with the sequencer
+/// ID and run count removed, and the tile is the combined tile field, so keys built from FASTQ
+/// headers and from BAM read names agree.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::position::PositionKey;
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     let key = seq_id.position_key();
+///     assert_eq!(key.to_string(), "000000000-B54L5:1:2108:4127:8949");
+///     assert_eq!("000000000-B54L5:1:2108:4127:8949".parse::<PositionKey>().unwrap(), key);
+/// }
+/// ```
+pub struct PositionKey {
+    /// ID of the flow cell
+    pub flow_cell_id: IdString,
+    /// Lane number
+    pub lane: u8,
+    /// The tile field, combining the side, swath, camera and tile, e.g. 2108
+    pub tile: u32,
+    /// The x-coordinate of the cluster
+    pub x: u16,
+    /// The y-coordinate of the cluster
+    pub y: u16
+}
+
+impl<'a> From<&'a SequenceIdentifier> for PositionKey {
+    fn from(seq_id: &'a SequenceIdentifier) -> PositionKey {
+        PositionKey {
+            flow_cell_id: seq_id.flow_cell_id.clone(),
+            lane: seq_id.lane,
+            tile: seq_id.tile_field(),
+            x: seq_id.x,
+            y: seq_id.y
+        }
+    }
+}
+
+impl fmt::Display for PositionKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}:{}:{}", self.flow_cell_id, self.lane, self.tile, self.x, self.y)
+    }
+}
+
+impl FromStr for PositionKey {
+    type Err = IlluminaError;
+
+    fn from_str(text: &str) -> Result<PositionKey, IlluminaError> {
+        let fields: Vec<&str> = text.split(':').collect();
+        if fields.len() != 5 || fields[0].is_empty() {
+            return Err(IlluminaError::SplitError);
+        }
+        Ok(PositionKey {
+            flow_cell_id: IdString::from(fields[0]),
+            lane: fields[1].parse()?,
+            tile: fields[2].parse()?,
+            x: fields[3].parse()?,
+            y: fields[4].parse()?
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_round_trip() {
+        let seq_id = parse_sequence_identifier("@NS500358:204:HTN5KAFXY:4:21101:20886:1073 1:N:0:TAAGGC").unwrap();
+        let position = decode_position(encode_position(&seq_id).unwrap());
+        assert_eq!(position, Position { lane: 4, side: 2, swath: 1, camera: Some(1), tile: 1, x: 20886, y: 1073 });
+        assert_eq!(Position::from(&seq_id), position);
+        let seq_id = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+        assert_eq!(decode_position(encode_position(&seq_id).unwrap()).camera, None);
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let mut seq_id = parse_sequence_identifier("@NS500358:204:HTN5KAFXY:4:21101:20886:1073 1:N:0:TAAGGC").unwrap();
+        seq_id.camera = Some(14);
+        assert_eq!(decode_position(encode_position(&seq_id).unwrap()).camera, Some(14));
+        for &(swath, camera) in &[(1, Some(15)), (1, Some(255)), (16, None)] {
+            seq_id.swath = swath;
+            seq_id.camera = camera;
+            assert!(encode_position(&seq_id).is_err(), "{} {:?}", swath, camera);
+        }
+        // Positions that can't be packed still hash apart from the ones that can
+        seq_id.swath = 1;
+        seq_id.camera = Some(255);
+        let mut none = seq_id.clone();
+        none.camera = None;
+        assert_ne!(position_hash(&seq_id), position_hash(&none));
+    }
+
+    #[test]
+    fn test_ordering() {
+        let a = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:1101:65535:65535 1:N:0:0").unwrap();
+        let b = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:1102:1000:1000 1:N:0:0").unwrap();
+        let c = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2101:1000:1000 1:N:0:0").unwrap();
+        assert!(encode_position(&a).unwrap() < encode_position(&b).unwrap());
+        assert!(encode_position(&b).unwrap() < encode_position(&c).unwrap());
+        assert!(Position::from(&a) < Position::from(&b));
+        assert!(Position::from(&b) < Position::from(&c));
+    }
+
+    #[test]
+    fn test_position_key() {
+        let seq_id = parse_sequence_identifier("@NS500358:204:HTN5KAFXY:4:21101:20886:1073 1:N:0:TAAGGC").unwrap();
+        let key = PositionKey::from(&seq_id);
+        assert_eq!(key.to_string(), "HTN5KAFXY:4:21101:20886:1073");
+        assert_eq!(key.to_string().parse::<PositionKey>().unwrap(), key);
+        for text in &["", "HTN5KAFXY:4:21101:20886", ":4:21101:20886:1073", "HTN5KAFXY:4:21101:20886:1073:1",
+                      "HTN5KAFXY:4:21101:20886:y"] {
+            assert!(text.parse::<PositionKey>().is_err(), "{}", text);
+        }
+    }
+}
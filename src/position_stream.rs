@@ -0,0 +1,341 @@
+//! A compact binary stream of cluster positions, for the intermediate coordinate dumps of
+//! duplicate analysis.
+//!
+//! Positions written in sorted order change little from one to the next: the tile stays the
+//! same for millions of positions, and the leading coordinate only creeps forward. Each position
+//! is written as the difference from the previous one, as variable-length integers, so most take
+//! two bytes rather than the sixteen or so of a line of TSV.
+//!
+//! The stream starts with a magic number, a format version and the coordinate the positions are
+//! sorted by first. Each position starts with a variable-length integer whose low bits say how it
+//! differs from the previous one:
+//!
+//! - `0`: same tile and leading coordinate. The rest of the integer is the increase in the other
+//!   coordinate.
+//! - `01`: same tile. The rest is the increase in the leading coordinate, and the other
+//!   coordinate follows.
+//! - `11`: a new tile, or a position before the previous one. The lane, side, swath, camera and
+//!   tile follow as bytes, the camera being 0 for none and one more than the camera otherwise,
+//!   then both coordinates.
+//!
+//! Positions in any order can be written, but unsorted ones take more space.
+
+use std::io::{self, BufRead, Write};
+use position::Position;
+use super::IlluminaError;
+
+const MAGIC: &[u8] = b"ICPS";
+
+/// The version of the format that `PositionWriter` writes and `PositionReader` reads
+pub const VERSION: u8 = 1;
+
+// Position tags, in the low bits of the first integer of each position
+const SAME_LEADING: u64 = 0;
+const SAME_TILE: u64 = 1;
+const NEW_TILE: u64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The coordinate that positions are sorted by first, within each tile
+pub enum SortedBy {
+    /// x, then y, the order of `position::encode_position`
+    X,
+    /// y, then x, the order of `external_sort`
+    Y
+}
+
+impl SortedBy {
+    /// The leading and other coordinates of a position
+    fn split(self, position: &Position) -> (u16, u16) {
+        match self {
+            SortedBy::X => (position.x, position.y),
+            SortedBy::Y => (position.y, position.x)
+        }
+    }
+}
+
+/// Whether two positions are on the same tile
+fn same_tile(a: &Position, b: &Position) -> bool {
+    (a.lane, a.side, a.swath, a.camera, a.tile) == (b.lane, b.side, b.swath, b.camera, b.tile)
+}
+
+/// Writes positions to a stream
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::parse_sequence_identifier;
+/// use illumina_coordinates::position::Position;
+/// use illumina_coordinates::position_stream::{read_positions, PositionWriter, SortedBy};
+///
+/// fn main() {
+///     let lines = ["@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+///                  "@M03745:11:000000000-B54L5:1:2108:4127:9120 1:N:0:0"];
+///     let positions: Vec<_> = lines.iter()
+///         .map(|line| Position::from(&parse_sequence_identifier(line).unwrap()))
+///         .collect();
+///     let mut writer = PositionWriter::new(vec![], SortedBy::X).unwrap();
+///     for position in &positions {
+///         writer.write(position).unwrap();
+///     }
+///     let bytes = writer.finish().unwrap();
+///     assert_eq!(bytes.len(), 6 + 10 + 2);
+///     assert_eq!(read_positions(&bytes[..]).unwrap(), positions);
+/// }
+/// ```
+pub struct PositionWriter<W: Write> {
+    writer: W,
+    sorted_by: SortedBy,
+    previous: Option<Position>,
+    buffer: Vec<u8>
+}
+
+impl<W: Write> PositionWriter<W> {
+    /// Creates a writer, and writes the magic number, version and sort order
+    pub fn new(mut writer: W, sorted_by: SortedBy) -> io::Result<PositionWriter<W>> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, sorted_by as u8])?;
+        Ok(PositionWriter { writer, sorted_by, previous: None, buffer: vec![] })
+    }
+
+    /// Writes a single position. Cameras go up to 254, since the stream takes one more than the
+    /// camera.
+    pub fn write(&mut self, position: &Position) -> io::Result<()> {
+        let (leading, other) = self.sorted_by.split(position);
+        let buffer = &mut self.buffer;
+        buffer.clear();
+        match self.previous {
+            Some(ref previous) if same_tile(previous, position) && self.sorted_by.split(previous) <= (leading, other) => {
+                let (previous_leading, previous_other) = self.sorted_by.split(previous);
+                if leading == previous_leading {
+                    write_varint(buffer, u64::from(other - previous_other) << 1 | SAME_LEADING);
+                } else {
+                    write_varint(buffer, u64::from(leading - previous_leading) << 2 | SAME_TILE);
+                    write_varint(buffer, other.into());
+                }
+            },
+            _ => {
+                let camera = match position.camera {
+                    Some(camera) => camera.checked_add(1).ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidInput, "camera 255 can't be written to a position stream")
+                    })?,
+                    None => 0
+                };
+                write_varint(buffer, NEW_TILE);
+                buffer.extend_from_slice(&[position.lane, position.side, position.swath, camera, position.tile]);
+                write_varint(buffer, leading.into());
+                write_varint(buffer, other.into());
+            }
+        }
+        self.writer.write_all(buffer)?;
+        self.previous = Some(*position);
+        Ok(())
+    }
+
+    /// Flushes and returns the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Writes all of the positions to a stream, and returns the underlying writer
+pub fn write_positions<'a, W, I>(writer: W, sorted_by: SortedBy, positions: I) -> io::Result<W>
+    where W: Write, I: IntoIterator<Item=&'a Position> {
+    let mut writer = PositionWriter::new(writer, sorted_by)?;
+    for position in positions {
+        writer.write(position)?;
+    }
+    writer.finish()
+}
+
+/// Reads the positions in a stream, in the order they were written
+pub struct PositionReader<R: BufRead> {
+    reader: R,
+    sorted_by: SortedBy,
+    previous: Option<Position>
+}
+
+impl<R: BufRead> PositionReader<R> {
+    /// Creates a reader, and checks the magic number and version
+    pub fn new(mut reader: R) -> Result<PositionReader<R>, IlluminaError> {
+        let mut header = [0u8; 6];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(IlluminaError::FormatError("not a position stream".to_string()));
+        }
+        if header[4] != VERSION {
+            return Err(IlluminaError::FormatError(format!("unsupported position stream version {}", header[4])));
+        }
+        let sorted_by = match header[5] {
+            0 => SortedBy::X,
+            1 => SortedBy::Y,
+            order => return Err(IlluminaError::FormatError(format!("unknown position stream order {}", order)))
+        };
+        Ok(PositionReader { reader, sorted_by, previous: None })
+    }
+
+    /// The coordinate the positions were sorted by first
+    pub fn sorted_by(&self) -> SortedBy {
+        self.sorted_by
+    }
+
+    /// Reads the next position, or returns `None` at the end of the stream
+    pub fn read(&mut self) -> Result<Option<Position>, IlluminaError> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let first = self.varint()?;
+        let position = match (self.previous, first & 3) {
+            (_, NEW_TILE) => {
+                let mut tile = [0u8; 5];
+                self.reader.read_exact(&mut tile)?;
+                let camera = if tile[3] == 0 { None } else { Some(tile[3] - 1) };
+                let mut position = Position { lane: tile[0], side: tile[1], swath: tile[2], camera, tile: tile[4], x: 0, y: 0 };
+                let (leading, other) = (self.varint_u16()?, self.varint_u16()?);
+                self.set(&mut position, leading, other);
+                position
+            },
+            (Some(previous), tag) => {
+                let (previous_leading, previous_other) = self.sorted_by.split(&previous);
+                let (leading, other) = if tag & 1 == SAME_LEADING {
+                    (previous_leading, add(previous_other, first >> 1)?)
+                } else {
+                    (add(previous_leading, first >> 2)?, self.varint_u16()?)
+                };
+                let mut position = previous;
+                self.set(&mut position, leading, other);
+                position
+            },
+            (None, _) => return Err(IlluminaError::FormatError("first position in stream has no tile".to_string()))
+        };
+        self.previous = Some(position);
+        Ok(Some(position))
+    }
+
+    /// Sets the coordinates of a position from its leading and other coordinates
+    fn set(&self, position: &mut Position, leading: u16, other: u16) {
+        match self.sorted_by {
+            SortedBy::X => {
+                position.x = leading;
+                position.y = other;
+            },
+            SortedBy::Y => {
+                position.y = leading;
+                position.x = other;
+            }
+        }
+    }
+
+    fn varint(&mut self) -> Result<u64, IlluminaError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let mut byte = [0u8];
+            self.reader.read_exact(&mut byte)?;
+            value |= u64::from(byte[0] & 0x7f) << shift;
+            if byte[0] & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(IlluminaError::FormatError("variable-length integer in position stream is too long".to_string()))
+    }
+
+    fn varint_u16(&mut self) -> Result<u16, IlluminaError> {
+        add(0, self.varint()?)
+    }
+}
+
+impl<R: BufRead> Iterator for PositionReader<R> {
+    type Item = Result<Position, IlluminaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+/// Reads all of the positions in a stream
+pub fn read_positions<R: BufRead>(reader: R) -> Result<Vec<Position>, IlluminaError> {
+    PositionReader::new(reader)?.collect()
+}
+
+/// Adds a difference read from the stream to a coordinate, checking that the result fits
+fn add(coordinate: u16, difference: u64) -> Result<u16, IlluminaError> {
+    let value = u64::from(coordinate) + difference;
+    if value > u64::from(u16::MAX) {
+        return Err(IlluminaError::FormatError(format!("coordinate {} in position stream is out of range", value)));
+    }
+    Ok(value as u16)
+}
+
+/// Writes an unsigned LEB128 integer: seven bits per byte, least significant first
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(lane: u8, camera: Option<u8>, tile: u8, x: u16, y: u16) -> Position {
+        Position { lane, side: 1, swath: 2, camera, tile, x, y }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let positions = [position(1, None, 1, 10, 20), position(1, None, 1, 10, 25), position(1, None, 1, 12, 3),
+                         position(1, None, 1, 11, 3), position(1, Some(0), 1, 11, 3), position(2, Some(5), 9, 65535, 65535),
+                         position(2, Some(5), 9, 65535, 65535)];
+        for &sorted_by in &[SortedBy::X, SortedBy::Y] {
+            let bytes = write_positions(vec![], sorted_by, &positions).unwrap();
+            assert_eq!(&bytes[..5], b"ICPS\x01");
+            let reader = PositionReader::new(&bytes[..]).unwrap();
+            assert_eq!(reader.sorted_by(), sorted_by);
+            assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), positions);
+        }
+    }
+
+    #[test]
+    fn test_size() {
+        // A tile's worth of positions, sorted by y then x, about 150 per row
+        let mut positions = vec![];
+        for i in 0..100_000u32 {
+            let x = (i.wrapping_mul(2_654_435_761) >> 17) as u16;
+            positions.push(position(1, None, 1, x, (i / 150) as u16));
+        }
+        positions.sort_by_key(|position| (position.y, position.x));
+        let bytes = write_positions(vec![], SortedBy::Y, &positions).unwrap();
+        let tsv: usize = positions.iter()
+            .map(|position| format!("{}\t{}\t{}\t{}\n", position.lane, 1201, position.x, position.y).len())
+            .sum();
+        assert!(bytes.len() * 6 < tsv, "{} {}", bytes.len(), tsv);
+        assert_eq!(read_positions(&bytes[..]).unwrap(), positions);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(read_positions(&b"ICPS"[..]).is_err());
+        assert!(read_positions(&b"ICID\x01\x00"[..]).is_err());
+        assert!(read_positions(&b"ICPS\x02\x00"[..]).is_err());
+        assert!(read_positions(&b"ICPS\x01\x02"[..]).is_err());
+        assert_eq!(read_positions(&b"ICPS\x01\x00"[..]).unwrap(), vec![]);
+        // A difference from a previous position that isn't there
+        assert!(read_positions(&b"ICPS\x01\x00\x02"[..]).is_err());
+        // A coordinate past 65535
+        assert!(read_positions(&b"ICPS\x01\x00\x03\x01\x01\x01\x00\x01\x80\x80\x04\x00"[..]).is_err());
+        let bytes = write_positions(vec![], SortedBy::X, &[position(1, None, 1, 300, 300)]).unwrap();
+        assert!(read_positions(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_camera_range() {
+        let bytes = write_positions(vec![], SortedBy::X, &[position(1, Some(254), 1, 3, 4)]).unwrap();
+        assert_eq!(read_positions(&bytes[..]).unwrap()[0].camera, Some(254));
+        let error = write_positions(vec![], SortedBy::X, &[position(1, Some(255), 1, 3, 4)]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
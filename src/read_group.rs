@@ -0,0 +1,78 @@
+//! SAM read groups derived from sequence identifiers.
+//!
+//! Aligners and variant callers expect each read to belong to a read group naming the flow cell
+//! and lane it was sequenced on, so that reads from different lanes can be told apart when
+//! recalibrating base qualities or marking duplicates. The usual convention, which GATK and most
+//! aligner wrappers follow, is an ID and platform unit of `<flow cell>.<lane>`.
+
+use std::fmt;
+use super::SequenceIdentifier;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The fields of a SAM `@RG` header line. The platform is always `ILLUMINA`.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::read_group::ReadGroup;
+///
+/// fn main() {
+///     let line = "@A00123:8:H3KJ7DSXX:2:1101:1000:1000 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     let mut read_group = ReadGroup::new(&seq_id);
+///     assert_eq!(read_group.to_string(), "@RG\tID:H3KJ7DSXX.2\tPU:H3KJ7DSXX.2\tPL:ILLUMINA");
+///     read_group.sample = Some("NA12878".to_string());
+///     read_group.library = Some("lib1".to_string());
+///     assert_eq!(read_group.to_string(), "@RG\tID:H3KJ7DSXX.2\tPU:H3KJ7DSXX.2\tPL:ILLUMINA\tSM:NA12878\tLB:lib1");
+/// }
+/// ```
+pub struct ReadGroup {
+    /// The read group ID, written to the `RG` tag of each read
+    pub id: String,
+    /// The platform unit, the flow cell and lane
+    pub platform_unit: String,
+    /// The sample, if known
+    pub sample: Option<String>,
+    /// The library, if known
+    pub library: Option<String>
+}
+
+impl ReadGroup {
+    /// The read group of a read's flow cell and lane, without a sample or library
+    pub fn new(seq_id: &SequenceIdentifier) -> ReadGroup {
+        let id = seq_id.read_group_id();
+        ReadGroup { platform_unit: id.clone(), id, sample: None, library: None }
+    }
+}
+
+impl fmt::Display for ReadGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "@RG\tID:{}\tPU:{}\tPL:ILLUMINA", self.id, self.platform_unit)?;
+        if let Some(ref sample) = self.sample {
+            write!(f, "\tSM:{}", sample)?;
+        }
+        if let Some(ref library) = self.library {
+            write!(f, "\tLB:{}", library)?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_read_group() {
+        let seq_id = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+        assert_eq!(seq_id.read_group_id(), "000000000-B54L5.1");
+        let mut read_group = ReadGroup::new(&seq_id);
+        read_group.library = Some("lib1".to_string());
+        assert_eq!(read_group.to_string(), "@RG\tID:000000000-B54L5.1\tPU:000000000-B54L5.1\tPL:ILLUMINA\tLB:lib1");
+        let mate = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:1101:1:1 2:Y:0:0").unwrap();
+        assert_eq!(ReadGroup::new(&mate), ReadGroup::new(&seq_id));
+    }
+}
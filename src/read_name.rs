@@ -0,0 +1,155 @@
+//! Utilities for working with read names, the part of a sequence identifier that names the
+//! cluster.
+
+#[cfg(any(feature = "rust-htslib", feature = "noodles-sam"))]
+use std::convert::TryFrom;
+use std::str;
+#[cfg(feature = "noodles-sam")]
+use noodles_sam;
+#[cfg(feature = "rust-htslib")]
+use rust_htslib;
+use super::{parse_read_name_borrowed, ClusterKey, IlluminaError};
+
+/// Canonicalizes a read name so that FASTQ headers and BAM read names can be joined. The
+/// leading `@`, surrounding whitespace, the comment half and any trailing `/1` or `/2` are
+/// removed, leaving the bare cluster name.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::read_name;
+///
+/// fn main() {
+///     let header = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     assert_eq!(read_name::normalize(header), "M03745:11:000000000-B54L5:1:2108:4127:8949");
+///     let bam_name = "M03745:11:000000000-B54L5:1:2108:4127:8949/1";
+///     assert_eq!(read_name::normalize(bam_name), "M03745:11:000000000-B54L5:1:2108:4127:8949");
+/// }
+/// ```
+pub fn normalize(name: &str) -> &str {
+    let name = name.trim();
+    let name = name.strip_prefix('@').unwrap_or(name);
+    let name = match name.find(char::is_whitespace) {
+        Some(end) => &name[..end],
+        None => name
+    };
+    name.strip_suffix("/1")
+        .or_else(|| name.strip_suffix("/2"))
+        .unwrap_or(name)
+}
+
+/// Parses a read name as stored in BAM and SAM files, which have no `@` and no comment half, into
+/// the fields that locate the cluster. A trailing `/1` or `/2` is ignored. Names can be given as
+/// strings or as the raw bytes BAM libraries return. With the `rust-htslib` and `noodles-sam`
+/// features, their records convert to a `ClusterKey` through `TryFrom` this way.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::read_name;
+///
+/// fn main() {
+///     let cluster = read_name::parse(b"M03745:11:000000000-B54L5:1:2108:4127:8949").unwrap();
+///     assert_eq!((cluster.tile, cluster.x, cluster.y), (8, 4127, 8949));
+///     assert!(read_name::parse("read1").is_err());
+/// }
+/// ```
+pub fn parse<N: AsRef<[u8]>>(name: N) -> Result<ClusterKey, IlluminaError> {
+    let name = str::from_utf8(name.as_ref())
+        .map_err(|_| IlluminaError::FormatError("read name isn't valid UTF-8".to_string()))?;
+    let name = name.trim();
+    let name = name.strip_suffix("/1").or_else(|| name.strip_suffix("/2")).unwrap_or(name);
+    Ok(parse_read_name_borrowed(name)?.into_owned().cluster_key())
+}
+
+
+#[cfg(feature = "noodles-sam")]
+fn parse_record_name(name: Option<&[u8]>) -> Result<ClusterKey, IlluminaError> {
+    parse(name.ok_or_else(|| IlluminaError::FormatError("record has no read name".to_string()))?)
+}
+
+#[cfg(feature = "rust-htslib")]
+impl<'a> TryFrom<&'a rust_htslib::bam::Record> for ClusterKey {
+    type Error = IlluminaError;
+
+    fn try_from(record: &'a rust_htslib::bam::Record) -> Result<ClusterKey, IlluminaError> {
+        parse(record.qname())
+    }
+}
+
+#[cfg(feature = "noodles-sam")]
+impl<'a> TryFrom<&'a noodles_sam::Record> for ClusterKey {
+    type Error = IlluminaError;
+
+    fn try_from(record: &'a noodles_sam::Record) -> Result<ClusterKey, IlluminaError> {
+        parse_record_name(record.name().map(|name| name.as_ref()))
+    }
+}
+
+#[cfg(feature = "noodles-sam")]
+impl<'a> TryFrom<&'a noodles_sam::alignment::RecordBuf> for ClusterKey {
+    type Error = IlluminaError;
+
+    fn try_from(record: &'a noodles_sam::alignment::RecordBuf) -> Result<ClusterKey, IlluminaError> {
+        parse_record_name(record.name().map(|name| name.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let expected = "M03745:11:000000000-B54L5:1:2108:4127:8949";
+        assert_eq!(normalize("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\n"), expected);
+        assert_eq!(normalize("@M03745:11:000000000-B54L5:1:2108:4127:8949\t1:N:0:0"), expected);
+        assert_eq!(normalize("M03745:11:000000000-B54L5:1:2108:4127:8949/2"), expected);
+        assert_eq!(normalize("  @M03745:11:000000000-B54L5:1:2108:4127:8949/1 extra"), expected);
+        assert_eq!(normalize(expected), expected);
+    }
+
+    #[test]
+    fn test_parse() {
+        let cluster = parse("NS500358:204:HTN5KAFXY:1:11101:20886:1073/2").unwrap();
+        assert_eq!(cluster, parse(b"NS500358:204:HTN5KAFXY:1:11101:20886:1073").unwrap());
+        assert_eq!((cluster.lane, cluster.camera, cluster.tile, cluster.x), (1, Some(1), 1, 20886));
+        assert!(parse("NS500358:204:HTN5KAFXY:1:11101:20886:1073 1:N:0:0").is_err());
+        assert!(parse(&b"NS500358:204:\xff"[..]).is_err());
+    }
+
+    #[cfg(feature = "rust-htslib")]
+    #[test]
+    fn test_htslib_record() {
+        use rust_htslib::bam::Record;
+        let mut record = Record::new();
+        record.set(b"NS500358:204:HTN5KAFXY:1:11101:20886:1073", None, b"ACGT", b"FFFF");
+        assert_eq!(ClusterKey::try_from(&record).unwrap(), parse("NS500358:204:HTN5KAFXY:1:11101:20886:1073").unwrap());
+        record.set_qname(b"read1");
+        assert!(ClusterKey::try_from(&record).is_err());
+    }
+
+    #[cfg(feature = "noodles-sam")]
+    #[test]
+    fn test_noodles_sam_record() {
+        use noodles_sam::alignment::RecordBuf;
+        let expected = parse("NS500358:204:HTN5KAFXY:1:11101:20886:1073").unwrap();
+        let text = "NS500358:204:HTN5KAFXY:1:11101:20886:1073/1\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\tFFFF\n";
+        let mut reader = noodles_sam::io::Reader::new(text.as_bytes());
+        let header = reader.read_header().unwrap();
+        let mut record = noodles_sam::Record::default();
+        reader.read_record(&mut record).unwrap();
+        assert_eq!(ClusterKey::try_from(&record).unwrap(), expected);
+        let record_buf = RecordBuf::try_from_alignment_record(&header, &record).unwrap();
+        assert_eq!(ClusterKey::try_from(&record_buf).unwrap(), expected);
+        assert!(ClusterKey::try_from(&RecordBuf::default()).is_err());
+    }
+
+    #[test]
+    fn test_normalize_empty() {
+        assert_eq!(normalize(""), "");
+        assert_eq!(normalize("@"), "");
+    }
+}
@@ -0,0 +1,124 @@
+//! Keeps going past malformed records rather than stopping at the first one.
+//!
+//! Files that went through a flaky transfer often have a few garbage lines in them, and it's
+//! usually better to skip the affected records than to give up on the whole file. A long run of
+//! errors, on the other hand, means the file isn't what it was expected to be, so a cap on
+//! consecutive errors can be set to stop early.
+
+use super::IlluminaError;
+
+/// An iterator adapter that passes through both parsed records and errors, counting the errors.
+/// If there are more consecutive errors than the maximum, the first error over the limit is
+/// replaced by one saying so, and iteration stops.
+///
+/// The adapter can only keep going if the underlying iterator does. The headers of a file mapped
+/// by `mmap::MappedFile` stop at the first error, and `FastqReader` loses track of where records
+/// start after a garbage line, so this is most useful for iterators over header lines.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::parse_sequence_identifier;
+/// use illumina_coordinates::recover::Recovering;
+///
+/// fn main() {
+///     let headers = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\n\
+///                    @M03745:11:000000000-B5\n\
+///                    @M03745:11:000000000-B54L5:1:2108:4128:8950 1:N:0:0";
+///     let mut seq_ids = Recovering::new(headers.lines().map(parse_sequence_identifier), Some(10));
+///     let x: Vec<u16> = seq_ids.by_ref().filter_map(Result::ok).map(|seq_id| seq_id.x).collect();
+///     assert_eq!(x, vec![4127, 4128]);
+///     assert_eq!(seq_ids.error_count(), 1);
+/// }
+/// ```
+pub struct Recovering<I> {
+    inner: I,
+    max_consecutive_errors: Option<usize>,
+    consecutive_errors: usize,
+    error_count: usize,
+    stopped: bool
+}
+
+impl<T, I: Iterator<Item=Result<T, IlluminaError>>> Recovering<I> {
+    /// Wraps an iterator, stopping if there are more than `max_consecutive_errors` errors in a
+    /// row, if it's set
+    pub fn new(inner: I, max_consecutive_errors: Option<usize>) -> Recovering<I> {
+        Recovering { inner, max_consecutive_errors, consecutive_errors: 0, error_count: 0, stopped: false }
+    }
+
+    /// The number of errors the underlying iterator has yielded so far
+    pub fn error_count(&self) -> usize {
+        self.error_count
+    }
+
+    /// Whether iteration stopped because there were too many consecutive errors
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Returns the underlying iterator
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+}
+
+impl<T, I: Iterator<Item=Result<T, IlluminaError>>> Iterator for Recovering<I> {
+    type Item = Result<T, IlluminaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.stopped {
+            return None;
+        }
+        let result = self.inner.next()?;
+        match result {
+            Ok(_) => self.consecutive_errors = 0,
+            Err(_) => {
+                self.consecutive_errors += 1;
+                self.error_count += 1;
+                if let Some(max) = self.max_consecutive_errors {
+                    if self.consecutive_errors > max {
+                        self.stopped = true;
+                        return Some(Err(IlluminaError::FormatError(
+                            format!("stopped after more than {} consecutive errors", max))));
+                    }
+                }
+            }
+        }
+        Some(result)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(pattern: &str) -> Vec<Result<char, IlluminaError>> {
+        pattern.chars().map(|c| if c == 'x' { Err(IlluminaError::ParseError) } else { Ok(c) }).collect()
+    }
+
+    #[test]
+    fn test_recovering() {
+        let mut recovering = Recovering::new(results("axxbxc").into_iter(), None);
+        let ok: String = recovering.by_ref().filter_map(Result::ok).collect();
+        assert_eq!(ok, "abc");
+        assert_eq!(recovering.error_count(), 3);
+        assert!(!recovering.is_stopped());
+    }
+
+    #[test]
+    fn test_max_consecutive_errors() {
+        let recovering = Recovering::new(results("axxbxxxc").into_iter(), Some(2));
+        let items: Vec<String> = recovering.map(|item| match item {
+            Ok(c) => c.to_string(),
+            Err(error) => error.to_string()
+        }).collect();
+        assert_eq!(items, vec!["a", "expected an integer", "expected an integer", "b", "expected an integer",
+                               "expected an integer", "stopped after more than 2 consecutive errors"]);
+        let mut recovering = Recovering::new(results("xa").into_iter(), Some(0));
+        assert!(recovering.next().unwrap().is_err());
+        assert!(recovering.next().is_none());
+        assert!(recovering.is_stopped());
+    }
+}
@@ -0,0 +1,326 @@
+//! Edits the fields of FASTQ headers and writes them back out, for tools that move, rename or
+//! tag reads.
+//!
+//! Headers carry more than the fields of a `SequenceIdentifier`. bcl2fastq and BCL Convert
+//! append the UMI to the read name, as in `@A00123:8:H3KJ7DSXX:1:1101:1000:1000:ACGTACGT`, and
+//! other tools add tags such as `BC:Z:TAAGGCGA` after the comment. A `Header` keeps both, so
+//! that rewriting the fields leaves the rest of the header as it was.
+//!
+//! Formatting the parsed fields normalizes them, dropping zero padding from coordinates written
+//! as `01000` and turning a tab after the read name into a space. `Header::parse_preserving` also
+//! keeps the original text of each field, so that only the fields that were changed are written
+//! differently.
+
+use std::fmt;
+use super::{parse_comment, parse_read_name_borrowed, IlluminaError, ParseOptions, Sample, SequenceIdentifier};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// A field of a header, as it's written between the colons
+pub enum Field {
+    /// The sequencer ID
+    SequencerId,
+    /// The run count
+    RunCount,
+    /// The flow cell ID
+    FlowCellId,
+    /// The lane
+    Lane,
+    /// The tile field, combining the side, swath, camera and tile
+    Tile,
+    /// The x-coordinate
+    X,
+    /// The y-coordinate
+    Y,
+    /// The read number
+    Read,
+    /// The filter flag
+    Filter,
+    /// The control number
+    ControlNumber,
+    /// The sample number or index sequence
+    Sample
+}
+
+// The fields in the order they're written, the first seven making up the read name
+const FIELDS: [Field; 11] = [Field::SequencerId, Field::RunCount, Field::FlowCellId, Field::Lane, Field::Tile,
+                             Field::X, Field::Y, Field::Read, Field::Filter, Field::ControlNumber, Field::Sample];
+
+impl Field {
+    /// The field of an identifier, formatted as `SequenceIdentifier` writes it
+    fn format(self, seq_id: &SequenceIdentifier) -> String {
+        match self {
+            Field::SequencerId => seq_id.sequencer_id.to_string(),
+            Field::RunCount => seq_id.run_count.to_string(),
+            Field::FlowCellId => seq_id.flow_cell_id.to_string(),
+            Field::Lane => seq_id.lane.to_string(),
+            Field::Tile => seq_id.tile_field().to_string(),
+            Field::X => seq_id.x.to_string(),
+            Field::Y => seq_id.y.to_string(),
+            Field::Read => seq_id.read.to_string(),
+            Field::Filter => if seq_id.is_filtered { "Y" } else { "N" }.to_string(),
+            Field::ControlNumber => seq_id.control_number.to_string(),
+            Field::Sample => seq_id.sample.to_string()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The original text of a header's fields and the values parsed from them
+struct RawFields {
+    fields: Vec<String>,
+    // The whitespace between the read name and the comment
+    separator: String,
+    original: SequenceIdentifier
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A parsed FASTQ header whose fields can be changed
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::rewrite::Header;
+///
+/// fn main() {
+///     let mut header = Header::parse("@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:TAAGGCGA BC:Z:TAAGGCGA").unwrap();
+///     header.seq_id.lane = 2;
+///     header.strip_barcode();
+///     header.set_umi(Some("ACGTACGT")).unwrap();
+///     assert_eq!(header.to_string(), "@A00123:8:H3KJ7DSXX:2:1101:1000:1000:ACGTACGT 1:N:0:0 BC:Z:TAAGGCGA");
+/// }
+/// ```
+pub struct Header {
+    /// The fields of the sequence identifier
+    pub seq_id: SequenceIdentifier,
+    umi: Option<String>,
+    extras: String,
+    raw: Option<RawFields>
+}
+
+impl Header {
+    /// Parses a header, with or without the leading `@`. An eighth field of the read name is
+    /// the UMI, and anything after the comment is kept as it is.
+    pub fn parse(text: &str) -> Result<Header, IlluminaError> {
+        let text = text.trim_end_matches(&['\n', '\r'][..]);
+        let text = text.strip_prefix('@').unwrap_or(text);
+        let (name, rest) = split_whitespace(text).ok_or(IlluminaError::SplitError)?;
+        // The extras keep the whitespace before them, which may be a tab
+        let (comment, extras) = rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len()));
+        let (name, umi) = match name.match_indices(':').nth(6) {
+            Some((colon, _)) => (&name[..colon], Some(name[colon + 1..].to_string())),
+            None => (name, None)
+        };
+        let mut seq_id = parse_read_name_borrowed(name)?;
+        parse_comment(&mut seq_id, comment, &ParseOptions::default())?;
+        Ok(Header { seq_id: seq_id.into_owned(), umi, extras: extras.to_string(), raw: None })
+    }
+
+    /// Parses a header like `parse`, also keeping the original text of each field and the
+    /// whitespace after the read name. Fields whose values are unchanged are written back exactly
+    /// as they were.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::rewrite::{Field, Header};
+    ///
+    /// fn main() {
+    ///     let mut header = Header::parse_preserving("@A00123:8:H3KJ7DSXX:1:1101:01000:00500\t1:N:0:1").unwrap();
+    ///     assert_eq!(header.raw(Field::X), Some("01000"));
+    ///     header.seq_id.lane = 2;
+    ///     assert_eq!(header.to_string(), "@A00123:8:H3KJ7DSXX:2:1101:01000:00500\t1:N:0:1");
+    /// }
+    /// ```
+    pub fn parse_preserving(text: &str) -> Result<Header, IlluminaError> {
+        let mut header = Header::parse(text)?;
+        let text = text.trim_end_matches(&['\n', '\r'][..]);
+        let text = text.strip_prefix('@').unwrap_or(text);
+        let name_end = text.find(char::is_whitespace).unwrap_or(text.len());
+        let comment_start = name_end + (text[name_end..].len() - text[name_end..].trim_start().len());
+        let comment_end = comment_start + text[comment_start..].find(char::is_whitespace).unwrap_or(text.len() - comment_start);
+        let fields = text[..name_end].split(':').take(7)
+            .chain(text[comment_start..comment_end].split(':'))
+            .map(|field| field.to_string())
+            .collect();
+        header.raw = Some(RawFields {
+            fields,
+            separator: text[name_end..comment_start].to_string(),
+            original: header.seq_id.clone()
+        });
+        Ok(header)
+    }
+
+    /// The original text of a field, if the header was parsed with `parse_preserving`
+    pub fn raw(&self, field: Field) -> Option<&str> {
+        let raw = self.raw.as_ref()?;
+        let index = FIELDS.iter().position(|&other| other == field)?;
+        Some(&raw.fields[index])
+    }
+
+    /// The UMI at the end of the read name, if there is one
+    pub fn umi(&self) -> Option<&str> {
+        self.umi.as_deref()
+    }
+
+    /// Sets or removes the UMI. UMIs can't contain colons or whitespace, which would make the
+    /// header unreadable.
+    pub fn set_umi(&mut self, umi: Option<&str>) -> Result<(), IlluminaError> {
+        if let Some(umi) = umi {
+            if umi.is_empty() || umi.contains(|c: char| c == ':' || c.is_whitespace()) {
+                return Err(IlluminaError::FormatError(format!("UMI isn't valid in a header: {:?}", umi)));
+            }
+        }
+        self.umi = umi.map(|umi| umi.to_string());
+        Ok(())
+    }
+
+    /// Replaces an index sequence in the sample field with sample number 0, as bcl2fastq writes
+    /// for runs without indexes. Sample numbers are left alone.
+    pub fn strip_barcode(&mut self) {
+        if self.seq_id.sample.is_undetermined() {
+            self.seq_id.sample = Sample::Number(0);
+        }
+    }
+
+    /// Whatever followed the comment, including the whitespace before it, or an empty string
+    pub fn extras(&self) -> &str {
+        &self.extras
+    }
+
+    /// Removes whatever followed the comment
+    pub fn clear_extras(&mut self) {
+        self.extras.clear();
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref raw) = self.raw {
+            f.write_str("@")?;
+            for (index, &field) in FIELDS.iter().enumerate() {
+                match index {
+                    0 => {},
+                    7 => {
+                        if let Some(ref umi) = self.umi {
+                            write!(f, ":{}", umi)?;
+                        }
+                        f.write_str(&raw.separator)?;
+                    },
+                    _ => f.write_str(":")?
+                }
+                let value = field.format(&self.seq_id);
+                if value == field.format(&raw.original) {
+                    f.write_str(&raw.fields[index])?;
+                } else {
+                    f.write_str(&value)?;
+                }
+            }
+            return f.write_str(&self.extras);
+        }
+        let text = self.seq_id.to_string();
+        let space = text.find(' ').unwrap_or(text.len());
+        f.write_str(&text[..space])?;
+        if let Some(ref umi) = self.umi {
+            write!(f, ":{}", umi)?;
+        }
+        write!(f, "{}{}", &text[space..], self.extras)
+    }
+}
+
+/// Parses a header, lets `edit` change it and formats the result
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::rewrite::rewrite;
+///
+/// fn main() {
+///     let header = "@M03745:11:000000000-B54L5:1:2108:4127:8949:GGTTAACC 1:N:0:0";
+///     let rewritten = rewrite(header, |header| header.set_umi(None).unwrap()).unwrap();
+///     assert_eq!(rewritten, "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0");
+/// }
+/// ```
+pub fn rewrite<F: FnOnce(&mut Header)>(text: &str, edit: F) -> Result<String, IlluminaError> {
+    let mut header = Header::parse(text)?;
+    edit(&mut header);
+    Ok(header.to_string())
+}
+
+/// Rewrites a header like `rewrite`, writing the fields `edit` leaves unchanged exactly as they
+/// were
+pub fn rewrite_preserving<F: FnOnce(&mut Header)>(text: &str, edit: F) -> Result<String, IlluminaError> {
+    let mut header = Header::parse_preserving(text)?;
+    edit(&mut header);
+    Ok(header.to_string())
+}
+
+/// Splits text at its first run of whitespace
+fn split_whitespace(text: &str) -> Option<(&str, &str)> {
+    let end = text.find(char::is_whitespace)?;
+    Some((&text[..end], text[end..].trim_start()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for text in &["@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+                      "@NS500358:204:HTN5KAFXY:1:11101:20886:1073:ACGT+TTGA 2:Y:0:TAAGGC+CTTAGG",
+                      "@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:1\tBC:Z:TAAGGCGA\tRX:Z:ACGT",
+                      "@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:1  spaced extra"] {
+            assert_eq!(Header::parse(text).unwrap().to_string(), *text);
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let header = Header::parse("A00123:8:H3KJ7DSXX:1:1101:1000:1000:ACGT 1:N:0:1\tBC:Z:TAAGGCGA\n").unwrap();
+        assert_eq!(header.umi(), Some("ACGT"));
+        assert_eq!(header.extras(), "\tBC:Z:TAAGGCGA");
+        assert_eq!((header.seq_id.x, header.seq_id.y), (1000, 1000));
+        assert!(Header::parse("@A00123:8:H3KJ7DSXX:1:1101:1000:1000").is_err());
+        assert!(Header::parse("@A00123:8:H3KJ7DSXX:1:1101:1000 1:N:0:1").is_err());
+    }
+
+    #[test]
+    fn test_preserving() {
+        for text in &["@M03745:11:000000000-B54L5:1:2108:04127:8949 1:N:0:0",
+                      "@NS500358:204:HTN5KAFXY:01:11101:20886:1073:ACGT+TTGA\t2:Y:00:TAAGGC+CTTAGG",
+                      "A00123:8:H3KJ7DSXX:1:1101:1000:1000  1:N:0:001\tBC:Z:TAAGGCGA"] {
+            let expected = if text.starts_with('@') { text.to_string() } else { format!("@{}", text) };
+            assert_eq!(Header::parse_preserving(text).unwrap().to_string(), expected);
+        }
+        let text = "@NS500358:204:HTN5KAFXY:01:11101:020886:01073:ACGT\t2:Y:00:TAAGGC+CTTAGG RX:Z:ACGT";
+        let mut header = Header::parse_preserving(text).unwrap();
+        assert_eq!(header.raw(Field::Lane), Some("01"));
+        assert_eq!(header.raw(Field::Sample), Some("TAAGGC+CTTAGG"));
+        assert_eq!(Header::parse(text).unwrap().raw(Field::Lane), None);
+        header.seq_id.y = 1074;
+        header.strip_barcode();
+        header.set_umi(None).unwrap();
+        assert_eq!(header.to_string(), "@NS500358:204:HTN5KAFXY:01:11101:020886:1074\t2:Y:00:0 RX:Z:ACGT");
+        assert_eq!(rewrite_preserving(text, |header| header.seq_id.read = 1).unwrap(),
+                   "@NS500358:204:HTN5KAFXY:01:11101:020886:01073:ACGT\t1:Y:00:TAAGGC+CTTAGG RX:Z:ACGT");
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut header = Header::parse("@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:TAAGGCGA\tBC:Z:TAAGGCGA").unwrap();
+        assert!(header.set_umi(Some("AC GT")).is_err());
+        assert!(header.set_umi(Some("AC:GT")).is_err());
+        assert_eq!(header.umi(), None);
+        header.strip_barcode();
+        header.clear_extras();
+        header.seq_id.read = 2;
+        assert_eq!(header.to_string(), "@A00123:8:H3KJ7DSXX:1:1101:1000:1000 2:N:0:0");
+        header.seq_id.sample = Sample::Number(3);
+        header.strip_barcode();
+        assert_eq!(header.seq_id.sample, Sample::Number(3));
+    }
+}
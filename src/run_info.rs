@@ -0,0 +1,294 @@
+//! Parses the `RunInfo.xml` file that Illumina sequencers write into every run folder. It
+//! describes the reads that were sequenced and the layout of the flow cell, which is needed to
+//! make sense of the lane, side, swath and tile fields of sequence identifiers.
+
+use std::fs;
+use std::path::Path;
+use geometry::FlowCellLayout;
+use super::{IlluminaError, SequenceIdentifier};
+
+#[derive(Debug, Clone, PartialEq)]
+/// A read (or index read) that was sequenced during the run
+pub struct ReadInfo {
+    /// The position of the read in the order of sequencing, starting at 1
+    pub number: u8,
+    /// The number of cycles in the read
+    pub cycles: u16,
+    /// Whether this was an index read rather than a read of the insert
+    pub is_index: bool
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The contents of a `RunInfo.xml` file
+pub struct RunInfo {
+    /// The name of the run folder, e.g. "160204_M03745_0011_000000000-B54L5"
+    pub run_id: String,
+    /// The number of sequencing runs this machine has performed
+    pub run_count: u16,
+    /// ID of the flow cell
+    pub flow_cell_id: String,
+    /// ID of the sequencing machine
+    pub sequencer_id: String,
+    /// The date of the run, in whatever format the sequencer wrote it
+    pub date: Option<String>,
+    /// The reads and index reads, in the order they were sequenced
+    pub reads: Vec<ReadInfo>,
+    /// The layout of the flow cell
+    pub layout: FlowCellLayout
+}
+
+impl RunInfo {
+    /// Reads and parses a `RunInfo.xml` file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<RunInfo, IlluminaError> {
+        let text = fs::read_to_string(path)?;
+        parse_run_info(&text)
+    }
+
+    /// The total number of cycles across all reads and index reads
+    pub fn total_cycles(&self) -> u32 {
+        self.reads.iter().map(|read| u32::from(read.cycles)).sum()
+    }
+
+    /// Whether the read was produced by this run, on a lane and tile that exist on its flow cell
+    pub fn contains(&self, seq_id: &SequenceIdentifier) -> bool {
+        seq_id.sequencer_id == self.sequencer_id
+            && seq_id.run_count == self.run_count
+            && seq_id.flow_cell_id == self.flow_cell_id
+            && self.layout.contains(seq_id.tile_id())
+    }
+}
+
+/// Parses the text of a `RunInfo.xml` file
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::run_info::parse_run_info;
+///
+/// fn main() {
+///     let xml = r#"<?xml version="1.0"?>
+/// <RunInfo Version="2">
+///   <Run Id="160204_M03745_0011_000000000-B54L5" Number="11">
+///     <Flowcell>000000000-B54L5</Flowcell>
+///     <Instrument>M03745</Instrument>
+///     <Date>160204</Date>
+///     <Reads>
+///       <Read Number="1" NumCycles="151" IsIndexedRead="N" />
+///       <Read Number="2" NumCycles="8" IsIndexedRead="Y" />
+///     </Reads>
+///     <FlowcellLayout LaneCount="1" SurfaceCount="2" SwathCount="1" TileCount="19" />
+///   </Run>
+/// </RunInfo>"#;
+///     let run_info = parse_run_info(xml).unwrap();
+///     assert_eq!(run_info.flow_cell_id, "000000000-B54L5");
+///     assert_eq!(run_info.reads.len(), 2);
+///     assert_eq!(run_info.layout.tiles_per_swath, 19);
+/// }
+/// ```
+pub fn parse_run_info(text: &str) -> Result<RunInfo, IlluminaError> {
+    let mut run_id = None;
+    let mut run_count = None;
+    let mut flow_cell_id = None;
+    let mut sequencer_id = None;
+    let mut date = None;
+    let mut reads = vec![];
+    let mut layout = None;
+
+    for element in Elements::new(text) {
+        let element = element?;
+        match element.name {
+            "Run" => {
+                run_id = Some(element.required_attribute("Id")?.to_string());
+                run_count = Some(element.required_attribute("Number")?.parse::<u16>()?);
+            },
+            "Flowcell" => flow_cell_id = Some(element.text.trim().to_string()),
+            "Instrument" => sequencer_id = Some(element.text.trim().to_string()),
+            "Date" => date = Some(element.text.trim().to_string()),
+            "Read" => reads.push(ReadInfo {
+                number: element.required_attribute("Number")?.parse::<u8>()?,
+                cycles: element.required_attribute("NumCycles")?.parse::<u16>()?,
+                is_index: element.required_attribute("IsIndexedRead")? == "Y"
+            }),
+            "FlowcellLayout" => layout = Some(FlowCellLayout {
+                lanes: element.required_attribute("LaneCount")?.parse::<u8>()?,
+                sides: element.required_attribute("SurfaceCount")?.parse::<u8>()?,
+                swaths: element.required_attribute("SwathCount")?.parse::<u8>()?,
+                // NextSeqs and MiniSeqs image each swath in sections, one per camera
+                cameras: match element.attribute("SectionPerLane") {
+                    Some(sections) => sections.parse::<u8>()?,
+                    None => 1
+                },
+                tiles_per_swath: element.required_attribute("TileCount")?.parse::<u8>()?
+            }),
+            _ => {}
+        }
+    }
+    reads.sort_by_key(|read| read.number);
+
+    Ok(RunInfo {
+        run_id: run_id.ok_or_else(|| missing("Run"))?,
+        run_count: run_count.ok_or_else(|| missing("Run"))?,
+        flow_cell_id: flow_cell_id.ok_or_else(|| missing("Flowcell"))?,
+        sequencer_id: sequencer_id.ok_or_else(|| missing("Instrument"))?,
+        date,
+        reads,
+        layout: layout.ok_or_else(|| missing("FlowcellLayout"))?
+    })
+}
+
+fn missing(name: &str) -> IlluminaError {
+    IlluminaError::FormatError(format!("RunInfo.xml has no {} element", name))
+}
+
+/// An opening or self-closing XML tag, along with the text that immediately follows it
+struct Element<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, &'a str)>,
+    text: &'a str
+}
+
+impl<'a> Element<'a> {
+    fn attribute(&self, key: &str) -> Option<&'a str> {
+        self.attributes.iter()
+            .find(|&&(name, _)| name == key)
+            .map(|&(_, value)| value)
+    }
+
+    fn required_attribute(&self, key: &str) -> Result<&'a str, IlluminaError> {
+        self.attribute(key)
+            .ok_or_else(|| IlluminaError::FormatError(
+                format!("{} element in RunInfo.xml has no {} attribute", self.name, key)))
+    }
+}
+
+/// Iterates over the opening tags of an XML document. This only understands as much XML as
+/// `RunInfo.xml` files use: there is no support for entities, CDATA or namespaces.
+struct Elements<'a> {
+    remaining: &'a str
+}
+
+impl<'a> Elements<'a> {
+    fn new(text: &'a str) -> Elements<'a> {
+        Elements { remaining: text }
+    }
+}
+
+impl<'a> Iterator for Elements<'a> {
+    type Item = Result<Element<'a>, IlluminaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let start = self.remaining.find('<')?;
+            let tag = &self.remaining[start + 1..];
+            let end = match tag.find('>') {
+                Some(end) => end,
+                None => return Some(Err(IlluminaError::FormatError("unterminated XML tag".to_string())))
+            };
+            let body = &tag[..end];
+            self.remaining = &tag[end + 1..];
+            if body.starts_with('/') || body.starts_with('?') || body.starts_with('!') {
+                continue;
+            }
+            let body = body.strip_suffix('/').unwrap_or(body);
+            let name_end = body.find(char::is_whitespace).unwrap_or(body.len());
+            let text_end = self.remaining.find('<').unwrap_or(self.remaining.len());
+            return Some(parse_attributes(&body[name_end..]).map(|attributes| Element {
+                name: &body[..name_end],
+                attributes,
+                text: &self.remaining[..text_end]
+            }));
+        }
+    }
+}
+
+fn parse_attributes(mut text: &str) -> Result<Vec<(&str, &str)>, IlluminaError> {
+    let mut attributes = vec![];
+    loop {
+        text = text.trim_start();
+        if text.is_empty() {
+            return Ok(attributes);
+        }
+        let error = || IlluminaError::FormatError(format!("malformed XML attribute: {}", text));
+        let equals = text.find('=').ok_or_else(error)?;
+        let name = text[..equals].trim();
+        let value = text[equals + 1..].trim_start();
+        let quote = value.chars().next().filter(|&c| c == '"' || c == '\'').ok_or_else(error)?;
+        let value = &value[1..];
+        let value_end = value.find(quote).ok_or_else(error)?;
+        attributes.push((name, &value[..value_end]));
+        text = &value[value_end + 1..];
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    const NEXTSEQ_RUN_INFO: &str = r#"<?xml version="1.0"?>
+<RunInfo xmlns:xsd="http://www.w3.org/2001/XMLSchema" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" Version="2">
+  <Run Id="170102_NS500358_0204_AHTN5KAFXY" Number="204">
+    <Flowcell>HTN5KAFXY</Flowcell>
+    <Instrument>NS500358</Instrument>
+    <Date>170102</Date>
+    <Reads>
+      <Read Number="1" NumCycles="75" IsIndexedRead="N" />
+      <Read Number="3" NumCycles="75" IsIndexedRead="N" />
+      <Read Number="2" NumCycles="6" IsIndexedRead="Y" />
+    </Reads>
+    <FlowcellLayout LaneCount="4" SurfaceCount="2" SwathCount="3" TileCount="12" SectionPerLane="3" LanePerSection="2" />
+  </Run>
+</RunInfo>"#;
+
+    #[test]
+    fn test_parse_run_info() {
+        let run_info = parse_run_info(NEXTSEQ_RUN_INFO).unwrap();
+        assert_eq!(run_info.run_id, "170102_NS500358_0204_AHTN5KAFXY");
+        assert_eq!(run_info.run_count, 204);
+        assert_eq!(run_info.flow_cell_id, "HTN5KAFXY");
+        assert_eq!(run_info.sequencer_id, "NS500358");
+        assert_eq!(run_info.date, Some("170102".to_string()));
+        assert_eq!(run_info.reads, vec![
+            ReadInfo { number: 1, cycles: 75, is_index: false },
+            ReadInfo { number: 2, cycles: 6, is_index: true },
+            ReadInfo { number: 3, cycles: 75, is_index: false },
+        ]);
+        assert_eq!(run_info.total_cycles(), 156);
+        assert_eq!(run_info.layout, FlowCellLayout { lanes: 4, sides: 2, swaths: 3, cameras: 3, tiles_per_swath: 12 });
+        assert_eq!(run_info.layout.tiles_per_lane(), 216);
+        let seq_id = parse_sequence_identifier("@NS500358:204:HTN5KAFXY:4:23612:11289:19567 1:N:0:3").unwrap();
+        assert!(run_info.contains(&seq_id));
+    }
+
+    #[test]
+    fn test_contains() {
+        let xml = r#"<RunInfo Version="2">
+  <Run Id="160204_M03745_0011_000000000-B54L5" Number="11">
+    <Flowcell>000000000-B54L5</Flowcell>
+    <Instrument>M03745</Instrument>
+    <Reads><Read Number="1" NumCycles="151" IsIndexedRead="N"/></Reads>
+    <FlowcellLayout LaneCount="1" SurfaceCount="2" SwathCount="1" TileCount="19"/>
+  </Run>
+</RunInfo>"#;
+        let run_info = parse_run_info(xml).unwrap();
+        assert_eq!(run_info.date, None);
+        let seq_id = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+        assert!(run_info.contains(&seq_id));
+        let seq_id = parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2120:4127:8949 1:N:0:0").unwrap();
+        assert!(!run_info.layout.contains(seq_id.tile_id()));
+        let seq_id = parse_sequence_identifier("@M03745:12:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+        assert!(run_info.layout.contains(seq_id.tile_id()));
+        assert!(!run_info.contains(&seq_id));
+    }
+
+    #[test]
+    fn test_missing_layout() {
+        let xml = r#"<RunInfo><Run Id="x" Number="1"><Flowcell>A</Flowcell><Instrument>M1</Instrument></Run></RunInfo>"#;
+        match parse_run_info(xml) {
+            Err(IlluminaError::FormatError(_)) => {},
+            _ => panic!("expected a FormatError")
+        }
+    }
+}
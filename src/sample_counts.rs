@@ -0,0 +1,201 @@
+//! Counts the reads of each sample from the headers of a run's FASTQ files, giving the numbers of
+//! the lane summary in bcl2fastq's demultiplexing reports.
+//!
+//! Reads assigned to a sample carry its number from the sample sheet, while those in
+//! Undetermined Reads carry the index sequence that was read. Counting the reads of each sample
+//! number and each unknown barcode recovers how many clusters went to each sample, what fraction
+//! of the lane that is and which barcodes failed to match any sample, without the report itself.
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::Path;
+use fastq::{FastqReader, Record};
+use file_role::FileRole;
+use gzip;
+use stats::ReadCounts;
+use super::{IlluminaError, Sample, SequenceIdentifier};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The reads of one sample in one lane, or of Undetermined Reads
+pub struct SampleSummary {
+    /// The lane
+    pub lane: u8,
+    /// The sample number, or `None` for Undetermined Reads
+    pub sample: Option<u8>,
+    /// The number of reads, and how many failed filtering
+    pub counts: ReadCounts,
+    /// The fraction of the lane's reads that passed filtering that belong to this sample, or
+    /// `None` if none of them passed
+    pub lane_fraction: Option<f64>
+}
+
+#[derive(Debug, Clone, Default)]
+/// Counts reads by lane and sample. Each cluster should be counted once, so only the read 1 files
+/// of a paired-end run should be added.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::sample_counts::SampleCounter;
+///
+/// fn main() {
+///     let mut counter = SampleCounter::new();
+///     for line in &["@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:1",
+///                   "@A00123:8:H3KJ7DSXX:1:1101:1000:2000 1:N:0:1",
+///                   "@A00123:8:H3KJ7DSXX:1:1101:1000:3000 1:Y:0:2",
+///                   "@A00123:8:H3KJ7DSXX:1:1101:1000:4000 1:N:0:GGGGGGGG"] {
+///         counter.add(&illumina_coordinates::parse_sequence_identifier(line).unwrap());
+///     }
+///     let summaries = counter.summaries();
+///     assert_eq!(summaries.len(), 3);
+///     assert_eq!(summaries[0].sample, Some(1));
+///     assert_eq!(summaries[0].lane_fraction, Some(2.0 / 3.0));
+///     assert_eq!(summaries[2].sample, None);
+///     assert_eq!(counter.unknown_barcodes(1, 10)[0].0.as_sequence(), Some("GGGGGGGG"));
+/// }
+/// ```
+pub struct SampleCounter {
+    counts: BTreeMap<(u8, Sample), ReadCounts>
+}
+
+impl SampleCounter {
+    /// Creates a counter that hasn't counted any reads
+    pub fn new() -> SampleCounter {
+        SampleCounter::default()
+    }
+
+    /// Counts a read
+    pub fn add(&mut self, seq_id: &SequenceIdentifier) {
+        let filtered = if seq_id.is_filtered { 1 } else { 0 };
+        self.counts.entry((seq_id.lane, seq_id.sample.clone())).or_default().add(ReadCounts { reads: 1, filtered });
+    }
+
+    /// Counts every read in a FASTQ file, returning how many there were
+    pub fn add_reader<R: BufRead>(&mut self, reader: R) -> Result<u64, IlluminaError> {
+        let mut reader = FastqReader::new(reader);
+        let mut record = Record::default();
+        let mut reads = 0;
+        while reader.read_record(&mut record)? {
+            self.add(&record.sequence_identifier()?);
+            reads += 1;
+        }
+        Ok(reads)
+    }
+
+    /// Counts every read in a FASTQ file, which may be gzipped, returning how many there were.
+    /// Files whose names say they hold a read other than read 1, such as
+    /// `Sample_S1_L001_R2_001.fastq.gz`, are skipped so that clusters aren't counted twice.
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<u64, IlluminaError> {
+        let path = path.as_ref();
+        let role = path.file_name().and_then(|name| name.to_str()).and_then(FileRole::from_file_name);
+        match role {
+            Some(FileRole::Read(1)) | None => self.add_reader(gzip::open(path)?),
+            Some(_) => Ok(0)
+        }
+    }
+
+    /// The counts for each sample value in each lane, keyed by lane and sample
+    pub fn counts(&self) -> &BTreeMap<(u8, Sample), ReadCounts> {
+        &self.counts
+    }
+
+    /// The counts for each lane, including Undetermined Reads
+    pub fn lanes(&self) -> BTreeMap<u8, ReadCounts> {
+        let mut lanes: BTreeMap<u8, ReadCounts> = BTreeMap::new();
+        for (&(lane, _), &counts) in &self.counts {
+            lanes.entry(lane).or_default().add(counts);
+        }
+        lanes
+    }
+
+    /// The counts for each sample number in each lane, followed by those of Undetermined Reads,
+    /// in order of lane
+    pub fn summaries(&self) -> Vec<SampleSummary> {
+        let lanes = self.lanes();
+        let mut summaries: Vec<SampleSummary> = vec![];
+        let mut undetermined: BTreeMap<u8, ReadCounts> = BTreeMap::new();
+        for (&(lane, ref sample), &counts) in &self.counts {
+            match sample.as_number() {
+                Some(number) => summaries.push(SampleSummary { lane, sample: Some(number), counts, lane_fraction: None }),
+                None => undetermined.entry(lane).or_default().add(counts)
+            }
+        }
+        for (&lane, &counts) in &undetermined {
+            summaries.push(SampleSummary { lane, sample: None, counts, lane_fraction: None });
+        }
+        // The sort is stable, so Undetermined Reads stay after the samples of their lane
+        summaries.sort_by_key(|summary| summary.lane);
+        for summary in &mut summaries {
+            let passed = lanes[&summary.lane].passed();
+            if passed > 0 {
+                summary.lane_fraction = Some(summary.counts.passed() as f64 / passed as f64);
+            }
+        }
+        summaries
+    }
+
+    /// The `n` barcodes of Undetermined Reads in a lane with the most reads, most first, as in the
+    /// top unknown barcodes of bcl2fastq's reports
+    pub fn unknown_barcodes(&self, lane: u8, n: usize) -> Vec<(&Sample, ReadCounts)> {
+        let mut barcodes: Vec<(&Sample, ReadCounts)> = self.counts.iter()
+            .filter(|&(key, _)| key.0 == lane && key.1.as_sequence().is_some())
+            .map(|(key, &counts)| (&key.1, counts))
+            .collect();
+        barcodes.sort_by(|a, b| b.1.reads.cmp(&a.1.reads).then_with(|| a.0.cmp(b.0)));
+        barcodes.truncate(n);
+        barcodes
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::env;
+    use parse_sequence_identifier;
+
+    fn fastq(headers: &[&str]) -> String {
+        headers.iter().map(|header| format!("{}\nACGT\n+\nAAAA\n", header)).collect()
+    }
+
+    #[test]
+    fn test_summaries() {
+        let mut counter = SampleCounter::new();
+        let text = fastq(&["@A00123:8:H3KJ7DSXX:2:1101:1000:1000 1:N:0:1",
+                           "@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:2",
+                           "@A00123:8:H3KJ7DSXX:1:1101:1000:2000 1:Y:0:2",
+                           "@A00123:8:H3KJ7DSXX:1:1101:1000:3000 1:N:0:TTTTTTTT",
+                           "@A00123:8:H3KJ7DSXX:1:1101:1000:4000 1:N:0:GGGGGGGG",
+                           "@A00123:8:H3KJ7DSXX:1:1101:1000:5000 1:N:0:TTTTTTTT"]);
+        assert_eq!(counter.add_reader(text.as_bytes()).unwrap(), 6);
+        assert_eq!(counter.lanes()[&1], ReadCounts { reads: 5, filtered: 1 });
+        let summaries = counter.summaries();
+        let rows: Vec<(u8, Option<u8>, u64, Option<f64>)> = summaries.iter()
+            .map(|summary| (summary.lane, summary.sample, summary.counts.reads, summary.lane_fraction))
+            .collect();
+        assert_eq!(rows, [(1, Some(2), 2, Some(0.25)), (1, None, 3, Some(0.75)), (2, Some(1), 1, Some(1.0))]);
+        let unknown = counter.unknown_barcodes(1, 1);
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].0.as_sequence(), Some("TTTTTTTT"));
+        assert_eq!(unknown[0].1.reads, 2);
+        assert!(counter.unknown_barcodes(2, 10).is_empty());
+    }
+
+    #[test]
+    fn test_add_file() {
+        let dir = env::temp_dir().join(format!("illumina_coordinates_sample_counts_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let read1 = dir.join("Sample_S1_L001_R1_001.fastq");
+        let read2 = dir.join("Sample_S1_L001_R2_001.fastq");
+        fs::write(&read1, fastq(&["@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:1"])).unwrap();
+        fs::write(&read2, fastq(&["@A00123:8:H3KJ7DSXX:1:1101:1000:1000 2:N:0:1"])).unwrap();
+        let mut counter = SampleCounter::new();
+        assert_eq!(counter.add_file(&read1).unwrap(), 1);
+        assert_eq!(counter.add_file(&read2).unwrap(), 0);
+        let seq_id = parse_sequence_identifier("@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:1").unwrap();
+        assert_eq!(counter.counts()[&(1, seq_id.sample)].reads, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,265 @@
+//! Parses `SampleSheet.csv` files, in both the original (v1) format used by bcl2fastq and the
+//! v2 format used by BCL Convert, so that the sample field of a sequence identifier can be
+//! resolved to the name and index sequences of the sample it belongs to.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use super::{IlluminaError, Sample};
+
+#[derive(Debug, Clone, PartialEq)]
+/// A row from the data section of a sample sheet
+pub struct SampleEntry {
+    /// The lane the sample was loaded into, if the sample sheet specifies lanes
+    pub lane: Option<u8>,
+    /// ID of the sample
+    pub sample_id: String,
+    /// Name of the sample. Only v1 sample sheets have this column
+    pub sample_name: Option<String>,
+    /// The i7 index sequence
+    pub index: Option<String>,
+    /// The i5 index sequence
+    pub index2: Option<String>
+}
+
+impl SampleEntry {
+    /// The name of the sample, falling back to its ID if it has no name
+    pub fn name(&self) -> &str {
+        self.sample_name.as_ref().map_or(&self.sample_id, |name| name)
+    }
+
+    /// The barcode reads from the sample should have: the index, or `index+index2` for
+    /// dual-indexed samples, as in the sample field of a sequence identifier. `None` if the
+    /// sample has no index.
+    pub fn barcode(&self) -> Option<String> {
+        match (&self.index, &self.index2) {
+            (Some(index), Some(index2)) => Some(format!("{}+{}", index, index2)),
+            (Some(index), None) => Some(index.clone()),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// The contents of a sample sheet
+pub struct SampleSheet {
+    /// The file format version, either 1 or 2
+    pub version: u8,
+    /// The rows of the data section, in the order they appear in the file
+    pub entries: Vec<SampleEntry>
+}
+
+impl SampleSheet {
+    /// Reads and parses a sample sheet
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<SampleSheet, IlluminaError> {
+        let text = fs::read_to_string(path)?;
+        parse_sample_sheet(&text)
+    }
+}
+
+/// Parses the text of a v1 or v2 sample sheet. The version is determined by the
+/// `FileFormatVersion` setting in the `[Header]` section.
+pub fn parse_sample_sheet(text: &str) -> Result<SampleSheet, IlluminaError> {
+    let mut sections: HashMap<String, Vec<Vec<&str>>> = HashMap::new();
+    let mut section = String::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            section = match line.find(']') {
+                Some(end) => line[1..end].to_string(),
+                None => return Err(IlluminaError::FormatError(format!("malformed section header: {}", line)))
+            };
+            continue;
+        }
+        let fields: Vec<&str> = line.trim_end_matches(',').split(',').map(|field| field.trim()).collect();
+        if fields.iter().all(|field| field.is_empty()) {
+            continue;
+        }
+        sections.entry(section.clone()).or_default().push(fields);
+    }
+
+    let version = match sections.get("Header") {
+        Some(rows) if rows.iter().any(|row| row.len() > 1 && row[0] == "FileFormatVersion" && row[1] == "2") => 2,
+        _ => 1
+    };
+    let data_section = if version == 2 { "BCLConvert_Data" } else { "Data" };
+    let rows = match sections.get(data_section) {
+        Some(rows) => rows,
+        None => return Err(IlluminaError::FormatError(format!("sample sheet has no [{}] section", data_section)))
+    };
+    let header = &rows[0];
+    let column = |name: &str| header.iter().position(|column| column.eq_ignore_ascii_case(name));
+    let sample_id_column = match column("Sample_ID") {
+        Some(index) => index,
+        None => return Err(IlluminaError::FormatError("sample sheet has no Sample_ID column".to_string()))
+    };
+    let lane_column = column("Lane");
+    let sample_name_column = column("Sample_Name");
+    let index_column = column("index");
+    let index2_column = column("index2");
+
+    let mut entries = vec![];
+    for row in &rows[1..] {
+        let field = |column: Option<usize>| column
+            .and_then(|index| row.get(index))
+            .filter(|field| !field.is_empty())
+            .map(|field| field.to_string());
+        let lane = match lane_column.and_then(|index| row.get(index)) {
+            Some(lane) if !lane.is_empty() => Some(lane.parse::<u8>()?),
+            _ => None
+        };
+        let sample_id = match field(Some(sample_id_column)) {
+            Some(sample_id) => sample_id,
+            None => return Err(IlluminaError::FormatError("sample sheet row has no Sample_ID".to_string()))
+        };
+        entries.push(SampleEntry {
+            lane,
+            sample_id,
+            sample_name: field(sample_name_column),
+            index: field(index_column),
+            index2: field(index2_column)
+        });
+    }
+    Ok(SampleSheet { version, entries })
+}
+
+/// Looks up the sample sheet entry that a `Sample` refers to.
+///
+/// Samples are numbered from 1 in the order their IDs first appear in the sample sheet, which is
+/// how bcl2fastq numbers its output files. A sample listed on several lanes has one number.
+/// Sequences are matched against the index sequences, with dual indexes joined by a `+`.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::barcode::Barcode;
+/// use illumina_coordinates::Sample;
+/// use illumina_coordinates::sample_sheet::{parse_sample_sheet, SampleResolver};
+///
+/// fn main() {
+///     let text = "[Header]\nIEMFileVersion,4\n\n[Data]\nSample_ID,Sample_Name,index\nS1,liver,TAAGGCGA\nS2,heart,CGTACTAG\n";
+///     let sheet = parse_sample_sheet(text).unwrap();
+///     let resolver = SampleResolver::new(&sheet);
+///     assert_eq!(resolver.resolve(&Sample::Number(2)).unwrap().name(), "heart");
+///     assert_eq!(resolver.resolve(&Sample::Sequence(Barcode::new("TAAGGCGA").unwrap())).unwrap().name(), "liver");
+/// }
+/// ```
+pub struct SampleResolver<'a> {
+    samples: Vec<&'a SampleEntry>,
+    by_sequence: HashMap<String, &'a SampleEntry>
+}
+
+impl<'a> SampleResolver<'a> {
+    /// Creates a resolver for the samples in a sample sheet
+    pub fn new(sheet: &'a SampleSheet) -> SampleResolver<'a> {
+        let mut samples: Vec<&SampleEntry> = vec![];
+        let mut by_sequence = HashMap::new();
+        for entry in &sheet.entries {
+            if !samples.iter().any(|sample| sample.sample_id == entry.sample_id) {
+                samples.push(entry);
+            }
+            if let Some(sequence) = entry.barcode() {
+                by_sequence.entry(sequence).or_insert(entry);
+            }
+        }
+        SampleResolver { samples, by_sequence }
+    }
+
+    /// Returns the sample sheet entry for the sample, if there is one
+    pub fn resolve(&self, sample: &Sample) -> Option<&'a SampleEntry> {
+        match *sample {
+            Sample::Number(0) => None,
+            Sample::Number(n) => self.samples.get(n as usize - 1).cloned(),
+            Sample::Sequence(ref sequence) => self.by_sequence.get(sequence.as_str()).cloned(),
+            Sample::Other(ref text) => match text.parse::<usize>() {
+                Ok(n) => self.samples.get(n - 1).cloned(),
+                Err(_) => self.by_sequence.get(text.as_str()).cloned()
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use barcode::Barcode;
+
+    const V1: &str = "[Header],,,,
+IEMFileVersion,4,,,
+Investigator Name,jim,,,
+,,,,
+[Reads],,,,
+151,,,,
+151,,,,
+,,,,
+[Data],,,,
+Lane,Sample_ID,Sample_Name,index,index2
+1,S1,liver,TAAGGCGA,GCGTAAGA
+2,S1,liver,TAAGGCGA,GCGTAAGA
+1,S2,,CGTACTAG,GCGTAAGA
+";
+
+    const V2: &str = "[Header]
+FileFormatVersion,2
+RunName,test
+
+[Reads]
+Read1Cycles,151
+Index1Cycles,8
+
+[BCLConvert_Settings]
+SoftwareVersion,3.7.4
+
+[BCLConvert_Data]
+Lane,Sample_ID,Index
+1,heart,CGTACTAG
+";
+
+    #[test]
+    fn test_parse_v1() {
+        let sheet = parse_sample_sheet(V1).unwrap();
+        assert_eq!(sheet.version, 1);
+        assert_eq!(sheet.entries.len(), 3);
+        assert_eq!(sheet.entries[0], SampleEntry {
+            lane: Some(1),
+            sample_id: "S1".to_string(),
+            sample_name: Some("liver".to_string()),
+            index: Some("TAAGGCGA".to_string()),
+            index2: Some("GCGTAAGA".to_string())
+        });
+        assert_eq!(sheet.entries[2].sample_name, None);
+        assert_eq!(sheet.entries[2].name(), "S2");
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        let sheet = parse_sample_sheet(V2).unwrap();
+        assert_eq!(sheet.version, 2);
+        assert_eq!(sheet.entries, vec![SampleEntry {
+            lane: Some(1),
+            sample_id: "heart".to_string(),
+            sample_name: None,
+            index: Some("CGTACTAG".to_string()),
+            index2: None
+        }]);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let sheet = parse_sample_sheet(V1).unwrap();
+        let resolver = SampleResolver::new(&sheet);
+        assert_eq!(resolver.resolve(&Sample::Number(0)), None);
+        assert_eq!(resolver.resolve(&Sample::Number(1)).unwrap().sample_id, "S1");
+        assert_eq!(resolver.resolve(&Sample::Number(2)).unwrap().sample_id, "S2");
+        assert_eq!(resolver.resolve(&Sample::Number(3)), None);
+        assert_eq!(resolver.resolve(&Sample::Sequence(Barcode::new("CGTACTAG+GCGTAAGA").unwrap())).unwrap().sample_id, "S2");
+        assert_eq!(resolver.resolve(&Sample::Sequence(Barcode::new("CGTACTAG").unwrap())), None);
+    }
+
+    #[test]
+    fn test_missing_data_section() {
+        assert!(parse_sample_sheet("[Header]\nFileFormatVersion,2\n[Data]\nSample_ID\nS1\n").is_err());
+    }
+}
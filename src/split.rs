@@ -0,0 +1,252 @@
+//! Splits a FASTQ file into one file per tile or per range of tiles, so that tiles can be
+//! processed in parallel.
+//!
+//! Records are copied whole, and gzipped input produces gzipped output. Compressed output is
+//! written as BGZF, which any gzip tool can read and which can also be indexed.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use bgzf::BgzfWriter;
+use fastq::{FastqReader, Record};
+use gzip;
+use super::{IlluminaError, SequenceIdentifier, TileId};
+
+/// The name of the part that holds records from tiles outside every range
+pub const UNASSIGNED: &str = "unassigned";
+
+const EXTENSIONS: [&str; 4] = [".fastq.gz", ".fq.gz", ".fastq", ".fq"];
+
+#[derive(Debug, Clone, PartialEq)]
+/// How records are grouped into output files
+pub enum SplitBy {
+    /// One file per tile in each lane
+    Tile,
+    /// One file per inclusive range of tile numbers (e.g. 1101 to 1119) in each lane. Records from
+    /// tiles outside every range are written to the `unassigned` file.
+    Ranges(Vec<(u32, u32)>)
+}
+
+impl SplitBy {
+    /// The name of the part that records from the tile are written to, e.g. `L001_T1101`
+    pub fn part(&self, tile: TileId) -> String {
+        let number = tile.tile_field();
+        match *self {
+            SplitBy::Tile => format!("L{:03}_T{}", tile.lane, number),
+            SplitBy::Ranges(ref ranges) => {
+                match ranges.iter().find(|&&(start, end)| start <= number && number <= end) {
+                    Some(&(start, end)) => format!("L{:03}_T{}-{}", tile.lane, start, end),
+                    None => UNASSIGNED.to_string()
+                }
+            }
+        }
+    }
+}
+
+/// Writes a part, compressing it if the input was compressed
+pub(crate) enum PartWriter {
+    Plain(BufWriter<File>),
+    Compressed(BgzfWriter<BufWriter<File>>)
+}
+
+impl PartWriter {
+    /// Creates a file, compressed as BGZF if `compressed` is set
+    pub(crate) fn create<P: AsRef<Path>>(path: P, compressed: bool) -> io::Result<PartWriter> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(if compressed {
+            PartWriter::Compressed(BgzfWriter::new(file))
+        } else {
+            PartWriter::Plain(file)
+        })
+    }
+
+    pub(crate) fn finish(self) -> io::Result<()> {
+        match self {
+            PartWriter::Plain(mut writer) => writer.flush(),
+            PartWriter::Compressed(writer) => writer.finish().map(|_| ())
+        }
+    }
+}
+
+impl Write for PartWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            PartWriter::Plain(ref mut writer) => writer.write(buf),
+            PartWriter::Compressed(ref mut writer) => writer.write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            PartWriter::Plain(ref mut writer) => writer.flush(),
+            PartWriter::Compressed(ref mut writer) => writer.flush()
+        }
+    }
+}
+
+/// The path a part of the input is written to: the input's file name with the part name inserted
+/// before its extension, e.g. `reads_L001_T1101.fastq.gz`
+pub fn part_path<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output_dir: Q, part: &str) -> PathBuf {
+    let name = input.as_ref().file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = EXTENSIONS.iter().find(|&&extension| name.ends_with(extension)).cloned().unwrap_or("");
+    let stem = &name[..name.len() - extension.len()];
+    output_dir.as_ref().join(format!("{}_{}{}", stem, part, extension))
+}
+
+/// Splits a FASTQ file into parts, writing each to `output_dir`. Returns the path of each part
+/// written along with the number of records in it.
+///
+/// Every part is kept open until the input has been read, since tiles aren't guaranteed to be
+/// contiguous.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::split::{split_fastq, SplitBy};
+///
+/// fn main() {
+///     let parts = split_fastq("reads.fastq.gz", "tiles", &SplitBy::Tile).unwrap();
+///     for (path, records) in &parts {
+///         println!("{}: {}", path.display(), records);
+///     }
+/// }
+/// ```
+pub fn split_fastq<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output_dir: Q, split_by: &SplitBy)
+    -> Result<BTreeMap<PathBuf, u64>, IlluminaError> {
+    let mut part_names: HashMap<TileId, String> = HashMap::new();
+    write_parts(input, output_dir, |seq_id| {
+        let tile = seq_id.tile_id();
+        part_names.entry(tile).or_insert_with(|| split_by.part(tile)).clone()
+    })
+}
+
+/// Splits FASTQ records from a reader, such as standard input, like `split_fastq`. The parts are
+/// named after `name` as if it were the input's path, and are compressed if `compressed` is set.
+pub fn split_reader<R: BufRead, P: AsRef<Path>, Q: AsRef<Path>>(reader: R, compressed: bool, name: P, output_dir: Q,
+                                                                split_by: &SplitBy)
+    -> Result<BTreeMap<PathBuf, u64>, IlluminaError> {
+    let mut part_names: HashMap<TileId, String> = HashMap::new();
+    write_reader_parts(FastqReader::new(reader), compressed, name, output_dir, |seq_id| {
+        let tile = seq_id.tile_id();
+        part_names.entry(tile).or_insert_with(|| split_by.part(tile)).clone()
+    })
+}
+
+/// Copies each record of a FASTQ file to the part named by `part_of`, creating parts as they're
+/// needed. Returns the path of each part written along with the number of records in it.
+pub(crate) fn write_parts<P, Q, F>(input: P, output_dir: Q, part_of: F) -> Result<BTreeMap<PathBuf, u64>, IlluminaError>
+    where P: AsRef<Path>, Q: AsRef<Path>, F: FnMut(&SequenceIdentifier) -> String {
+    let compressed = gzip::is_gzipped(&input)?;
+    write_reader_parts(FastqReader::new(gzip::open(&input)?), compressed, input, output_dir, part_of)
+}
+
+/// Copies each record to the part named by `part_of`, like `write_parts`, naming the parts after
+/// `input` and compressing them if `compressed` is set
+pub(crate) fn write_reader_parts<R, P, Q, F>(mut reader: FastqReader<R>, compressed: bool, input: P, output_dir: Q,
+                                             mut part_of: F) -> Result<BTreeMap<PathBuf, u64>, IlluminaError>
+    where R: BufRead, P: AsRef<Path>, Q: AsRef<Path>, F: FnMut(&SequenceIdentifier) -> String {
+    let mut record = Record::default();
+    let mut parts: HashMap<String, (PathBuf, PartWriter, u64)> = HashMap::new();
+    while reader.read_record(&mut record)? {
+        let name = part_of(&record.sequence_identifier()?);
+        let part = match parts.entry(name) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let path = part_path(&input, &output_dir, entry.key());
+                let writer = PartWriter::create(&path, compressed)?;
+                entry.insert((path, writer, 0))
+            }
+        };
+        record.write_to(&mut part.1)?;
+        part.2 += 1;
+    }
+    let mut counts = BTreeMap::new();
+    for (_, (path, writer, count)) in parts {
+        writer.finish()?;
+        counts.insert(path, count);
+    }
+    Ok(counts)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::Read;
+
+    const FASTQ: &str = "@M03745:11:000000000-B54L5:1:1101:4127:8949 1:N:0:0\nACGT\n+\nFFFF\n\
+                         @M03745:11:000000000-B54L5:1:2108:4128:8950 1:N:0:0\nTTTT\n+\n####\n\
+                         @M03745:11:000000000-B54L5:1:1101:4129:8951 1:N:0:0\nGGGG\n+\nFFFF\n";
+
+    fn read_part(path: &Path) -> String {
+        let mut text = String::new();
+        gzip::open(path).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_part() {
+        let tile = TileId { lane: 2, side: 1, swath: 2, camera: None, tile: 15 };
+        assert_eq!(SplitBy::Tile.part(tile), "L002_T1215");
+        assert_eq!(SplitBy::Tile.part(TileId { lane: 1, side: 1, swath: 1, camera: None, tile: 101 }), "L001_T11101");
+        assert_eq!(SplitBy::Tile.part(TileId { lane: 1, side: 1, swath: 1, camera: Some(6), tile: 1 }), "L001_T11601");
+        let ranges = SplitBy::Ranges(vec![(1101, 1119), (1201, 1219)]);
+        assert_eq!(ranges.part(tile), "L002_T1201-1219");
+        assert_eq!(ranges.part(TileId { lane: 2, side: 2, swath: 1, camera: None, tile: 1 }), UNASSIGNED);
+    }
+
+    #[test]
+    fn test_part_path() {
+        assert_eq!(part_path("/data/reads.fastq.gz", "/out", "L001_T1101"), PathBuf::from("/out/reads_L001_T1101.fastq.gz"));
+        assert_eq!(part_path("reads.txt", "out", "unassigned"), PathBuf::from("out/reads.txt_unassigned"));
+    }
+
+    #[test]
+    fn test_split_fastq() {
+        let dir = env::temp_dir().join(format!("illumina_coordinates_split_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for &compressed in &[false, true] {
+            let input = dir.join(if compressed { "reads.fastq.gz" } else { "reads.fastq" });
+            if compressed {
+                let mut writer = BgzfWriter::new(vec![]);
+                writer.write_all(FASTQ.as_bytes()).unwrap();
+                fs::write(&input, writer.finish().unwrap()).unwrap();
+            } else {
+                fs::write(&input, FASTQ).unwrap();
+            }
+            let output = dir.join("parts");
+            fs::create_dir_all(&output).unwrap();
+            let parts = split_fastq(&input, &output, &SplitBy::Tile).unwrap();
+            let first = part_path(&input, &output, "L001_T1101");
+            let second = part_path(&input, &output, "L001_T2108");
+            assert_eq!(parts.len(), 2);
+            assert_eq!(parts[&first], 2);
+            assert_eq!(parts[&second], 1);
+            assert_eq!(gzip::is_gzipped(&first).unwrap(), compressed);
+            let text = read_part(&first);
+            assert!(text.starts_with("@M03745:11:000000000-B54L5:1:1101:4127:8949 1:N:0:0\nACGT\n+\nFFFF\n@"));
+            assert!(text.ends_with("GGGG\n+\nFFFF\n"));
+            assert_eq!(read_part(&second), "@M03745:11:000000000-B54L5:1:2108:4128:8950 1:N:0:0\nTTTT\n+\n####\n");
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_split_reader() {
+        let dir = env::temp_dir().join(format!("illumina_coordinates_split_reader_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let ranges = SplitBy::Ranges(vec![(1101, 1119)]);
+        let parts = split_reader(FASTQ.as_bytes(), true, "stdin.fastq.gz", &dir, &ranges).unwrap();
+        let first = dir.join("stdin_L001_T1101-1119.fastq.gz");
+        assert_eq!(parts[&first], 2);
+        assert_eq!(parts[&dir.join("stdin_unassigned.fastq.gz")], 1);
+        assert!(gzip::is_gzipped(&first).unwrap());
+        assert!(read_part(&first).ends_with("GGGG\n+\nFFFF\n"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
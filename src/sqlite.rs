@@ -0,0 +1,456 @@
+//! Writes parsed sequence identifiers into a new SQLite database, for SQL queries over the reads
+//! of a whole run.
+//!
+//! The database has a single `reads` table with a column for each field of `SequenceIdentifier`,
+//! in the same order as the CSV and Parquet output, and an index on the lane and tile
+//! (`lane, side, swath, camera, tile`), so queries for the reads on a tile don't scan the table.
+//! Rows are written out in pages as they arrive, and the index is built once all rows have been
+//! written, from the runs of consecutive rows on each tile.
+
+use std::collections::BTreeMap;
+use std::io::{self, Seek, SeekFrom, Write};
+use super::SequenceIdentifier;
+
+const PAGE_SIZE: usize = 4096;
+const HEADER_SIZE: usize = 100;
+
+// B-tree page types
+const INTERIOR_INDEX: u8 = 2;
+const INTERIOR_TABLE: u8 = 5;
+const LEAF_INDEX: u8 = 10;
+const LEAF_TABLE: u8 = 13;
+
+/// The largest row stored in a table page without overflow pages, which aren't written
+const MAX_ROW_SIZE: usize = PAGE_SIZE - 35;
+
+const CREATE_TABLE: &str = "CREATE TABLE reads(sequencer_id TEXT, run_count INTEGER, flow_cell_id TEXT, lane INTEGER, \
+                            side INTEGER, swath INTEGER, camera INTEGER, tile INTEGER, x INTEGER, y INTEGER, read INTEGER, \
+                            is_filtered INTEGER, control_number INTEGER, sample TEXT)";
+const CREATE_INDEX: &str = "CREATE INDEX reads_tile ON reads(lane, side, swath, camera, tile)";
+
+// The indexed columns of a row: lane, side, swath, camera and tile
+type TileKey = (u8, u8, u8, Option<u8>, u8);
+
+/// A value in a record
+enum Value<'a> {
+    Null,
+    Integer(i64),
+    Text(&'a str)
+}
+
+/// The number of bytes `write_varint` uses for the value
+fn varint_size(value: u64) -> usize {
+    if value >> 56 != 0 {
+        return 9;
+    }
+    let mut size = 1;
+    while value >> (7 * size) != 0 {
+        size += 1;
+    }
+    size
+}
+
+/// Writes a big-endian variable-length integer, seven bits per byte except for the ninth byte,
+/// which holds eight
+fn write_varint(out: &mut Vec<u8>, value: u64) {
+    let size = varint_size(value);
+    if size == 9 {
+        for i in 0..8 {
+            out.push((value >> (57 - 7 * i)) as u8 | 0x80);
+        }
+        out.push(value as u8);
+        return;
+    }
+    for i in (0..size).rev() {
+        let byte = (value >> (7 * i)) as u8 & 0x7f;
+        out.push(if i > 0 { byte | 0x80 } else { byte });
+    }
+}
+
+/// Encodes values in the record format: a header of serial types, then the values
+fn record(values: &[Value]) -> Vec<u8> {
+    let mut types = vec![];
+    let mut body = vec![];
+    for value in values {
+        match *value {
+            Value::Null => write_varint(&mut types, 0),
+            // Schema format 4 stores 0 and 1 in the serial type alone
+            Value::Integer(0) => write_varint(&mut types, 8),
+            Value::Integer(1) => write_varint(&mut types, 9),
+            Value::Integer(n) => {
+                let (serial_type, size) = match n {
+                    -0x80..=0x7f => (1, 1),
+                    -0x8000..=0x7fff => (2, 2),
+                    -0x80_0000..=0x7f_ffff => (3, 3),
+                    -0x8000_0000..=0x7fff_ffff => (4, 4),
+                    -0x8000_0000_0000..=0x7fff_ffff_ffff => (5, 6),
+                    _ => (6, 8)
+                };
+                write_varint(&mut types, serial_type);
+                body.extend_from_slice(&n.to_be_bytes()[8 - size..]);
+            },
+            Value::Text(text) => {
+                write_varint(&mut types, 13 + 2 * text.len() as u64);
+                body.extend_from_slice(text.as_bytes());
+            }
+        }
+    }
+    // The size of the header includes the varint that gives it
+    let mut header_size = types.len() + 1;
+    while types.len() + varint_size(header_size as u64) != header_size {
+        header_size = types.len() + varint_size(header_size as u64);
+    }
+    let mut record = Vec::with_capacity(header_size + body.len());
+    write_varint(&mut record, header_size as u64);
+    record.extend_from_slice(&types);
+    record.extend_from_slice(&body);
+    record
+}
+
+/// A b-tree page being filled with cells
+struct Page {
+    page_type: u8,
+    cells: Vec<Vec<u8>>,
+    cell_bytes: usize
+}
+
+impl Page {
+    fn new(page_type: u8) -> Page {
+        Page { page_type, cells: vec![], cell_bytes: 0 }
+    }
+
+    fn header_size(&self) -> usize {
+        match self.page_type {
+            INTERIOR_INDEX | INTERIOR_TABLE => 12,
+            _ => 8
+        }
+    }
+
+    /// Whether the cell fits on the page with its pointer
+    fn fits(&self, cell: &[u8]) -> bool {
+        self.header_size() + 2 * (self.cells.len() + 1) + self.cell_bytes + cell.len() <= PAGE_SIZE
+    }
+
+    fn push(&mut self, cell: Vec<u8>) {
+        self.cell_bytes += cell.len();
+        self.cells.push(cell);
+    }
+
+    fn pop(&mut self) -> Option<Vec<u8>> {
+        let cell = self.cells.pop()?;
+        self.cell_bytes -= cell.len();
+        Some(cell)
+    }
+
+    /// Lays out the page, with its header `offset` bytes in. The cells are packed at the end of the
+    /// page and their pointers follow the header.
+    fn encode(&self, offset: usize, right_child: u32) -> Vec<u8> {
+        let mut page = vec![0u8; PAGE_SIZE];
+        let pointers = offset + self.header_size();
+        let mut content = PAGE_SIZE;
+        for (i, cell) in self.cells.iter().enumerate() {
+            content -= cell.len();
+            page[content..content + cell.len()].copy_from_slice(cell);
+            page[pointers + 2 * i..pointers + 2 * i + 2].copy_from_slice(&(content as u16).to_be_bytes());
+        }
+        page[offset] = self.page_type;
+        page[offset + 3..offset + 5].copy_from_slice(&(self.cells.len() as u16).to_be_bytes());
+        page[offset + 5..offset + 7].copy_from_slice(&(content as u16).to_be_bytes());
+        if self.header_size() == 12 {
+            page[offset + 8..offset + 12].copy_from_slice(&right_child.to_be_bytes());
+        }
+        page
+    }
+}
+
+/// Appends pages to the database file, which starts with a placeholder for the first page
+struct Pages<W: Write> {
+    writer: W,
+    count: u32
+}
+
+impl<W: Write> Pages<W> {
+    /// Writes the page and returns its page number, counting from 1
+    fn append(&mut self, page: &[u8]) -> io::Result<u32> {
+        self.writer.write_all(page)?;
+        self.count += 1;
+        Ok(self.count)
+    }
+}
+
+/// Builds a b-tree bottom up from cells in key order, writing each page once it's full. Only the
+/// last page of each level is kept in memory.
+///
+/// When a page is full, a cell is moved up to the level above with the full page as its left
+/// child: for tables, the largest row ID in the page, and for indexes, the page's last entry,
+/// since interior cells of an index hold entries of their own.
+struct TreeBuilder {
+    index: bool,
+    leaf: Page,
+    interior: Vec<Page>,
+    last_rowid: u64
+}
+
+impl TreeBuilder {
+    fn new(index: bool) -> TreeBuilder {
+        TreeBuilder { index, leaf: Page::new(if index { LEAF_INDEX } else { LEAF_TABLE }), interior: vec![], last_rowid: 0 }
+    }
+
+    /// Adds a leaf cell, which for tables is for the row with the given ID
+    fn add<W: Write>(&mut self, pages: &mut Pages<W>, cell: Vec<u8>, rowid: u64) -> io::Result<()> {
+        if !self.leaf.fits(&cell) {
+            let key = if self.index {
+                self.leaf.pop().expect("a full page has cells")
+            } else {
+                let mut key = vec![];
+                write_varint(&mut key, self.last_rowid);
+                key
+            };
+            let child = pages.append(&self.leaf.encode(0, 0))?;
+            self.leaf = Page::new(self.leaf.page_type);
+            self.promote(pages, 0, child, key)?;
+        }
+        self.leaf.push(cell);
+        self.last_rowid = rowid;
+        Ok(())
+    }
+
+    /// Adds a cell to an interior level, splitting it if it's full
+    fn promote<W: Write>(&mut self, pages: &mut Pages<W>, level: usize, child: u32, key: Vec<u8>) -> io::Result<()> {
+        if self.interior.len() == level {
+            self.interior.push(Page::new(if self.index { INTERIOR_INDEX } else { INTERIOR_TABLE }));
+        }
+        let mut cell = child.to_be_bytes().to_vec();
+        cell.extend_from_slice(&key);
+        if !self.interior[level].fits(&cell) {
+            // The last cell's child becomes the page's rightmost child, and its key moves up
+            let last = self.interior[level].pop().expect("a full page has cells");
+            let right_child = u32::from_be_bytes([last[0], last[1], last[2], last[3]]);
+            let page = pages.append(&self.interior[level].encode(0, right_child))?;
+            self.interior[level] = Page::new(self.interior[level].page_type);
+            self.promote(pages, level + 1, page, last[4..].to_vec())?;
+        }
+        self.interior[level].push(cell);
+        Ok(())
+    }
+
+    /// Writes the last page of each level and returns the page number of the root
+    fn finish<W: Write>(self, pages: &mut Pages<W>) -> io::Result<u32> {
+        let mut child = pages.append(&self.leaf.encode(0, 0))?;
+        for page in &self.interior {
+            child = pages.append(&page.encode(0, child))?;
+        }
+        Ok(child)
+    }
+}
+
+/// Writes sequence identifiers as rows of a new SQLite database. `finish` must be called once all
+/// the identifiers have been written, to write the index and the schema. The writer must be
+/// empty to begin with, such as a newly created file.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use std::io::Cursor;
+/// use illumina_coordinates::sqlite::SqliteWriter;
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     let mut writer = SqliteWriter::new(Cursor::new(vec![])).unwrap();
+///     writer.write(&seq_id).unwrap();
+///     let database = writer.finish().unwrap().into_inner();
+///     assert!(database.starts_with(b"SQLite format 3\0"));
+/// }
+/// ```
+pub struct SqliteWriter<W: Write + Seek> {
+    pages: Pages<W>,
+    table: TreeBuilder,
+    rows: u64,
+    // The runs of consecutive row IDs on each tile, which are enough to build the index
+    tiles: BTreeMap<TileKey, Vec<(u64, u64)>>
+}
+
+impl<W: Write + Seek> SqliteWriter<W> {
+    /// Creates a writer, leaving room for the first page, which is written by `finish`
+    pub fn new(writer: W) -> io::Result<SqliteWriter<W>> {
+        let mut pages = Pages { writer, count: 0 };
+        pages.append(&[0u8; PAGE_SIZE])?;
+        Ok(SqliteWriter { pages, table: TreeBuilder::new(false), rows: 0, tiles: BTreeMap::new() })
+    }
+
+    /// Writes a single sequence identifier as a row
+    pub fn write(&mut self, seq_id: &SequenceIdentifier) -> io::Result<()> {
+        let sample = seq_id.sample.to_string();
+        let row = record(&[
+            Value::Text(&seq_id.sequencer_id),
+            Value::Integer(seq_id.run_count.into()),
+            Value::Text(&seq_id.flow_cell_id),
+            Value::Integer(seq_id.lane.into()),
+            Value::Integer(seq_id.side.into()),
+            Value::Integer(seq_id.swath.into()),
+            seq_id.camera.map(|camera| Value::Integer(camera.into())).unwrap_or(Value::Null),
+            Value::Integer(seq_id.tile.into()),
+            Value::Integer(seq_id.x.into()),
+            Value::Integer(seq_id.y.into()),
+            Value::Integer(seq_id.read.into()),
+            Value::Integer(seq_id.is_filtered.into()),
+            Value::Integer(seq_id.control_number.into()),
+            Value::Text(&sample)
+        ]);
+        if row.len() > MAX_ROW_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "sequence identifier is too long to store in a row"));
+        }
+        let rowid = self.rows + 1;
+        let mut cell = vec![];
+        write_varint(&mut cell, row.len() as u64);
+        write_varint(&mut cell, rowid);
+        cell.extend_from_slice(&row);
+        self.table.add(&mut self.pages, cell, rowid)?;
+        self.rows = rowid;
+        let runs = self.tiles.entry((seq_id.lane, seq_id.side, seq_id.swath, seq_id.camera, seq_id.tile)).or_default();
+        match runs.last_mut() {
+            Some(run) if run.1 + 1 == rowid => run.1 = rowid,
+            _ => runs.push((rowid, rowid))
+        }
+        Ok(())
+    }
+
+    /// Writes the index and the schema, and returns the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        let table_root = self.table.finish(&mut self.pages)?;
+        let mut index = TreeBuilder::new(true);
+        for (&(lane, side, swath, camera, tile), runs) in &self.tiles {
+            for &(start, end) in runs {
+                for rowid in start..=end {
+                    let entry = record(&[
+                        Value::Integer(lane.into()),
+                        Value::Integer(side.into()),
+                        Value::Integer(swath.into()),
+                        camera.map(|camera| Value::Integer(camera.into())).unwrap_or(Value::Null),
+                        Value::Integer(tile.into()),
+                        Value::Integer(rowid as i64)
+                    ]);
+                    let mut cell = vec![];
+                    write_varint(&mut cell, entry.len() as u64);
+                    cell.extend_from_slice(&entry);
+                    index.add(&mut self.pages, cell, rowid)?;
+                }
+            }
+        }
+        let index_root = index.finish(&mut self.pages)?;
+
+        let mut schema = Page::new(LEAF_TABLE);
+        let objects = [("table", "reads", table_root, CREATE_TABLE), ("index", "reads_tile", index_root, CREATE_INDEX)];
+        for (rowid, &(kind, name, root, sql)) in objects.iter().enumerate() {
+            let row = record(&[Value::Text(kind), Value::Text(name), Value::Text("reads"), Value::Integer(root.into()), Value::Text(sql)]);
+            let mut cell = vec![];
+            write_varint(&mut cell, row.len() as u64);
+            write_varint(&mut cell, rowid as u64 + 1);
+            cell.extend_from_slice(&row);
+            schema.push(cell);
+        }
+        let mut first_page = schema.encode(HEADER_SIZE, 0);
+        first_page[..HEADER_SIZE].copy_from_slice(&database_header(self.pages.count));
+        let mut writer = self.pages.writer;
+        writer.seek(SeekFrom::Start(0))?;
+        writer.write_all(&first_page)?;
+        writer.seek(SeekFrom::End(0))?;
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+/// The header at the start of the file
+fn database_header(pages: u32) -> [u8; HEADER_SIZE] {
+    let mut header = [0u8; HEADER_SIZE];
+    header[..16].copy_from_slice(b"SQLite format 3\0");
+    header[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    // The file format versions, for rollback journalling
+    header[18] = 1;
+    header[19] = 1;
+    // The payload fractions, which must have these values
+    header[21] = 64;
+    header[22] = 32;
+    header[23] = 32;
+    // The change counter, which the version-valid-for number at offset 92 must match
+    header[24..28].copy_from_slice(&1u32.to_be_bytes());
+    header[28..32].copy_from_slice(&pages.to_be_bytes());
+    // The schema cookie and the schema format
+    header[40..44].copy_from_slice(&1u32.to_be_bytes());
+    header[44..48].copy_from_slice(&4u32.to_be_bytes());
+    // UTF-8 text
+    header[56..60].copy_from_slice(&1u32.to_be_bytes());
+    header[92..96].copy_from_slice(&1u32.to_be_bytes());
+    // The SQLite version that wrote the file, 3.31.1, the oldest that reads it the same way
+    header[96..100].copy_from_slice(&3_031_001u32.to_be_bytes());
+    header
+}
+
+/// Writes all of the sequence identifiers into a new SQLite database, and returns the underlying
+/// writer
+pub fn write_sqlite<'a, W, I>(writer: W, seq_ids: I) -> io::Result<W>
+    where W: Write + Seek, I: IntoIterator<Item=&'a SequenceIdentifier> {
+    let mut writer = SqliteWriter::new(writer)?;
+    for seq_id in seq_ids {
+        writer.write(seq_id)?;
+    }
+    writer.finish()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use parse_sequence_identifier;
+
+    fn varint(value: u64) -> Vec<u8> {
+        let mut out = vec![];
+        write_varint(&mut out, value);
+        out
+    }
+
+    #[test]
+    fn test_varint() {
+        assert_eq!(varint(0), vec![0]);
+        assert_eq!(varint(127), vec![0x7f]);
+        assert_eq!(varint(128), vec![0x81, 0]);
+        assert_eq!(varint(300), vec![0x82, 0x2c]);
+        assert_eq!(varint(u64::MAX), vec![0xff; 9]);
+        for &value in &[0, 127, 128, 1 << 20, 1 << 55, 1 << 56, u64::MAX] {
+            assert_eq!(varint(value).len(), varint_size(value));
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        assert_eq!(record(&[Value::Null, Value::Integer(1), Value::Integer(300), Value::Text("ab")]),
+                   vec![5, 0, 9, 2, 17, 0x01, 0x2c, b'a', b'b']);
+    }
+
+    #[test]
+    fn test_write_sqlite() {
+        let seq_ids: Vec<SequenceIdentifier> = (0..5000).map(|i| {
+            let line = format!("@NB501234:12:H2VGLBGXY:1:{}:{}:1000 1:N:0:1", [11101, 21612, 11203][i % 3], i + 1);
+            parse_sequence_identifier(&line).unwrap()
+        }).collect();
+        let database = write_sqlite(Cursor::new(vec![]), &seq_ids).unwrap().into_inner();
+        assert_eq!(database.len() % PAGE_SIZE, 0);
+        assert_eq!(&database[28..32], &((database.len() / PAGE_SIZE) as u32).to_be_bytes());
+        // The schema is a leaf page after the header, giving the roots of the table and index
+        assert_eq!(database[HEADER_SIZE], LEAF_TABLE);
+        assert_eq!(&database[HEADER_SIZE + 3..HEADER_SIZE + 5], &[0, 2]);
+        let contains = |text: &str| database.windows(text.len()).any(|window| window == text.as_bytes());
+        assert!(contains(CREATE_TABLE) && contains(CREATE_INDEX));
+        // The rows span many pages, so both roots are interior pages, the last pages written
+        let pages = database.len() / PAGE_SIZE;
+        assert_eq!(database[(pages - 1) * PAGE_SIZE], INTERIOR_INDEX);
+        assert!(database.chunks(PAGE_SIZE).any(|page| page[0] == INTERIOR_TABLE));
+
+        let empty = write_sqlite(Cursor::new(vec![]), &[]).unwrap().into_inner();
+        assert_eq!(empty.len(), 3 * PAGE_SIZE);
+        assert_eq!((empty[PAGE_SIZE], empty[2 * PAGE_SIZE]), (LEAF_TABLE, LEAF_INDEX));
+    }
+}
@@ -0,0 +1,1305 @@
+//! Summary statistics of the reads in a run, broken down by tile, lane, surface and swath.
+//!
+//! The fraction of reads that pass filtering is usually similar across a run. Tiles that stand
+//! out, with many more reads failing the filter than the rest, often point to bubbles, debris or
+//! focusing problems on that part of the flow cell.
+//!
+//! For paired-end runs, the read 1 and read 2 files should hold the same clusters. Comparing
+//! them catches files that were truncated, filtered separately or mixed up between runs.
+//!
+//! Clusters are also spread evenly over each tile, so the coordinates of a tile's reads should
+//! have much the same mean and spread as those of the whole run. A tile whose mean is shifted, or
+//! whose spread is narrower because part of it yielded no reads, points to an imaging problem.
+//! Quantiles of the coordinates show directly whether a tile's clusters reach its edges.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use dedupe::DuplicateCounts;
+use geometry::TileExtent;
+use position::Position;
+use super::{SequenceIdentifier, TileId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// The number of reads in part of a run, and how many of them failed filtering
+pub struct ReadCounts {
+    /// The number of reads
+    pub reads: u64,
+    /// The number of reads that failed filtering
+    pub filtered: u64
+}
+
+impl ReadCounts {
+    /// The number of reads that passed filtering
+    pub fn passed(&self) -> u64 {
+        self.reads - self.filtered
+    }
+
+    /// The fraction of reads that passed filtering, or `None` if there were no reads
+    pub fn pass_filter_rate(&self) -> Option<f64> {
+        if self.reads == 0 {
+            None
+        } else {
+            Some(self.passed() as f64 / self.reads as f64)
+        }
+    }
+
+    /// The number of clusters per square millimetre in an area of the given size
+    pub fn density(&self, area_mm2: f64) -> f64 {
+        self.reads as f64 / area_mm2
+    }
+
+    /// The number of clusters that passed filtering per square millimetre in an area of the given
+    /// size
+    pub fn pass_filter_density(&self, area_mm2: f64) -> f64 {
+        self.passed() as f64 / area_mm2
+    }
+
+    pub(crate) fn add(&mut self, other: ReadCounts) {
+        self.reads += other.reads;
+        self.filtered += other.filtered;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// A tile whose pass-filter rate is far from that of the whole run
+pub struct PassFilterOutlier {
+    /// The tile
+    pub tile: TileId,
+    /// The fraction of the tile's reads that passed filtering
+    pub pass_filter_rate: f64,
+    /// The fraction of all reads in the run that passed filtering
+    pub run_pass_filter_rate: f64
+}
+
+impl PassFilterOutlier {
+    /// How far the tile's pass-filter rate is from the run's. Negative if fewer of the tile's
+    /// reads passed.
+    pub fn deviation(&self) -> f64 {
+        self.pass_filter_rate - self.run_pass_filter_rate
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How far a tile's read count must be from the others' to be an outlier
+pub enum OutlierThreshold {
+    /// The number of standard deviations from the mean count
+    ZScore(f64),
+    /// The number of scaled median absolute deviations from the median count. This is robust:
+    /// a few very bad tiles don't hide each other by inflating the spread, as they can with
+    /// z-scores.
+    Mad(f64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// A tile whose read count is far from those of the other tiles
+pub struct CountOutlier {
+    /// The tile
+    pub tile: TileId,
+    /// The number of reads on the tile
+    pub reads: u64,
+    /// How far the count is from the others, in the units of the threshold: standard deviations
+    /// from the mean, or scaled median absolute deviations from the median. Negative if the tile
+    /// has fewer reads.
+    pub score: f64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The reads on one surface of a lane
+pub struct SurfaceSummary {
+    /// The number of reads and how many failed filtering
+    pub counts: ReadCounts,
+    /// The mean density of clusters over the surface's tiles that had reads, in clusters per
+    /// square millimetre
+    pub density: f64,
+    /// The number of reads and duplicates on the surface's tiles, if duplicates were counted
+    pub duplicates: DuplicateCounts
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The top and bottom surfaces of a lane side by side. A failure of one surface, such as a
+/// bubble or a focus problem on the bottom of the flow cell, shows up as a difference between
+/// them that's absent from the lane's totals.
+pub struct SurfaceComparison {
+    /// The lane
+    pub lane: u8,
+    /// Surface 1, or `None` if it had no reads
+    pub top: Option<SurfaceSummary>,
+    /// Surface 2, or `None` if it had no reads
+    pub bottom: Option<SurfaceSummary>
+}
+
+impl SurfaceComparison {
+    /// The top surface's pass-filter rate minus the bottom's, if both had reads
+    pub fn pass_filter_rate_difference(&self) -> Option<f64> {
+        match (self.top, self.bottom) {
+            (Some(top), Some(bottom)) => Some(top.counts.pass_filter_rate()? - bottom.counts.pass_filter_rate()?),
+            _ => None
+        }
+    }
+
+    /// The top surface's density divided by the bottom's, if both had reads
+    pub fn density_ratio(&self) -> Option<f64> {
+        match (self.top, self.bottom) {
+            (Some(top), Some(bottom)) => Some(top.density / bottom.density),
+            _ => None
+        }
+    }
+
+    /// The top surface's duplicate rate minus the bottom's, if duplicates were counted on both
+    pub fn duplicate_rate_difference(&self) -> Option<f64> {
+        match (self.top, self.bottom) {
+            (Some(top), Some(bottom)) => Some(top.duplicates.duplicate_rate()? - bottom.duplicates.duplicate_rate()?),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The metrics of a lane's tiles added up, for run-level reports
+pub struct LaneSummary {
+    /// The lane
+    pub lane: u8,
+    /// The number of tiles that had reads
+    pub tiles: usize,
+    /// The number of reads and how many failed filtering
+    pub counts: ReadCounts,
+    /// The mean density of clusters over the tiles, in clusters per square millimetre
+    pub density: f64,
+    /// The mean density of clusters that passed filtering over the tiles, in clusters per square
+    /// millimetre
+    pub pass_filter_density: f64,
+    /// The number of reads and duplicates on the tiles, if duplicates were counted
+    pub duplicates: DuplicateCounts
+}
+
+impl LaneSummary {
+    /// The fraction of reads that passed filtering, or `None` if there were no reads
+    pub fn pass_filter_rate(&self) -> Option<f64> {
+        self.counts.pass_filter_rate()
+    }
+
+    /// The fraction of reads that were duplicates, or `None` if duplicates weren't counted
+    pub fn duplicate_rate(&self) -> Option<f64> {
+        self.duplicates.duplicate_rate()
+    }
+
+    /// Formats the summary as a single JSON object. Rates that aren't known are `null`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use std::collections::BTreeMap;
+    /// use illumina_coordinates::stats::StatsCollector;
+    ///
+    /// fn main() {
+    ///     let mut stats = StatsCollector::new();
+    ///     for line in &["@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+    ///                   "@M03745:11:000000000-B54L5:1:2108:4128:8950 1:Y:0:0"] {
+    ///         stats.add(&illumina_coordinates::parse_sequence_identifier(line).unwrap());
+    ///     }
+    ///     let summary = stats.lane_summaries(0.5, &BTreeMap::new())[0];
+    ///     assert_eq!(summary.to_json(),
+    ///                concat!(r#"{"lane":1,"tiles":1,"reads":2,"filtered":1,"passed":1,"pass_filter_rate":0.5,"#,
+    ///                        r#""density":4,"pass_filter_density":2,"duplicates":0,"duplicate_rate":null}"#));
+    /// }
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        let _ = write!(json, "{{\"lane\":{},\"tiles\":{},\"reads\":{},\"filtered\":{},\"passed\":{},",
+                       self.lane, self.tiles, self.counts.reads, self.counts.filtered, self.counts.passed());
+        let _ = write!(json, "\"pass_filter_rate\":{},\"density\":{},\"pass_filter_density\":{},",
+                       json_number(self.pass_filter_rate()), json_number(Some(self.density)),
+                       json_number(Some(self.pass_filter_density)));
+        let _ = write!(json, "\"duplicates\":{},\"duplicate_rate\":{}}}",
+                       self.duplicates.duplicates, json_number(self.duplicate_rate()));
+        json
+    }
+}
+
+// JSON has no infinities or NaN, so those are written as null along with unknown values
+fn json_number(value: Option<f64>) -> String {
+    match value {
+        Some(value) if value.is_finite() => value.to_string(),
+        _ => "null".to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// Collects read counts for each tile of a run
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::stats::StatsCollector;
+///
+/// fn main() {
+///     let mut stats = StatsCollector::new();
+///     for line in &["@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0",
+///                   "@M03745:11:000000000-B54L5:1:2108:4128:8950 1:Y:0:0",
+///                   "@M03745:11:000000000-B54L5:1:2109:4127:8949 1:N:0:0"] {
+///         stats.add(&illumina_coordinates::parse_sequence_identifier(line).unwrap());
+///     }
+///     assert_eq!(stats.total().filtered, 1);
+///     assert_eq!(stats.lanes()[&1].reads, 3);
+///     let outliers = stats.pass_filter_outliers(0.1);
+///     assert_eq!(outliers.len(), 2);
+///     assert_eq!(outliers[0].pass_filter_rate, 0.5);
+/// }
+/// ```
+pub struct StatsCollector {
+    tiles: BTreeMap<TileId, ReadCounts>
+}
+
+impl StatsCollector {
+    /// Creates a collector that hasn't counted any reads
+    pub fn new() -> StatsCollector {
+        StatsCollector::default()
+    }
+
+    /// Counts a read
+    pub fn add(&mut self, seq_id: &SequenceIdentifier) {
+        let counts = self.tiles.entry(seq_id.tile_id()).or_default();
+        counts.reads += 1;
+        if seq_id.is_filtered {
+            counts.filtered += 1;
+        }
+    }
+
+    /// The counts for each tile that had at least one read
+    pub fn tiles(&self) -> &BTreeMap<TileId, ReadCounts> {
+        &self.tiles
+    }
+
+    /// The counts for each lane
+    pub fn lanes(&self) -> BTreeMap<u8, ReadCounts> {
+        self.group_by(|tile| tile.lane)
+    }
+
+    /// The counts for each surface, keyed by lane and side
+    pub fn surfaces(&self) -> BTreeMap<(u8, u8), ReadCounts> {
+        self.group_by(|tile| (tile.lane, tile.side))
+    }
+
+    /// The counts for each swath, keyed by lane, side and swath. On HiSeq and NovaSeq flow cells
+    /// the swaths of a lane are imaged on alternate passes of the camera, so differences between
+    /// them point to a problem with one pass.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::stats::StatsCollector;
+    ///
+    /// fn main() {
+    ///     let mut stats = StatsCollector::new();
+    ///     for line in &["@A00123:8:H3KJ7DSXX:1:1101:1000:1000 1:N:0:0",
+    ///                   "@A00123:8:H3KJ7DSXX:1:1201:1000:1000 1:Y:0:0",
+    ///                   "@A00123:8:H3KJ7DSXX:1:1202:1000:1000 1:N:0:0"] {
+    ///         stats.add(&illumina_coordinates::parse_sequence_identifier(line).unwrap());
+    ///     }
+    ///     let swaths = stats.swaths();
+    ///     assert_eq!(swaths[&(1, 1, 1)].reads, 1);
+    ///     assert_eq!(swaths[&(1, 1, 2)].pass_filter_rate(), Some(0.5));
+    /// }
+    /// ```
+    pub fn swaths(&self) -> BTreeMap<(u8, u8, u8), ReadCounts> {
+        self.group_by(|tile| (tile.lane, tile.side, tile.swath))
+    }
+
+    /// The counts for each camera of each swath, keyed by lane, side, swath and camera, for
+    /// sequencers that number tiles by camera. Tiles without a camera are left out.
+    pub fn cameras(&self) -> BTreeMap<(u8, u8, u8, u8), ReadCounts> {
+        let mut cameras = BTreeMap::new();
+        for (tile, &counts) in &self.tiles {
+            if let Some(camera) = tile.camera {
+                cameras.entry((tile.lane, tile.side, tile.swath, camera)).or_insert_with(ReadCounts::default).add(counts);
+            }
+        }
+        cameras
+    }
+
+    /// The counts for the whole run
+    pub fn total(&self) -> ReadCounts {
+        let mut total = ReadCounts::default();
+        for &counts in self.tiles.values() {
+            total.add(counts);
+        }
+        total
+    }
+
+    /// The tiles whose pass-filter rate differs from that of the whole run by more than
+    /// `threshold`, in order of tile
+    pub fn pass_filter_outliers(&self, threshold: f64) -> Vec<PassFilterOutlier> {
+        let run_pass_filter_rate = match self.total().pass_filter_rate() {
+            Some(rate) => rate,
+            None => return vec![]
+        };
+        self.tiles.iter()
+            .filter_map(|(&tile, counts)| counts.pass_filter_rate()
+                .map(|pass_filter_rate| PassFilterOutlier { tile, pass_filter_rate, run_pass_filter_rate }))
+            .filter(|outlier| outlier.deviation().abs() > threshold)
+            .collect()
+    }
+
+    /// The tiles whose read counts are outliers among the run's tiles, in order of tile. Tiles
+    /// with no reads at all aren't known, so they can't be flagged. The outliers' tiles can be
+    /// collected into a `filter::TileBlacklist` and saved for filtering.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use illumina_coordinates::stats::{OutlierThreshold, StatsCollector};
+    ///
+    /// fn main() {
+    ///     let mut stats = StatsCollector::new();
+    ///     for (tile, reads) in (2101..2111).zip(vec![100, 104, 98, 101, 97, 103, 99, 102, 100, 10]) {
+    ///         for read in 0..reads {
+    ///             let line = format!("@M03745:11:000000000-B54L5:1:{}:1000:{} 1:N:0:0", tile, 1000 + read);
+    ///             stats.add(&illumina_coordinates::parse_sequence_identifier(&line).unwrap());
+    ///         }
+    ///     }
+    ///     let outliers = stats.count_outliers(OutlierThreshold::Mad(3.5));
+    ///     assert_eq!(outliers.len(), 1);
+    ///     assert_eq!((outliers[0].tile.tile, outliers[0].reads), (10, 10));
+    /// }
+    /// ```
+    pub fn count_outliers(&self, threshold: OutlierThreshold) -> Vec<CountOutlier> {
+        let counts: Vec<f64> = self.tiles.values().map(|counts| counts.reads as f64).collect();
+        if counts.is_empty() {
+            return vec![];
+        }
+        let (center, spread, limit) = match threshold {
+            OutlierThreshold::ZScore(limit) => {
+                let mut moments = RunningMoments::new();
+                for &count in &counts {
+                    moments.add(count);
+                }
+                (moments.mean().unwrap_or(0.0), moments.std_dev().unwrap_or(0.0), limit)
+            },
+            OutlierThreshold::Mad(limit) => {
+                let center = median(counts.clone());
+                let deviations = counts.iter().map(|count| (count - center).abs()).collect();
+                // Scaled so that it estimates the standard deviation of normally distributed counts
+                (center, 1.4826 * median(deviations), limit)
+            }
+        };
+        self.tiles.iter()
+            .map(|(&tile, counts)| {
+                let difference = counts.reads as f64 - center;
+                let score = if difference == 0.0 { 0.0 } else { difference / spread };
+                CountOutlier { tile, reads: counts.reads, score }
+            })
+            .filter(|outlier| outlier.score.abs() > limit)
+            .collect()
+    }
+
+    /// The density of clusters in each tile, in clusters per square millimetre, given the area of
+    /// a tile. This approximates the density Sequencing Analysis Viewer reports from InterOp
+    /// files, as long as the reads include those that failed filtering. An instrument's tile area
+    /// can be found with `InstrumentModel::tile_area_mm2`.
+    pub fn tile_densities(&self, tile_area_mm2: f64) -> BTreeMap<TileId, f64> {
+        self.tiles.iter().map(|(&tile, counts)| (tile, counts.density(tile_area_mm2))).collect()
+    }
+
+    /// The mean density of clusters over the tiles of each lane that had reads, in clusters per
+    /// square millimetre
+    pub fn lane_densities(&self, tile_area_mm2: f64) -> BTreeMap<u8, f64> {
+        self.densities_by(tile_area_mm2, |tile| tile.lane)
+    }
+
+    /// The mean density of clusters over the tiles of each swath that had reads, in clusters per
+    /// square millimetre, keyed by lane, side and swath
+    pub fn swath_densities(&self, tile_area_mm2: f64) -> BTreeMap<(u8, u8, u8), f64> {
+        self.densities_by(tile_area_mm2, |tile| (tile.lane, tile.side, tile.swath))
+    }
+
+    /// Compares the top and bottom surfaces of each lane, in order of lane. `duplicates` are the
+    /// duplicate counts for each tile, as from `Deduplicator::tiles`; pass an empty map to
+    /// leave duplicates out.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// extern crate illumina_coordinates;
+    /// use std::collections::BTreeMap;
+    /// use illumina_coordinates::dedupe::{DedupeOptions, Deduplicator};
+    /// use illumina_coordinates::stats::StatsCollector;
+    ///
+    /// fn main() {
+    ///     let mut stats = StatsCollector::new();
+    ///     let mut deduplicator = Deduplicator::new(DedupeOptions::default());
+    ///     for line in &["@M03745:11:000000000-B54L5:1:1101:1000:1000 1:N:0:0",
+    ///                   "@M03745:11:000000000-B54L5:1:1101:2000:1000 1:N:0:0",
+    ///                   "@M03745:11:000000000-B54L5:1:2101:1000:1000 1:Y:0:0",
+    ///                   "@M03745:11:000000000-B54L5:1:2101:1000:1000 1:N:0:0"] {
+    ///         let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+    ///         stats.add(&seq_id);
+    ///         deduplicator.is_duplicate(&seq_id, b"");
+    ///     }
+    ///     let lanes = stats.compare_surfaces(1.0, deduplicator.tiles());
+    ///     assert_eq!(lanes[0].pass_filter_rate_difference(), Some(0.5));
+    ///     assert_eq!(lanes[0].density_ratio(), Some(1.0));
+    ///     assert_eq!(lanes[0].duplicate_rate_difference(), Some(-0.5));
+    ///     assert_eq!(stats.compare_surfaces(1.0, &BTreeMap::new())[0].duplicate_rate_difference(), None);
+    /// }
+    /// ```
+    pub fn compare_surfaces(&self, tile_area_mm2: f64, duplicates: &BTreeMap<TileId, DuplicateCounts>)
+        -> Vec<SurfaceComparison> {
+        let densities = self.densities_by(tile_area_mm2, |tile| (tile.lane, tile.side));
+        let mut surface_duplicates: BTreeMap<(u8, u8), DuplicateCounts> = BTreeMap::new();
+        for (tile, counts) in duplicates {
+            let total = surface_duplicates.entry((tile.lane, tile.side)).or_default();
+            total.reads += counts.reads;
+            total.duplicates += counts.duplicates;
+        }
+        let mut lanes: BTreeMap<u8, SurfaceComparison> = BTreeMap::new();
+        for (surface, counts) in self.surfaces() {
+            let (lane, side) = surface;
+            let summary = SurfaceSummary {
+                counts,
+                density: densities[&surface],
+                duplicates: surface_duplicates.get(&surface).cloned().unwrap_or_default()
+            };
+            let comparison = lanes.entry(lane).or_insert(SurfaceComparison { lane, top: None, bottom: None });
+            match side {
+                1 => comparison.top = Some(summary),
+                _ => comparison.bottom = Some(summary)
+            }
+        }
+        lanes.values().cloned().collect()
+    }
+
+    /// Summarizes each lane, in order of lane. `duplicates` are the duplicate counts for each
+    /// tile, as from `Deduplicator::tiles`; pass an empty map to leave duplicates out.
+    pub fn lane_summaries(&self, tile_area_mm2: f64, duplicates: &BTreeMap<TileId, DuplicateCounts>)
+        -> Vec<LaneSummary> {
+        let mut lanes: BTreeMap<u8, LaneSummary> = BTreeMap::new();
+        for (tile, &counts) in &self.tiles {
+            let summary = lanes.entry(tile.lane).or_insert(LaneSummary {
+                lane: tile.lane,
+                tiles: 0,
+                counts: ReadCounts::default(),
+                density: 0.0,
+                pass_filter_density: 0.0,
+                duplicates: DuplicateCounts::default()
+            });
+            summary.tiles += 1;
+            summary.counts.add(counts);
+            if let Some(tile_duplicates) = duplicates.get(tile) {
+                summary.duplicates.reads += tile_duplicates.reads;
+                summary.duplicates.duplicates += tile_duplicates.duplicates;
+            }
+        }
+        for summary in lanes.values_mut() {
+            let area = tile_area_mm2 * summary.tiles as f64;
+            summary.density = summary.counts.density(area);
+            summary.pass_filter_density = summary.counts.pass_filter_density(area);
+        }
+        lanes.values().cloned().collect()
+    }
+
+    fn densities_by<K: Ord + Copy, F: Fn(&TileId) -> K>(&self, tile_area_mm2: f64, key: F) -> BTreeMap<K, f64> {
+        let mut tile_counts = BTreeMap::new();
+        for tile in self.tiles.keys() {
+            *tile_counts.entry(key(tile)).or_insert(0) += 1;
+        }
+        self.group_by(key).into_iter()
+            .map(|(group, counts)| (group, counts.density(tile_area_mm2 * f64::from(tile_counts[&group]))))
+            .collect()
+    }
+
+    fn group_by<K: Ord, F: Fn(&TileId) -> K>(&self, key: F) -> BTreeMap<K, ReadCounts> {
+        let mut groups = BTreeMap::new();
+        for (tile, &counts) in &self.tiles {
+            groups.entry(key(tile)).or_insert_with(ReadCounts::default).add(counts);
+        }
+        groups
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// The count, mean and variance of a series of values, updated one value at a time with
+/// Welford's algorithm so that the values don't need to be kept
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::stats::RunningMoments;
+///
+/// fn main() {
+///     let mut moments = RunningMoments::new();
+///     for &value in &[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+///         moments.add(value);
+///     }
+///     assert_eq!(moments.mean(), Some(5.0));
+///     assert_eq!(moments.variance(), Some(4.0));
+///     assert_eq!(moments.std_dev(), Some(2.0));
+/// }
+/// ```
+pub struct RunningMoments {
+    count: u64,
+    mean: f64,
+    // The sum of squared differences from the mean
+    squares: f64
+}
+
+impl RunningMoments {
+    /// Creates moments of no values
+    pub fn new() -> RunningMoments {
+        RunningMoments::default()
+    }
+
+    /// Adds a value
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.squares += delta * (value - self.mean);
+    }
+
+    /// Combines the moments of another series of values, as if its values had been added, so
+    /// that series can be summarized separately, e.g. on several threads, and then combined
+    pub fn merge(&mut self, other: RunningMoments) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.squares += other.squares + delta * delta * self.count as f64 * other.count as f64 / count as f64;
+        self.count = count;
+    }
+
+    /// The number of values
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The mean of the values, if there are any
+    pub fn mean(&self) -> Option<f64> {
+        if self.count > 0 { Some(self.mean) } else { None }
+    }
+
+    /// The population variance of the values, if there are any
+    pub fn variance(&self) -> Option<f64> {
+        if self.count > 0 { Some(self.squares / self.count as f64) } else { None }
+    }
+
+    /// The population standard deviation of the values, if there are any
+    pub fn std_dev(&self) -> Option<f64> {
+        self.variance().map(f64::sqrt)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// An axis of a tile
+pub enum Axis {
+    /// The x-axis
+    X,
+    /// The y-axis
+    Y
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// The moments of the x- and y-coordinates of a set of reads
+pub struct CoordinateMoments {
+    /// The moments of the x-coordinates
+    pub x: RunningMoments,
+    /// The moments of the y-coordinates
+    pub y: RunningMoments
+}
+
+impl CoordinateMoments {
+    /// The moments along an axis
+    pub fn axis(&self, axis: Axis) -> &RunningMoments {
+        match axis {
+            Axis::X => &self.x,
+            Axis::Y => &self.y
+        }
+    }
+
+    fn merge(&mut self, other: CoordinateMoments) {
+        self.x.merge(other.x);
+        self.y.merge(other.y);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// A tile whose coordinates along an axis are distributed differently from those of the whole
+/// run
+pub struct CoordinateOutlier {
+    /// The tile
+    pub tile: TileId,
+    /// The axis
+    pub axis: Axis,
+    /// How far the mean of the tile's coordinates is from the mean of the run's, in standard
+    /// deviations of the run's coordinates. Negative if the tile's mean is lower.
+    pub shift: f64,
+    /// The standard deviation of the tile's coordinates as a fraction of that of the run's. Well
+    /// below 1 if the tile's distribution is truncated.
+    pub spread: f64
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// Collects the moments of the coordinates of each tile's reads in a single pass
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::stats::{Axis, CoordinateStats};
+///
+/// fn main() {
+///     let mut stats = CoordinateStats::new();
+///     for x in 0..100 {
+///         for &(tile, y) in &[(2108, x * 200), (2109, x * 200), (2110, x * 100)] {
+///             let line = format!("@M03745:11:000000000-B54L5:1:{}:{}:{} 1:N:0:0", tile, x * 200, y);
+///             stats.add(&illumina_coordinates::parse_sequence_identifier(&line).unwrap());
+///         }
+///     }
+///     assert_eq!(stats.tiles().len(), 3);
+///     let outliers = stats.coordinate_outliers(0.5, 0.75);
+///     assert_eq!(outliers.len(), 1);
+///     assert_eq!((outliers[0].tile.tile, outliers[0].axis), (10, Axis::Y));
+/// }
+/// ```
+pub struct CoordinateStats {
+    tiles: BTreeMap<TileId, CoordinateMoments>
+}
+
+impl CoordinateStats {
+    /// Creates a collector that hasn't seen any reads
+    pub fn new() -> CoordinateStats {
+        CoordinateStats::default()
+    }
+
+    /// Adds the coordinates of a read
+    pub fn add(&mut self, seq_id: &SequenceIdentifier) {
+        let moments = self.tiles.entry(seq_id.tile_id()).or_default();
+        moments.x.add(f64::from(seq_id.x));
+        moments.y.add(f64::from(seq_id.y));
+    }
+
+    /// Combines the moments collected from other reads, such as another file of the same run
+    pub fn merge(&mut self, other: &CoordinateStats) {
+        for (&tile, &moments) in &other.tiles {
+            self.tiles.entry(tile).or_default().merge(moments);
+        }
+    }
+
+    /// The moments for each tile that had at least one read
+    pub fn tiles(&self) -> &BTreeMap<TileId, CoordinateMoments> {
+        &self.tiles
+    }
+
+    /// The moments for the whole run
+    pub fn total(&self) -> CoordinateMoments {
+        let mut total = CoordinateMoments::default();
+        for &moments in self.tiles.values() {
+            total.merge(moments);
+        }
+        total
+    }
+
+    /// The tiles and axes whose mean is more than `max_shift` of the run's standard deviations
+    /// from the run's mean, or whose standard deviation is less than `min_spread` of the run's,
+    /// in order of tile
+    pub fn coordinate_outliers(&self, max_shift: f64, min_spread: f64) -> Vec<CoordinateOutlier> {
+        let total = self.total();
+        let mut outliers = vec![];
+        for (&tile, moments) in &self.tiles {
+            for &axis in &[Axis::X, Axis::Y] {
+                let (run, moments) = (total.axis(axis), moments.axis(axis));
+                let (run_mean, run_std_dev, mean, std_dev) = match (run.mean(), run.std_dev(), moments.mean(), moments.std_dev()) {
+                    (Some(run_mean), Some(run_std_dev), Some(mean), Some(std_dev)) if run_std_dev > 0.0 => {
+                        (run_mean, run_std_dev, mean, std_dev)
+                    },
+                    _ => continue
+                };
+                let outlier = CoordinateOutlier { tile, axis, shift: (mean - run_mean) / run_std_dev, spread: std_dev / run_std_dev };
+                if outlier.shift.abs() > max_shift || outlier.spread < min_spread {
+                    outliers.push(outlier);
+                }
+            }
+        }
+        outliers
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Estimates a quantile of a series of values without keeping them, with the P² algorithm of
+/// Jain and Chlamtac. Five markers are moved as values arrive so that the middle one tracks the
+/// quantile; the estimate is exact for up to five values.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::stats::QuantileEstimator;
+///
+/// fn main() {
+///     let mut median = QuantileEstimator::new(0.5);
+///     for value in 0..1001 {
+///         median.add(f64::from((value * 7919) % 1001));
+///     }
+///     assert!((median.estimate().unwrap() - 500.0).abs() < 10.0);
+/// }
+/// ```
+pub struct QuantileEstimator {
+    quantile: f64,
+    count: u64,
+    // The heights and positions of the markers, and the positions they should be at
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5]
+}
+
+impl QuantileEstimator {
+    /// Creates an estimator of the quantile, which is between 0 and 1, e.g. 0.5 for the median
+    pub fn new(quantile: f64) -> QuantileEstimator {
+        let quantile = f64::min(quantile.max(0.0), 1.0);
+        QuantileEstimator {
+            quantile,
+            count: 0,
+            heights: [0.0; 5],
+            positions: [0.0, 1.0, 2.0, 3.0, 4.0],
+            desired: [0.0, 2.0 * quantile, 4.0 * quantile, 2.0 + 2.0 * quantile, 4.0]
+        }
+    }
+
+    /// The quantile being estimated
+    pub fn quantile(&self) -> f64 {
+        self.quantile
+    }
+
+    /// The number of values
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Adds a value
+    pub fn add(&mut self, value: f64) {
+        if self.count < 5 {
+            self.heights[self.count as usize] = value;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            }
+            return;
+        }
+        self.count += 1;
+        let cell = if value < self.heights[0] {
+            self.heights[0] = value;
+            0
+        } else if value >= self.heights[4] {
+            self.heights[4] = value;
+            3
+        } else {
+            (1..5).find(|&i| value < self.heights[i]).unwrap_or(4) - 1
+        };
+        for position in &mut self.positions[cell + 1..] {
+            *position += 1.0;
+        }
+        let quantile = self.quantile;
+        for (desired, increment) in self.desired.iter_mut().zip(&[0.0, quantile / 2.0, quantile, (1.0 + quantile) / 2.0, 1.0]) {
+            *desired += increment;
+        }
+        for i in 1..4 {
+            let offset = self.desired[i] - self.positions[i];
+            if (offset >= 1.0 && self.positions[i + 1] - self.positions[i] > 1.0)
+                || (offset <= -1.0 && self.positions[i - 1] - self.positions[i] < -1.0) {
+                let step = offset.signum();
+                let height = self.parabolic(i, step);
+                self.heights[i] = if self.heights[i - 1] < height && height < self.heights[i + 1] {
+                    height
+                } else {
+                    self.linear(i, step)
+                };
+                self.positions[i] += step;
+            }
+        }
+    }
+
+    /// The estimated quantile, if there are any values
+    pub fn estimate(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            count if count < 5 => {
+                let mut values = self.heights[..count as usize].to_vec();
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                Some(values[((count - 1) as f64 * self.quantile).round() as usize])
+            },
+            _ => Some(self.heights[2])
+        }
+    }
+
+    /// The marker's new height on a parabola through it and its neighbours
+    fn parabolic(&self, i: usize, step: f64) -> f64 {
+        let (heights, positions) = (&self.heights, &self.positions);
+        heights[i] + step / (positions[i + 1] - positions[i - 1])
+            * ((positions[i] - positions[i - 1] + step) * (heights[i + 1] - heights[i]) / (positions[i + 1] - positions[i])
+               + (positions[i + 1] - positions[i] - step) * (heights[i] - heights[i - 1]) / (positions[i] - positions[i - 1]))
+    }
+
+    /// The marker's new height on the line to the neighbour it's moving towards
+    fn linear(&self, i: usize, step: f64) -> f64 {
+        let neighbour = if step > 0.0 { i + 1 } else { i - 1 };
+        self.heights[i] + step * (self.heights[neighbour] - self.heights[i]) / (self.positions[neighbour] - self.positions[i])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Estimates quantiles of the x- and y-coordinates of each tile's reads in a single pass, to
+/// show whether the clusters cover the whole tile or stop short of its edges
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::geometry::TileExtent;
+/// use illumina_coordinates::stats::{Axis, CoordinateQuantiles};
+///
+/// fn main() {
+///     let mut quantiles = CoordinateQuantiles::new(&[0.01, 0.99]);
+///     for i in 0..10000 {
+///         let (x, y) = (i * 7 % 10000, i * 13 % 5000);
+///         let line = format!("@M03745:11:000000000-B54L5:1:2108:{}:{} 1:N:0:0", x, y);
+///         quantiles.add(&illumina_coordinates::parse_sequence_identifier(&line).unwrap());
+///     }
+///     let tile = quantiles.tiles()[0];
+///     let y = quantiles.estimates(tile, Axis::Y).unwrap();
+///     assert!((y[1] - 4950.0).abs() < 50.0);
+///     let extent = TileExtent { x_min: 0, x_max: 9999, y_min: 0, y_max: 9999 };
+///     assert!((quantiles.coverage(tile, extent).unwrap() - 0.48).abs() < 0.01);
+/// }
+/// ```
+pub struct CoordinateQuantiles {
+    quantiles: Vec<f64>,
+    tiles: BTreeMap<TileId, (Vec<QuantileEstimator>, Vec<QuantileEstimator>)>
+}
+
+impl CoordinateQuantiles {
+    /// Creates a collector that estimates the quantiles, each between 0 and 1
+    pub fn new(quantiles: &[f64]) -> CoordinateQuantiles {
+        CoordinateQuantiles { quantiles: quantiles.to_vec(), tiles: BTreeMap::new() }
+    }
+
+    /// The quantiles being estimated
+    pub fn quantiles(&self) -> &[f64] {
+        &self.quantiles
+    }
+
+    /// Adds the coordinates of a read
+    pub fn add(&mut self, seq_id: &SequenceIdentifier) {
+        let quantiles = &self.quantiles;
+        let estimators = || quantiles.iter().map(|&quantile| QuantileEstimator::new(quantile)).collect();
+        let (x, y) = self.tiles.entry(seq_id.tile_id()).or_insert_with(|| (estimators(), estimators()));
+        for estimator in x {
+            estimator.add(f64::from(seq_id.x));
+        }
+        for estimator in y {
+            estimator.add(f64::from(seq_id.y));
+        }
+    }
+
+    /// The tiles that had at least one read, in order
+    pub fn tiles(&self) -> Vec<TileId> {
+        self.tiles.keys().cloned().collect()
+    }
+
+    /// The estimates of the quantiles of the tile's coordinates along the axis, in the order the
+    /// quantiles were given, or `None` if the tile had no reads
+    pub fn estimates(&self, tile: TileId, axis: Axis) -> Option<Vec<f64>> {
+        let (x, y) = self.tiles.get(&tile)?;
+        let estimators = match axis {
+            Axis::X => x,
+            Axis::Y => y
+        };
+        estimators.iter().map(QuantileEstimator::estimate).collect()
+    }
+
+    /// The fraction of the tile's extent that lies between the lowest and highest of the
+    /// quantiles along both axes. With quantiles of 0.01 and 0.99, a tile whose clusters are
+    /// spread over all of it has a coverage of about 0.96, and less if its clusters are clipped.
+    pub fn coverage(&self, tile: TileId, extent: TileExtent) -> Option<f64> {
+        let span = |axis: Axis, min: u16, max: u16| {
+            let estimates = self.estimates(tile, axis)?;
+            let low = estimates.iter().cloned().fold(f64::INFINITY, f64::min);
+            let high = estimates.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let clamp = |value: f64| value.max(f64::from(min)).min(f64::from(max));
+            Some((clamp(high) - clamp(low)) / (f64::from(max - min) + 1.0))
+        };
+        Some(span(Axis::X, extent.x_min, extent.x_max)? * span(Axis::Y, extent.y_min, extent.y_max)?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// How the clusters of a tile matched up between the read 1 and read 2 files
+pub struct TilePairing {
+    /// The number of clusters found in both files
+    pub paired: u64,
+    /// The number of clusters found only in the read 1 file
+    pub only_read1: u64,
+    /// The number of clusters found only in the read 2 file
+    pub only_read2: u64
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+/// Compares the clusters in the read 1 and read 2 files of a paired-end run. Reads are matched by
+/// their position, so the files can be read in any order, but only the clusters not yet seen in
+/// the other file are kept in memory.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::parse_sequence_identifier;
+/// use illumina_coordinates::stats::PairedStats;
+///
+/// fn main() {
+///     let mut stats = PairedStats::new();
+///     stats.add_read1(&parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap());
+///     stats.add_read1(&parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4128:8950 1:N:0:0").unwrap());
+///     stats.add_read2(&parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 2:N:0:0").unwrap());
+///     assert!(!stats.is_consistent());
+///     assert_eq!(stats.only_in_read1()[0].x, 4128);
+///     let tiles = stats.tiles();
+///     assert_eq!(tiles.values().next().unwrap().paired, 1);
+/// }
+/// ```
+pub struct PairedStats {
+    paired: BTreeMap<TileId, u64>,
+    // The number of reads at each position that haven't been matched in read 1 and in read 2
+    unmatched: HashMap<Position, (u32, u32)>
+}
+
+impl PairedStats {
+    /// Creates a comparison that hasn't counted any reads
+    pub fn new() -> PairedStats {
+        PairedStats::default()
+    }
+
+    /// Counts a read from the read 1 file
+    pub fn add_read1(&mut self, seq_id: &SequenceIdentifier) {
+        self.add(seq_id, true);
+    }
+
+    /// Counts a read from the read 2 file
+    pub fn add_read2(&mut self, seq_id: &SequenceIdentifier) {
+        self.add(seq_id, false);
+    }
+
+    fn add(&mut self, seq_id: &SequenceIdentifier, is_read1: bool) {
+        let position = Position::from(seq_id);
+        let counts = self.unmatched.entry(position).or_insert((0, 0));
+        let (mine, theirs) = if is_read1 { (&mut counts.0, &mut counts.1) } else { (&mut counts.1, &mut counts.0) };
+        if *theirs == 0 {
+            *mine += 1;
+            return;
+        }
+        *theirs -= 1;
+        if counts.0 == 0 && counts.1 == 0 {
+            self.unmatched.remove(&position);
+        }
+        *self.paired.entry(seq_id.tile_id()).or_insert(0) += 1;
+    }
+
+    /// Whether every cluster counted so far was found in both files
+    pub fn is_consistent(&self) -> bool {
+        self.unmatched.is_empty()
+    }
+
+    /// The counts for each tile that had at least one read in either file
+    pub fn tiles(&self) -> BTreeMap<TileId, TilePairing> {
+        let mut tiles: BTreeMap<TileId, TilePairing> = self.paired.iter()
+            .map(|(&tile, &paired)| (tile, TilePairing { paired, ..TilePairing::default() }))
+            .collect();
+        for (&position, &(read1, read2)) in &self.unmatched {
+            let pairing = tiles.entry(tile_of(&position)).or_default();
+            pairing.only_read1 += u64::from(read1);
+            pairing.only_read2 += u64::from(read2);
+        }
+        tiles
+    }
+
+    /// The positions of the clusters found only in the read 1 file, in order
+    pub fn only_in_read1(&self) -> Vec<Position> {
+        self.unmatched_positions(|&(read1, _)| read1)
+    }
+
+    /// The positions of the clusters found only in the read 2 file, in order
+    pub fn only_in_read2(&self) -> Vec<Position> {
+        self.unmatched_positions(|&(_, read2)| read2)
+    }
+
+    fn unmatched_positions<F: Fn(&(u32, u32)) -> u32>(&self, count: F) -> Vec<Position> {
+        let mut positions: Vec<Position> = self.unmatched.iter()
+            .flat_map(|(&position, counts)| (0..count(counts)).map(move |_| position))
+            .collect();
+        positions.sort();
+        positions
+    }
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    let middle = values.len() / 2;
+    if values.len() & 1 == 0 {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+fn tile_of(position: &Position) -> TileId {
+    TileId {
+        lane: position.lane,
+        side: position.side,
+        swath: position.swath,
+        camera: position.camera,
+        tile: position.tile
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    fn collect(tiles: &[(u8, u32, u32, u32)]) -> StatsCollector {
+        let mut stats = StatsCollector::new();
+        for &(lane, tile, passed, filtered) in tiles {
+            for read in 0..passed + filtered {
+                let filter = if read < filtered { "Y" } else { "N" };
+                let line = format!("@A00123:8:H3KJ7DSXX:{}:{}:1000:{} 1:{}:0:0", lane, tile, read, filter);
+                stats.add(&parse_sequence_identifier(&line).unwrap());
+            }
+        }
+        stats
+    }
+
+    #[test]
+    fn test_counts() {
+        let stats = collect(&[(1, 1101, 9, 1), (1, 2101, 5, 5), (2, 1101, 10, 0)]);
+        assert_eq!(stats.tiles().len(), 3);
+        assert_eq!(stats.total(), ReadCounts { reads: 30, filtered: 6 });
+        assert_eq!(stats.lanes()[&1], ReadCounts { reads: 20, filtered: 6 });
+        assert_eq!(stats.surfaces()[&(1, 2)].pass_filter_rate(), Some(0.5));
+        assert_eq!(stats.surfaces().len(), 3);
+        assert_eq!(ReadCounts::default().pass_filter_rate(), None);
+    }
+
+    #[test]
+    fn test_pass_filter_outliers() {
+        let stats = collect(&[(1, 1101, 9, 1), (1, 1102, 9, 1), (1, 1103, 2, 8), (1, 1104, 8, 2)]);
+        let outliers = stats.pass_filter_outliers(0.25);
+        assert_eq!(outliers.len(), 1);
+        assert_eq!(outliers[0].tile.tile, 3);
+        assert_eq!(outliers[0].pass_filter_rate, 0.2);
+        assert_eq!(outliers[0].run_pass_filter_rate, 0.7);
+        assert!((outliers[0].deviation() + 0.5).abs() < 1e-9);
+        assert_eq!(stats.pass_filter_outliers(0.0).len(), 4);
+        assert!(StatsCollector::new().pass_filter_outliers(0.0).is_empty());
+    }
+    #[test]
+    fn test_count_outliers() {
+        let stats = collect(&[(1, 1101, 100, 0), (1, 1102, 96, 4), (1, 1103, 103, 0), (1, 1104, 20, 0),
+                              (1, 1105, 99, 0), (1, 1106, 400, 0), (1, 1107, 101, 0), (1, 1108, 98, 0)]);
+        let mad = stats.count_outliers(OutlierThreshold::Mad(3.5));
+        assert_eq!(mad.iter().map(|outlier| (outlier.tile.tile, outlier.reads)).collect::<Vec<_>>(), vec![(4, 20), (6, 400)]);
+        assert!(mad[0].score < 0.0 && mad[1].score > 0.0);
+        // The very full tile inflates the standard deviation enough to hide the empty one
+        let z = stats.count_outliers(OutlierThreshold::ZScore(2.0));
+        assert_eq!(z.iter().map(|outlier| outlier.tile.tile).collect::<Vec<_>>(), vec![6]);
+        assert!(collect(&[(1, 1101, 10, 0), (1, 1102, 10, 0)]).count_outliers(OutlierThreshold::Mad(1.0)).is_empty());
+        assert!(StatsCollector::new().count_outliers(OutlierThreshold::ZScore(1.0)).is_empty());
+    }
+
+    #[test]
+    fn test_densities() {
+        let stats = collect(&[(1, 1101, 9, 1), (1, 1102, 20, 10), (2, 1101, 10, 0)]);
+        let densities = stats.tile_densities(0.5);
+        assert_eq!(densities.values().cloned().collect::<Vec<_>>(), vec![20.0, 60.0, 20.0]);
+        let lanes = stats.lane_densities(0.5);
+        assert_eq!(lanes[&1], 40.0);
+        assert_eq!(lanes[&2], 20.0);
+        assert_eq!(stats.total().pass_filter_density(2.0), 19.5);
+        let swaths = collect(&[(1, 1101, 10, 0), (1, 1102, 20, 0), (1, 1201, 5, 5)]).swath_densities(0.5);
+        assert_eq!(swaths.into_iter().collect::<Vec<_>>(), vec![((1, 1, 1), 30.0), ((1, 1, 2), 20.0)]);
+    }
+
+    #[test]
+    fn test_compare_surfaces() {
+        let stats = collect(&[(1, 1101, 90, 10), (1, 1102, 90, 10), (1, 2101, 30, 20), (2, 1101, 5, 0)]);
+        let mut duplicates = BTreeMap::new();
+        for (&tile, counts) in stats.tiles() {
+            let duplicates_on_tile = if tile.side == 2 { counts.reads / 5 } else { 0 };
+            duplicates.insert(tile, DuplicateCounts { reads: counts.reads, duplicates: duplicates_on_tile });
+        }
+        let lanes = stats.compare_surfaces(0.5, &duplicates);
+        assert_eq!(lanes.len(), 2);
+        let lane = lanes[0];
+        assert_eq!(lane.top.unwrap().counts, ReadCounts { reads: 200, filtered: 20 });
+        assert_eq!(lane.bottom.unwrap().density, 100.0);
+        assert!((lane.pass_filter_rate_difference().unwrap() - 0.3).abs() < 1e-9);
+        assert_eq!(lane.density_ratio(), Some(2.0));
+        assert!((lane.duplicate_rate_difference().unwrap() + 0.2).abs() < 1e-9);
+        assert_eq!((lanes[1].lane, lanes[1].bottom), (2, None));
+        assert_eq!(lanes[1].density_ratio(), None);
+    }
+
+    #[test]
+    fn test_lane_summaries() {
+        let stats = collect(&[(1, 1101, 90, 10), (1, 1102, 50, 50), (2, 1101, 5, 0)]);
+        let mut duplicates = BTreeMap::new();
+        duplicates.insert(*stats.tiles().keys().next().unwrap(), DuplicateCounts { reads: 100, duplicates: 10 });
+        let lanes = stats.lane_summaries(0.5, &duplicates);
+        assert_eq!(lanes.iter().map(|lane| (lane.lane, lane.tiles)).collect::<Vec<_>>(), vec![(1, 2), (2, 1)]);
+        assert_eq!(lanes[0].counts, ReadCounts { reads: 200, filtered: 60 });
+        assert_eq!((lanes[0].density, lanes[0].pass_filter_density), (200.0, 140.0));
+        assert_eq!(lanes[0].pass_filter_rate(), Some(0.7));
+        assert_eq!(lanes[0].duplicate_rate(), Some(0.1));
+        assert_eq!(lanes[1].duplicate_rate(), None);
+        assert!(lanes[1].to_json().ends_with(r#""density":10,"pass_filter_density":10,"duplicates":0,"duplicate_rate":null}"#));
+        assert_eq!(json_number(Some(f64::NAN)), "null");
+    }
+
+    #[test]
+    fn test_swaths_and_cameras() {
+        let stats = collect(&[(1, 1101, 9, 1), (1, 1102, 10, 0), (1, 1201, 5, 5), (1, 2201, 10, 0), (2, 1101, 1, 0)]);
+        let swaths = stats.swaths();
+        assert_eq!(swaths.keys().cloned().collect::<Vec<_>>(), vec![(1, 1, 1), (1, 1, 2), (1, 2, 2), (2, 1, 1)]);
+        assert_eq!(swaths[&(1, 1, 1)], ReadCounts { reads: 20, filtered: 1 });
+        assert!(stats.cameras().is_empty());
+
+        let mut stats = StatsCollector::new();
+        for name in &["1:11101:1000:1000", "1:11203:1000:1000", "1:11204:1000:1000", "1:12301:1000:1000"] {
+            stats.add(&parse_sequence_identifier(&format!("@NS500358:204:HTN5KAFXY:{} 1:N:0:0", name)).unwrap());
+        }
+        let cameras = stats.cameras();
+        assert_eq!(cameras.iter().map(|(&camera, counts)| (camera, counts.reads)).collect::<Vec<_>>(),
+                   vec![((1, 1, 1, 1), 1), ((1, 1, 1, 2), 2), ((1, 1, 2, 3), 1)]);
+    }
+
+    #[test]
+    fn test_running_moments() {
+        let mut moments = RunningMoments::new();
+        assert_eq!((moments.mean(), moments.variance()), (None, None));
+        moments.add(3.0);
+        assert_eq!((moments.mean(), moments.variance()), (Some(3.0), Some(0.0)));
+        let values: Vec<f64> = (0..1000).map(|value| f64::from(value * value % 997) + 1e9).collect();
+        let (mut first, mut second, mut all) = (RunningMoments::new(), RunningMoments::new(), RunningMoments::new());
+        for (index, &value) in values.iter().enumerate() {
+            all.add(value);
+            if index < 300 { first.add(value) } else { second.add(value) }
+        }
+        let mean = values.iter().sum::<f64>() / 1000.0;
+        let variance = values.iter().map(|value| (value - mean) * (value - mean)).sum::<f64>() / 1000.0;
+        assert!((all.mean().unwrap() - mean).abs() < 1e-6);
+        assert!((all.variance().unwrap() - variance).abs() < 1e-6 * variance);
+        first.merge(second);
+        first.merge(RunningMoments::new());
+        assert_eq!(first.count(), 1000);
+        assert!((first.mean().unwrap() - mean).abs() < 1e-6);
+        assert!((first.variance().unwrap() - variance).abs() < 1e-6 * variance);
+    }
+
+    #[test]
+    fn test_coordinate_outliers() {
+        let mut stats = CoordinateStats::new();
+        let mut shifted = CoordinateStats::new();
+        for i in 0..200 {
+            for tile in 1101..1104 {
+                let line = format!("@A00123:8:H3KJ7DSXX:1:{}:{}:{} 1:N:0:0", tile, 1000 + i * 100, 1000 + (i * 37 % 200) * 100);
+                stats.add(&parse_sequence_identifier(&line).unwrap());
+            }
+            let line = format!("@A00123:8:H3KJ7DSXX:1:1104:{}:{} 1:N:0:0", 11000 + i * 50, 1000 + i * 100);
+            shifted.add(&parse_sequence_identifier(&line).unwrap());
+        }
+        assert!(stats.coordinate_outliers(0.1, 0.9).is_empty());
+        stats.merge(&shifted);
+        assert_eq!(stats.total().x.count(), 800);
+        let outliers = stats.coordinate_outliers(0.5, 0.75);
+        assert_eq!(outliers.len(), 1);
+        assert_eq!((outliers[0].tile.tile, outliers[0].axis), (4, Axis::X));
+        assert!(outliers[0].shift > 0.5 && outliers[0].spread < 0.75);
+        assert!(CoordinateStats::new().coordinate_outliers(0.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn test_quantile_estimator() {
+        let mut few = QuantileEstimator::new(0.5);
+        assert_eq!(few.estimate(), None);
+        for &value in &[5.0, 1.0, 3.0] {
+            few.add(value);
+        }
+        assert_eq!(few.estimate(), Some(3.0));
+        for &quantile in &[0.01, 0.25, 0.5, 0.9, 0.99] {
+            let mut estimator = QuantileEstimator::new(quantile);
+            for value in 0..100_000u32 {
+                estimator.add(f64::from(value.wrapping_mul(2_654_435_761) % 100_000));
+            }
+            assert_eq!(estimator.count(), 100_000);
+            let error = (estimator.estimate().unwrap() - quantile * 100_000.0).abs();
+            assert!(error < 500.0, "{} {}", quantile, error);
+        }
+        let mut constant = QuantileEstimator::new(0.9);
+        for _ in 0..100 {
+            constant.add(7.0);
+        }
+        assert_eq!(constant.estimate(), Some(7.0));
+    }
+
+    #[test]
+    fn test_coordinate_quantiles() {
+        let mut quantiles = CoordinateQuantiles::new(&[0.05, 0.95]);
+        for i in 0..2000u32 {
+            let spread = i.wrapping_mul(2_654_435_761) % 1000;
+            for &(tile, y) in &[(1101, 1000 + spread * 20), (1102, 1000 + spread * 10)] {
+                let line = format!("@A00123:8:H3KJ7DSXX:1:{}:{}:{} 1:N:0:0", tile, 1000 + spread * 20, y);
+                quantiles.add(&parse_sequence_identifier(&line).unwrap());
+            }
+        }
+        let tiles = quantiles.tiles();
+        assert_eq!(tiles.len(), 2);
+        let extent = TileExtent { x_min: 1000, x_max: 21000, y_min: 1000, y_max: 21000 };
+        let full = quantiles.coverage(tiles[0], extent).unwrap();
+        let clipped = quantiles.coverage(tiles[1], extent).unwrap();
+        assert!((full - 0.81).abs() < 0.02, "{}", full);
+        assert!((clipped - 0.405).abs() < 0.02, "{}", clipped);
+        let y = quantiles.estimates(tiles[1], Axis::Y).unwrap();
+        assert!((y[1] - 10500.0).abs() < 200.0);
+        assert_eq!(quantiles.estimates(TileId { tile: 3, ..tiles[0] }, Axis::X), None);
+    }
+
+    #[test]
+    fn test_paired_stats() {
+        let read = |name: &str, read: u8| {
+            parse_sequence_identifier(&format!("@A00123:8:H3KJ7DSXX:{} {}:N:0:0", name, read)).unwrap()
+        };
+        let mut stats = PairedStats::new();
+        for name in &["1:1101:1000:1000", "1:1101:1000:1200", "1:1102:1000:1000", "2:1101:1000:1000"] {
+            stats.add_read1(&read(name, 1));
+        }
+        for name in &["2:1101:1000:1000", "1:1101:1000:1200", "1:1101:1000:1000", "1:1102:3000:3000"] {
+            stats.add_read2(&read(name, 2));
+        }
+        let tiles: Vec<TilePairing> = stats.tiles().values().cloned().collect();
+        assert_eq!(tiles, vec![TilePairing { paired: 2, only_read1: 0, only_read2: 0 },
+                               TilePairing { paired: 0, only_read1: 1, only_read2: 1 },
+                               TilePairing { paired: 1, only_read1: 0, only_read2: 0 }]);
+        assert!(!stats.is_consistent());
+        assert_eq!(stats.only_in_read1().iter().map(|position| position.x).collect::<Vec<_>>(), vec![1000]);
+        assert_eq!(stats.only_in_read2().iter().map(|position| position.x).collect::<Vec<_>>(), vec![3000]);
+        stats.add_read1(&read("1:1102:3000:3000", 1));
+        stats.add_read2(&read("1:1102:1000:1000", 2));
+        assert!(stats.is_consistent());
+        assert_eq!(stats.tiles().values().map(|tile| tile.paired).sum::<u64>(), 5);
+    }
+}
@@ -0,0 +1,139 @@
+//! Reproducible subsampling of reads, for quick quality checks of huge runs.
+//!
+//! Sampling a fraction of reads decides for each read from its position and a seed, rather than
+//! from a random number generator, so the same reads are picked every time and from every file
+//! of a paired-end run.
+
+use std::collections::HashMap;
+use position::position_hash;
+use super::{SequenceIdentifier, TileId};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Which reads to keep
+pub enum SampleBy {
+    /// Each read with this probability, decided by its position and the seed
+    Fraction {
+        /// The probability of keeping a read, between 0 and 1
+        fraction: f64,
+        /// Different seeds pick different reads
+        seed: u64
+    },
+    /// The first read and every nth read after it
+    EveryNth(u64),
+    /// The first reads on each tile, up to this many
+    PerTile(u64)
+}
+
+/// Decides which reads to keep
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::subsample::{SampleBy, Subsampler};
+///
+/// fn main() {
+///     let mut subsampler = Subsampler::new(SampleBy::PerTile(1));
+///     let first = illumina_coordinates::parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0").unwrap();
+///     let second = illumina_coordinates::parse_sequence_identifier("@M03745:11:000000000-B54L5:1:2108:4128:8950 1:N:0:0").unwrap();
+///     assert!(subsampler.keep(&first));
+///     assert!(!subsampler.keep(&second));
+/// }
+/// ```
+pub struct Subsampler {
+    sample_by: SampleBy,
+    // The position of the next read within its group of n, for every nth read
+    offset: u64,
+    tiles: HashMap<TileId, u64>
+}
+
+impl Subsampler {
+    /// Creates a subsampler that hasn't seen any reads
+    pub fn new(sample_by: SampleBy) -> Subsampler {
+        Subsampler { sample_by, offset: 0, tiles: HashMap::new() }
+    }
+
+    /// Whether to keep the read. Reads must be passed in the order they appear in the file.
+    pub fn keep(&mut self, seq_id: &SequenceIdentifier) -> bool {
+        match self.sample_by {
+            SampleBy::Fraction { fraction, seed } => {
+                // Scale the hash to [0, 1) using its top 53 bits, which fit in an f64 exactly
+                let value = (mix(seed ^ position_hash(seq_id)) >> 11) as f64 / (1u64 << 53) as f64;
+                value < fraction
+            },
+            SampleBy::EveryNth(n) => {
+                let keep = self.offset == 0;
+                self.offset = (self.offset + 1) % n.max(1);
+                keep
+            },
+            SampleBy::PerTile(cap) => {
+                let kept = self.tiles.entry(seq_id.tile_id()).or_insert(0);
+                if *kept < cap {
+                    *kept += 1;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// The SplitMix64 finalizer, which scrambles the bits of a value
+pub(crate) fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Hashes the bytes, starting from a seed
+pub(crate) fn hash(seed: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(mix(seed), |state, &byte| mix(state ^ u64::from(byte)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    fn reads() -> Vec<SequenceIdentifier> {
+        (0..2000).map(|i| {
+            let line = format!("@A00123:8:H3KJ7DSXX:1:{}:{}:1000 1:N:0:0", 1101 + i % 2, 1000 + i);
+            parse_sequence_identifier(&line).unwrap()
+        }).collect()
+    }
+
+    fn kept(sample_by: SampleBy, reads: &[SequenceIdentifier]) -> Vec<usize> {
+        let mut subsampler = Subsampler::new(sample_by);
+        (0..reads.len()).filter(|&i| subsampler.keep(&reads[i])).collect()
+    }
+
+    #[test]
+    fn test_fraction() {
+        let reads = reads();
+        let sample = kept(SampleBy::Fraction { fraction: 0.1, seed: 1 }, &reads);
+        assert!(sample.len() > 150 && sample.len() < 250, "{}", sample.len());
+        assert_eq!(kept(SampleBy::Fraction { fraction: 0.1, seed: 1 }, &reads), sample);
+        assert_ne!(kept(SampleBy::Fraction { fraction: 0.1, seed: 2 }, &reads), sample);
+        // The same reads are picked from read 2, and in any order
+        let mut read2: Vec<SequenceIdentifier> = reads.iter().rev().cloned().collect();
+        for seq_id in &mut read2 {
+            seq_id.read = 2;
+        }
+        let mut subsampler = Subsampler::new(SampleBy::Fraction { fraction: 0.1, seed: 1 });
+        let picked = read2.iter().filter(|seq_id| subsampler.keep(seq_id)).count();
+        assert_eq!(picked, sample.len());
+        assert_eq!(kept(SampleBy::Fraction { fraction: 1.0, seed: 1 }, &reads).len(), reads.len());
+        assert!(kept(SampleBy::Fraction { fraction: 0.0, seed: 1 }, &reads).is_empty());
+    }
+
+    #[test]
+    fn test_every_nth_and_per_tile() {
+        let reads = reads();
+        assert_eq!(kept(SampleBy::EveryNth(700), &reads), vec![0, 700, 1400]);
+        assert_eq!(kept(SampleBy::EveryNth(0), &reads).len(), reads.len());
+        assert_eq!(kept(SampleBy::PerTile(2), &reads), vec![0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,134 @@
+//! Draws maps of flow cells as SVG, coloring each tile by a metric such as its read count,
+//! duplicate rate or the fraction of reads that passed filtering. Problems like bubbles, edge
+//! effects and failed swaths show up as patterns across neighbouring tiles, which are much easier
+//! to spot in a picture than in a table.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use geometry::FlowCellLayout;
+use super::TileId;
+
+const TILE_WIDTH: u32 = 24;
+const TILE_HEIGHT: u32 = 10;
+const LANE_GAP: u32 = 8;
+const SIDE_GAP: u32 = 32;
+const MARGIN: u32 = 24;
+const MISSING_COLOR: &str = "#dddddd";
+// Colors for the smallest and largest values, with values in between blended linearly
+const LOW_COLOR: (f64, f64, f64) = (255.0, 255.0, 204.0);
+const HIGH_COLOR: (f64, f64, f64) = (189.0, 0.0, 38.0);
+
+/// Writes an SVG map of the flow cell. Each side of the flow cell is drawn separately, with its
+/// lanes from left to right, the swaths of each lane side by side, and the tiles of each swath
+/// from top to bottom. Tiles are colored from pale yellow for the smallest value to dark red for
+/// the largest, and tiles without a value are grey. Hovering over a tile shows its value.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use std::collections::HashMap;
+/// use illumina_coordinates::flow_cell::FlowCellType;
+/// use illumina_coordinates::svg::write_flow_cell_svg;
+///
+/// fn main() {
+///     let layout = FlowCellType::MiSeqV2.layout();
+///     let mut reads = HashMap::new();
+///     let line = "@M03745:11:000000000-A54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     *reads.entry(seq_id.tile_id()).or_insert(0.0) += 1.0;
+///     let mut svg = vec![];
+///     write_flow_cell_svg(&mut svg, &layout, &reads).unwrap();
+///     assert!(String::from_utf8(svg).unwrap().starts_with("<svg"));
+/// }
+/// ```
+pub fn write_flow_cell_svg<W: Write>(mut writer: W, layout: &FlowCellLayout, values: &HashMap<TileId, f64>)
+        -> io::Result<()> {
+    let tiles = layout.tiles();
+    let (min, max) = tiles.iter()
+        .filter_map(|tile| values.get(tile))
+        .filter(|value| value.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &value| (min.min(value), max.max(value)));
+    let lane_width = u32::from(layout.swaths) * TILE_WIDTH;
+    let side_width = u32::from(layout.lanes) * (lane_width + LANE_GAP) - LANE_GAP;
+    let length = u32::from(layout.cameras) * u32::from(layout.tiles_per_swath);
+    let width = 2 * MARGIN + u32::from(layout.sides) * (side_width + SIDE_GAP) - SIDE_GAP;
+    let height = 2 * MARGIN + length * TILE_HEIGHT;
+    writeln!(writer, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="10">"#,
+             width, height)?;
+    for side in 1..=layout.sides {
+        let left = MARGIN + u32::from(side - 1) * (side_width + SIDE_GAP);
+        writeln!(writer, r#"<text x="{}" y="{}">Side {}</text>"#, left, MARGIN - 12, side)?;
+        for lane in 1..=layout.lanes {
+            let x = left + u32::from(lane - 1) * (lane_width + LANE_GAP);
+            writeln!(writer, r#"<text x="{}" y="{}">L{}</text>"#, x, MARGIN - 2, lane)?;
+        }
+    }
+    for tile in &tiles {
+        let x = MARGIN + u32::from(tile.side - 1) * (side_width + SIDE_GAP)
+            + u32::from(tile.lane - 1) * (lane_width + LANE_GAP) + u32::from(tile.swath - 1) * TILE_WIDTH;
+        // Each camera images the next segment of the swath
+        let segment = tile.camera.map_or(0, |camera| u32::from((camera - 1) % layout.cameras));
+        let y = MARGIN + (segment * u32::from(layout.tiles_per_swath) + u32::from(tile.tile - 1)) * TILE_HEIGHT;
+        let value = values.get(tile).cloned();
+        let color = match value {
+            Some(value) if value.is_finite() => color(value, min, max),
+            _ => MISSING_COLOR.to_string()
+        };
+        write!(writer, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" stroke="white"><title>L{} {}"#,
+               x, y, TILE_WIDTH, TILE_HEIGHT, color, tile.lane, tile.tile_field())?;
+        if let Some(value) = value {
+            write!(writer, ": {}", value)?;
+        }
+        writeln!(writer, "</title></rect>")?;
+    }
+    if min <= max {
+        writeln!(writer, r#"<text x="{}" y="{}">{} to {}</text>"#, MARGIN, height - 8, min, max)?;
+    }
+    writeln!(writer, "</svg>")
+}
+
+/// Draws an SVG map of the flow cell like `write_flow_cell_svg`, returning it as a string
+pub fn flow_cell_svg(layout: &FlowCellLayout, values: &HashMap<TileId, f64>) -> String {
+    let mut svg = vec![];
+    write_flow_cell_svg(&mut svg, layout, values).expect("writing to a Vec can't fail");
+    String::from_utf8(svg).expect("SVG is UTF-8")
+}
+
+/// The color of the value on the scale from `min` to `max`
+fn color(value: f64, min: f64, max: f64) -> String {
+    let fraction = if max > min { (value - min) / (max - min) } else { 1.0 };
+    let blend = |low: f64, high: f64| (low + (high - low) * fraction).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", blend(LOW_COLOR.0, HIGH_COLOR.0), blend(LOW_COLOR.1, HIGH_COLOR.1),
+            blend(LOW_COLOR.2, HIGH_COLOR.2))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_cell_svg() {
+        let layout = FlowCellLayout { lanes: 4, sides: 2, swaths: 3, cameras: 3, tiles_per_swath: 12 };
+        let tiles = layout.tiles();
+        let mut values = HashMap::new();
+        values.insert(tiles[0], 10.0);
+        values.insert(tiles[1], 20.0);
+        values.insert(tiles[2], f64::NAN);
+        let svg = flow_cell_svg(&layout, &values);
+        assert_eq!(svg.matches("<rect").count(), tiles.len());
+        assert!(svg.contains(r##"fill="#ffffcc" stroke="white"><title>L1 11101: 10</title>"##));
+        assert!(svg.contains(r##"fill="#bd0026" stroke="white"><title>L1 11102: 20</title>"##));
+        assert!(svg.contains(r##"fill="#dddddd" stroke="white"><title>L1 11103: NaN</title>"##));
+        assert!(svg.contains(r##"fill="#dddddd" stroke="white"><title>L4 23612</title>"##));
+        assert!(svg.contains(">10 to 20</text>"));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn test_color() {
+        assert_eq!(color(5.0, 0.0, 10.0), "#de8079");
+        assert_eq!(color(3.0, 3.0, 3.0), "#bd0026");
+    }
+}
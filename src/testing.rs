@@ -0,0 +1,210 @@
+//! Random values for property-based testing of code that consumes sequence identifiers. Enabled
+//! by the `testing` feature.
+//!
+//! Generated identifiers use the whole range of each field, not just realistic values, and always
+//! round-trip through `Display` and `parse_sequence_identifier`. Randomness comes from a seeded
+//! `Rng`, so a failing seed reproduces the failing value.
+//!
+//! `SequenceIdentifier` and `Sample` also implement `proptest::arbitrary::Arbitrary` and
+//! `quickcheck::Arbitrary` with the same generators, so `any::<SequenceIdentifier>()` is a
+//! proptest strategy and `quickcheck` can take them as arguments. Values are generated from a
+//! seed, so failures aren't shrunk.
+//!
+//! # Example
+//!
+//! ```rust
+//! extern crate illumina_coordinates;
+//! use illumina_coordinates::{parse_sequence_identifier, SequenceIdentifier};
+//! use illumina_coordinates::testing::{Arbitrary, Rng};
+//!
+//! fn main() {
+//!     let mut rng = Rng::new(42);
+//!     for _ in 0..100 {
+//!         let seq_id = SequenceIdentifier::arbitrary(&mut rng);
+//!         assert_eq!(parse_sequence_identifier(&seq_id.to_string()).unwrap(), seq_id);
+//!     }
+//! }
+//! ```
+
+use proptest;
+use proptest::strategy::Strategy;
+use quickcheck;
+use barcode::Barcode;
+use instrument::InstrumentModel;
+use super::{Sample, SequenceIdentifier};
+
+const ID_CHARACTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-";
+const BASES: &[u8] = b"ACGTN";
+
+#[derive(Debug, Clone)]
+/// A small, fast pseudorandom number generator (SplitMix64). It isn't suitable for anything but
+/// generating test data.
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    /// Creates a generator. The same seed always produces the same values.
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    /// Returns a random 64-bit number
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random number less than `n`, which must not be zero
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// Returns true with the given probability
+    pub fn chance(&mut self, probability: f64) -> bool {
+        let value = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        value < probability
+    }
+
+    pub(crate) fn string(&mut self, alphabet: &[u8], min_length: u64, max_length: u64) -> String {
+        let length = min_length + self.below(max_length - min_length + 1);
+        (0..length).map(|_| alphabet[self.below(alphabet.len() as u64) as usize] as char).collect()
+    }
+}
+
+/// Types that can be generated at random for property-based tests
+pub trait Arbitrary {
+    /// Generates a random value
+    fn arbitrary(rng: &mut Rng) -> Self;
+}
+
+impl Arbitrary for Sample {
+    /// Generates a sample number or a single or dual index sequence
+    fn arbitrary(rng: &mut Rng) -> Sample {
+        if rng.chance(0.5) {
+            return Sample::Number(rng.next_u64() as u8);
+        }
+        let mut sequence = rng.string(BASES, 1, 12);
+        if rng.chance(0.5) {
+            sequence.push('+');
+            sequence.push_str(&rng.string(BASES, 1, 12));
+        }
+        Sample::Sequence(Barcode::new(&sequence).expect("generated barcodes are valid"))
+    }
+}
+
+impl Arbitrary for SequenceIdentifier {
+    fn arbitrary(rng: &mut Rng) -> SequenceIdentifier {
+        let sequencer_id = rng.string(ID_CHARACTERS, 1, 10);
+        // Tile fields only have a camera digit for some sequencers, and then the tile has two digits
+        let (camera, tile) = match InstrumentModel::from_sequencer_id(&sequencer_id) {
+            Some(model) if model.has_cameras() => (Some(rng.below(10) as u8), rng.below(100) as u8),
+            _ => (None, rng.next_u64() as u8)
+        };
+        SequenceIdentifier {
+            sequencer_id: sequencer_id.into(),
+            run_count: rng.next_u64() as u16,
+            flow_cell_id: rng.string(ID_CHARACTERS, 1, 15).into(),
+            lane: rng.next_u64() as u8,
+            side: rng.below(10) as u8,
+            swath: rng.below(10) as u8,
+            camera,
+            tile,
+            x: rng.next_u64() as u16,
+            y: rng.next_u64() as u16,
+            read: rng.next_u64() as u8,
+            is_filtered: rng.chance(0.5),
+            control_number: rng.next_u64() as u16,
+            sample: Sample::arbitrary(rng)
+        }
+    }
+}
+
+
+// A proptest strategy that generates values from a random seed
+type SeededStrategy<T> = proptest::strategy::Map<proptest::num::u64::Any, fn(u64) -> T>;
+
+fn from_seed<T: Arbitrary>(seed: u64) -> T {
+    T::arbitrary(&mut Rng::new(seed))
+}
+
+impl proptest::arbitrary::Arbitrary for Sample {
+    type Parameters = ();
+    type Strategy = SeededStrategy<Sample>;
+
+    fn arbitrary_with(_: ()) -> SeededStrategy<Sample> {
+        proptest::num::u64::ANY.prop_map(from_seed)
+    }
+}
+
+impl proptest::arbitrary::Arbitrary for SequenceIdentifier {
+    type Parameters = ();
+    type Strategy = SeededStrategy<SequenceIdentifier>;
+
+    fn arbitrary_with(_: ()) -> SeededStrategy<SequenceIdentifier> {
+        proptest::num::u64::ANY.prop_map(from_seed)
+    }
+}
+
+impl quickcheck::Arbitrary for Sample {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Sample {
+        from_seed(<u64 as quickcheck::Arbitrary>::arbitrary(g))
+    }
+}
+
+impl quickcheck::Arbitrary for SequenceIdentifier {
+    fn arbitrary(g: &mut quickcheck::Gen) -> SequenceIdentifier {
+        from_seed(<u64 as quickcheck::Arbitrary>::arbitrary(g))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_sequence_identifier;
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = Rng::new(0);
+        for _ in 0..10_000 {
+            let seq_id = SequenceIdentifier::arbitrary(&mut rng);
+            assert_eq!(parse_sequence_identifier(&seq_id.to_string()).unwrap(), seq_id);
+        }
+    }
+
+    #[test]
+    fn test_seeds_are_reproducible() {
+        let first = SequenceIdentifier::arbitrary(&mut Rng::new(7));
+        assert_eq!(SequenceIdentifier::arbitrary(&mut Rng::new(7)), first);
+        assert!(SequenceIdentifier::arbitrary(&mut Rng::new(8)) != first);
+    }
+
+    #[test]
+    fn test_proptest() {
+        let mut runner = proptest::test_runner::TestRunner::default();
+        runner.run(&proptest::arbitrary::any::<SequenceIdentifier>(), |seq_id| {
+            assert_eq!(parse_sequence_identifier(&seq_id.to_string()).unwrap(), seq_id);
+            Ok(())
+        }).unwrap();
+        runner.run(&proptest::arbitrary::any::<Sample>(), |sample| {
+            assert_eq!(Sample::from_field(&sample.to_string()), sample);
+            Ok(())
+        }).unwrap();
+    }
+
+    #[test]
+    fn test_quickcheck() {
+        fn round_trips(seq_id: SequenceIdentifier) -> bool {
+            parse_sequence_identifier(&seq_id.to_string()).ok() == Some(seq_id)
+        }
+        fn sample_round_trips(sample: Sample) -> bool {
+            Sample::from_field(&sample.to_string()) == sample
+        }
+        quickcheck::QuickCheck::new().quickcheck(round_trips as fn(SequenceIdentifier) -> bool);
+        quickcheck::QuickCheck::new().quickcheck(sample_round_trips as fn(Sample) -> bool);
+    }
+}
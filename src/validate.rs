@@ -0,0 +1,182 @@
+//! Checks that the fields of parsed sequence identifiers make sense for the sequencer and flow
+//! cell that produced them.
+//!
+//! An identifier can be well-formed but still be garbage, for example when a transfer corrupted
+//! some digits. Fields that are out of range for the flow cell are a quick sign of this. The
+//! instrument and flow cell type are inferred from the sequencer and flow cell IDs, and checks
+//! that need one that isn't recognized are skipped.
+
+use std::fmt;
+use geometry::{FlowCellLayout, TileExtent};
+use super::SequenceIdentifier;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A field that is out of range for the flow cell
+pub enum ValidationIssue {
+    /// The lane is 0 or more than the flow cell has
+    Lane {
+        /// The lane of the read
+        lane: u8,
+        /// The number of lanes on the flow cell
+        lanes: u8
+    },
+    /// The side is neither 1 nor 2, or is 2 on a flow cell that's only imaged on one side
+    Side {
+        /// The side of the read
+        side: u8,
+        /// The number of imaged sides of the flow cell
+        sides: u8
+    },
+    /// The swath is 0 or more than the flow cell has
+    Swath {
+        /// The swath of the read
+        swath: u8,
+        /// The number of swaths on each side of a lane
+        swaths: u8
+    },
+    /// The camera is missing, unexpected, or doesn't image the read's lane
+    Camera {
+        /// The camera of the read
+        camera: Option<u8>
+    },
+    /// The tile is 0 or more than the swath has
+    Tile {
+        /// The tile of the read
+        tile: u8,
+        /// The number of tiles in each swath
+        tiles_per_swath: u8
+    },
+    /// The x-coordinate is outside the tile
+    X {
+        /// The x-coordinate of the read
+        x: u16
+    },
+    /// The y-coordinate is outside the tile
+    Y {
+        /// The y-coordinate of the read
+        y: u16
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationIssue::Lane { lane, lanes } => write!(f, "lane {} is not between 1 and {}", lane, lanes),
+            ValidationIssue::Side { side, sides } => write!(f, "side {} is not between 1 and {}", side, sides),
+            ValidationIssue::Swath { swath, swaths } => write!(f, "swath {} is not between 1 and {}", swath, swaths),
+            ValidationIssue::Camera { camera: Some(camera) } => write!(f, "camera {} does not image this lane", camera),
+            ValidationIssue::Camera { camera: None } => write!(f, "tile has no camera"),
+            ValidationIssue::Tile { tile, tiles_per_swath } => {
+                write!(f, "tile {} is not between 1 and {}", tile, tiles_per_swath)
+            },
+            ValidationIssue::X { x } => write!(f, "x-coordinate {} is outside the tile", x),
+            ValidationIssue::Y { y } => write!(f, "y-coordinate {} is outside the tile", y)
+        }
+    }
+}
+
+/// Checks the fields of the identifier against the standard layout of its flow cell and the
+/// extent of its sequencer's tiles, and returns the fields that are out of range. Without a
+/// recognized flow cell, only the side is checked against the layout.
+///
+/// # Example
+///
+/// ```rust
+/// extern crate illumina_coordinates;
+/// use illumina_coordinates::validate::{validate, ValidationIssue};
+///
+/// fn main() {
+///     let line = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     assert!(validate(&seq_id).is_empty());
+///
+///     let line = "@M03745:11:000000000-B54L5:3:2128:4127:8949 1:N:0:0";
+///     let seq_id = illumina_coordinates::parse_sequence_identifier(line).unwrap();
+///     assert_eq!(validate(&seq_id), vec![ValidationIssue::Lane { lane: 3, lanes: 1 },
+///                                        ValidationIssue::Tile { tile: 28, tiles_per_swath: 19 }]);
+/// }
+/// ```
+pub fn validate(seq_id: &SequenceIdentifier) -> Vec<ValidationIssue> {
+    let extent = seq_id.instrument().and_then(|model| model.tile_extent());
+    validate_with(seq_id, seq_id.flow_cell_layout(), extent)
+}
+
+/// Checks the fields of the identifier against the given layout and extent, which might come
+/// from `RunInfo.xml` rather than being inferred
+pub fn validate_with(seq_id: &SequenceIdentifier, layout: Option<FlowCellLayout>, extent: Option<TileExtent>)
+        -> Vec<ValidationIssue> {
+    let mut issues = vec![];
+    let in_range = |value: u8, count: u8| value >= 1 && value <= count;
+    match layout {
+        Some(layout) => {
+            if !in_range(seq_id.lane, layout.lanes) {
+                issues.push(ValidationIssue::Lane { lane: seq_id.lane, lanes: layout.lanes });
+            }
+            if !in_range(seq_id.side, layout.sides) {
+                issues.push(ValidationIssue::Side { side: seq_id.side, sides: layout.sides });
+            }
+            if !in_range(seq_id.swath, layout.swaths) {
+                issues.push(ValidationIssue::Swath { swath: seq_id.swath, swaths: layout.swaths });
+            }
+            // Checking the whole tile with the lane and side in range isolates the camera
+            let mut tile = seq_id.tile_id();
+            tile.lane = 1.max(tile.lane.min(layout.lanes));
+            tile.side = 1;
+            tile.swath = 1;
+            tile.tile = 1;
+            if !layout.contains(tile) {
+                issues.push(ValidationIssue::Camera { camera: seq_id.camera });
+            }
+            if !in_range(seq_id.tile, layout.tiles_per_swath) {
+                issues.push(ValidationIssue::Tile { tile: seq_id.tile, tiles_per_swath: layout.tiles_per_swath });
+            }
+        },
+        None => {
+            if !in_range(seq_id.side, 2) {
+                issues.push(ValidationIssue::Side { side: seq_id.side, sides: 2 });
+            }
+        }
+    }
+    if let Some(extent) = extent {
+        if seq_id.x < extent.x_min || seq_id.x > extent.x_max {
+            issues.push(ValidationIssue::X { x: seq_id.x });
+        }
+        if seq_id.y < extent.y_min || seq_id.y > extent.y_max {
+            issues.push(ValidationIssue::Y { y: seq_id.y });
+        }
+    }
+    issues
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geometry;
+    use parse_sequence_identifier;
+
+    fn issues(line: &str) -> Vec<ValidationIssue> {
+        validate(&parse_sequence_identifier(line).unwrap())
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(issues("@NB501234:12:H2VGLBGXY:4:23612:11289:19567 1:N:0:1").is_empty());
+        assert_eq!(issues("@NB501234:12:H2VGLBGXY:1:24612:11289:19567 1:N:0:1"),
+                   vec![ValidationIssue::Swath { swath: 4, swaths: 3 }, ValidationIssue::Camera { camera: Some(6) }]);
+        assert_eq!(issues("@A00123:8:HFWJ3DSXX:1:3101:100:40000 1:N:0:0"),
+                   vec![ValidationIssue::Side { side: 3, sides: 2 }, ValidationIssue::X { x: 100 }, ValidationIssue::Y { y: 40000 }]);
+        // Unrecognized sequencers and flow cells only have their side checked
+        assert!(issues("@custom:1:flowcell:9:2999:1:1 1:N:0:0").is_empty());
+        assert_eq!(issues("@custom:1:flowcell:9:0999:1:1 1:N:0:0"), vec![ValidationIssue::Side { side: 0, sides: 2 }]);
+    }
+
+    #[test]
+    fn test_validate_with() {
+        let seq_id = parse_sequence_identifier("@custom:1:flowcell:1:2101:500:1500 1:N:0:0").unwrap();
+        let layout = FlowCellLayout { lanes: 1, sides: 1, swaths: 1, cameras: 1, tiles_per_swath: 1 };
+        let issues = validate_with(&seq_id, Some(layout), Some(geometry::MISEQ));
+        assert_eq!(issues, vec![ValidationIssue::Side { side: 2, sides: 1 }, ValidationIssue::X { x: 500 }]);
+        assert_eq!(issues[0].to_string(), "side 2 is not between 1 and 1");
+    }
+}
@@ -0,0 +1,42 @@
+//! Runs the `illumina-coords` binary on small FASTQ files.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const FASTQ: &str = "@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\nACGT\n+\nFFFF\n\
+                     @M03745:11:000000000-B54L5:1:2108:4128:9000 1:N:0:0\nACGT\n+\nFFFF\n\
+                     @M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\nACGA\n+\nFFFF\n";
+
+fn run(args: &[&Path]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_illumina-coords")).args(args).output().unwrap();
+    assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    output
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("illumina_coordinates_cli_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_dedupe_mark() {
+    let dir = temp_dir("dedupe_mark");
+    let (input, marked, remarked, deduped) = (dir.join("in.fastq"), dir.join("marked.fastq"), dir.join("remarked.fastq"),
+                                              dir.join("deduped.fastq"));
+    fs::write(&input, FASTQ).unwrap();
+    run(&[Path::new("dedupe"), Path::new("--mark"), &input, &marked]);
+    let text = fs::read_to_string(&marked).unwrap();
+    assert_eq!(text.lines().filter(|line| line.ends_with("1:N:0:0\tduplicate")).count(), 1);
+    assert_eq!(text.lines().nth(8), Some("@M03745:11:000000000-B54L5:1:2108:4127:8949 1:N:0:0\tduplicate"));
+
+    // Marked headers still parse, and aren't marked twice
+    run(&[Path::new("validate"), &marked]);
+    run(&[Path::new("dedupe"), Path::new("--mark"), &marked, &remarked]);
+    assert_eq!(fs::read_to_string(&remarked).unwrap(), text);
+    run(&[Path::new("dedupe"), &marked, &deduped]);
+    assert_eq!(fs::read_to_string(&deduped).unwrap().lines().count(), 8);
+    fs::remove_dir_all(&dir).unwrap();
+}